@@ -0,0 +1,71 @@
+//! Decode throughput of the compiled DBC plans, run with `cargo bench --features dbc`.
+//!
+//! A 500 kbit/s bus fully loaded with 8 byte standard frames carries about 4 000 frames per
+//! second (135 bits per frame including worst case stuff bits).
+
+use peak_can::dbc::{Dbc, Decoder};
+use peak_can::socket::{CanFrame, MessageType};
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DBC: &str = r#"
+BO_ 256 Engine: 8 ECU
+ SG_ Speed : 0|16@1+ (0.1,0) [0|6553.5] "km/h" Vector__XXX
+ SG_ Rpm : 16|16@1+ (0.25,0) [0|16383.75] "rpm" Vector__XXX
+ SG_ Temperature : 39|8@0- (0.5,-10) [-74|53.5] "degC" Vector__XXX
+ SG_ Torque : 47|12@0- (0.5,0) [-1024|1023.5] "Nm" Vector__XXX
+ SG_ Gear : 56|4@1+ (1,0) [0|15] "" Vector__XXX
+ SG_ Flags : 60|4@1+ (1,0) [0|15] "" Vector__XXX
+
+BO_ 2566852849 Diagnostics: 8 ECU
+ SG_ Mode M : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+ SG_ Voltage m1 : 8|16@1+ (0.01,0) [0|655.35] "V" Vector__XXX
+ SG_ Current m2 : 8|16@1- (0.1,0) [-3276.8|3276.7] "A" Vector__XXX
+ SG_ Counter : 56|8@1+ (1,0) [0|255] "" Vector__XXX
+"#;
+
+const FULL_LOAD_FRAMES_PER_SECOND: f64 = 500_000.0 / 135.0;
+
+fn bench(name: &str, frames: &[CanFrame], mut decode: impl FnMut(&CanFrame) -> usize) {
+    let mut decoded = 0;
+    let mut count = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        for frame in frames {
+            decoded += decode(black_box(frame));
+        }
+        count += frames.len() as u64;
+    }
+    let rate = count as f64 / start.elapsed().as_secs_f64();
+    println!(
+        "{name:<24} {:>12.0} frames/s {:>8.1}x full load ({decoded} signals)",
+        rate,
+        rate / FULL_LOAD_FRAMES_PER_SECOND
+    );
+}
+
+fn main() {
+    let dbc = Dbc::parse(DBC).unwrap();
+    let decoder = Decoder::new(&dbc);
+    let frames = (0..64u8)
+        .map(|i| match i % 2 {
+            0 => CanFrame::new(0x100, MessageType::Standard, &[i; 8]).unwrap(),
+            _ => CanFrame::new(
+                0x18FF_10F1,
+                MessageType::Extended,
+                &[1 + i / 2 % 2, i, 0, 0, 0, 0, 0, i],
+            )
+            .unwrap(),
+        })
+        .collect::<Vec<_>>();
+
+    bench("decode", &frames, |frame| decoder.decode(frame).len());
+
+    let mut out = Vec::new();
+    bench("decode_into", &frames, |frame| {
+        out.clear();
+        decoder.decode_into(frame, &mut out);
+        out.len()
+    });
+}
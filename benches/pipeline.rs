@@ -0,0 +1,17 @@
+//! Throughput and latency of the send and receive path on virtual channels, run with
+//! `cargo bench --features bench`.
+
+use peak_can::bench::{self, BenchConfig};
+
+fn main() {
+    for (window, data_len) in [(1, 8), (32, 4), (32, 8), (256, 8)] {
+        let config = BenchConfig {
+            frames: 100_000,
+            data_len,
+            window,
+            ..BenchConfig::default()
+        };
+        let report = bench::run_virtual(&config).unwrap();
+        println!("window {window:<4} {data_len} bytes  {report}");
+    }
+}
@@ -0,0 +1,277 @@
+//! A logger for headless appliances: records USB1 listen-only into a Vector ASCII log until it
+//! is stopped, without needing a console.
+//!
+//! On Linux it runs under systemd, see `headless_logger.service`, and stops on SIGTERM or
+//! SIGINT. On Windows it runs as a service, e.g. registered with
+//!
+//! ```text
+//! sc create peak-can-logger binPath= "C:\peak\headless_logger.exe C:\logs\capture.asc"
+//! ```
+//!
+//! and stops with the service. Started from a console it runs in the foreground until Ctrl+C.
+//! Either way the log is closed properly through the shutdown hook of its writer.
+
+use peak_can::bus::UsbBus;
+use peak_can::error::CanError;
+use peak_can::shutdown::Shutdown;
+use peak_can::socket::{Baudrate, CanSocket, RecvCan, WaitStrategy};
+use peak_can::trace::ShutdownWriter;
+use peak_can::trace::asc::AscWriter;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+fn log(path: &Path, shutdown: &Shutdown) -> Result<(), Box<dyn Error>> {
+    let socket = CanSocket::open_listen_only(UsbBus::USB1, Baudrate::Baud500K)?;
+    let file = BufWriter::new(File::create(path)?);
+    let writer = ShutdownWriter::new(AscWriter::new(file), shutdown);
+
+    let frames = socket
+        .iter()
+        .with_wait_strategy(WaitStrategy::Sleep(Duration::from_millis(5)))
+        .with_shutdown(shutdown.clone());
+    for received in frames {
+        match received {
+            Ok((frame, timestamp)) => writer.with(|asc| asc.write_frame(1, &frame, &timestamp))?,
+            // bus errors are part of the traffic of a live bus
+            Err(CanError::BusLight | CanError::BusHeavy | CanError::BusPassive) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("usage: headless_logger <capture.asc>");
+        return ExitCode::FAILURE;
+    };
+    platform::run(path)
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::*;
+
+    use std::ffi::c_int;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+
+    static STOP: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    extern "C" fn on_signal(_: c_int) {
+        // only async-signal-safe operations here, the shutdown is triggered by a watcher
+        STOP.store(true, Ordering::Release);
+    }
+
+    pub fn run(path: PathBuf) -> ExitCode {
+        unsafe {
+            signal(SIGINT, on_signal);
+            signal(SIGTERM, on_signal);
+        }
+        let shutdown = Shutdown::new();
+        let watcher = shutdown.clone();
+        thread::spawn(move || {
+            while !STOP.load(Ordering::Acquire) {
+                thread::sleep(Duration::from_millis(50));
+            }
+            watcher.trigger();
+        });
+
+        let result = log(&path, &shutdown);
+        shutdown.trigger();
+        match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                // ends up in the journal under systemd
+                eprintln!("logging failed: {err}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    use std::ffi::c_void;
+    use std::sync::OnceLock;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    const SERVICE_NAME: &str = "peak-can-logger";
+
+    const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+    const SERVICE_STOPPED: u32 = 1;
+    const SERVICE_STOP_PENDING: u32 = 3;
+    const SERVICE_RUNNING: u32 = 4;
+    const SERVICE_ACCEPT_STOP: u32 = 0x1;
+    const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+    const SERVICE_CONTROL_STOP: u32 = 1;
+    const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+    const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+    const NO_ERROR: u32 = 0;
+    const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+    const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
+    #[repr(C)]
+    struct ServiceStatus {
+        service_type: u32,
+        current_state: u32,
+        controls_accepted: u32,
+        win32_exit_code: u32,
+        service_specific_exit_code: u32,
+        check_point: u32,
+        wait_hint: u32,
+    }
+
+    type ServiceMain = unsafe extern "system" fn(argc: u32, argv: *mut *mut u16);
+    type Handler = unsafe extern "system" fn(
+        control: u32,
+        event: u32,
+        data: *mut c_void,
+        context: *mut c_void,
+    ) -> u32;
+    type ConsoleHandler = unsafe extern "system" fn(event: u32) -> i32;
+
+    #[repr(C)]
+    struct ServiceTableEntry {
+        name: *const u16,
+        main: Option<ServiceMain>,
+    }
+
+    #[link(name = "advapi32")]
+    unsafe extern "system" {
+        fn StartServiceCtrlDispatcherW(table: *const ServiceTableEntry) -> i32;
+        fn RegisterServiceCtrlHandlerExW(
+            name: *const u16,
+            handler: Handler,
+            context: *mut c_void,
+        ) -> *mut c_void;
+        fn SetServiceStatus(handle: *mut c_void, status: *const ServiceStatus) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<ConsoleHandler>, add: i32) -> i32;
+    }
+
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    static SHUTDOWN: OnceLock<Shutdown> = OnceLock::new();
+    static STATUS: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain([0]).collect()
+    }
+
+    fn shutdown() -> &'static Shutdown {
+        SHUTDOWN.get_or_init(Shutdown::new)
+    }
+
+    fn set_status(state: u32, exit_code: u32) {
+        let status = ServiceStatus {
+            service_type: SERVICE_WIN32_OWN_PROCESS,
+            current_state: state,
+            controls_accepted: match state {
+                SERVICE_RUNNING => SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN,
+                _ => 0,
+            },
+            win32_exit_code: exit_code,
+            service_specific_exit_code: (exit_code == ERROR_SERVICE_SPECIFIC_ERROR) as u32,
+            check_point: 0,
+            wait_hint: 5_000,
+        };
+        unsafe { SetServiceStatus(STATUS.load(Ordering::Acquire), &status) };
+    }
+
+    unsafe extern "system" fn on_control(
+        control: u32,
+        _: u32,
+        _: *mut c_void,
+        _: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                set_status(SERVICE_STOP_PENDING, NO_ERROR);
+                // runs on the dispatcher thread, not in a signal context
+                shutdown().trigger();
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    unsafe extern "system" fn service_main(_: u32, _: *mut *mut u16) {
+        let name = wide(SERVICE_NAME);
+        let handle = unsafe {
+            RegisterServiceCtrlHandlerExW(name.as_ptr(), on_control, std::ptr::null_mut())
+        };
+        if handle.is_null() {
+            return;
+        }
+        STATUS.store(handle, Ordering::Release);
+        set_status(SERVICE_RUNNING, NO_ERROR);
+
+        let path = PATH.get().expect("path set before the dispatcher starts");
+        let result = log(path, shutdown());
+        shutdown().trigger();
+        match result {
+            Ok(()) => set_status(SERVICE_STOPPED, NO_ERROR),
+            Err(_) => set_status(SERVICE_STOPPED, ERROR_SERVICE_SPECIFIC_ERROR),
+        }
+    }
+
+    unsafe extern "system" fn on_console(_: u32) -> i32 {
+        shutdown().trigger();
+        1
+    }
+
+    pub fn run(path: PathBuf) -> ExitCode {
+        let _ = PATH.set(path);
+        let name = wide(SERVICE_NAME);
+        let table = [
+            ServiceTableEntry {
+                name: name.as_ptr(),
+                main: Some(service_main),
+            },
+            ServiceTableEntry {
+                name: std::ptr::null(),
+                main: None,
+            },
+        ];
+        // returns once the service stopped
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } != 0 {
+            return ExitCode::SUCCESS;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) {
+            eprintln!("starting the service failed: {err}");
+            return ExitCode::FAILURE;
+        }
+
+        // not started by the service control manager
+        unsafe { SetConsoleCtrlHandler(Some(on_console), 1) };
+        let result = log(PATH.get().expect("path set above"), shutdown());
+        shutdown().trigger();
+        match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("logging failed: {err}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
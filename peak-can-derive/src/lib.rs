@@ -0,0 +1,333 @@
+//! Derive macros for the `peak-can` crate, enabled by its `derive` feature.
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+use std::fmt::Write;
+
+/// Implements `peak_can::payload::CanPayload` for a struct with named fields.
+///
+/// Every field is mapped to a bit field of the payload with a `#[can(...)]` attribute:
+///
+/// - `start = N, len = N`: start bit and length in bits, see `peak_can::payload` for the bit
+///   numbering
+/// - `byte = N`: alternatively the byte offset, the length is the size of the field type
+/// - `big_endian`: Motorola byte order, little endian by default
+/// - `signed`: the raw value of a scaled field is two's complement
+/// - `scale = X, offset = X`: the field holds the physical value `raw * scale + offset`
+///
+/// The payload length is 8 bytes unless given with `#[can(len = N)]` on the struct.
+///
+/// # Examples
+///
+/// ```
+/// use peak_can::payload::CanPayload;
+///
+/// #[derive(CanPayload, Debug, PartialEq)]
+/// #[can(len = 4)]
+/// struct Engine {
+///     #[can(byte = 0)]
+///     rpm: u16,
+///     #[can(start = 16, len = 8, signed, scale = 0.5, offset = 20)]
+///     temperature: f32,
+///     #[can(start = 24, len = 1)]
+///     running: bool,
+/// }
+///
+/// let engine = Engine { rpm: 1500, temperature: -10.0, running: true };
+/// let mut data = [0u8; 4];
+/// engine.write_payload(&mut data)?;
+/// assert_eq!(data, [0xDC, 0x05, 0xC4, 0x01]);
+/// assert_eq!(Engine::read_payload(&data)?, engine);
+/// # Ok::<(), peak_can::payload::PayloadError>(())
+/// ```
+#[proc_macro_derive(CanPayload, attributes(can))]
+pub fn derive_can_payload(input: TokenStream) -> TokenStream {
+    let code = match expand(input) {
+        Ok(code) => code,
+        Err(msg) => format!("::core::compile_error!({msg:?});"),
+    };
+    code.parse().unwrap()
+}
+
+struct Field {
+    name: String,
+    ty: String,
+    attr: FieldAttr,
+}
+
+#[derive(Default)]
+struct FieldAttr {
+    start: Option<String>,
+    len: Option<String>,
+    byte: Option<String>,
+    big_endian: bool,
+    signed: bool,
+    scale: Option<String>,
+    offset: Option<String>,
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let tokens = input.into_iter().collect::<Vec<_>>();
+    let mut payload_len = String::from("8");
+    let mut i = 0;
+
+    while is_punct(tokens.get(i), '#') {
+        if let Some(args) = tokens.get(i + 1).and_then(can_attribute) {
+            for (key, value) in args {
+                match (key.as_str(), value) {
+                    ("len", Some(value)) => payload_len = value,
+                    _ => return Err(format!("unknown struct attribute `{key}`")),
+                }
+            }
+        }
+        i += 2;
+    }
+    i = skip_visibility(&tokens, i);
+
+    match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => {}
+        _ => return Err(String::from("CanPayload can only be derived for structs")),
+    }
+    let name = match tokens.get(i + 1) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err(String::from("expected struct name")),
+    };
+    let body = match tokens.get(i + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err(String::from(
+                "CanPayload can't be derived for generic structs",
+            ));
+        }
+        _ => {
+            return Err(String::from(
+                "CanPayload requires a struct with named fields",
+            ));
+        }
+    };
+
+    let fields = split_top_level(body)
+        .into_iter()
+        .map(|tokens| parse_field(&tokens))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut write = String::new();
+    let mut read = String::new();
+    for field in &fields {
+        let (start, len, order) = field.layout()?;
+        let raw = field.encode(&len);
+        writeln!(
+            write,
+            "::peak_can::payload::insert_bits(data, {start}, {len}, {raw}, {order})?;"
+        )
+        .unwrap();
+        let raw = format!(
+            "::peak_can::payload::extract_bits(data, {start}, {len}, {order})\
+             .ok_or(::peak_can::payload::PayloadError::OutOfRange)?"
+        );
+        writeln!(read, "{}: {},", field.name, field.decode(&raw, &len)).unwrap();
+    }
+
+    Ok(format!(
+        "impl ::peak_can::payload::CanPayload for {name} {{
+            const LEN: usize = {payload_len};
+
+            fn write_payload(
+                &self,
+                data: &mut [u8],
+            ) -> ::core::result::Result<(), ::peak_can::payload::PayloadError> {{
+                {write}
+                ::core::result::Result::Ok(())
+            }}
+
+            fn read_payload(
+                data: &[u8],
+            ) -> ::core::result::Result<Self, ::peak_can::payload::PayloadError> {{
+                ::core::result::Result::Ok({name} {{ {read} }})
+            }}
+        }}"
+    ))
+}
+
+impl Field {
+    /// Start bit, length and byte order expressions of the field.
+    fn layout(&self) -> Result<(String, String, String), String> {
+        let attr = &self.attr;
+        let order = if attr.big_endian {
+            "::peak_can::payload::ByteOrder::BigEndian"
+        } else {
+            "::peak_can::payload::ByteOrder::LittleEndian"
+        };
+        let (start, len) = match (&attr.start, &attr.len, &attr.byte) {
+            (Some(start), Some(len), None) => (start.clone(), len.clone()),
+            (None, None, Some(byte)) => {
+                // the start bit of big endian fields is the most significant bit
+                let msb = if attr.big_endian { 7 } else { 0 };
+                (
+                    format!("(({byte}) * 8 + {msb}) as u16"),
+                    format!("(::core::mem::size_of::<{}>() * 8) as u8", self.ty),
+                )
+            }
+            _ => {
+                return Err(format!(
+                    "field `{}` needs either `start` and `len` or `byte`",
+                    self.name
+                ));
+            }
+        };
+        Ok((start, len, order.to_string()))
+    }
+
+    fn encode(&self, len: &str) -> String {
+        let attr = &self.attr;
+        if attr.scale.is_none() && attr.offset.is_none() {
+            return format!(
+                "::peak_can::message::SignalValue::to_raw(self.{}, {len})",
+                self.name
+            );
+        }
+
+        let scale = attr.scale.as_deref().unwrap_or("1");
+        let offset = attr.offset.as_deref().unwrap_or("0");
+        let raw = format!(
+            "((self.{} as f64 - ({offset}) as f64) / ({scale}) as f64).round()",
+            self.name
+        );
+        if attr.signed {
+            format!("::peak_can::message::SignalValue::to_raw({raw} as i64, {len})")
+        } else {
+            format!("::peak_can::message::SignalValue::to_raw({raw} as u64, {len})")
+        }
+    }
+
+    fn decode(&self, raw: &str, len: &str) -> String {
+        let attr = &self.attr;
+        if attr.scale.is_none() && attr.offset.is_none() {
+            return format!(
+                "<{} as ::peak_can::message::SignalValue>::from_raw({raw}, {len})",
+                self.ty
+            );
+        }
+
+        let scale = attr.scale.as_deref().unwrap_or("1");
+        let offset = attr.offset.as_deref().unwrap_or("0");
+        let raw = if attr.signed {
+            format!("<i64 as ::peak_can::message::SignalValue>::from_raw({raw}, {len}) as f64")
+        } else {
+            format!("{raw} as f64")
+        };
+        format!(
+            "({raw} * ({scale}) as f64 + ({offset}) as f64) as {}",
+            self.ty
+        )
+    }
+}
+
+fn parse_field(tokens: &[TokenTree]) -> Result<Field, String> {
+    let mut attr = None;
+    let mut i = 0;
+    while is_punct(tokens.get(i), '#') {
+        if let Some(args) = tokens.get(i + 1).and_then(can_attribute) {
+            attr = Some(parse_field_attr(args)?);
+        }
+        i += 2;
+    }
+    i = skip_visibility(tokens, i);
+
+    let name = match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err(String::from("expected field name")),
+    };
+    if !is_punct(tokens.get(i + 1), ':') {
+        return Err(format!("expected type of field `{name}`"));
+    }
+    let ty = tokens[i + 2..]
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let attr = attr.ok_or_else(|| format!("field `{name}` is missing a #[can(...)] attribute"))?;
+
+    Ok(Field { name, ty, attr })
+}
+
+fn parse_field_attr(args: Vec<(String, Option<String>)>) -> Result<FieldAttr, String> {
+    let mut attr = FieldAttr::default();
+    for (key, value) in args {
+        match (key.as_str(), value) {
+            ("start", Some(value)) => attr.start = Some(value),
+            ("len", Some(value)) => attr.len = Some(value),
+            ("byte", Some(value)) => attr.byte = Some(value),
+            ("scale", Some(value)) => attr.scale = Some(value),
+            ("offset", Some(value)) => attr.offset = Some(value),
+            ("big_endian", None) => attr.big_endian = true,
+            ("little_endian", None) => attr.big_endian = false,
+            ("signed", None) => attr.signed = true,
+            _ => return Err(format!("unknown field attribute `{key}`")),
+        }
+    }
+    Ok(attr)
+}
+
+/// Returns the `key` or `key = value` arguments if `token` is the bracket group of a
+/// `#[can(...)]` attribute.
+fn can_attribute(token: &TokenTree) -> Option<Vec<(String, Option<String>)>> {
+    let TokenTree::Group(group) = token else {
+        return None;
+    };
+    let mut tokens = group.stream().into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "can" => {}
+        _ => return None,
+    }
+    let Some(TokenTree::Group(args)) = tokens.next() else {
+        return Some(Vec::new());
+    };
+
+    let args = split_top_level(args.stream())
+        .into_iter()
+        .map(|arg| {
+            let key = arg.first().map(|t| t.to_string()).unwrap_or_default();
+            let value = is_punct(arg.get(1), '=')
+                .then(|| arg[2..].iter().map(|t| t.to_string()).collect::<String>());
+            (key, value)
+        })
+        .collect();
+    Some(args)
+}
+
+fn skip_visibility(tokens: &[TokenTree], mut i: usize) -> usize {
+    if matches!(tokens.get(i), Some(TokenTree::Ident(ident)) if ident.to_string() == "pub") {
+        i += 1;
+        if matches!(tokens.get(i), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis)
+        {
+            i += 1;
+        }
+    }
+    i
+}
+
+/// Splits `stream` at the commas outside of angle brackets, skipping empty parts.
+fn split_top_level(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = 0usize;
+    for token in stream {
+        if let TokenTree::Punct(punct) = &token {
+            match punct.as_char() {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        parts.last_mut().unwrap().push(token);
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == c)
+}
@@ -0,0 +1,52 @@
+use peak_can::payload::{CanPayload, PayloadError};
+use peak_can::socket::MessageType;
+
+#[derive(CanPayload, Debug, PartialEq)]
+struct Status {
+    #[can(byte = 0, big_endian)]
+    pub counter: u16,
+    #[can(start = 16, len = 4)]
+    pub mode: u8,
+    #[can(start = 20, len = 12, signed)]
+    pub raw: i16,
+    #[can(start = 39, len = 16, big_endian, scale = 0.1, offset = -40)]
+    pub temperature: f64,
+}
+
+#[derive(CanPayload, Debug, PartialEq)]
+#[can(len = 2)]
+struct Short {
+    #[can(byte = 1)]
+    value: u8,
+}
+
+#[test]
+fn write_and_read_fields() {
+    let status = Status {
+        counter: 0x1234,
+        mode: 0xA,
+        raw: -2,
+        temperature: 25.0,
+    };
+    let frame = status.to_frame(0x300, MessageType::Standard).unwrap();
+    // 650 = (25.0 + 40) / 0.1
+    assert_eq!(
+        frame.data(),
+        &[0x12, 0x34, 0xEA, 0xFF, 0x02, 0x8A, 0x00, 0x00]
+    );
+
+    let decoded = Status::from_frame(&frame).unwrap();
+    assert_eq!(decoded.counter, 0x1234);
+    assert_eq!(decoded.mode, 0xA);
+    assert_eq!(decoded.raw, -2);
+    assert!((decoded.temperature - 25.0).abs() < 1e-9);
+}
+
+#[test]
+fn payload_length() {
+    let frame = Short { value: 7 }
+        .to_frame(0x10, MessageType::Standard)
+        .unwrap();
+    assert_eq!(frame.data(), &[0, 7]);
+    assert_eq!(Short::read_payload(&[0]), Err(PayloadError::OutOfRange));
+}
@@ -0,0 +1,175 @@
+//! Human-readable names for channels, e.g. `powertrain` and `chassis`.
+//!
+//! [ChannelAliases] maps the channel numbers used by [TraceRecord](crate::trace::TraceRecord)
+//! and [ReceivedFrame](crate::socket::ReceivedFrame) to names. Formats naming their buses, such
+//! as the interface names of [candump](crate::trace::candump) logs, write and read the aliases.
+
+use core::fmt;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AliasError {
+    /// An alias is empty or contains whitespace, `=` or `,`.
+    InvalidAlias(String),
+    /// The alias is already assigned to another channel.
+    Duplicate { alias: String, channel: u16 },
+    /// An entry of a list is not of the form `<channel>=<alias>`.
+    InvalidEntry(String),
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasError::InvalidAlias(alias) => write!(f, "Invalid channel alias {alias:?}"),
+            AliasError::Duplicate { alias, channel } => {
+                write!(
+                    f,
+                    "Alias {alias:?} is already assigned to channel {channel}"
+                )
+            }
+            AliasError::InvalidEntry(entry) => write!(f, "Invalid alias entry {entry:?}"),
+        }
+    }
+}
+
+impl std::error::Error for AliasError {}
+
+/// Aliases of channels, unique in both directions.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::alias::ChannelAliases;
+/// let aliases: ChannelAliases = "1=powertrain, 2=chassis".parse()?;
+/// assert_eq!(aliases.alias(1), Some("powertrain"));
+/// assert_eq!(aliases.channel("chassis"), Some(2));
+/// assert_eq!(aliases.label(Some(3)), "3");
+/// # Ok::<(), peak_can::alias::AliasError>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelAliases {
+    names: BTreeMap<u16, String>,
+}
+
+impl ChannelAliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assigns `alias` to `channel`, replacing its previous alias which is returned.
+    pub fn insert(&mut self, channel: u16, alias: &str) -> Result<Option<String>, AliasError> {
+        if alias.is_empty() || alias.contains(|c: char| c.is_whitespace() || c == '=' || c == ',') {
+            return Err(AliasError::InvalidAlias(alias.to_string()));
+        }
+        if let Some(other) = self.channel(alias).filter(|other| *other != channel) {
+            return Err(AliasError::Duplicate {
+                alias: alias.to_string(),
+                channel: other,
+            });
+        }
+        Ok(self.names.insert(channel, alias.to_string()))
+    }
+
+    pub fn remove(&mut self, channel: u16) -> Option<String> {
+        self.names.remove(&channel)
+    }
+
+    pub fn alias(&self, channel: u16) -> Option<&str> {
+        self.names.get(&channel).map(String::as_str)
+    }
+
+    pub fn channel(&self, alias: &str) -> Option<u16> {
+        self.names
+            .iter()
+            .find(|(_, name)| *name == alias)
+            .map(|(channel, _)| *channel)
+    }
+
+    /// The alias of `channel`, its number if it has none and `-` for an unknown channel.
+    pub fn label(&self, channel: Option<u16>) -> String {
+        match channel {
+            Some(channel) => self
+                .alias(channel)
+                .map_or_else(|| channel.to_string(), str::to_string),
+            None => String::from("-"),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names
+            .iter()
+            .map(|(channel, name)| (*channel, name.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Parses a comma separated list like `1=powertrain,2=chassis`, e.g. from a configuration file
+/// or command line.
+impl FromStr for ChannelAliases {
+    type Err = AliasError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut aliases = ChannelAliases::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (channel, alias) = entry
+                .split_once('=')
+                .and_then(|(channel, alias)| Some((channel.trim().parse().ok()?, alias.trim())))
+                .ok_or_else(|| AliasError::InvalidEntry(entry.to_string()))?;
+            aliases.insert(channel, alias)?;
+        }
+        Ok(aliases)
+    }
+}
+
+impl fmt::Display for ChannelAliases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (channel, alias)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{channel}={alias}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_aliases_round_trip() {
+        let mut aliases = ChannelAliases::new();
+        assert_eq!(aliases.insert(1, "powertrain"), Ok(None));
+        assert_eq!(
+            aliases.insert(1, "body"),
+            Ok(Some(String::from("powertrain")))
+        );
+        assert!(matches!(
+            aliases.insert(2, "body"),
+            Err(AliasError::Duplicate { channel: 1, .. })
+        ));
+        assert!(matches!(
+            aliases.insert(2, "front axle"),
+            Err(AliasError::InvalidAlias(_))
+        ));
+        aliases.insert(2, "chassis").unwrap();
+
+        let parsed = aliases.to_string().parse::<ChannelAliases>().unwrap();
+        assert_eq!(parsed, aliases);
+        assert_eq!(parsed.label(Some(2)), "chassis");
+        assert_eq!(parsed.label(None), "-");
+        assert!(matches!(
+            "1:body".parse::<ChannelAliases>(),
+            Err(AliasError::InvalidEntry(_))
+        ));
+    }
+}
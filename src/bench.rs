@@ -0,0 +1,197 @@
+//! End-to-end throughput and latency of the send and receive path.
+//!
+//! [run] sends numbered frames on one socket and receives them on another, measuring frames
+//! per second and the time from the send call until the frame is received. [run_virtual] runs it
+//! on a [VirtualPair] to compare releases without hardware, [run] with two channels wired to the
+//! same bus gives the figures of a test rig, e.g. as an acceptance test before deployment.
+
+use crate::bus::VirtualPair;
+use crate::error::CanError;
+use crate::latency::{Histogram, LatencySummary};
+use crate::socket::{Baudrate, CanFrame, CanSocket, MessageType, RecvCan, SendCan};
+
+use core::fmt;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchConfig {
+    /// Number of frames to send.
+    pub frames: u32,
+    /// Standard ID of the sent frames, other frames received meanwhile are ignored.
+    pub can_id: u32,
+    /// Payload length between 4 and 8, the first 4 bytes carry the sequence number.
+    pub data_len: u8,
+    /// Maximum number of frames sent but not received yet.
+    pub window: usize,
+    /// The run ends when no frame was received for this long, the missing frames are lost.
+    pub timeout: Duration,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            frames: 10_000,
+            can_id: 0x7F0,
+            data_len: 8,
+            window: 32,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub sent: u64,
+    pub received: u64,
+    /// Time from the first send call until the last frame was received.
+    pub elapsed: Duration,
+    /// Time from the send call until the frame was received, in nanoseconds.
+    pub latency: Histogram,
+}
+
+impl BenchReport {
+    /// Frames sent but never received.
+    pub fn lost(&self) -> u64 {
+        self.sent - self.received
+    }
+
+    /// Received frames per second.
+    pub fn frames_per_second(&self) -> f64 {
+        self.received as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    pub fn latency_summary(&self) -> Option<LatencySummary> {
+        self.latency.summary()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} frames in {:?} ({:.0} frames/s, {} lost)",
+            self.received,
+            self.sent,
+            self.elapsed,
+            self.frames_per_second(),
+            self.lost()
+        )?;
+        if let Some(summary) = self.latency_summary() {
+            write!(f, ", latency {summary}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends `config.frames` frames on `sender` and receives them on `receiver`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::bench::{self, BenchConfig};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// let sender = CanSocket::open(UsbBus::USB1, Baudrate::Baud1M)?;
+/// let receiver = CanSocket::open(UsbBus::USB2, Baudrate::Baud1M)?;
+/// let report = bench::run(&sender, &receiver, &BenchConfig::default())?;
+/// println!("{report}");
+/// assert!(report.frames_per_second() > 7_000.0 && report.lost() == 0);
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub fn run<S, R>(sender: &S, receiver: &R, config: &BenchConfig) -> Result<BenchReport, CanError>
+where
+    S: SendCan + ?Sized,
+    R: RecvCan + ?Sized,
+{
+    let data_len = config.data_len.clamp(4, 8) as usize;
+    let mut in_flight: VecDeque<(u32, Instant)> = VecDeque::with_capacity(config.window);
+    let mut report = BenchReport {
+        sent: 0,
+        received: 0,
+        elapsed: Duration::ZERO,
+        latency: Histogram::new(),
+    };
+    let mut next = 0;
+    let start = Instant::now();
+    let mut last_progress = start;
+
+    while next < config.frames || !in_flight.is_empty() {
+        while next < config.frames && in_flight.len() < config.window.max(1) {
+            let mut data = [0; 8];
+            data[..4].copy_from_slice(&next.to_le_bytes());
+            let frame = CanFrame::new(config.can_id, MessageType::Standard, &data[..data_len])
+                .map_err(|_| CanError::IllParamVal)?;
+            match sender.send(frame) {
+                Ok(()) => {}
+                // the frames in flight are received first
+                Err(CanError::QxmtFull) => break,
+                Err(err) => return Err(err),
+            }
+            in_flight.push_back((next, Instant::now()));
+            report.sent += 1;
+            next += 1;
+        }
+
+        let frame = match receiver.recv() {
+            Ok((frame, _)) => frame,
+            Err(CanError::QrcvEmpty) => {
+                if last_progress.elapsed() > config.timeout {
+                    break;
+                }
+                std::thread::yield_now();
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let now = Instant::now();
+        if frame.can_id() != config.can_id || frame.is_extended_frame() || frame.dlc() < 4 {
+            continue;
+        }
+        let seq = u32::from_le_bytes(frame.data()[..4].try_into().unwrap());
+        // frames sent before the received one are lost
+        while let Some(&(pending, sent)) = in_flight.front() {
+            if pending > seq {
+                break;
+            }
+            in_flight.pop_front();
+            if pending == seq {
+                report.latency.record_duration(now - sent);
+                report.received += 1;
+                report.elapsed = now - start;
+                last_progress = now;
+                break;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Runs the benchmark between the channels of a new [VirtualPair].
+pub fn run_virtual(config: &BenchConfig) -> Result<BenchReport, CanError> {
+    let pair = VirtualPair::new()?;
+    let sender = CanSocket::open(pair.a(), Baudrate::Baud1M)?;
+    let receiver = CanSocket::open(pair.b(), Baudrate::Baud1M)?;
+    run(&sender, &receiver, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_run_receives_all() {
+        let config = BenchConfig {
+            frames: 500,
+            window: 8,
+            ..BenchConfig::default()
+        };
+        let report = run_virtual(&config).unwrap();
+        assert_eq!(report.sent, 500);
+        assert_eq!(report.received, 500);
+        assert_eq!(report.lost(), 0);
+        assert_eq!(report.latency.count(), 500);
+        assert!(report.frames_per_second() > 0.0);
+        assert!(report.to_string().starts_with("500 of 500 frames"));
+    }
+}
@@ -8,6 +8,7 @@ pub mod lan;
 pub mod pcc;
 pub mod pci;
 pub mod usb;
+pub mod vcan;
 
 ///
 pub trait Bus {
@@ -21,3 +22,4 @@ pub use lan::LanBus;
 pub use pcc::PccBus;
 pub use pci::PciBus;
 pub use usb::UsbBus;
+pub use vcan::{FuzzPolicy, VirtualBus, VirtualPair};
@@ -0,0 +1,277 @@
+use crate::bus::Bus;
+use crate::channel::Channel;
+use crate::error::CanError;
+use crate::socket::{Backend, VirtualBackend, vcan};
+
+use std::time::Duration;
+
+/// In-process channels which are opened with [CanSocket](crate::socket::CanSocket) like the
+/// channels of an adapter, so that tests run the production code paths without hardware.
+///
+/// Frames sent on a channel are received by its linked peer. Only initialization, frame
+/// transfer and receive timeouts are emulated, parameters and status calls fail with the errors
+/// of the driver for an unknown handle.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum VirtualBus {
+    Virtual1,
+    Virtual2,
+    Virtual3,
+    Virtual4,
+    Virtual5,
+    Virtual6,
+    Virtual7,
+    Virtual8,
+}
+
+const ALL: [VirtualBus; vcan::CHANNELS as usize] = [
+    VirtualBus::Virtual1,
+    VirtualBus::Virtual2,
+    VirtualBus::Virtual3,
+    VirtualBus::Virtual4,
+    VirtualBus::Virtual5,
+    VirtualBus::Virtual6,
+    VirtualBus::Virtual7,
+    VirtualBus::Virtual8,
+];
+
+impl From<VirtualBus> for u16 {
+    fn from(value: VirtualBus) -> Self {
+        vcan::FIRST_HANDLE + value as u16
+    }
+}
+
+impl TryFrom<u16> for VirtualBus {
+    type Error = ();
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let index = value.checked_sub(vcan::FIRST_HANDLE).ok_or(())?;
+        ALL.get(index as usize).copied().ok_or(())
+    }
+}
+
+/// Mutations of the ISO-TP frames a virtual channel sends, so that tests can check how the
+/// receiving [isotp](crate::isotp) layer and the protocols on top of it cope with a faulty
+/// peer. The protocol control information is expected in the first data byte, frames of other
+/// kinds pass unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FuzzPolicy {
+    /// Only mutates the frames with this ID, `None` mutates all frames.
+    pub can_id: Option<u32>,
+    /// Cuts first frames to at most this many bytes, up to 8.
+    pub truncate_first_frame: Option<u8>,
+    /// Added to the sequence number of every consecutive frame, modulo 16.
+    pub sequence_offset: u8,
+    /// Holds flow control frames back for this long, along with the frames sent after them.
+    pub flow_control_delay: Duration,
+}
+
+/// Two linked virtual channels, unlinked again on drop.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::bus::VirtualPair;
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType, RecvCan, SendCan};
+/// let pair = VirtualPair::new()?;
+/// let tester = CanSocket::open(pair.a(), Baudrate::Baud500K)?;
+/// let ecu = CanSocket::open(pair.b(), Baudrate::Baud500K)?;
+///
+/// tester.send(CanFrame::new(0x7DF, MessageType::Standard, &[0x01, 0x0D])?)?;
+/// let (request, _) = ecu.recv()?;
+/// assert_eq!(request.can_id(), 0x7DF);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct VirtualPair {
+    a: VirtualBus,
+    b: VirtualBus,
+}
+
+impl VirtualPair {
+    /// Links the next two free neighbouring channels, e.g. `Virtual1` and `Virtual2`.
+    /// [CanError::Resource] if all channels are linked.
+    pub fn new() -> Result<Self, CanError> {
+        let (a, b) = vcan::link_free_pair()?;
+        Ok(VirtualPair {
+            a: VirtualBus::try_from(a).expect("virtual handle"),
+            b: VirtualBus::try_from(b).expect("virtual handle"),
+        })
+    }
+
+    /// Links two given channels, [CanError::HwInUse] if one of them is linked already.
+    pub fn link(a: VirtualBus, b: VirtualBus) -> Result<Self, CanError> {
+        vcan::link(a.into(), b.into())?;
+        Ok(VirtualPair { a, b })
+    }
+
+    pub fn a(&self) -> VirtualBus {
+        self.a
+    }
+
+    pub fn b(&self) -> VirtualBus {
+        self.b
+    }
+
+    /// Applies `policy` to the frames `sender` sends from now on, `None` sends them unchanged
+    /// again. [CanError::IllHw] if `sender` is not a channel of this pair. The policies are
+    /// cleared when the pair is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use peak_can::bus::{FuzzPolicy, VirtualPair};
+    /// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType, RecvCan, SendCan};
+    /// let pair = VirtualPair::new()?;
+    /// let tester = CanSocket::open(pair.a(), Baudrate::Baud500K)?;
+    /// let ecu = CanSocket::open(pair.b(), Baudrate::Baud500K)?;
+    /// let policy = FuzzPolicy {
+    ///     sequence_offset: 1,
+    ///     ..FuzzPolicy::default()
+    /// };
+    /// pair.set_fuzz_policy(pair.a(), Some(policy))?;
+    ///
+    /// tester.send(CanFrame::new(0x7E0, MessageType::Standard, &[0x21, 1, 2])?)?;
+    /// let (frame, _) = ecu.recv()?;
+    /// assert_eq!(frame.data(), [0x22, 1, 2]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_fuzz_policy(
+        &self,
+        sender: VirtualBus,
+        policy: Option<FuzzPolicy>,
+    ) -> Result<(), CanError> {
+        if sender != self.a && sender != self.b {
+            return Err(CanError::IllHw);
+        }
+        vcan::set_fuzz_policy(sender.into(), policy);
+        Ok(())
+    }
+}
+
+impl Drop for VirtualPair {
+    fn drop(&mut self) {
+        vcan::unlink(self.a.into());
+    }
+}
+
+/* Bus trait implementation */
+
+impl Bus for VirtualBus {
+    fn channel(&self) -> u16 {
+        u16::from(*self)
+    }
+}
+
+/* Channel trait implementation */
+
+impl Channel for VirtualBus {
+    fn channel(&self) -> u16 {
+        Bus::channel(self)
+    }
+
+    fn backend(&self) -> &dyn Backend {
+        &VirtualBackend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{Baudrate, CanFdFrame, CanFrame, CanSocket, MessageType};
+    use crate::socket::{RecvCan, RecvCanFd, SendCan, SendCanFd};
+    use std::time::Instant;
+
+    #[test]
+    fn linked_sockets() {
+        let first = VirtualPair::new().unwrap();
+        let second = VirtualPair::new().unwrap();
+        assert_ne!(first.a(), second.a());
+        assert!(matches!(
+            VirtualPair::link(first.b(), second.b()),
+            Err(CanError::HwInUse)
+        ));
+
+        let a = CanSocket::open(second.a(), Baudrate::Baud500K).unwrap();
+        let b = CanSocket::open_fd_with_bitrate(second.b(), "f_clock_mhz=80").unwrap();
+        assert!(matches!(
+            CanSocket::open(second.a(), Baudrate::Baud500K),
+            Err(CanError::Initialize)
+        ));
+
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap();
+        a.send(frame).unwrap();
+        let (received, _) = b.recv_fd().unwrap();
+        assert_eq!(received.data(), frame.data());
+        assert!(matches!(b.try_recv_fd(), Ok(None)));
+
+        // CAN FD frames don't reach the classic channel
+        let fd = CanFdFrame::new(0x321, MessageType::Standard, &[0; 12], true, false).unwrap();
+        b.send_fd(fd).unwrap();
+        b.send(frame).unwrap();
+        assert_eq!(a.recv_timeout(Duration::from_secs(1)).unwrap().0, frame);
+        assert!(matches!(
+            a.recv_timeout(Duration::from_millis(1)),
+            Err(CanError::QrcvEmpty)
+        ));
+
+        a.close().unwrap();
+        assert!(b.send(frame).is_ok());
+        let (second_a, second_b) = (second.a(), second.b());
+        drop(second);
+        // the unlinked channels are free again
+        assert!(VirtualPair::link(second_a, second_b).is_ok());
+    }
+
+    #[test]
+    fn fuzzed_isotp_frames() {
+        let pair = VirtualPair::new().unwrap();
+        let a = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let b = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+        let delay = Duration::from_millis(50);
+        let policy = FuzzPolicy {
+            can_id: Some(0x7E0),
+            truncate_first_frame: Some(4),
+            sequence_offset: 15,
+            flow_control_delay: delay,
+        };
+        let unpaired = ALL
+            .into_iter()
+            .find(|bus| ![pair.a(), pair.b()].contains(bus));
+        assert!(matches!(
+            pair.set_fuzz_policy(unpaired.unwrap(), Some(policy)),
+            Err(CanError::IllHw)
+        ));
+        pair.set_fuzz_policy(pair.a(), Some(policy)).unwrap();
+
+        let sent = [
+            [0x10, 0x14, 1, 2, 3, 4, 5, 6],
+            [0x21, 7, 8, 9, 10, 11, 12, 13],
+            [0x30, 0, 0, 0, 0, 0, 0, 0],
+        ];
+        let start = Instant::now();
+        for data in sent {
+            a.send(CanFrame::new(0x7E0, MessageType::Standard, &data).unwrap())
+                .unwrap();
+        }
+        // frames of other IDs pass unchanged
+        let other = CanFrame::new(0x7E8, MessageType::Standard, &[0x21]).unwrap();
+        a.send(other).unwrap();
+
+        let timeout = Duration::from_secs(1);
+        let (first, _) = b.recv_timeout(timeout).unwrap();
+        assert_eq!(first.data(), [0x10, 0x14, 1, 2]);
+        let (consecutive, _) = b.recv_timeout(timeout).unwrap();
+        assert_eq!(consecutive.data(), [0x20, 7, 8, 9, 10, 11, 12, 13]);
+        // the flow control frame and the frame behind it are held back
+        assert!(matches!(b.try_recv(), Ok(None)));
+        let (flow_control, _) = b.recv_timeout(timeout).unwrap();
+        assert!(start.elapsed() >= delay);
+        assert_eq!(flow_control.data(), sent[2]);
+        assert_eq!(b.recv_timeout(timeout).unwrap().0, other);
+
+        pair.set_fuzz_policy(pair.a(), None).unwrap();
+        a.send(CanFrame::new(0x7E0, MessageType::Standard, &sent[1]).unwrap())
+            .unwrap();
+        assert_eq!(b.recv_timeout(timeout).unwrap().0.data(), sent[1]);
+    }
+}
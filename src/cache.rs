@@ -0,0 +1,294 @@
+//! The latest payload of every ID, with notifications when a payload changes.
+//!
+//! An [IdCache] is fed with received frames, directly or as a [TraceSink] registered at a
+//! [Dispatcher](crate::dispatch::Dispatcher), and invokes its subscribers only for frames whose
+//! payload differs from the previous frame of the same ID. Subscribers with a mask only see
+//! changes of the masked bits, e.g. a signal without the rolling counter next to it.
+
+use crate::socket::Id;
+use crate::trace::{Annotations, TraceError, TraceFrame, TraceRecord, TraceSink};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Identifies a subscriber, e.g. to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// The latest frame of an ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedFrame {
+    pub frame: TraceFrame,
+    pub timestamp: Duration,
+    /// Number of frames received with this ID.
+    pub count: u64,
+    /// Timestamp of the frame which last changed the payload.
+    pub changed: Duration,
+}
+
+/// A payload change passed to subscribers.
+#[derive(Debug)]
+pub struct ValueChange<'a> {
+    pub id: Id,
+    /// Payload before the change, `None` for the first frame of the ID.
+    pub previous: Option<&'a [u8]>,
+    pub frame: &'a TraceFrame,
+    pub timestamp: Duration,
+}
+
+type ChangeCallback = Box<dyn FnMut(&ValueChange)>;
+
+struct Subscriber {
+    id: SubscriptionId,
+    can_id: Option<Id>,
+    mask: Option<Vec<u8>>,
+    callback: ChangeCallback,
+}
+
+impl Subscriber {
+    fn is_changed(&self, previous: Option<&[u8]>, data: &[u8]) -> bool {
+        let Some(previous) = previous else {
+            return true;
+        };
+        if previous.len() != data.len() {
+            return true;
+        }
+        let Some(mask) = &self.mask else {
+            return previous != data;
+        };
+        // bytes beyond the mask are compared in full
+        let masks = mask.iter().copied().chain(std::iter::repeat(0xFF));
+        previous
+            .iter()
+            .zip(data)
+            .zip(masks)
+            .any(|((old, new), mask)| (old ^ new) & mask != 0)
+    }
+}
+
+/// A last-value cache keyed by [Id].
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::cache::IdCache;
+/// # use peak_can::socket::{CanFrame, MessageType, StandardId};
+/// # use peak_can::trace::{TraceFrame, TraceRecord};
+/// # use std::time::Duration;
+/// let speed = StandardId::new(0x100).unwrap();
+/// let mut cache = IdCache::new();
+/// // ignore the counter in the last byte
+/// cache.on_masked_change(speed, &[0xFF, 0xFF, 0x00], |change| {
+///     println!("{} at {:?}: {:02X?}", change.id, change.timestamp, change.frame.data());
+/// });
+///
+/// for (ms, data) in [(0, [0x10, 0x00, 1]), (10, [0x10, 0x00, 2]), (20, [0x12, 0x00, 3])] {
+///     cache.update(&TraceRecord {
+///         timestamp: Duration::from_millis(ms),
+///         channel: None,
+///         frame: TraceFrame::Can(CanFrame::from_id(speed, &data)?),
+///     });
+/// }
+/// assert_eq!(cache.get(speed).unwrap().count, 3);
+/// # Ok::<(), peak_can::socket::FrameConstructionError>(())
+/// ```
+#[derive(Default)]
+pub struct IdCache {
+    entries: HashMap<Id, CachedFrame>,
+    subscribers: Vec<Subscriber>,
+    next_id: u64,
+}
+
+impl IdCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invokes `callback` when the payload of any ID changes.
+    pub fn on_change<F>(&mut self, callback: F) -> SubscriptionId
+    where
+        F: FnMut(&ValueChange) + 'static,
+    {
+        self.subscribe(None, None, Box::new(callback))
+    }
+
+    /// Invokes `callback` when the payload of `can_id` changes.
+    pub fn on_id_change<F>(&mut self, can_id: impl Into<Id>, callback: F) -> SubscriptionId
+    where
+        F: FnMut(&ValueChange) + 'static,
+    {
+        self.subscribe(Some(can_id.into()), None, Box::new(callback))
+    }
+
+    /// Invokes `callback` when a bit set in `mask` changes in the payload of `can_id`, or when
+    /// the length of the payload changes. `mask[i]` applies to byte `i`, bytes beyond the mask
+    /// are compared in full.
+    pub fn on_masked_change<F>(
+        &mut self,
+        can_id: impl Into<Id>,
+        mask: &[u8],
+        callback: F,
+    ) -> SubscriptionId
+    where
+        F: FnMut(&ValueChange) + 'static,
+    {
+        self.subscribe(Some(can_id.into()), Some(mask.to_vec()), Box::new(callback))
+    }
+
+    /// Returns `false` if no subscriber with `id` is registered.
+    pub fn remove(&mut self, id: SubscriptionId) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|subscriber| subscriber.id != id);
+        self.subscribers.len() != len
+    }
+
+    /// Stores the frame of `record` as the latest of its ID and notifies the subscribers which
+    /// see a change.
+    pub fn update(&mut self, record: &TraceRecord) {
+        let id = record.frame.id();
+        let previous = self.entries.get(&id);
+        let previous_data = previous.map(|cached| cached.frame.data());
+        let change = ValueChange {
+            id,
+            previous: previous_data,
+            frame: &record.frame,
+            timestamp: record.timestamp,
+        };
+        for subscriber in &mut self.subscribers {
+            if subscriber.can_id.is_some_and(|can_id| can_id != id) {
+                continue;
+            }
+            if subscriber.is_changed(previous_data, record.frame.data()) {
+                (subscriber.callback)(&change);
+            }
+        }
+
+        let changed = match previous {
+            Some(cached) if cached.frame.data() == record.frame.data() => cached.changed,
+            _ => record.timestamp,
+        };
+        let count = previous.map_or(0, |cached| cached.count) + 1;
+        self.entries.insert(
+            id,
+            CachedFrame {
+                frame: record.frame,
+                timestamp: record.timestamp,
+                count,
+                changed,
+            },
+        );
+    }
+
+    pub fn get(&self, can_id: impl Into<Id>) -> Option<&CachedFrame> {
+        self.entries.get(&can_id.into())
+    }
+
+    /// The cached frames in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Id, &CachedFrame)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets all frames, the next frame of every ID is reported as a change.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn subscribe(
+        &mut self,
+        can_id: Option<Id>,
+        mask: Option<Vec<u8>>,
+        callback: ChangeCallback,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push(Subscriber {
+            id,
+            can_id,
+            mask,
+            callback,
+        });
+        id
+    }
+}
+
+impl TraceSink for IdCache {
+    fn on_frame(&mut self, record: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+        self.update(record);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::StandardId;
+    use crate::trace::test_util::record;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn notifies_changes_only() {
+        let ms = Duration::from_millis;
+        let mut cache = IdCache::new();
+        let all = Rc::new(RefCell::new(Vec::new()));
+        let masked = Rc::new(RefCell::new(Vec::new()));
+        let log = all.clone();
+        cache.on_change(move |change| {
+            log.borrow_mut()
+                .push((change.id.as_raw(), change.previous.map(<[u8]>::to_vec)))
+        });
+        let log = masked.clone();
+        let id = StandardId::new(0x100).unwrap();
+        let masked_id = cache.on_masked_change(id, &[0x0F], move |change| {
+            log.borrow_mut().push(change.frame.data().to_vec())
+        });
+
+        cache.update(&record(ms(0), None, 0x100, &[0x01, 0x01]));
+        cache.update(&record(ms(10), None, 0x100, &[0x01, 0x01]));
+        cache.update(&record(ms(20), None, 0x100, &[0x11, 0x01]));
+        cache.update(&record(ms(30), None, 0x100, &[0x12, 0x01]));
+        cache.update(&record(ms(40), None, 0x200, &[]));
+        cache.update(&record(ms(50), None, 0x100, &[0x12, 0x02]));
+        cache.update(&record(ms(60), None, 0x100, &[0x12]));
+
+        assert_eq!(
+            *all.borrow(),
+            [
+                (0x100, None),
+                (0x100, Some(vec![0x01, 0x01])),
+                (0x100, Some(vec![0x11, 0x01])),
+                (0x200, None),
+                (0x100, Some(vec![0x12, 0x01])),
+                (0x100, Some(vec![0x12, 0x02])),
+            ]
+        );
+        assert_eq!(
+            *masked.borrow(),
+            [
+                vec![0x01, 0x01],
+                vec![0x12, 0x01],
+                vec![0x12, 0x02],
+                vec![0x12]
+            ]
+        );
+
+        let cached = cache.get(id).unwrap();
+        assert_eq!(cached.count, 6);
+        assert_eq!(cached.changed, Duration::from_millis(60));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.remove(masked_id));
+        assert!(!cache.remove(masked_id));
+    }
+}
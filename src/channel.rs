@@ -1,6 +1,13 @@
 //!
 //!
 
+use crate::socket::{Backend, PcanBackend};
+
 pub trait Channel {
     fn channel(&self) -> u16;
+
+    /// The backend the parameters of the channel are read and written with.
+    fn backend(&self) -> &dyn Backend {
+        &PcanBackend
+    }
 }
@@ -0,0 +1,178 @@
+//! Conversion of device timestamps into host and wall-clock time.
+//!
+//! PCAN devices timestamp frames with their own clock, counting from the moment the device
+//! started. A [TimestampMapper] correlates it with the host clocks using frames received
+//! together with their host time, e.g. from
+//! [recv_with_host_time](crate::socket::RecvCan::recv_with_host_time), so that logs of several
+//! devices or other sources can be merged on a common wall-clock time base.
+
+use crate::socket::{ReceivedFrame, Timestamp};
+use crate::trace::TraceRecord;
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Maps device timestamps onto [Instant] and [SystemTime].
+///
+/// The host receives a frame some time after the device timestamped it. The mapper takes the
+/// lowest difference of both clocks observed as their offset, i.e. the frame which waited
+/// the shortest in the driver. Since device and host clocks drift apart, the offset is replaced
+/// by the lowest difference of the last interval at the end of each resync interval.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::clock::TimestampMapper;
+/// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut mapper = TimestampMapper::new();
+/// loop {
+///     let (frame, timestamp, host_time) = socket.recv_with_host_time()?;
+///     mapper.observe(timestamp.as_micros(), host_time);
+///     println!("{:?} {:03X}", mapper.to_system_time(&timestamp), frame.can_id());
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampMapper {
+    host_anchor: Instant,
+    wall_anchor: SystemTime,
+    /// Nanoseconds of host time since the anchor minus nanoseconds of device time.
+    offset: Option<i128>,
+    window_offset: Option<i128>,
+    window_start: Option<Instant>,
+    resync_interval: Duration,
+}
+
+impl Default for TimestampMapper {
+    fn default() -> Self {
+        TimestampMapper {
+            host_anchor: Instant::now(),
+            wall_anchor: SystemTime::now(),
+            offset: None,
+            window_offset: None,
+            window_start: None,
+            resync_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TimestampMapper {
+    /// Samples the host clocks, create it when the channel is opened.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the offset every `interval`, 10 s by default.
+    pub fn with_resync_interval(mut self, interval: Duration) -> Self {
+        self.resync_interval = interval;
+        self
+    }
+
+    /// Correlates a device timestamp in microseconds with the host time the frame was read.
+    pub fn observe(&mut self, device_micros: u64, host_time: Instant) {
+        let host = host_time
+            .saturating_duration_since(self.host_anchor)
+            .as_nanos() as i128;
+        let offset = host - device_micros as i128 * 1000;
+
+        let window_start = *self.window_start.get_or_insert(host_time);
+        let window_offset = self.window_offset.map_or(offset, |min| min.min(offset));
+        self.window_offset = Some(window_offset);
+        self.offset = Some(self.offset.map_or(offset, |min| min.min(offset)));
+        if host_time.saturating_duration_since(window_start) >= self.resync_interval {
+            self.offset = Some(window_offset);
+            self.window_offset = None;
+            self.window_start = None;
+        }
+    }
+
+    /// Correlates the timestamps of a received frame.
+    pub fn observe_received(&mut self, received: &ReceivedFrame) {
+        self.observe(received.device_timestamp, received.host_timestamp);
+    }
+
+    /// Whether a timestamp was observed, the conversions return `None` before.
+    pub fn is_synced(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    /// The host time of a device timestamp in microseconds.
+    pub fn micros_to_instant(&self, device_micros: u64) -> Option<Instant> {
+        let host = self.host_nanos(device_micros)?;
+        if host >= 0 {
+            self.host_anchor
+                .checked_add(Duration::from_nanos(host as u64))
+        } else {
+            self.host_anchor
+                .checked_sub(Duration::from_nanos(host.unsigned_abs() as u64))
+        }
+    }
+
+    /// The wall-clock time of a device timestamp in microseconds.
+    pub fn micros_to_system_time(&self, device_micros: u64) -> Option<SystemTime> {
+        let host = self.host_nanos(device_micros)?;
+        if host >= 0 {
+            self.wall_anchor
+                .checked_add(Duration::from_nanos(host as u64))
+        } else {
+            self.wall_anchor
+                .checked_sub(Duration::from_nanos(host.unsigned_abs() as u64))
+        }
+    }
+
+    pub fn to_instant(&self, timestamp: &Timestamp) -> Option<Instant> {
+        self.micros_to_instant(timestamp.as_micros())
+    }
+
+    pub fn to_system_time(&self, timestamp: &Timestamp) -> Option<SystemTime> {
+        self.micros_to_system_time(timestamp.as_micros())
+    }
+
+    /// Observes a received frame and converts it into a trace record whose timestamp is the
+    /// wall-clock time since the UNIX epoch, e.g. to merge it with the traces of other devices
+    /// by [TraceMerge](crate::trace::TraceMerge).
+    pub fn to_trace_record(&mut self, received: &ReceivedFrame) -> TraceRecord {
+        self.observe_received(received);
+        let mut record = TraceRecord::from(received);
+        if let Some(time) = self.micros_to_system_time(received.device_timestamp) {
+            record.timestamp = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        }
+        record
+    }
+
+    fn host_nanos(&self, device_micros: u64) -> Option<i128> {
+        Some(device_micros as i128 * 1000 + self.offset?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowest_delay_and_resync() {
+        let mut mapper = TimestampMapper::new().with_resync_interval(Duration::from_secs(1));
+        let anchor = mapper.host_anchor;
+        let at = |micros| anchor + Duration::from_micros(micros);
+        assert!(!mapper.is_synced());
+        assert_eq!(mapper.micros_to_instant(0), None);
+
+        // the device started 5 s before the host anchor
+        mapper.observe(5_001_000, at(1_500));
+        mapper.observe(5_002_000, at(2_100));
+        mapper.observe(5_003_000, at(3_400));
+        assert_eq!(mapper.micros_to_instant(5_010_000), Some(at(10_100)));
+        assert_eq!(
+            mapper.micros_to_system_time(5_010_000),
+            Some(mapper.wall_anchor + Duration::from_micros(10_100))
+        );
+
+        // the device clock runs slow, the lowest difference grows by 100 µs
+        mapper.observe(6_001_300, at(1_001_500));
+        mapper.observe(6_499_800, at(1_500_000));
+        assert_eq!(mapper.micros_to_instant(5_010_000), Some(at(10_100)));
+        mapper.observe(7_499_800, at(2_500_000));
+        assert_eq!(mapper.micros_to_instant(5_010_000), Some(at(10_200)));
+    }
+}
@@ -0,0 +1,696 @@
+//! Signal decoding and encoding with DBC files.
+//!
+//! Only the message (`BO_`) and signal (`SG_`) definitions are read, all other sections are
+//! skipped. Simple multiplexing with one multiplexor signal (`M`) per message and multiplexed
+//! signals (`m<value>`) is supported. Signals of extended multiplexing (`m<value>M`) are
+//! skipped with a [DbcWarning], the rest of the file is still read.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use peak_can::dbc::{Dbc, Decoder};
+//! # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+//! # use peak_can::bus::UsbBus;
+//! let dbc = Dbc::load("vehicle.dbc")?;
+//! let decoder = Decoder::new(&dbc);
+//! let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+//! for received in socket.iter() {
+//!     let (frame, _) = received?;
+//!     for signal in decoder.decode(&frame) {
+//!         println!("{} = {} {}", signal.name, signal.value, signal.unit);
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::message::SignalValue;
+use crate::payload::{ByteOrder, PayloadError, extract_bits, insert_bits};
+use crate::socket::{CanFrame, FrameConstructionError, MessageType};
+
+use core::fmt;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum DbcError {
+    Io(std::io::Error),
+    /// Invalid definition in the given line, counted from 1.
+    Parse {
+        line: usize,
+        reason: &'static str,
+    },
+    UnknownMessage(String),
+    UnknownSignal(String),
+    Payload(PayloadError),
+    Frame(FrameConstructionError),
+}
+
+impl fmt::Display for DbcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbcError::Io(err) => write!(f, "{err}"),
+            DbcError::Parse { line, reason } => write!(f, "Line {line}: {reason}"),
+            DbcError::UnknownMessage(name) => write!(f, "Unknown message {name}"),
+            DbcError::UnknownSignal(name) => write!(f, "Unknown signal {name}"),
+            DbcError::Payload(err) => write!(f, "{err}"),
+            DbcError::Frame(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for DbcError {}
+
+impl From<std::io::Error> for DbcError {
+    fn from(value: std::io::Error) -> Self {
+        DbcError::Io(value)
+    }
+}
+
+impl From<PayloadError> for DbcError {
+    fn from(value: PayloadError) -> Self {
+        DbcError::Payload(value)
+    }
+}
+
+impl From<FrameConstructionError> for DbcError {
+    fn from(value: FrameConstructionError) -> Self {
+        DbcError::Frame(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Multiplex {
+    /// The signal is always present.
+    None,
+    /// The signal selects which multiplexed signals are present.
+    Multiplexor,
+    /// The signal is present if the multiplexor has the given value.
+    Multiplexed(u64),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignalDef {
+    pub name: String,
+    pub start_bit: u16,
+    pub len: u8,
+    pub order: ByteOrder,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub multiplex: Multiplex,
+}
+
+impl SignalDef {
+    /// The physical value of the raw bit field.
+    pub fn physical(&self, raw: u64) -> f64 {
+        let value = if self.signed {
+            i64::from_raw(raw, self.len) as f64
+        } else {
+            raw as f64
+        };
+        value * self.factor + self.offset
+    }
+
+    fn encode(&self, data: &mut [u8], value: f64) -> Result<(), PayloadError> {
+        let raw = ((value - self.offset) / self.factor).round();
+        let (low, high) = if self.signed {
+            let half = 2f64.powi(self.len as i32 - 1);
+            (-half, half - 1.0)
+        } else {
+            (0.0, 2f64.powi(self.len as i32) - 1.0)
+        };
+        if !(low..=high).contains(&raw) {
+            return Err(PayloadError::ValueTooLarge);
+        }
+
+        let raw = if self.signed {
+            (raw as i64).to_raw(self.len)
+        } else {
+            raw as u64
+        };
+        insert_bits(data, self.start_bit, self.len, raw, self.order)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MessageDef {
+    pub id: u32,
+    pub msg_type: MessageType,
+    pub name: String,
+    pub dlc: u8,
+    pub signals: Vec<SignalDef>,
+}
+
+impl MessageDef {
+    fn multiplexor(&self) -> Option<&SignalDef> {
+        self.signals
+            .iter()
+            .find(|signal| signal.multiplex == Multiplex::Multiplexor)
+    }
+}
+
+/// A definition skipped while parsing, in the given line counted from 1.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DbcWarning {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for DbcWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.reason)
+    }
+}
+
+/// The messages and signals of a DBC file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Dbc {
+    pub messages: Vec<MessageDef>,
+    /// Definitions which were skipped because they are not supported.
+    pub warnings: Vec<DbcWarning>,
+}
+
+impl Dbc {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Dbc, DbcError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(s: &str) -> Result<Dbc, DbcError> {
+        let mut messages: Vec<MessageDef> = Vec::new();
+        let mut warnings = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let error = |reason| DbcError::Parse {
+                line: i + 1,
+                reason,
+            };
+
+            if line.starts_with("BO_ ") {
+                let message = parse_message(line).ok_or(error("invalid message definition"))?;
+                messages.push(message);
+            } else if line.starts_with("SG_ ") {
+                if is_extended_multiplexed(line) {
+                    warnings.push(DbcWarning {
+                        line: i + 1,
+                        reason: "extended multiplexing is not supported, signal skipped",
+                    });
+                    continue;
+                }
+                let signal = parse_signal(line).ok_or(error("invalid signal definition"))?;
+                messages
+                    .last_mut()
+                    .ok_or(error("signal outside of a message"))?
+                    .signals
+                    .push(signal);
+            }
+        }
+        Ok(Dbc { messages, warnings })
+    }
+
+    pub fn message(&self, name: &str) -> Option<&MessageDef> {
+        self.messages.iter().find(|message| message.name == name)
+    }
+
+    /// The definition of the message with the ID and type of `frame`.
+    pub fn message_of(&self, frame: &CanFrame) -> Option<&MessageDef> {
+        let msg_type = if frame.is_extended_frame() {
+            MessageType::Extended
+        } else {
+            MessageType::Standard
+        };
+        self.messages
+            .iter()
+            .find(|message| message.id == frame.can_id() && message.msg_type == msg_type)
+    }
+}
+
+fn parse_message(line: &str) -> Option<MessageDef> {
+    // BO_ <id> <name>: <dlc> <transmitter>
+    let mut fields = line.split_whitespace().skip(1);
+    let id: u32 = fields.next()?.parse().ok()?;
+    let name = fields.next()?.strip_suffix(':')?;
+    let dlc = fields.next()?.parse().ok()?;
+
+    // bit 31 marks extended IDs
+    let (id, msg_type) = if id & 0x8000_0000 != 0 {
+        (id & 0x7FFF_FFFF, MessageType::Extended)
+    } else {
+        (id, MessageType::Standard)
+    };
+    Some(MessageDef {
+        id,
+        msg_type,
+        name: name.to_string(),
+        dlc,
+        signals: Vec::new(),
+    })
+}
+
+/// Whether the signal is a multiplexed multiplexor (`m<value>M`) of extended multiplexing.
+fn is_extended_multiplexed(line: &str) -> bool {
+    let head = line.split_once(':').map_or(line, |(head, _)| head);
+    head.split_whitespace().nth(2).is_some_and(|indicator| {
+        indicator
+            .strip_prefix('m')
+            .and_then(|value| value.strip_suffix('M'))
+            .is_some_and(|value| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+fn parse_signal(line: &str) -> Option<SignalDef> {
+    // SG_ <name> [M|m<value>] : <start>|<len>@<order><sign> (<factor>,<offset>) [<min>|<max>]
+    //     "<unit>" <receivers>
+    let (head, spec) = line.strip_prefix("SG_")?.split_once(':')?;
+    let mut head = head.split_whitespace();
+    let name = head.next()?;
+    let multiplex = match head.next() {
+        None => Multiplex::None,
+        Some("M") => Multiplex::Multiplexor,
+        Some(m) => Multiplex::Multiplexed(m.strip_prefix('m')?.parse().ok()?),
+    };
+
+    let (layout, rest) = spec.trim_start().split_once(' ')?;
+    let (start_bit, layout) = layout.split_once('|')?;
+    let (len, layout) = layout.split_once('@')?;
+    let order = match layout.get(..1)? {
+        "0" => ByteOrder::BigEndian,
+        "1" => ByteOrder::LittleEndian,
+        _ => return None,
+    };
+    let signed = match layout.get(1..)? {
+        "+" => false,
+        "-" => true,
+        _ => return None,
+    };
+
+    let (scaling, rest) = rest.trim_start().strip_prefix('(')?.split_once(')')?;
+    let (factor, offset) = scaling.split_once(',')?;
+    let (range, rest) = rest.trim_start().strip_prefix('[')?.split_once(']')?;
+    let (min, max) = range.split_once('|')?;
+    let (unit, _) = rest.trim_start().strip_prefix('"')?.split_once('"')?;
+
+    Some(SignalDef {
+        name: name.to_string(),
+        start_bit: start_bit.parse().ok()?,
+        len: len.parse().ok().filter(|len| (1..=64).contains(len))?,
+        order,
+        signed,
+        factor: factor.trim().parse().ok()?,
+        offset: offset.trim().parse().ok()?,
+        min: min.trim().parse().ok()?,
+        max: max.trim().parse().ok()?,
+        unit: unit.to_string(),
+        multiplex,
+    })
+}
+
+/// A decoded signal value, scaled to its physical unit.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signal {
+    pub name: String,
+    pub raw: u64,
+    pub value: f64,
+    pub unit: String,
+}
+
+/// Decodes received frames into signals. The [DecodePlan]s of all messages are compiled when
+/// the decoder is created.
+pub struct Decoder<'a> {
+    plans: HashMap<(bool, u32), DecodePlan<'a>>,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(dbc: &'a Dbc) -> Self {
+        let plans = dbc
+            .messages
+            .iter()
+            .map(|message| {
+                let key = (message.msg_type == MessageType::Extended, message.id);
+                (key, DecodePlan::compile(message))
+            })
+            .collect();
+        Decoder { plans }
+    }
+
+    /// The compiled plan of the message of `frame`.
+    pub fn plan(&self, frame: &CanFrame) -> Option<&DecodePlan<'a>> {
+        self.plans.get(&(frame.is_extended_frame(), frame.can_id()))
+    }
+
+    /// The signals present in `frame`. Unknown frames have no signals, signals beyond the
+    /// received data are left out.
+    pub fn decode(&self, frame: &CanFrame) -> Vec<Signal> {
+        let mut decoded = Vec::new();
+        self.decode_into(frame, &mut decoded);
+        decoded.iter().map(DecodedSignal::to_signal).collect()
+    }
+
+    /// Like [decode](Decoder::decode), appending to `out` without allocating once `out` has
+    /// grown to the largest message. Returns `false` for unknown frames.
+    pub fn decode_into(&self, frame: &CanFrame, out: &mut Vec<DecodedSignal<'a>>) -> bool {
+        match self.plan(frame) {
+            Some(plan) => {
+                plan.decode_into(frame.data(), out);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/* DECODE PLANS */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Extraction {
+    /// Shift of the first 8 payload bytes loaded as a little endian word.
+    LittleWord(u8),
+    /// Shift of the first 8 payload bytes loaded as a big endian word.
+    BigWord(u8),
+    /// Bit by bit, for fields beyond the first 8 bytes of CAN FD payloads.
+    Bits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SignalPlan {
+    extraction: Extraction,
+    mask: u64,
+    /// Payload length the field needs.
+    min_len: usize,
+}
+
+impl SignalPlan {
+    fn compile(signal: &SignalDef) -> SignalPlan {
+        let start = signal.start_bit as usize;
+        let len = signal.len as usize;
+        let mask = if len == 64 { u64::MAX } else { (1 << len) - 1 };
+
+        let last_bit = match signal.order {
+            ByteOrder::LittleEndian => start + len - 1,
+            // the least significant bit, counted from the most significant bit of byte 0
+            ByteOrder::BigEndian => start / 8 * 8 + (7 - start % 8) + len - 1,
+        };
+        let min_len = last_bit / 8 + 1;
+        let extraction = match signal.order {
+            _ if min_len > 8 => Extraction::Bits,
+            ByteOrder::LittleEndian => Extraction::LittleWord(start as u8),
+            ByteOrder::BigEndian => Extraction::BigWord((63 - last_bit) as u8),
+        };
+        SignalPlan {
+            extraction,
+            mask,
+            min_len,
+        }
+    }
+}
+
+/// The signal extraction of a message compiled to a shift and a mask per signal.
+///
+/// The first 8 payload bytes are loaded once as a little and a big endian 64 bit word, so each
+/// signal within them costs a single shift and mask instead of a loop over its bits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodePlan<'a> {
+    message: &'a MessageDef,
+    signals: Vec<SignalPlan>,
+    multiplexor: Option<usize>,
+}
+
+impl<'a> DecodePlan<'a> {
+    pub fn compile(message: &'a MessageDef) -> Self {
+        DecodePlan {
+            message,
+            signals: message.signals.iter().map(SignalPlan::compile).collect(),
+            multiplexor: message
+                .signals
+                .iter()
+                .position(|signal| signal.multiplex == Multiplex::Multiplexor),
+        }
+    }
+
+    pub fn message(&self) -> &'a MessageDef {
+        self.message
+    }
+
+    /// Appends the signals present in `data` to `out`.
+    pub fn decode_into(&self, data: &[u8], out: &mut Vec<DecodedSignal<'a>>) {
+        let mut word = [0u8; 8];
+        let head = data.len().min(8);
+        word[..head].copy_from_slice(&data[..head]);
+        let little = u64::from_le_bytes(word);
+        let big = u64::from_be_bytes(word);
+
+        let raw = |index: usize| {
+            let plan = &self.signals[index];
+            if data.len() < plan.min_len {
+                return None;
+            }
+            match plan.extraction {
+                Extraction::LittleWord(shift) => Some(little >> shift & plan.mask),
+                Extraction::BigWord(shift) => Some(big >> shift & plan.mask),
+                Extraction::Bits => {
+                    let def = &self.message.signals[index];
+                    extract_bits(data, def.start_bit, def.len, def.order)
+                }
+            }
+        };
+
+        let selector = self.multiplexor.and_then(raw);
+        for (index, def) in self.message.signals.iter().enumerate() {
+            let present = match def.multiplex {
+                Multiplex::None | Multiplex::Multiplexor => true,
+                Multiplex::Multiplexed(value) => selector == Some(value),
+            };
+            if let Some(raw) = raw(index).filter(|_| present) {
+                out.push(DecodedSignal {
+                    def,
+                    raw,
+                    value: def.physical(raw),
+                });
+            }
+        }
+    }
+}
+
+/// A decoded signal borrowing its definition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodedSignal<'a> {
+    pub def: &'a SignalDef,
+    pub raw: u64,
+    pub value: f64,
+}
+
+impl DecodedSignal<'_> {
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            name: self.def.name.clone(),
+            raw: self.raw,
+            value: self.value,
+            unit: self.def.unit.clone(),
+        }
+    }
+}
+
+/// Encodes signal values into frames.
+pub struct Encoder<'a> {
+    dbc: &'a Dbc,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(dbc: &'a Dbc) -> Self {
+        Encoder { dbc }
+    }
+
+    /// Builds a frame of the message `message_name` with the given physical signal values.
+    /// Signals that are not given are 0. If multiplexed signals are given without the
+    /// multiplexor, the multiplexor is set to their multiplexor value.
+    pub fn encode(
+        &self,
+        message_name: &str,
+        signals: &[(&str, f64)],
+    ) -> Result<CanFrame, DbcError> {
+        let message = self
+            .dbc
+            .message(message_name)
+            .ok_or_else(|| DbcError::UnknownMessage(message_name.to_string()))?;
+        let mut data = vec![0u8; message.dlc as usize];
+
+        let mut selector = None;
+        for (name, value) in signals {
+            let signal = message
+                .signals
+                .iter()
+                .find(|signal| signal.name == *name)
+                .ok_or_else(|| DbcError::UnknownSignal(name.to_string()))?;
+            if let Multiplex::Multiplexed(value) = signal.multiplex {
+                selector = Some(value);
+            }
+            signal.encode(&mut data, *value)?;
+        }
+
+        if let Some(multiplexor) = message.multiplexor()
+            && let Some(selector) = selector
+            && !signals.iter().any(|(name, _)| *name == multiplexor.name)
+        {
+            insert_bits(
+                &mut data,
+                multiplexor.start_bit,
+                multiplexor.len,
+                selector,
+                multiplexor.order,
+            )?;
+        }
+        Ok(CanFrame::new(message.id, message.msg_type, &data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DBC: &str = r#"VERSION ""
+
+BU_: ECU
+
+BO_ 256 Engine: 4 ECU
+ SG_ Speed : 0|16@1+ (0.1,0) [0|6553.5] "km/h" Vector__XXX
+ SG_ Temperature : 23|8@0- (0.5,-10) [-74|53.5] "degC" Vector__XXX
+
+BO_ 2566852849 Diagnostics: 3 ECU
+ SG_ Mode M : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+ SG_ Voltage m1 : 8|16@1+ (0.01,0) [0|655.35] "V" Vector__XXX
+ SG_ Current m2 : 8|16@1- (0.1,0) [-3276.8|3276.7] "A" Vector__XXX
+
+CM_ SG_ 256 Speed "Vehicle speed";
+"#;
+
+    #[test]
+    fn parse_and_decode() {
+        let dbc = Dbc::parse(DBC).unwrap();
+        assert_eq!(dbc.messages.len(), 2);
+        let diagnostics = dbc.message("Diagnostics").unwrap();
+        assert_eq!(diagnostics.id, 0x18FF_10F1);
+        assert_eq!(diagnostics.msg_type, MessageType::Extended);
+        assert_eq!(diagnostics.signals[2].multiplex, Multiplex::Multiplexed(2));
+
+        let decoder = Decoder::new(&dbc);
+        let frame = CanFrame::new(0x100, MessageType::Standard, &[0xE8, 0x03, 0xFC, 0]).unwrap();
+        let signals = decoder.decode(&frame);
+        assert_eq!(signals[0].value, 100.0);
+        assert_eq!(signals[0].unit, "km/h");
+        assert_eq!(signals[1].value, -12.0);
+
+        let frame = CanFrame::new(0x18FF_10F1, MessageType::Extended, &[2, 0xF6, 0xFF]).unwrap();
+        let signals = decoder.decode(&frame);
+        assert_eq!(signals.len(), 2);
+        assert_eq!(signals[1].name, "Current");
+        assert_eq!(signals[1].value, -1.0);
+
+        assert!(matches!(
+            Dbc::parse("BO_ 1 A: 8 ECU\n SG_ B : 0|8@2+ (1,0) [0|0] \"\" ECU"),
+            Err(DbcError::Parse { line: 2, .. })
+        ));
+        assert!(dbc.warnings.is_empty());
+    }
+
+    #[test]
+    fn extended_multiplexing_is_skipped() {
+        let dbc = Dbc::parse(
+            "BO_ 1 A: 8 ECU
+ SG_ Service M : 0|8@1+ (1,0) [0|255] \"\" ECU
+ SG_ Subservice m1M : 8|8@1+ (1,0) [0|255] \"\" ECU
+ SG_ Value m2 : 16|8@1+ (1,0) [0|255] \"\" ECU
+SG_MUL_VAL_ 1 Value Subservice 2-2;
+",
+        )
+        .unwrap();
+        let names: Vec<_> = dbc.messages[0].signals.iter().map(|s| &s.name).collect();
+        assert_eq!(names, ["Service", "Value"]);
+        assert_eq!(
+            dbc.warnings,
+            [DbcWarning {
+                line: 3,
+                reason: "extended multiplexing is not supported, signal skipped",
+            }]
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let dbc = Dbc::parse(DBC).unwrap();
+        let encoder = Encoder::new(&dbc);
+
+        let frame = encoder
+            .encode("Engine", &[("Speed", 100.0), ("Temperature", -12.0)])
+            .unwrap();
+        assert_eq!(frame.can_id(), 0x100);
+        assert_eq!(frame.data(), &[0xE8, 0x03, 0xFC, 0]);
+
+        let frame = encoder.encode("Diagnostics", &[("Voltage", 12.5)]).unwrap();
+        assert!(frame.is_extended_frame());
+        assert_eq!(frame.data(), &[1, 0xE2, 0x04]);
+
+        assert!(matches!(
+            encoder.encode("Engine", &[("Speed", -1.0)]),
+            Err(DbcError::Payload(PayloadError::ValueTooLarge))
+        ));
+        assert!(matches!(
+            encoder.encode("Engine", &[("Rpm", 0.0)]),
+            Err(DbcError::UnknownSignal(_))
+        ));
+    }
+
+    #[test]
+    fn plans_match_bitwise_extraction() {
+        let mut signals = Vec::new();
+        for order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            for start_bit in (0..128).step_by(5) {
+                for len in [1, 7, 8, 13, 32, 64] {
+                    signals.push(SignalDef {
+                        name: format!("S{start_bit}_{len}"),
+                        start_bit,
+                        len,
+                        order,
+                        signed: true,
+                        factor: 1.0,
+                        offset: 0.0,
+                        min: 0.0,
+                        max: 0.0,
+                        unit: String::new(),
+                        multiplex: Multiplex::None,
+                    });
+                }
+            }
+        }
+        let message = MessageDef {
+            id: 0x100,
+            msg_type: MessageType::Standard,
+            name: String::from("Test"),
+            dlc: 16,
+            signals,
+        };
+        let plan = DecodePlan::compile(&message);
+
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for len in [0, 3, 8, 12, 16] {
+            let data = (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect::<Vec<_>>();
+            let mut decoded = Vec::new();
+            plan.decode_into(&data, &mut decoded);
+
+            let expected = message
+                .signals
+                .iter()
+                .filter_map(|def| extract_bits(&data, def.start_bit, def.len, def.order))
+                .collect::<Vec<_>>();
+            assert_eq!(decoded.iter().map(|s| s.raw).collect::<Vec<_>>(), expected);
+        }
+    }
+}
@@ -3,10 +3,9 @@
 //!
 
 use crate::channel::Channel;
-use crate::error::{CanError, CanOkError};
-use crate::peak_lib;
+use crate::error::CanError;
 use crate::peak_can;
-use std::ffi::c_void;
+use crate::socket::{EXTENDED_MASK, MessageType, STANDARD_MASK};
 
 /* MessageFilter traits */
 
@@ -20,49 +19,21 @@ pub trait MessageFilter {
 impl<T: HasMessageFilter + Channel> MessageFilter for T {
     fn is_open_filter(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_MESSAGE_FILTER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                if u32::from_le_bytes(data) == peak_can::PEAK_FILTER_OPEN {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_MESSAGE_FILTER as u8, &mut data)?;
+        if u32::from_le_bytes(data) == peak_can::PEAK_FILTER_OPEN {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
     fn is_closed_filter(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_MESSAGE_FILTER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                if u32::from_le_bytes(data) == peak_can::PEAK_FILTER_CLOSE {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_MESSAGE_FILTER as u8, &mut data)?;
+        if u32::from_le_bytes(data) == peak_can::PEAK_FILTER_CLOSE {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -76,39 +47,13 @@ pub trait SetMessageFilter {
 
 impl<T: HasSetMessageFilter + Channel> SetMessageFilter for T {
     fn set_open_filter(&self) -> Result<(), CanError> {
-        let mut data = peak_can::PEAK_FILTER_OPEN.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_MESSAGE_FILTER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = peak_can::PEAK_FILTER_OPEN.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_MESSAGE_FILTER as u8, &data)
     }
 
     fn set_closed_filter(&self) -> Result<(), CanError> {
-        let mut data = peak_can::PEAK_FILTER_CLOSE.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_MESSAGE_FILTER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = peak_can::PEAK_FILTER_CLOSE.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_MESSAGE_FILTER as u8, &data)
     }
 }
 
@@ -123,28 +68,14 @@ pub trait ReceiveStatus {
 impl<T: HasReceiveStatus + Channel> ReceiveStatus for T {
     fn is_receiving(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_RECEIVE_STATUS as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_RECEIVE_STATUS as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -157,24 +88,11 @@ pub trait SetReceiveStatus {
 
 impl<T: HasSetReceiveStatus + Channel> SetReceiveStatus for T {
     fn set_receiving(&self, status: bool) -> Result<(), CanError> {
-        let mut data = match status {
+        let data = match status {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_RECEIVE_STATUS as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_RECEIVE_STATUS as u8, &data)
     }
 }
 
@@ -189,28 +107,18 @@ pub trait AllowStatusFrames {
 impl<T: HasAllowStatusFrames + Channel> AllowStatusFrames for T {
     fn allows_status_frames(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_STATUS_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_ALLOW_STATUS_FRAMES as u8,
+            &mut data,
+        )?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -223,24 +131,11 @@ pub trait SetAllowStatusFrames {
 
 impl<T: HasSetAllowStatusFrames + Channel> SetAllowStatusFrames for T {
     fn allow_status_frames(&self, enable: bool) -> Result<(), CanError> {
-        let mut data = match enable {
+        let data = match enable {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_STATUS_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_ALLOW_STATUS_FRAMES as u8, &data)
     }
 }
 
@@ -255,28 +150,14 @@ pub trait AllowRTRFrames {
 impl<T: HasAllowRTRFrames + Channel> AllowRTRFrames for T {
     fn allows_rtr_frames(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_RTR_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_ALLOW_RTR_FRAMES as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -289,24 +170,11 @@ pub trait SetAllowRTRFrames {
 
 impl<T: HasSetAllowRTRFrames + Channel> SetAllowRTRFrames for T {
     fn allow_rtr_frames(&self, enable: bool) -> Result<(), CanError> {
-        let mut data = match enable {
+        let data = match enable {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_RTR_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_ALLOW_RTR_FRAMES as u8, &data)
     }
 }
 
@@ -321,28 +189,18 @@ pub trait AllowErrorFrames {
 impl<T: HasAllowErrorFrames + Channel> AllowErrorFrames for T {
     fn allows_error_frames(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_ERROR_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_ALLOW_ERROR_FRAMES as u8,
+            &mut data,
+        )?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -355,24 +213,11 @@ pub trait SetAllowErrorFrames {
 
 impl<T: HasSetAllowErrorFrames + Channel> SetAllowErrorFrames for T {
     fn allow_error_frames(&self, enable: bool) -> Result<(), CanError> {
-        let mut data = match enable {
+        let data = match enable {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_ERROR_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_ALLOW_ERROR_FRAMES as u8, &data)
     }
 }
 
@@ -387,28 +232,18 @@ pub trait AllowEchoFrames {
 impl<T: HasAllowEchoFrames + Channel> AllowEchoFrames for T {
     fn allows_echo_frames(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_ECHO_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_ALLOW_ECHO_FRAMES as u8,
+            &mut data,
+        )?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -421,24 +256,85 @@ pub trait SetAllowEchoFrames {
 
 impl<T: HasSetAllowEchoFrames + Channel> SetAllowEchoFrames for T {
     fn allow_echo_frames(&self, enable: bool) -> Result<(), CanError> {
-        let mut data = match enable {
+        let data = match enable {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ALLOW_ECHO_FRAMES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
+        self.backend().set_value(self.channel(), peak_can::PEAK_ALLOW_ECHO_FRAMES as u8, &data)
+    }
+}
+
+/* ACCEPTANCE FILTER */
+
+/// Acceptance code and mask as programmed by [SetAcceptanceFilter11Bit] and
+/// [SetAcceptanceFilter29Bit]. A set mask bit means "don't care", all other bits of the ID must
+/// equal the code.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AcceptanceFilter {
+    pub msg_type: MessageType,
+    pub code: u32,
+    pub mask: u32,
+}
+
+impl AcceptanceFilter {
+    pub fn new(msg_type: MessageType, code: u32, mask: u32) -> Self {
+        AcceptanceFilter {
+            msg_type,
+            code,
+            mask,
+        }
+    }
+
+    /// The filter the acceptance filter setters program for `ids`.
+    pub fn from_ids(msg_type: MessageType, ids: &[u32]) -> Self {
+        let id_mask = Self::id_mask(msg_type);
+        let code = ids
+            .iter()
+            .map(|x| *x & id_mask)
+            .fold(0xFF_FF_FF_FFu32, |x, y| x & y);
+        let mask = ids.iter().map(|x| *x & id_mask).fold(0u32, |x, y| x ^ y);
+        AcceptanceFilter::new(msg_type, code, mask)
+    }
+
+    fn id_mask(msg_type: MessageType) -> u32 {
+        match msg_type {
+            MessageType::Standard => STANDARD_MASK,
+            MessageType::Extended => EXTENDED_MASK,
+        }
+    }
+
+    fn write<C: Channel + ?Sized>(&self, channel: &C) -> Result<(), CanError> {
+        let parameter = match self.msg_type {
+            MessageType::Standard => peak_can::PEAK_ACCEPTANCE_FILTER_11BIT,
+            MessageType::Extended => peak_can::PEAK_ACCEPTANCE_FILTER_29BIT,
         };
+        let acceptance_mask_data = self.mask.to_le_bytes();
+        let acceptance_code_data = self.code.to_le_bytes();
+
+        let data = [
+            acceptance_mask_data[0],
+            acceptance_mask_data[1],
+            acceptance_mask_data[2],
+            acceptance_mask_data[3],
+            acceptance_code_data[0],
+            acceptance_code_data[1],
+            acceptance_code_data[2],
+            acceptance_code_data[3],
+        ];
+        channel
+            .backend()
+            .set_value(channel.channel(), parameter as u8, &data)
+    }
 
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+    /// Whether a frame passes the filter. Like the hardware filter, frames of the other
+    /// message type are not affected and always pass.
+    pub fn accepts(&self, msg_type: MessageType, can_id: u32) -> bool {
+        if msg_type != self.msg_type {
+            return true;
         }
+        let id_mask = Self::id_mask(msg_type);
+        (can_id ^ self.code) & !self.mask & id_mask == 0
     }
 }
 
@@ -453,24 +349,14 @@ pub trait AcceptanceFilter11Bit {
 impl<T: HasAcceptanceFilter11Bit + Channel> AcceptanceFilter11Bit for T {
     fn acceptance_filter_11bit(&self) -> Result<(u32, u32), CanError> {
         let mut data = [0u8; 8];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ACCEPTANCE_FILTER_11BIT as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let acceptance_mask = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
-                let acceptance_code = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
-                Ok((acceptance_mask, acceptance_code))
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_ACCEPTANCE_FILTER_11BIT as u8,
+            &mut data,
+        )?;
+        let acceptance_mask = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let acceptance_code = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        Ok((acceptance_mask, acceptance_code))
     }
 }
 
@@ -482,39 +368,7 @@ pub trait SetAcceptanceFilter11Bit {
 
 impl<T: HasSetAcceptanceFilter11Bit + Channel> SetAcceptanceFilter11Bit for T {
     fn set_acceptance_filter_11bit(&self, ids: &[u32]) -> Result<(), CanError> {
-        let acceptance_code = ids
-            .iter()
-            .map(|x| *x & 0x7_FFu32)
-            .fold(0xFF_FF_FF_FFu32, |x, y| x & y);
-        let acceptance_code_data = acceptance_code.to_le_bytes();
-
-        let acceptance_mask = ids.iter().map(|x| *x & 0x7_FFu32).fold(0u32, |x, y| x ^ y);
-        let acceptance_mask_data = acceptance_mask.to_le_bytes();
-
-        let mut data = [
-            acceptance_mask_data[0],
-            acceptance_mask_data[1],
-            acceptance_mask_data[2],
-            acceptance_mask_data[3],
-            acceptance_code_data[0],
-            acceptance_code_data[1],
-            acceptance_code_data[2],
-            acceptance_code_data[3],
-        ];
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ACCEPTANCE_FILTER_11BIT as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        AcceptanceFilter::from_ids(MessageType::Standard, ids).write(self)
     }
 }
 
@@ -529,24 +383,14 @@ pub trait AcceptanceFilter29Bit {
 impl<T: HasAcceptanceFilter29Bit + Channel> AcceptanceFilter29Bit for T {
     fn acceptance_filter_29bit(&self) -> Result<(u32, u32), CanError> {
         let mut data = [0u8; 8];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_ACCEPTANCE_FILTER_29BIT as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let acceptance_mask = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
-                let acceptance_code = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
-                Ok((acceptance_mask, acceptance_code))
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_ACCEPTANCE_FILTER_29BIT as u8,
+            &mut data,
+        )?;
+        let acceptance_mask = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let acceptance_code = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        Ok((acceptance_mask, acceptance_code))
     }
 }
 
@@ -558,41 +402,56 @@ pub trait SetAcceptanceFilter29Bit {
 
 impl<T: HasSetAcceptanceFilter29Bit + Channel> SetAcceptanceFilter29Bit for T {
     fn set_acceptance_filter_29bit(&self, ids: &[u32]) -> Result<(), CanError> {
-        let acceptance_code = ids
-            .iter()
-            .map(|x| *x & 0x1F_FF_FF_FFu32)
-            .fold(0xFF_FF_FF_FFu32, |x, y| x & y);
-        let acceptance_code_data = acceptance_code.to_le_bytes();
+        AcceptanceFilter::from_ids(MessageType::Extended, ids).write(self)
+    }
+}
 
-        let acceptance_mask = ids
-            .iter()
-            .map(|x| *x & 0x1F_FF_FF_FFu32)
-            .fold(0u32, |x, y| x ^ y);
-        let acceptance_mask_data = acceptance_mask.to_le_bytes();
+/* FilterMessages traits */
 
-        let mut data = [
-            acceptance_mask_data[0],
-            acceptance_mask_data[1],
-            acceptance_mask_data[2],
-            acceptance_mask_data[3],
-            acceptance_code_data[0],
-            acceptance_code_data[1],
-            acceptance_code_data[2],
-            acceptance_code_data[3],
-        ];
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_ACCEPTANCE_FILTER_29BIT as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
+pub(crate) trait HasFilterMessages {}
+
+pub trait FilterMessages {
+    /// Lets frames with IDs from `from_id` to `to_id` of the given type pass the driver
+    /// filter. The first range narrows an open filter, further ranges widen it.
+    fn filter(&self, from_id: u32, to_id: u32, msg_type: MessageType) -> Result<(), CanError>;
+    /// Programs the hardware acceptance filter for the message type of `filter`.
+    fn set_acceptance_filter(&self, filter: &AcceptanceFilter) -> Result<(), CanError>;
+    /// Drops all received frames until a range is added or the filter is reopened.
+    fn close_filter(&self) -> Result<(), CanError>;
+    /// Opens the driver filter and both acceptance filters again, so that all frames pass.
+    fn reopen_filter(&self) -> Result<(), CanError>;
+}
+
+impl<T: HasFilterMessages + Channel> FilterMessages for T {
+    fn filter(&self, from_id: u32, to_id: u32, msg_type: MessageType) -> Result<(), CanError> {
+        let mode = match msg_type {
+            MessageType::Standard => peak_can::PEAK_MODE_STANDARD,
+            MessageType::Extended => peak_can::PEAK_MODE_EXTENDED,
         };
+        self.backend()
+            .filter_messages(self.channel(), from_id, to_id, mode as u8)
+    }
 
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+    fn set_acceptance_filter(&self, filter: &AcceptanceFilter) -> Result<(), CanError> {
+        filter.write(self)
     }
+
+    fn close_filter(&self) -> Result<(), CanError> {
+        set_message_filter(self, peak_can::PEAK_FILTER_CLOSE)
+    }
+
+    fn reopen_filter(&self) -> Result<(), CanError> {
+        set_message_filter(self, peak_can::PEAK_FILTER_OPEN)?;
+        AcceptanceFilter::new(MessageType::Standard, 0, STANDARD_MASK).write(self)?;
+        AcceptanceFilter::new(MessageType::Extended, 0, EXTENDED_MASK).write(self)
+    }
+}
+
+fn set_message_filter<C: Channel + ?Sized>(channel: &C, value: u32) -> Result<(), CanError> {
+    let data = value.to_le_bytes();
+    channel.backend().set_value(
+        channel.channel(),
+        peak_can::PEAK_MESSAGE_FILTER as u8,
+        &data,
+    )
 }
@@ -0,0 +1,184 @@
+//! Discovery of the channels attached to the system.
+//!
+//! [attached_channels] lists every channel known to the driver so that applications can pick
+//! their hardware at runtime instead of hard-coding e.g. [UsbBus::USB1].
+
+use crate::bus::{Bus, DngBus, IsaBus, LanBus, PccBus, PciBus, UsbBus};
+use crate::error::CanError;
+use crate::hw::{self, ChannelConditionStatus, ChannelInformation, HasChannelCondition};
+use crate::info::{FeatureFlags, HasChannelFeatures, HasChannelVersion};
+use crate::peak_can;
+
+/// The bus of an attached channel, usable wherever a [Bus] is expected.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ChannelBus {
+    Isa(IsaBus),
+    Dng(DngBus),
+    Pci(PciBus),
+    Usb(UsbBus),
+    Pcc(PccBus),
+    Lan(LanBus),
+}
+
+impl TryFrom<u16> for ChannelBus {
+    type Error = ();
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        IsaBus::try_from(value)
+            .map(ChannelBus::Isa)
+            .or_else(|_| DngBus::try_from(value).map(ChannelBus::Dng))
+            .or_else(|_| PciBus::try_from(value).map(ChannelBus::Pci))
+            .or_else(|_| UsbBus::try_from(value).map(ChannelBus::Usb))
+            .or_else(|_| PccBus::try_from(value).map(ChannelBus::Pcc))
+            .or_else(|_| LanBus::try_from(value).map(ChannelBus::Lan))
+            .map_err(|_| ())
+    }
+}
+
+impl Bus for ChannelBus {
+    fn channel(&self) -> u16 {
+        match self {
+            ChannelBus::Isa(bus) => bus.channel(),
+            ChannelBus::Dng(bus) => bus.channel(),
+            ChannelBus::Pci(bus) => bus.channel(),
+            ChannelBus::Usb(bus) => bus.channel(),
+            ChannelBus::Pcc(bus) => bus.channel(),
+            ChannelBus::Lan(bus) => bus.channel(),
+        }
+    }
+}
+
+impl crate::channel::Channel for ChannelBus {
+    fn channel(&self) -> u16 {
+        Bus::channel(self)
+    }
+}
+
+impl HasChannelVersion for ChannelBus {}
+impl HasChannelCondition for ChannelBus {}
+impl HasChannelFeatures for ChannelBus {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChannelInfo {
+    pub bus: ChannelBus,
+    pub channel_handle: u16,
+    pub device_name: String,
+    pub device_type: u8,
+    pub device_id: u32,
+    pub controller_number: u8,
+    pub condition: ChannelConditionStatus,
+    /// `FEATURE_*` flags of the channel.
+    pub features: u32,
+}
+
+impl ChannelInfo {
+    pub fn is_available(&self) -> bool {
+        self.condition == ChannelConditionStatus::Available
+    }
+
+    pub fn is_fd_capable(&self) -> bool {
+        self.features & peak_can::FEATURE_FD_CAPABLE != 0
+    }
+
+    pub fn is_delay_capable(&self) -> bool {
+        self.features & peak_can::FEATURE_DELAY_CAPABLE != 0
+    }
+
+    pub fn is_io_capable(&self) -> bool {
+        self.features & peak_can::FEATURE_IO_CAPABLE != 0
+    }
+
+    pub fn feature_flags(&self) -> FeatureFlags {
+        FeatureFlags::from_bits(self.features)
+    }
+}
+
+impl TryFrom<&ChannelInformation> for ChannelInfo {
+    type Error = CanError;
+
+    fn try_from(value: &ChannelInformation) -> Result<Self, Self::Error> {
+        let info = &value.channel_information;
+        let bus = ChannelBus::try_from(info.channel_handle).map_err(|_| CanError::Unknown)?;
+        let condition = ChannelConditionStatus::try_from(info.channel_condition)
+            .map_err(|_| CanError::Unknown)?;
+
+        Ok(ChannelInfo {
+            bus,
+            channel_handle: info.channel_handle,
+            device_name: value.device_name(),
+            device_type: info.device_type,
+            device_id: info.device_id,
+            controller_number: info.controller_number,
+            condition,
+            features: info.device_features,
+        })
+    }
+}
+
+/// Lists all channels attached to the system.
+pub fn attached_channels() -> Result<Vec<ChannelInfo>, CanError> {
+    hw::attached_channels()?
+        .iter()
+        .map(ChannelInfo::try_from)
+        .collect()
+}
+
+/// Returns the first attached channel that is available and satisfies `predicate`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::discover;
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// let info = discover::first_available(|info| info.is_fd_capable())?.expect("no CAN FD channel");
+/// let socket = CanSocket::open(info.bus, Baudrate::Baud500K)?;
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub fn first_available<F>(predicate: F) -> Result<Option<ChannelInfo>, CanError>
+where
+    F: Fn(&ChannelInfo) -> bool,
+{
+    Ok(attached_channels()?
+        .into_iter()
+        .find(|info| info.is_available() && predicate(info)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_info_from_information() {
+        let mut information = ChannelInformation::new();
+        information.channel_information.channel_handle = peak_can::PEAK_USBBUS2 as u16;
+        information.channel_information.device_id = 7;
+        information.channel_information.device_features = peak_can::FEATURE_FD_CAPABLE;
+        information.channel_information.channel_condition = peak_can::PEAK_CHANNEL_AVAILABLE;
+        for (dst, src) in information
+            .channel_information
+            .device_name
+            .iter_mut()
+            .zip(b"PCAN-USB FD")
+        {
+            *dst = *src as _;
+        }
+
+        let info = ChannelInfo::try_from(&information).unwrap();
+        assert_eq!(info.bus, ChannelBus::Usb(UsbBus::USB2));
+        assert_eq!(info.bus.channel(), peak_can::PEAK_USBBUS2 as u16);
+        assert_eq!(info.device_name, "PCAN-USB FD");
+        assert_eq!(info.device_id, 7);
+        assert!(info.is_available());
+        assert!(info.is_fd_capable());
+        assert!(!info.is_io_capable());
+        assert!(info.feature_flags().contains(FeatureFlags::FD_CAPABLE));
+        assert!(
+            !info
+                .feature_flags()
+                .contains(FeatureFlags::FD_CAPABLE | FeatureFlags::IO_CAPABLE)
+        );
+
+        information.channel_information.channel_handle = 0;
+        assert!(ChannelInfo::try_from(&information).is_err());
+    }
+}
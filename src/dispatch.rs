@@ -0,0 +1,807 @@
+//! Callback based handling of received frames, receive errors and bus state changes.
+//!
+//! A [Dispatcher] reads a socket and invokes the registered callbacks on the thread that calls
+//! [step](Dispatcher::step) or [run](Dispatcher::run), never concurrently. A panicking callback
+//! is removed, the other callbacks keep being invoked.
+//!
+//! Frame subscribers are routed by ID or [FilterSet] and receive frames through a callback or a
+//! [BoundedQueue], so several protocol stacks can share one channel. Subscribers get each frame
+//! as a [ReceivedFrame], numbered in receive order and stamped with the device and host time.
+//! [Dispatcher::spawn] runs the receive loop on a thread owning the socket.
+//!
+//! Annotators attach [Annotations] to the frames they match, e.g. the rule that matched or the
+//! decoded message. Annotated frame callbacks get them along with the frame, for instance to
+//! write them into a [csv](crate::trace::csv) or [json](crate::trace::json) trace.
+//!
+//! [TraceSink]s, such as the trace writers, are registered with
+//! [add_sink](Dispatcher::add_sink) and get every frame with its annotations and the bus state
+//! changes.
+
+use crate::error::CanError;
+use crate::filter::FilterSet;
+use crate::queue::BoundedQueue;
+use crate::shutdown::Shutdown;
+use crate::socket::wait::Waiter;
+use crate::socket::{
+    BusState, BusStatusFrame, CanFrame, MessageType, ReceivedFrame, RecvCan, Timestamp,
+    WaitStrategy,
+};
+use crate::trace::{Annotations, TraceError, TraceRecord, TraceSink};
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Longest time a full [OverflowPolicy::Block](crate::queue::OverflowPolicy::Block) queue is
+/// waited on before checking whether the dispatcher was stopped.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Identifies a registered callback, e.g. to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(u64);
+
+type FrameCallback = Box<dyn FnMut(&ReceivedFrame)>;
+type AnnotateCallback = Box<dyn FnMut(&ReceivedFrame, &mut Annotations)>;
+type AnnotatedFrameCallback = Box<dyn FnMut(&ReceivedFrame, &Annotations)>;
+type ErrorCallback = Box<dyn FnMut(&CanError)>;
+type StateChangeCallback = Box<dyn FnMut(Option<BusState>, BusState)>;
+
+/// Which received frames a subscriber gets.
+enum Route {
+    All,
+    Id(u32, MessageType),
+    Filter(FilterSet),
+}
+
+impl Route {
+    fn matches(&self, frame: &CanFrame) -> bool {
+        match self {
+            Route::All => true,
+            Route::Id(can_id, msg_type) => {
+                frame.can_id() == *can_id
+                    && frame.is_extended_frame() == (*msg_type == MessageType::Extended)
+            }
+            Route::Filter(filter) => filter.accepts_frame(frame),
+        }
+    }
+}
+
+enum Callback {
+    Frame(Route, FrameCallback),
+    Queue(Route, Arc<BoundedQueue<ReceivedFrame>>),
+    Annotate(Route, AnnotateCallback),
+    Annotated(AnnotatedFrameCallback),
+    Error(ErrorCallback),
+    StateChange(StateChangeCallback),
+    Sink(Box<dyn TraceSink>),
+}
+
+/// Event sources are registered with `on_*` methods.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::dispatch::Dispatcher;
+/// # use peak_can::filter::{FilterSet, IdMatch};
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut dispatcher = Dispatcher::new();
+/// let responses = FilterSet::deny_all().allow(IdMatch::List(vec![0x7E8]));
+/// dispatcher.on_frame_matching(responses, |received| {
+///     println!("response {:02x?}", received.frame.data());
+/// });
+/// dispatcher.on_error(|err| eprintln!("receive failed: {err}"));
+/// dispatcher.on_state_change(|old, new| println!("bus state {old:?} -> {new:?}"));
+/// dispatcher.run(&socket);
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Default)]
+pub struct Dispatcher {
+    callbacks: Vec<(CallbackId, Callback)>,
+    next_id: u64,
+    bus_state: Option<BusState>,
+    panicked: Vec<CallbackId>,
+    sink_errors: Vec<(CallbackId, TraceError)>,
+    wait_strategy: WaitStrategy,
+    shutdown: Option<Shutdown>,
+    channel: Option<u16>,
+    sequence: u64,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how [run](Dispatcher::run) waits while the receive queue is empty.
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.wait_strategy = strategy;
+        self
+    }
+
+    /// Makes [run](Dispatcher::run) return once `shutdown` is triggered.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Sets the channel of the dispatched frames and of the records passed to the sinks.
+    pub fn with_channel(mut self, channel: u16) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Like [with_channel](Dispatcher::with_channel), for the dispatcher set up by
+    /// [spawn](Dispatcher::spawn).
+    pub fn set_channel(&mut self, channel: u16) {
+        self.channel = Some(channel);
+    }
+
+    /// Registers a callback for every received data or remote frame.
+    pub fn on_frame<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(&ReceivedFrame) + 'static,
+    {
+        self.register(Callback::Frame(Route::All, Box::new(callback)))
+    }
+
+    /// Registers a callback for the received frames accepted by `filter`.
+    pub fn on_frame_matching<F>(&mut self, filter: FilterSet, callback: F) -> CallbackId
+    where
+        F: FnMut(&ReceivedFrame) + 'static,
+    {
+        self.register(Callback::Frame(Route::Filter(filter), Box::new(callback)))
+    }
+
+    /// Registers a callback for the received frames with `can_id`.
+    pub fn on_id<F>(&mut self, can_id: u32, msg_type: MessageType, callback: F) -> CallbackId
+    where
+        F: FnMut(&ReceivedFrame) + 'static,
+    {
+        self.register(Callback::Frame(
+            Route::Id(can_id, msg_type),
+            Box::new(callback),
+        ))
+    }
+
+    /// Queues the received frames accepted by `filter` into `queue`, its overflow policy decides
+    /// what happens when the consumer falls behind. The subscription is removed once the
+    /// dispatcher holds the only reference to the queue.
+    ///
+    /// A full [OverflowPolicy::Block](crate::queue::OverflowPolicy::Block) queue stalls the
+    /// dispatcher until there is room again, the queue is abandoned or the shutdown is triggered,
+    /// in which case the frame is dropped.
+    pub fn forward(
+        &mut self,
+        filter: FilterSet,
+        queue: Arc<BoundedQueue<ReceivedFrame>>,
+    ) -> CallbackId {
+        self.register(Callback::Queue(Route::Filter(filter), queue))
+    }
+
+    /// Registers an annotator for the received frames accepted by `filter`. All annotators run
+    /// in registration order before any frame callback is invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::dispatch::Dispatcher;
+    /// # use peak_can::filter::{FilterSet, IdMatch};
+    /// # use peak_can::socket::{Baudrate, CanSocket};
+    /// # use peak_can::bus::UsbBus;
+    /// # use peak_can::trace::csv::CsvWriter;
+    /// # use peak_can::trace::TraceRecord;
+    /// # use std::fs::File;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// let mut dispatcher = Dispatcher::new().with_channel(1);
+    /// let engine = FilterSet::deny_all().allow(IdMatch::List(vec![0x0CF00400]));
+    /// dispatcher.annotate(engine, |_, annotations| {
+    ///     annotations.tag("matched rule 7");
+    ///     annotations.set("decoded", "EngineSpeed");
+    /// });
+    /// let mut csv = CsvWriter::new(File::create("annotated.csv")?);
+    /// dispatcher.on_annotated_frame(move |received, annotations| {
+    ///     let record = TraceRecord::from(received);
+    ///     csv.write_annotated(&record, annotations).expect("writing the trace failed");
+    /// });
+    /// dispatcher.run(&socket);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn annotate<F>(&mut self, filter: FilterSet, callback: F) -> CallbackId
+    where
+        F: FnMut(&ReceivedFrame, &mut Annotations) + 'static,
+    {
+        self.register(Callback::Annotate(
+            Route::Filter(filter),
+            Box::new(callback),
+        ))
+    }
+
+    /// Registers a callback for every received data or remote frame together with the
+    /// annotations attached to it, which are empty if no annotator matched.
+    pub fn on_annotated_frame<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(&ReceivedFrame, &Annotations) + 'static,
+    {
+        self.register(Callback::Annotated(Box::new(callback)))
+    }
+
+    /// Registers a sink for every received data or remote frame, together with its annotations,
+    /// and the bus state changes. A sink failing to write is removed, its error is kept in
+    /// [sink_errors](Dispatcher::sink_errors).
+    pub fn add_sink<T: TraceSink + 'static>(&mut self, sink: T) -> CallbackId {
+        self.register(Callback::Sink(Box::new(sink)))
+    }
+
+    /// Flushes all sinks, which [run](Dispatcher::run) does before it returns.
+    pub fn flush_sinks(&mut self) {
+        let mut failed = Vec::new();
+        self.invoke(|id, callback| match callback {
+            Callback::Sink(sink) => sink_result(id, sink.flush(), &mut failed),
+            _ => true,
+        });
+        self.sink_errors.extend(failed);
+    }
+
+    /// The sinks removed because they failed, with their errors.
+    pub fn sink_errors(&self) -> &[(CallbackId, TraceError)] {
+        &self.sink_errors
+    }
+
+    /// Registers a callback for receive errors other than an empty receive queue.
+    pub fn on_error<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(&CanError) + 'static,
+    {
+        self.register(Callback::Error(Box::new(callback)))
+    }
+
+    /// Registers a callback for changes of the bus state, reported by status frames, error
+    /// frames and receive errors. The first argument is the previous state, `None` before the
+    /// first report.
+    pub fn on_state_change<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(Option<BusState>, BusState) + 'static,
+    {
+        self.register(Callback::StateChange(Box::new(callback)))
+    }
+
+    /// Removes a callback, returns `false` if it was not registered.
+    pub fn remove(&mut self, id: CallbackId) -> bool {
+        let len = self.callbacks.len();
+        self.callbacks.retain(|(registered, _)| *registered != id);
+        self.callbacks.len() != len
+    }
+
+    /// The callbacks removed because they panicked.
+    pub fn panicked(&self) -> &[CallbackId] {
+        &self.panicked
+    }
+
+    /// The last reported bus state.
+    pub fn bus_state(&self) -> Option<BusState> {
+        self.bus_state
+    }
+
+    /// Dispatches all queued frames without blocking.
+    pub fn step<S: RecvCan>(&mut self, socket: &S) {
+        loop {
+            match socket.recv_with_host_time() {
+                Ok((frame, timestamp, host_time)) => {
+                    self.dispatch_frame(frame, &timestamp, host_time)
+                }
+                Err(CanError::QrcvEmpty) => break,
+                Err(err) => {
+                    self.dispatch_error(&err);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Dispatches received frames until the shutdown is triggered.
+    pub fn run<S: RecvCan>(&mut self, socket: &S) {
+        let mut waiter = Waiter::new(self.wait_strategy);
+        while !self.shutdown.as_ref().is_some_and(Shutdown::is_requested) {
+            match socket.recv_with_host_time() {
+                Ok((frame, timestamp, host_time)) => {
+                    waiter.reset();
+                    self.dispatch_frame(frame, &timestamp, host_time);
+                }
+                Err(CanError::QrcvEmpty) => waiter.wait(socket, None),
+                Err(err) => {
+                    waiter.reset();
+                    self.dispatch_error(&err);
+                }
+            }
+        }
+        self.flush_sinks();
+    }
+
+    /// Moves `socket` to a new thread running a dispatcher until the returned handle is
+    /// stopped. The callbacks are registered by `setup` on that thread, so they need not be
+    /// [Send].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::dispatch::Dispatcher;
+    /// # use peak_can::filter::{FilterSet, IdMatch};
+    /// # use peak_can::queue::{BoundedQueue, OverflowPolicy};
+    /// # use peak_can::socket::{Baudrate, CanSocket, MessageType};
+    /// # use peak_can::bus::UsbBus;
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// let responses = Arc::new(BoundedQueue::new(256, OverflowPolicy::DropOldest));
+    /// let diagnostics = responses.clone();
+    /// let handle = Dispatcher::spawn(socket, move |dispatcher| {
+    ///     let j1939 = IdMatch::Mask { id: 0x18FE_0000, mask: 0x1FFF_0000 };
+    ///     dispatcher.forward(FilterSet::deny_all().allow(j1939), diagnostics);
+    ///     dispatcher.on_id(0x100, MessageType::Standard, |received| println!("{received:?}"));
+    /// });
+    /// while let Some(received) = responses.pop_timeout(Duration::from_secs(1)) {
+    ///     println!("{:?} {:?}", received.frame, received.host_timestamp);
+    /// }
+    /// let socket = handle.stop();
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    pub fn spawn<S, F>(socket: S, setup: F) -> DispatcherHandle<S>
+    where
+        S: RecvCan + Send + 'static,
+        F: FnOnce(&mut Dispatcher) + Send + 'static,
+    {
+        let shutdown = Shutdown::new();
+        let stop = shutdown.clone();
+        let thread = thread::spawn(move || {
+            let mut dispatcher = Dispatcher::new().with_shutdown(stop);
+            setup(&mut dispatcher);
+            dispatcher.run(&socket);
+            socket
+        });
+        DispatcherHandle {
+            shutdown,
+            thread: Some(thread),
+        }
+    }
+
+    fn register(&mut self, callback: Callback) -> CallbackId {
+        let id = CallbackId(self.next_id);
+        self.next_id += 1;
+        self.callbacks.push((id, callback));
+        id
+    }
+
+    fn dispatch_frame(&mut self, frame: CanFrame, timestamp: &Timestamp, host_time: Instant) {
+        let received =
+            ReceivedFrame::from_can(frame, timestamp, host_time, self.channel, self.sequence);
+        self.sequence += 1;
+        let frame = &frame;
+        if let Some(status) = BusStatusFrame::decode(frame) {
+            self.change_state(status.bus_state);
+            return;
+        }
+        if let Some(counters) = frame.error_counters() {
+            self.change_state(counters.bus_state());
+            return;
+        }
+        if frame.is_error_frame() {
+            return;
+        }
+        let mut annotations = Annotations::new();
+        self.invoke(|_, callback| {
+            match callback {
+                Callback::Annotate(route, callback) if route.matches(frame) => {
+                    callback(&received, &mut annotations)
+                }
+                _ => {}
+            }
+            true
+        });
+        let record = TraceRecord::from(&received);
+        let mut failed = Vec::new();
+        let shutdown = self.shutdown.clone();
+        self.invoke(|id, callback| match callback {
+            Callback::Frame(route, callback) if route.matches(frame) => {
+                callback(&received);
+                true
+            }
+            Callback::Annotated(callback) => {
+                callback(&received, &annotations);
+                true
+            }
+            Callback::Queue(route, queue) if route.matches(frame) => {
+                let mut item = received.clone();
+                loop {
+                    // nobody takes the frames out of the queue any more
+                    if Arc::strong_count(queue) == 1 {
+                        return false;
+                    }
+                    match queue.push_timeout(item, STOP_CHECK_INTERVAL) {
+                        Ok(_) => return true,
+                        Err(_) if shutdown.as_ref().is_some_and(Shutdown::is_requested) => {
+                            return true;
+                        }
+                        Err(back) => item = back,
+                    }
+                }
+            }
+            Callback::Sink(sink) => {
+                sink_result(id, sink.on_frame(&record, &annotations), &mut failed)
+            }
+            _ => true,
+        });
+        self.sink_errors.extend(failed);
+    }
+
+    fn dispatch_error(&mut self, err: &CanError) {
+        self.invoke(|_, callback| {
+            if let Callback::Error(callback) = callback {
+                callback(err)
+            }
+            true
+        });
+        let state = match err {
+            CanError::BusLight | CanError::BusHeavy => BusState::ErrorWarning,
+            CanError::BusPassive => BusState::ErrorPassive,
+            CanError::BusOff => BusState::BusOff,
+            _ => return,
+        };
+        self.change_state(state);
+    }
+
+    fn change_state(&mut self, state: BusState) {
+        let old = self.bus_state.replace(state);
+        if old == Some(state) {
+            return;
+        }
+        let mut failed = Vec::new();
+        self.invoke(|id, callback| match callback {
+            Callback::StateChange(callback) => {
+                callback(old, state);
+                true
+            }
+            Callback::Sink(sink) => sink_result(id, sink.on_state(old, state), &mut failed),
+            _ => true,
+        });
+        self.sink_errors.extend(failed);
+    }
+
+    /// Calls `f` for each callback, removing the callbacks that panic or for which `f` returns
+    /// `false`.
+    fn invoke<F: FnMut(CallbackId, &mut Callback) -> bool>(&mut self, mut f: F) {
+        let mut removed = Vec::new();
+        for (id, callback) in &mut self.callbacks {
+            match catch_unwind(AssertUnwindSafe(|| f(*id, callback))) {
+                Ok(true) => {}
+                Ok(false) => removed.push(*id),
+                Err(_) => {
+                    removed.push(*id);
+                    self.panicked.push(*id);
+                }
+            }
+        }
+        if !removed.is_empty() {
+            self.callbacks.retain(|(id, _)| !removed.contains(id));
+        }
+    }
+}
+
+/// Whether a sink is kept, collecting the error of a failed one.
+fn sink_result(
+    id: CallbackId,
+    result: Result<(), TraceError>,
+    failed: &mut Vec<(CallbackId, TraceError)>,
+) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            failed.push((id, err));
+            false
+        }
+    }
+}
+
+/// Handle of a dispatcher thread started by [Dispatcher::spawn]. Dropping the handle stops the
+/// thread.
+pub struct DispatcherHandle<S> {
+    shutdown: Shutdown,
+    thread: Option<JoinHandle<S>>,
+}
+
+impl<S> DispatcherHandle<S> {
+    /// Stops the thread and returns the socket.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the dispatcher thread, if it panicked outside a callback.
+    pub fn stop(mut self) -> S {
+        self.shutdown.trigger();
+        let thread = self.thread.take().expect("dispatcher thread joined twice");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+impl<S> Drop for DispatcherHandle<S> {
+    fn drop(&mut self) {
+        self.shutdown.trigger();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::IdMatch;
+    use crate::queue::OverflowPolicy;
+    use crate::socket::MockCanSocket;
+    use crate::trace::csv::CsvWriter;
+    use crate::trace::json::JsonWriter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    #[test]
+    fn callbacks_and_panic_isolation() {
+        let socket = MockCanSocket::new();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut dispatcher = Dispatcher::new();
+
+        let log = events.clone();
+        dispatcher.on_frame(move |received| {
+            log.borrow_mut()
+                .push(format!("{:x}", received.frame.can_id()))
+        });
+        let log = events.clone();
+        dispatcher.on_frame_matching(FilterSet::deny_all().allow(IdMatch::Any), move |_| {
+            log.borrow_mut().push(String::from("filtered"));
+            panic!("callback failure");
+        });
+        let log = events.clone();
+        dispatcher.on_error(move |err| log.borrow_mut().push(format!("{err:?}")));
+        let log = events.clone();
+        dispatcher
+            .on_state_change(move |old, new| log.borrow_mut().push(format!("{old:?} {new:?}")));
+
+        for id in [0x100, 0x200] {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+        socket
+            .backend()
+            .push_rx_error(socket.channel(), CanError::BusOff);
+        dispatcher.step(&socket);
+        socket
+            .backend()
+            .push_rx_error(socket.channel(), CanError::BusOff);
+        dispatcher.step(&socket);
+
+        assert_eq!(
+            *events.borrow(),
+            ["100", "filtered", "200", "BusOff", "None BusOff", "BusOff"]
+        );
+        assert_eq!(dispatcher.panicked(), &[CallbackId(1)]);
+        assert_eq!(dispatcher.bus_state(), Some(BusState::BusOff));
+        assert!(dispatcher.remove(CallbackId(0)) && !dispatcher.remove(CallbackId(1)));
+    }
+
+    #[test]
+    fn annotations_reach_annotated_callbacks() {
+        let socket = MockCanSocket::new();
+        let annotated = Rc::new(RefCell::new(Vec::new()));
+        let mut dispatcher = Dispatcher::new();
+
+        let log = annotated.clone();
+        dispatcher.on_annotated_frame(move |received, annotations| {
+            log.borrow_mut().push((
+                received.frame.can_id(),
+                received.sequence,
+                annotations.clone(),
+            ))
+        });
+        dispatcher.annotate(FilterSet::allow_all(), |_, annotations| {
+            annotations.tag("seen")
+        });
+        dispatcher.annotate(
+            FilterSet::deny_all().allow(IdMatch::List(vec![0x100])),
+            |_, annotations| annotations.set("decoded", "EngineSpeed"),
+        );
+        dispatcher.annotate(FilterSet::allow_all(), |_, _| panic!("annotator failure"));
+
+        for id in [0x100, 0x200] {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+        dispatcher.step(&socket);
+
+        let annotated = annotated.borrow();
+        assert_eq!(annotated.len(), 2);
+        assert_eq!(annotated[0].0, 0x100);
+        assert_eq!(annotated[0].2.get("decoded"), Some("EngineSpeed"));
+        assert!(annotated[0].2.has_tag("seen"));
+        assert_eq!(annotated[1].1, 1);
+        assert_eq!(annotated[1].2.get("decoded"), None);
+        assert!(annotated[1].2.has_tag("seen"));
+        assert_eq!(dispatcher.panicked(), &[CallbackId(3)]);
+    }
+
+    #[test]
+    fn sinks_get_frames_and_states() {
+        let socket = MockCanSocket::new();
+        let mut dispatcher = Dispatcher::new().with_channel(2);
+        dispatcher.annotate(FilterSet::allow_all(), |_, annotations| {
+            annotations.set("rule", "7")
+        });
+        let written = Shared::default();
+        let csv = dispatcher.add_sink(CsvWriter::new(written.clone()));
+        let failing = dispatcher.add_sink(JsonWriter::new(Failing));
+        let states = States::default();
+        dispatcher.add_sink(states.clone());
+
+        for (id, data) in [(0x100, [1]), (0x200, [2])] {
+            let frame = CanFrame::new(id, MessageType::Standard, &data).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::from_micros(1500));
+            dispatcher.step(&socket);
+        }
+        socket
+            .backend()
+            .push_rx_error(socket.channel(), CanError::BusOff);
+        dispatcher.step(&socket);
+        dispatcher.flush_sinks();
+
+        let csv_text = String::from_utf8(written.0.borrow().clone()).unwrap();
+        assert_eq!(
+            csv_text.lines().collect::<Vec<_>>(),
+            [
+                "time;channel;id;extended;fd;brs;data;tags;annotations",
+                "0.001500;2;100;0;0;0;01;;rule=7",
+                "0.001500;2;200;0;0;0;02;;rule=7",
+            ]
+        );
+        assert_eq!(*states.0.borrow(), [(None, BusState::BusOff)]);
+        assert_eq!(dispatcher.sink_errors().len(), 1);
+        assert_eq!(dispatcher.sink_errors()[0].0, failing);
+        assert!(dispatcher.remove(csv) && !dispatcher.remove(failing));
+    }
+
+    /// A writer whose buffer stays readable while the sink is registered.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A sink recording the bus state changes.
+    #[derive(Clone, Default)]
+    struct States(Rc<RefCell<Vec<(Option<BusState>, BusState)>>>);
+
+    impl TraceSink for States {
+        fn on_frame(&mut self, _: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+            Ok(())
+        }
+
+        fn on_state(&mut self, old: Option<BusState>, new: BusState) -> Result<(), TraceError> {
+            self.0.borrow_mut().push((old, new));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), TraceError> {
+            Ok(())
+        }
+    }
+
+    /// A writer failing every write.
+    struct Failing;
+
+    impl std::io::Write for Failing {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spawned_subscriptions() {
+        let socket = MockCanSocket::new();
+        let (ids, id_frames) = mpsc::channel();
+        let forwarded = Arc::new(BoundedQueue::new(2, OverflowPolicy::DropOldest));
+        let forward = forwarded.clone();
+        let dropped = Arc::new(BoundedQueue::new(16, OverflowPolicy::DropOldest));
+        let abandoned = Arc::downgrade(&dropped);
+        let handle = Dispatcher::spawn(socket.clone(), move |dispatcher| {
+            dispatcher.set_channel(5);
+            dispatcher.on_id(0x100, MessageType::Extended, move |received| {
+                ids.send(received.frame.can_id()).unwrap()
+            });
+            dispatcher.forward(
+                FilterSet::deny_all().allow(IdMatch::Range(0x100..=0x1FF)),
+                forward,
+            );
+            dispatcher.forward(FilterSet::allow_all(), dropped);
+        });
+
+        for (id, msg_type) in [
+            (0x100, MessageType::Standard),
+            (0x100, MessageType::Extended),
+            (0x200, MessageType::Standard),
+            (0x180, MessageType::Standard),
+        ] {
+            let frame = CanFrame::new(id, msg_type, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+        let timeout = Duration::from_secs(1);
+        assert_eq!(id_frames.recv_timeout(timeout).unwrap(), 0x100);
+        // the third matching frame pushes the first one out of the full queue
+        let deadline = Instant::now() + timeout;
+        while forwarded.dropped() == 0 || abandoned.strong_count() > 0 {
+            assert!(Instant::now() < deadline, "frames were not forwarded");
+            thread::yield_now();
+        }
+        let forwarded = std::iter::from_fn(|| forwarded.try_pop()).collect::<Vec<_>>();
+        assert!(forwarded.iter().all(|received| received.channel == Some(5)));
+        let forwarded_ids = forwarded
+            .iter()
+            .map(|received| (received.frame.can_id(), received.frame.is_extended_frame()))
+            .collect::<Vec<_>>();
+        assert_eq!(forwarded_ids, [(0x100, true), (0x180, false)]);
+
+        assert!(!handle.is_finished());
+        assert_eq!(handle.stop().channel(), socket.channel());
+    }
+
+    #[test]
+    fn stop_while_forward_queue_is_full() {
+        let socket = MockCanSocket::new();
+        let queue = Arc::new(BoundedQueue::new(1, OverflowPolicy::Block));
+        let forward = queue.clone();
+        let handle = Dispatcher::spawn(socket.clone(), move |dispatcher| {
+            dispatcher.forward(FilterSet::allow_all(), forward);
+        });
+
+        for id in [0x100, 0x101] {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while queue.is_empty() {
+            assert!(Instant::now() < deadline, "frame was not forwarded");
+            thread::yield_now();
+        }
+        // the dispatcher now waits for room for the second frame
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(handle.stop().channel(), socket.channel());
+        assert_eq!(queue.try_pop().unwrap().frame.can_id(), 0x100);
+        assert!(queue.is_empty());
+    }
+}
@@ -5,6 +5,7 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io;
 use std::sync::Arc;
 
 use crate::peak_can;
@@ -66,6 +67,15 @@ pub enum CanError {
     Initialize,
     ///
     IllOperation,
+    /// A status combining several of the codes above, e.g. a bus error reported together with
+    /// a queue overrun.
+    Multiple(Vec<CanError>),
+    /// Status bits not defined by PCAN-Basic.
+    Other(u32),
+    /// No attached channel has this device ID, see
+    /// [open_by_device_id](crate::socket::usb::UsbCanSocket::open_by_device_id). Converts to
+    /// the status code of [CanError::IllHw].
+    DeviceNotFound(u32),
 }
 
 /// Type modeling all possible states of an operation as exposed by [PEAK_basic_sys].
@@ -114,6 +124,11 @@ impl From<CanError> for u32 {
             CanError::Caution => peak_can::PEAK_ERROR_CAUTION,
             CanError::Initialize => peak_can::PEAK_ERROR_INITIALIZE,
             CanError::IllOperation => peak_can::PEAK_ERROR_ILLOPERATION,
+            CanError::Multiple(errors) => errors
+                .into_iter()
+                .fold(0, |code, err| code | u32::from(err)),
+            CanError::Other(code) => code,
+            CanError::DeviceNotFound(_) => peak_can::PEAK_ERROR_ILLHW,
         }
     }
 }
@@ -158,11 +173,39 @@ impl TryFrom<u32> for CanError {
             peak_can::PEAK_ERROR_CAUTION => Ok(CanError::Caution),
             peak_can::PEAK_ERROR_INITIALIZE => Ok(CanError::Initialize),
             peak_can::PEAK_ERROR_ILLOPERATION => Ok(CanError::IllOperation),
-            _ => Err(()),
+            peak_can::PEAK_ERROR_OK => Err(()),
+            _ => Ok(CanError::decompose(value)),
         }
     }
 }
 
+/// Status codes occupying a single bit, in the order they are reported by [CanError::Multiple].
+const FLAGS: [(u32, CanError); 20] = [
+    (peak_can::PEAK_ERROR_XMTFULL, CanError::XmtFull),
+    (peak_can::PEAK_ERROR_OVERRUN, CanError::Overrun),
+    (peak_can::PEAK_ERROR_BUSLIGHT, CanError::BusLight),
+    (peak_can::PEAK_ERROR_BUSHEAVY, CanError::BusHeavy),
+    (peak_can::PEAK_ERROR_BUSOFF, CanError::BusOff),
+    (peak_can::PEAK_ERROR_QRCVEMPTY, CanError::QrcvEmpty),
+    (peak_can::PEAK_ERROR_QOVERRUN, CanError::QOverrun),
+    (peak_can::PEAK_ERROR_QXMTFULL, CanError::QxmtFull),
+    (peak_can::PEAK_ERROR_REGTEST, CanError::RegTest),
+    (peak_can::PEAK_ERROR_NODRIVER, CanError::NoDriver),
+    (peak_can::PEAK_ERROR_RESOURCE, CanError::Resource),
+    (peak_can::PEAK_ERROR_ILLPARAMTYPE, CanError::IllParamType),
+    (peak_can::PEAK_ERROR_ILLPARAMVAL, CanError::IllParamVal),
+    (peak_can::PEAK_ERROR_UNKNOWN, CanError::Unknown),
+    (peak_can::PEAK_ERROR_ILLDATA, CanError::IllData),
+    (peak_can::PEAK_ERROR_BUSPASSIVE, CanError::BusPassive),
+    (peak_can::PEAK_ERROR_ILLMODE, CanError::IllMode),
+    (peak_can::PEAK_ERROR_CAUTION, CanError::Caution),
+    (peak_can::PEAK_ERROR_INITIALIZE, CanError::Initialize),
+    (peak_can::PEAK_ERROR_ILLOPERATION, CanError::IllOperation),
+];
+
+/// The handle errors share a field of three bits instead of having a bit each.
+const HANDLE_ERRORS: u32 = peak_can::PEAK_ERROR_ILLCLIENT;
+
 impl TryFrom<u32> for CanOkError {
     type Error = ();
 
@@ -183,38 +226,268 @@ impl From<libloading::Error> for CanError {
     }
 }
 
-impl fmt::Display for CanError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl CanError {
+    /// Splits a status code which is not a single PCAN-Basic code into its parts.
+    fn decompose(value: u32) -> CanError {
+        let mut errors = Vec::new();
+        let handle = value & HANDLE_ERRORS;
+        if handle != 0 {
+            errors.push(match handle {
+                peak_can::PEAK_ERROR_HWINUSE => CanError::HwInUse,
+                peak_can::PEAK_ERROR_NETINUSE => CanError::NetInUse,
+                peak_can::PEAK_ERROR_ILLHW => CanError::IllHw,
+                peak_can::PEAK_ERROR_ILLNET => CanError::IllNet,
+                peak_can::PEAK_ERROR_ILLCLIENT => CanError::IllClient,
+                _ => CanError::Other(handle),
+            });
+        }
+        let mut rest = value & !HANDLE_ERRORS;
+        for (code, err) in FLAGS.iter() {
+            if rest & code != 0 {
+                errors.push(err.clone());
+                rest &= !code;
+            }
+        }
+        if rest != 0 {
+            errors.push(CanError::Other(rest));
+        }
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => CanError::Multiple(errors),
+        }
+    }
+
+    /// Whether this error is `err` or, for a combined status, one of its parts contains it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use peak_can::error::CanError;
+    /// let status = CanError::try_from(0x44).unwrap();
+    /// assert!(status.contains(&CanError::BusLight));
+    /// assert!(status.contains(&CanError::QOverrun));
+    /// assert!(!status.contains(&CanError::BusOff));
+    /// ```
+    pub fn contains(&self, err: &CanError) -> bool {
+        match (self, err) {
+            (CanError::Multiple(errors), _) => errors.iter().any(|part| part.contains(err)),
+            (CanError::Other(code), CanError::Other(other)) => code == other,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(err),
+        }
+    }
+
+    /// The [io::ErrorKind] closest to the error, for generic I/O handling and retry layers.
+    ///
+    /// Full queues and an empty receive queue are [WouldBlock](io::ErrorKind::WouldBlock), as
+    /// is the timeout of [recv_timeout](crate::socket::RecvCan::recv_timeout), which reports an
+    /// empty receive queue. A missing library, driver or hardware is
+    /// [NotFound](io::ErrorKind::NotFound), hardware used by another application is
+    /// [PermissionDenied](io::ErrorKind::PermissionDenied).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use peak_can::error::CanError;
+    /// # use std::io;
+    /// assert_eq!(CanError::QxmtFull.io_kind(), io::ErrorKind::WouldBlock);
+    /// let err = io::Error::from(CanError::NoDriver);
+    /// assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    /// ```
+    pub fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            CanError::XmtFull | CanError::QxmtFull | CanError::QrcvEmpty => {
+                io::ErrorKind::WouldBlock
+            }
+            CanError::Libloading(_)
+            | CanError::NoDriver
+            | CanError::RegTest
+            | CanError::IllHw
+            | CanError::IllNet
+            | CanError::IllClient
+            | CanError::DeviceNotFound(_) => io::ErrorKind::NotFound,
+            CanError::HwInUse | CanError::NetInUse => io::ErrorKind::PermissionDenied,
+            CanError::IllParamType | CanError::IllParamVal | CanError::IllData => {
+                io::ErrorKind::InvalidInput
+            }
+            CanError::Multiple(errors) => errors
+                .first()
+                .map_or(io::ErrorKind::Other, CanError::io_kind),
+            _ => io::ErrorKind::Other,
+        }
+    }
+
+    /// Whether the channel handle became invalid, e.g. because the device was unplugged or
+    /// the driver was reloaded during system sleep. The channel has to be initialized again.
+    pub fn is_handle_lost(&self) -> bool {
+        if let CanError::Multiple(errors) = self {
+            return errors.iter().any(CanError::is_handle_lost);
+        }
+        matches!(
+            self,
+            CanError::Initialize
+                | CanError::IllHw
+                | CanError::IllNet
+                | CanError::IllClient
+                | CanError::NoDriver
+        )
+    }
+}
+
+/// Language of the texts returned by `CAN_GetErrorText`, English keeps logs searchable.
+const ERROR_TEXT_LANGUAGE: u16 = 0x09;
+
+impl CanError {
+    /// The text PCAN-Basic describes the status code with, if the library provides one.
+    fn error_text(&self, library: &peak_can::Pcan) -> Option<String> {
+        // the buffer has to hold at least 256 characters
+        let mut buffer = [0u8; 256];
+        let code = unsafe {
+            library.CAN_GetErrorText(
+                u32::from(self.clone()),
+                ERROR_TEXT_LANGUAGE,
+                buffer.as_mut_ptr().cast(),
+            )
+        };
+        if code != peak_can::PEAK_ERROR_OK {
+            return None;
+        }
+        let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+        let text = String::from_utf8_lossy(&buffer[..len]).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// The text of the PCAN-Basic documentation, used when the library is not available.
+    fn description(&self) -> &'static str {
+        match self {
+            CanError::XmtFull => "Transmit buffer in CAN controller is full",
+            CanError::Overrun => "CAN controller was read too late",
+            CanError::BusLight => "Bus error: an error counter reached the 'light' limit",
+            CanError::BusHeavy => "Bus error: an error counter reached the 'heavy' limit",
+            CanError::BusPassive => "Bus error: the CAN controller is error passive",
+            CanError::BusOff => "Bus error: the CAN controller is in bus-off state",
+            CanError::AnyBusErr => "Bus error",
+            CanError::QrcvEmpty => "Receive queue is empty",
+            CanError::QOverrun => "Receive queue was read too late",
+            CanError::QxmtFull => "Transmit queue is full",
+            CanError::RegTest => "Test of the CAN controller hardware registers failed",
+            CanError::NoDriver => "Driver not loaded",
+            CanError::HwInUse => "Hardware already in use by a Net",
+            CanError::NetInUse => "A Client is already connected to the Net",
+            CanError::IllHw => "Hardware handle is invalid",
+            CanError::IllNet => "Net handle is invalid",
+            CanError::IllClient => "Client handle is invalid",
+            CanError::Resource => "Resource (FIFO, Client, timeout) cannot be created",
+            CanError::IllParamType => "Invalid parameter",
+            CanError::IllParamVal => "Invalid parameter value",
+            CanError::Unknown => "Unknown error",
+            CanError::IllData => "Invalid data, function, or action",
+            CanError::IllMode => "Driver object state is wrong for the attempted operation",
+            CanError::Caution => "Operation succeeded, but irregularities were registered",
+            CanError::Initialize => "Channel is not initialized",
+            CanError::IllOperation => "Invalid operation",
+            CanError::DeviceNotFound(_) => "No attached channel has the device ID",
+            CanError::Libloading(_) | CanError::Multiple(_) | CanError::Other(_) => {
+                "Undefined status"
+            }
+        }
+    }
+
+    fn write_text(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        library: Option<&peak_can::Pcan>,
+    ) -> fmt::Result {
         match self {
             CanError::Libloading(e) => write!(f, "{e}"),
-            CanError::XmtFull => write!(f, "xmt full"),
-            CanError::Overrun => write!(f, "overrun"),
-            CanError::BusLight => write!(f, "bus light"),
-            CanError::BusHeavy => write!(f, "bus heavy"),
-            CanError::BusPassive => write!(f, "bus passive"),
-            CanError::BusOff => write!(f, "bus off"),
-            CanError::AnyBusErr => write!(f, "any bus error"),
-            CanError::QrcvEmpty => write!(f, "qrcv empty"),
-            CanError::QOverrun => write!(f, "q overrun"),
-            CanError::QxmtFull => write!(f, "qxmt full"),
-            CanError::RegTest => write!(f, "reg test"),
-            CanError::NoDriver => write!(f, "no driver"),
-            CanError::HwInUse => write!(f, "hardware in use"),
-            CanError::NetInUse => write!(f, "network in use"),
-            CanError::IllHw => write!(f, "illegal hardware"),
-            CanError::IllNet => write!(f, "illegal network"),
-            CanError::IllClient => write!(f, "illegal client"),
-            CanError::Resource => write!(f, "resource"),
-            CanError::IllParamType => write!(f, "illegal parameter type"),
-            CanError::IllParamVal => write!(f, "illegal parameter value"),
-            CanError::Unknown => write!(f, "unknown"),
-            CanError::IllData => write!(f, "illegal data"),
-            CanError::IllMode => write!(f, "illegal mode"),
-            CanError::Caution => write!(f, "caution"),
-            CanError::Initialize => write!(f, "initialize"),
-            CanError::IllOperation => write!(f, "illegal operation"),
+            CanError::Multiple(errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    err.write_text(f, library)?;
+                }
+                Ok(())
+            }
+            CanError::DeviceNotFound(id) => write!(f, "No attached channel has the device ID {id}"),
+            err => match library.and_then(|library| err.error_text(library)) {
+                Some(text) => write!(f, "{text}"),
+                None => match err {
+                    CanError::Other(code) => write!(f, "Undefined status {code:#X}"),
+                    err => write!(f, "{}", err.description()),
+                },
+            },
         }
     }
 }
 
+/// Uses the text of `CAN_GetErrorText`, falling back to the texts of the PCAN-Basic
+/// documentation when the library is not loaded or doesn't know the code. Formatting an error
+/// never loads the library itself.
+impl fmt::Display for CanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_text(f, crate::loaded_peak_lib())
+    }
+}
+
 impl Error for CanError {}
+
+impl From<CanError> for io::Error {
+    fn from(value: CanError) -> Self {
+        io::Error::new(value.io_kind(), value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats with the texts of the documentation, whether or not the library is loaded.
+    struct Fallback<'a>(&'a CanError);
+
+    impl fmt::Display for Fallback<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.write_text(f, None)
+        }
+    }
+
+    #[test]
+    fn combined_status_codes() {
+        let code = peak_can::PEAK_ERROR_BUSPASSIVE
+            | peak_can::PEAK_ERROR_QOVERRUN
+            | peak_can::PEAK_ERROR_ILLNET;
+        let err = CanError::try_from(code).unwrap();
+        assert!(matches!(
+            &err,
+            CanError::Multiple(errors) if matches!(
+                errors.as_slice(),
+                [CanError::IllNet, CanError::QOverrun, CanError::BusPassive]
+            )
+        ));
+        assert!(err.is_handle_lost());
+        assert_eq!(u32::from(err.clone()), code);
+        assert_eq!(
+            Fallback(&err).to_string(),
+            "Net handle is invalid, Receive queue was read too late, \
+             Bus error: the CAN controller is error passive"
+        );
+        assert_eq!(
+            Fallback(&CanError::Other(0x100_0000)).to_string(),
+            "Undefined status 0x1000000"
+        );
+        assert_eq!(CanError::QxmtFull.description(), "Transmit queue is full");
+
+        assert!(matches!(
+            CanError::try_from(peak_can::PEAK_ERROR_ANYBUSERR),
+            Ok(CanError::AnyBusErr)
+        ));
+        assert!(matches!(
+            CanError::try_from(0x100_0000),
+            Ok(CanError::Other(0x100_0000))
+        ));
+        assert_eq!(err.io_kind(), io::ErrorKind::NotFound);
+        assert!(matches!(
+            CanOkError::try_from(peak_can::PEAK_ERROR_OK),
+            Ok(CanOkError::Ok)
+        ));
+    }
+}
@@ -0,0 +1,372 @@
+//! Software filtering of received frames.
+//!
+//! The hardware acceptance filter of PCAN channels is a single code and mask per ID type, so it
+//! usually lets through more than needed. A [FilterSet] is an ordered list of allow and deny
+//! rules on ID lists, masks and ranges. [FilteredSocket] applies it to a receive path, its rules
+//! can be changed while other threads receive.
+
+use crate::error::CanError;
+use crate::socket::{CanFdFrame, CanFrame, MessageType, RecvCan, RecvCanFd, Timestamp};
+
+use std::ops::RangeInclusive;
+use std::sync::{RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdMatch {
+    Any,
+    List(Vec<u32>),
+    /// The ID bits set in `mask` equal those of `id`.
+    Mask {
+        id: u32,
+        mask: u32,
+    },
+    Range(RangeInclusive<u32>),
+}
+
+impl IdMatch {
+    pub fn matches(&self, can_id: u32) -> bool {
+        match self {
+            IdMatch::Any => true,
+            IdMatch::List(ids) => ids.contains(&can_id),
+            IdMatch::Mask { id, mask } => (can_id ^ id) & mask == 0,
+            IdMatch::Range(range) => range.contains(&can_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterRule {
+    pub action: Action,
+    /// Only frames of this type match, both types if `None`.
+    pub msg_type: Option<MessageType>,
+    pub ids: IdMatch,
+}
+
+impl FilterRule {
+    pub fn new(action: Action, msg_type: Option<MessageType>, ids: IdMatch) -> Self {
+        FilterRule {
+            action,
+            msg_type,
+            ids,
+        }
+    }
+
+    pub fn matches(&self, can_id: u32, msg_type: MessageType) -> bool {
+        self.msg_type.is_none_or(|t| t == msg_type) && self.ids.matches(can_id)
+    }
+}
+
+/// Ordered filter rules. The first rule matching a frame decides, frames no rule matches get
+/// the default action.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::filter::{FilterSet, IdMatch};
+/// # use peak_can::socket::MessageType;
+/// let filter = FilterSet::deny_all()
+///     .deny(IdMatch::List(vec![0x7DF]))
+///     .allow(IdMatch::Range(0x700..=0x7FF))
+///     .allow(IdMatch::Mask { id: 0x18DA_00F1, mask: 0x1FFF_00FF });
+///
+/// assert!(filter.accepts(0x7E8, MessageType::Standard));
+/// assert!(!filter.accepts(0x7DF, MessageType::Standard));
+/// assert!(filter.accepts(0x18DA_10F1, MessageType::Extended));
+/// assert!(!filter.accepts(0x100, MessageType::Standard));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterSet {
+    rules: Vec<FilterRule>,
+    default: Action,
+}
+
+impl FilterSet {
+    /// An empty set passing all frames, to add deny rules to.
+    pub fn allow_all() -> Self {
+        FilterSet {
+            rules: Vec::new(),
+            default: Action::Allow,
+        }
+    }
+
+    /// An empty set rejecting all frames, to add allow rules to.
+    pub fn deny_all() -> Self {
+        FilterSet {
+            rules: Vec::new(),
+            default: Action::Deny,
+        }
+    }
+
+    /// Appends a rule allowing `ids` of both message types.
+    pub fn allow(mut self, ids: IdMatch) -> Self {
+        self.push(FilterRule::new(Action::Allow, None, ids));
+        self
+    }
+
+    /// Appends a rule denying `ids` of both message types.
+    pub fn deny(mut self, ids: IdMatch) -> Self {
+        self.push(FilterRule::new(Action::Deny, None, ids));
+        self
+    }
+
+    pub fn rules(&self) -> &[FilterRule] {
+        &self.rules
+    }
+
+    pub fn push(&mut self, rule: FilterRule) {
+        self.rules.push(rule);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of rules.
+    pub fn insert(&mut self, index: usize, rule: FilterRule) {
+        self.rules.insert(index, rule);
+    }
+
+    /// Removes the rule at `index`, `None` if there is none.
+    pub fn remove(&mut self, index: usize) -> Option<FilterRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index))
+    }
+
+    /// Keeps only the rules matching `predicate`.
+    pub fn retain<F: FnMut(&FilterRule) -> bool>(&mut self, predicate: F) {
+        self.rules.retain(predicate);
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn default_action(&self) -> Action {
+        self.default
+    }
+
+    pub fn set_default_action(&mut self, action: Action) {
+        self.default = action;
+    }
+
+    pub fn accepts(&self, can_id: u32, msg_type: MessageType) -> bool {
+        let action = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(can_id, msg_type))
+            .map_or(self.default, |rule| rule.action);
+        action == Action::Allow
+    }
+
+    /// Whether `frame` passes. Error and status frames always pass.
+    pub fn accepts_frame(&self, frame: &CanFrame) -> bool {
+        frame.is_error_frame()
+            || frame.is_status_frame()
+            || self.accepts(frame.can_id(), msg_type(frame.is_extended_frame()))
+    }
+
+    /// Whether `frame` passes. Error frames always pass.
+    pub fn accepts_fd_frame(&self, frame: &CanFdFrame) -> bool {
+        frame.is_error_frame() || self.accepts(frame.can_id(), msg_type(frame.is_extended_frame()))
+    }
+}
+
+impl Default for FilterSet {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+fn msg_type(extended: bool) -> MessageType {
+    if extended {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    }
+}
+
+/// A socket wrapper dropping received frames rejected by a [FilterSet]. Receive calls return
+/// the next accepted frame, [CanError::QrcvEmpty] if only rejected frames were queued.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::filter::{Action, FilterRule, FilterSet, FilteredSocket, IdMatch};
+/// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+/// # use peak_can::bus::UsbBus;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let socket = FilteredSocket::new(socket, FilterSet::deny_all().allow(IdMatch::Any));
+/// // later, e.g. from another thread
+/// let rule = FilterRule::new(Action::Deny, None, IdMatch::List(vec![0x100]));
+/// socket.update_filter(|filter| filter.insert(0, rule));
+/// let (frame, _) = socket.recv()?;
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct FilteredSocket<S> {
+    socket: S,
+    filter: RwLock<FilterSet>,
+}
+
+impl<S> FilteredSocket<S> {
+    pub fn new(socket: S, filter: FilterSet) -> Self {
+        FilteredSocket {
+            socket,
+            filter: RwLock::new(filter),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    /// A copy of the current rules.
+    pub fn filter(&self) -> FilterSet {
+        self.read_filter().clone()
+    }
+
+    pub fn set_filter(&self, filter: FilterSet) {
+        self.update_filter(|current| *current = filter);
+    }
+
+    /// Changes the rules in place, frames received afterwards are filtered by the new rules.
+    pub fn update_filter<F: FnOnce(&mut FilterSet)>(&self, update: F) {
+        update(&mut self.filter.write().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    fn read_filter(&self) -> RwLockReadGuard<'_, FilterSet> {
+        // the rules stay consistent even if an update panicked
+        self.filter.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<S: RecvCan> RecvCan for FilteredSocket<S> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        loop {
+            let (frame, timestamp) = self.socket.recv()?;
+            if self.read_filter().accepts_frame(&frame) {
+                return Ok((frame, timestamp));
+            }
+        }
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.recv().map(|(frame, _)| frame)
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        loop {
+            let (frame, timestamp, host_time) = self.socket.recv_with_host_time()?;
+            if self.read_filter().accepts_frame(&frame) {
+                return Ok((frame, timestamp, host_time));
+            }
+        }
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (frame, timestamp) = self.socket.recv_timeout(remaining)?;
+            if self.read_filter().accepts_frame(&frame) {
+                return Ok((frame, timestamp));
+            }
+        }
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        self.socket.wait_readable(timeout)
+    }
+}
+
+impl<S: RecvCanFd> RecvCanFd for FilteredSocket<S> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        loop {
+            let (frame, timestamp) = self.socket.recv_fd()?;
+            if self.read_filter().accepts_fd_frame(&frame) {
+                return Ok((frame, timestamp));
+            }
+        }
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.recv_fd().map(|(frame, _)| frame)
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        loop {
+            let (frame, timestamp, host_time) = self.socket.recv_fd_with_host_time()?;
+            if self.read_filter().accepts_fd_frame(&frame) {
+                return Ok((frame, timestamp, host_time));
+            }
+        }
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (frame, timestamp) = self.socket.recv_fd_timeout(remaining)?;
+            if self.read_filter().accepts_fd_frame(&frame) {
+                return Ok((frame, timestamp));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::MockCanSocket;
+
+    #[test]
+    fn rules_and_runtime_update() {
+        let mut filter = FilterSet::allow_all().deny(IdMatch::Range(0x100..=0x1FF));
+        filter.insert(
+            0,
+            FilterRule::new(
+                Action::Allow,
+                Some(MessageType::Standard),
+                IdMatch::List(vec![0x123]),
+            ),
+        );
+        assert!(filter.accepts(0x123, MessageType::Standard));
+        assert!(!filter.accepts(0x123, MessageType::Extended));
+        assert!(!filter.accepts(0x124, MessageType::Standard));
+        assert!(filter.accepts(0x200, MessageType::Standard));
+        assert!(filter.remove(0).is_some() && filter.remove(5).is_none());
+        assert!(!filter.accepts(0x123, MessageType::Standard));
+
+        let mock = MockCanSocket::new();
+        let socket = FilteredSocket::new(mock.clone(), filter);
+        for id in [0x100, 0x150, 0x200, 0x180] {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            mock.backend()
+                .push_rx(mock.channel(), frame, Timestamp::default());
+        }
+        assert_eq!(socket.recv_frame().unwrap().can_id(), 0x200);
+        assert!(matches!(socket.recv(), Err(CanError::QrcvEmpty)));
+
+        socket.update_filter(|filter| filter.set_default_action(Action::Deny));
+        socket.update_filter(|filter| filter.clear());
+        let frame = CanFrame::new(0x300, MessageType::Standard, &[]).unwrap();
+        mock.backend()
+            .push_rx(mock.channel(), frame, Timestamp::default());
+        assert!(matches!(
+            socket.recv_timeout(Duration::from_millis(5)),
+            Err(CanError::QrcvEmpty)
+        ));
+        assert_eq!(socket.filter(), FilterSet::deny_all());
+    }
+}
@@ -0,0 +1,330 @@
+//! Loaders for Intel HEX and Motorola S-record firmware images.
+//!
+//! Both formats are parsed into an [Image] of contiguous, address sorted [DataBlock]s which can
+//! be transferred block by block during reflashing.
+
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Contiguous data starting at `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataBlock {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl DataBlock {
+    /// Address of the first byte after the block.
+    pub fn end_address(&self) -> u64 {
+        self.address as u64 + self.data.len() as u64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Image {
+    blocks: Vec<DataBlock>,
+    start_address: Option<u32>,
+}
+
+impl Image {
+    /// Data blocks sorted by address, adjacent records are merged into one block.
+    pub fn blocks(&self) -> &[DataBlock] {
+        &self.blocks
+    }
+
+    /// Entry point of the image, if the file contains one.
+    pub fn start_address(&self) -> Option<u32> {
+        self.start_address
+    }
+
+    /// Total number of data bytes in all blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|b| b.data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum HexError {
+    Io(io::Error),
+    /// A record is malformed.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The checksum of a record does not match its content.
+    Checksum {
+        line: usize,
+    },
+    /// Two records write to the same address.
+    Overlap {
+        address: u32,
+    },
+    /// The file is neither Intel HEX nor S-record.
+    UnknownFormat,
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexError::Io(err) => write!(f, "{err}"),
+            HexError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            HexError::Checksum { line } => write!(f, "line {line}: checksum mismatch"),
+            HexError::Overlap { address } => write!(f, "Overlapping data at 0x{address:08X}"),
+            HexError::UnknownFormat => write!(f, "Unknown firmware file format"),
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+impl From<io::Error> for HexError {
+    fn from(value: io::Error) -> Self {
+        HexError::Io(value)
+    }
+}
+
+/// Collects records and merges them into contiguous blocks.
+#[derive(Default)]
+struct ImageBuilder {
+    records: BTreeMap<u32, Vec<u8>>,
+    start_address: Option<u32>,
+}
+
+impl ImageBuilder {
+    fn add(&mut self, address: u32, data: Vec<u8>) -> Result<(), HexError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.records.contains_key(&address) {
+            return Err(HexError::Overlap { address });
+        }
+        self.records.insert(address, data);
+        Ok(())
+    }
+
+    fn build(self) -> Result<Image, HexError> {
+        let mut blocks: Vec<DataBlock> = Vec::new();
+        for (address, data) in self.records {
+            match blocks.last_mut() {
+                Some(last) if last.end_address() > address as u64 => {
+                    return Err(HexError::Overlap { address });
+                }
+                Some(last) if last.end_address() == address as u64 => last.data.extend(data),
+                _ => blocks.push(DataBlock { address, data }),
+            }
+        }
+        Ok(Image {
+            blocks,
+            start_address: self.start_address,
+        })
+    }
+}
+
+/// Decodes the hex digits of a record and verifies that all bytes sum up to `checksum_sum`.
+fn record_bytes(hex: &str, line: usize, checksum_sum: u8) -> Result<Vec<u8>, HexError> {
+    let parse_error = |message: &str| HexError::Parse {
+        line,
+        message: message.to_string(),
+    };
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) || hex.len() < 2 {
+        return Err(parse_error("odd or missing number of hex digits"));
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| parse_error("invalid hex digit"))?;
+
+    if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != checksum_sum {
+        return Err(HexError::Checksum { line });
+    }
+    Ok(bytes)
+}
+
+fn be_address(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |address, b| (address << 8) | *b as u32)
+}
+
+/// Parses the content of an Intel HEX file.
+pub fn parse_intel_hex(content: &str) -> Result<Image, HexError> {
+    let mut builder = ImageBuilder::default();
+    let mut base = 0u32;
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse_error = |message: &str| HexError::Parse {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| parse_error("missing ':'"))?;
+        let bytes = record_bytes(hex, line_number, 0)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(parse_error("length does not match byte count"));
+        }
+
+        let offset = be_address(&bytes[1..3]);
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => builder.add(base.wrapping_add(offset), data.to_vec())?,
+            0x01 => break,
+            0x02 if data.len() == 2 => base = be_address(data) << 4,
+            0x03 if data.len() == 4 => {
+                let segment = be_address(&data[..2]);
+                builder.start_address = Some((segment << 4) + be_address(&data[2..]));
+            }
+            0x04 if data.len() == 2 => base = be_address(data) << 16,
+            0x05 if data.len() == 4 => builder.start_address = Some(be_address(data)),
+            kind => return Err(parse_error(&format!("invalid record type {kind:02X}"))),
+        }
+    }
+    builder.build()
+}
+
+/// Parses the content of a Motorola S-record file.
+pub fn parse_srecord(content: &str) -> Result<Image, HexError> {
+    let mut builder = ImageBuilder::default();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse_error = |message: &str| HexError::Parse {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        let (kind, hex) = match line.strip_prefix(['S', 's']) {
+            Some(rest) if rest.is_char_boundary(1) => rest.split_at(1),
+            _ => return Err(parse_error("missing 'S' record type")),
+        };
+        let bytes = record_bytes(hex, line_number, 0xFF)?;
+        if bytes.len() != bytes[0] as usize + 1 {
+            return Err(parse_error("length does not match byte count"));
+        }
+
+        let address_len = match kind {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            kind => return Err(parse_error(&format!("invalid record type S{kind}"))),
+        };
+        if bytes.len() < address_len + 2 {
+            return Err(parse_error("record too short"));
+        }
+        let address = be_address(&bytes[1..1 + address_len]);
+        let data = &bytes[1 + address_len..bytes.len() - 1];
+
+        match kind {
+            "1" | "2" | "3" => builder.add(address, data.to_vec())?,
+            "7" | "8" | "9" => builder.start_address = Some(address),
+            // header and record counts carry no image data
+            _ => {}
+        }
+    }
+    builder.build()
+}
+
+/// Loads an Intel HEX or S-record file, detecting the format from the first record.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Image, HexError> {
+    let content = fs::read_to_string(path)?;
+    match content.trim_start().chars().next() {
+        Some(':') => parse_intel_hex(&content),
+        Some('S') | Some('s') => parse_srecord(&content),
+        _ => Err(HexError::UnknownFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intel_hex_with_extended_address() {
+        let hex = ":020000040800F2\n\
+                   :0400000001020304F2\n\
+                   :02000400AABB95\n\
+                   :04000005080001C12D\n\
+                   :00000001FF\n";
+        let image = parse_intel_hex(hex).unwrap();
+
+        assert_eq!(
+            image.blocks(),
+            &[DataBlock {
+                address: 0x0800_0000,
+                data: vec![1, 2, 3, 4, 0xAA, 0xBB]
+            }]
+        );
+        assert_eq!(image.start_address(), Some(0x0800_01C1));
+        assert_eq!(image.len(), 6);
+    }
+
+    #[test]
+    fn intel_hex_checksum_error() {
+        let hex = ":0400000001020304F3\n";
+        assert!(matches!(
+            parse_intel_hex(hex),
+            Err(HexError::Checksum { line: 1 })
+        ));
+    }
+
+    #[test]
+    fn srecord_blocks() {
+        let srec = "S00600004844521B\n\
+                    S107000001020304EE\n\
+                    S10500100A0BD5\n\
+                    S5030002FA\n\
+                    S9030000FC\n";
+        let image = parse_srecord(srec).unwrap();
+
+        assert_eq!(
+            image.blocks(),
+            &[
+                DataBlock {
+                    address: 0x0000,
+                    data: vec![1, 2, 3, 4]
+                },
+                DataBlock {
+                    address: 0x0010,
+                    data: vec![0x0A, 0x0B]
+                },
+            ]
+        );
+        assert_eq!(image.start_address(), Some(0));
+    }
+
+    #[test]
+    fn overlapping_records() {
+        let srec = "S107000001020304EE\nS1050002AABB93\n";
+        assert!(matches!(
+            parse_srecord(srec),
+            Err(HexError::Overlap { address: 2 })
+        ));
+    }
+
+    #[test]
+    fn duplicate_start_address() {
+        let hex = ":020000000102FB\n:020000000304F7\n:00000001FF\n";
+        assert!(matches!(
+            parse_intel_hex(hex),
+            Err(HexError::Overlap { address: 0 })
+        ));
+    }
+}
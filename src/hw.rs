@@ -11,11 +11,12 @@ use std::mem::size_of;
 use std::net::Ipv4Addr;
 use std::os::raw::c_char;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ChannelConditionStatus {
     Unavailable,
     Available,
     Occupied,
+    /// Used by PCAN-View, which allows other applications to connect as well.
     CanView,
 }
 
@@ -55,22 +56,15 @@ pub trait ChannelCondition {
 impl<T: HasChannelCondition + Channel> ChannelCondition for T {
     fn channel_condition(&self) -> Result<ChannelConditionStatus, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_CONDITION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                4,
-            )
-        };
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_CHANNEL_CONDITION as u8,
+            &mut data,
+        )?;
 
         let value: u32 = u32::from_le_bytes(data);
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match ChannelConditionStatus::try_from(value) {
-                Ok(status) => Ok(status),
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+        match ChannelConditionStatus::try_from(value) {
+            Ok(status) => Ok(status),
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -87,49 +81,26 @@ pub trait ChannelIdentifying {
 
 impl<T: HasChannelIdentifying + Channel> ChannelIdentifying for T {
     fn set_channel_identifying(&self, value: bool) -> Result<(), CanError> {
-        let mut data = match value {
+        let data = match value {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
 
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_IDENTIFYING as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_CHANNEL_IDENTIFYING as u8, &data)
     }
 
     fn is_channel_identifying(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_IDENTIFYING as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_CHANNEL_IDENTIFYING as u8,
+            &mut data,
+        )?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -145,20 +116,8 @@ pub trait DeviceId {
 impl<T: HasDeviceId + Channel> DeviceId for T {
     fn device_id(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_DEVICE_ID as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_DEVICE_ID as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -172,21 +131,8 @@ pub trait SetDeviceId {
 impl<T: HasSetDeviceId + Channel> SetDeviceId for T {
     type Item = u32;
     fn set_device_id(&self, value: Self::Item) -> Result<(), CanError> {
-        let mut data = value.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_DEVICE_ID as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = value.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_DEVICE_ID as u8, &data)
     }
 }
 
@@ -201,24 +147,12 @@ pub trait HardwareName {
 impl<T: HasHardwareName + Channel> HardwareName for T {
     fn hardware_name(&self) -> Result<String, CanError> {
         let mut data = [0u8; peak_can::MAX_LENGTH_HARDWARE_NAME as usize];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_HARDWARE_NAME as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    Ok(String::from(s))
-                }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+        self.backend().get_value(self.channel(), peak_can::PEAK_HARDWARE_NAME as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                Ok(String::from(s))
+            }
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -235,20 +169,12 @@ pub trait ControllerNumber {
 impl<T: HasControllerNumber + Channel> ControllerNumber for T {
     fn controller_number(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CONTROLLER_NUMBER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_CONTROLLER_NUMBER as u8,
+            &mut data,
+        )?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -262,21 +188,8 @@ pub trait SetControllerNumber {
 impl<T: HasSetControllerNumber + Channel> SetControllerNumber for T {
     type Item = u32;
     fn set_controller_number(&self, value: Self::Item) -> Result<(), CanError> {
-        let mut data = value.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_CONTROLLER_NUMBER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = value.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_CONTROLLER_NUMBER as u8, &data)
     }
 }
 
@@ -291,28 +204,16 @@ pub trait IpAddress {
 impl<T: HasIpAddress + Channel> IpAddress for T {
     fn ip_address(&self) -> Result<Ipv4Addr, CanError> {
         let mut data = [0u8; 20];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IP_ADDRESS as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    match s.parse() {
-                        Ok(ip) => Ok(ip),
-                        Err(_) => Err(CanError::Unknown),
-                    }
+        self.backend().get_value(self.channel(), peak_can::PEAK_IP_ADDRESS as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                match s.parse() {
+                    Ok(ip) => Ok(ip),
+                    Err(_) => Err(CanError::Unknown),
                 }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
-            _ => Err(CanError::Unknown),
+            }
+            Err(_) => Err(CanError::Unknown),
         }
     }
 }
@@ -415,24 +316,12 @@ pub trait DevicePartNumber {
 impl<T: HasDevicePartNumber + Channel> DevicePartNumber for T {
     fn device_part_number(&self) -> Result<String, CanError> {
         let mut data = [0u8; 100];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_DEVICE_NUMBER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    Ok(String::from(s))
-                }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+        self.backend().get_value(self.channel(), peak_can::PEAK_DEVICE_NUMBER as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                Ok(String::from(s))
+            }
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -5,9 +5,66 @@ use crate::channel::Channel;
 use crate::error::{CanError, CanOkError};
 use crate::peak_lib;
 use crate::peak_can;
+use core::fmt;
+use core::ops::BitOr;
 use std::ffi::c_void;
 
-pub fn api_version() -> Result<String, CanError> {
+/// A version number as reported by the driver, e.g. `4.6.1.728` for the API or `3.2.0` for
+/// the firmware of a device.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct VersionNumber {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The fourth component, if the version has one.
+    pub build: Option<u32>,
+}
+
+impl VersionNumber {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        VersionNumber {
+            major,
+            minor,
+            patch,
+            build: None,
+        }
+    }
+
+    /// Parses the first version number of `text`, which needs at least major and minor.
+    /// Missing components are 0.
+    pub fn parse(text: &str) -> Option<Self> {
+        let start = text.find(|c: char| c.is_ascii_digit())?;
+        let text = &text[start..];
+        let end = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(text.len());
+        let mut parts = text[..end].split('.').filter(|part| !part.is_empty());
+        let mut next = || parts.next().map(str::parse::<u32>).transpose().ok();
+        let major = next()??;
+        let minor = next()??;
+        let patch = next()?.unwrap_or(0);
+        let build = next()?;
+        Some(VersionNumber {
+            major,
+            minor,
+            patch,
+            build,
+        })
+    }
+}
+
+impl fmt::Display for VersionNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(build) = self.build {
+            write!(f, ".{build}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Version of the PCAN-Basic library.
+pub fn api_version() -> Result<VersionNumber, CanError> {
     let mut data = [0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize];
     let code = unsafe {
         peak_lib()?.CAN_GetValue(
@@ -19,18 +76,17 @@ pub fn api_version() -> Result<String, CanError> {
     };
 
     match CanOkError::try_from(code) {
-        Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-            Ok(s) => {
-                let s = s.trim_matches(char::from(0));
-                Ok(String::from(s))
-            }
-            Err(_) => Err(CanError::Unknown),
-        },
+        Ok(CanOkError::Ok) => parse_version(&data),
         Ok(CanOkError::Err(err)) => Err(err),
         Err(_) => Err(CanError::Unknown),
     }
 }
 
+fn parse_version(data: &[u8]) -> Result<VersionNumber, CanError> {
+    let text = std::str::from_utf8(data).map_err(|_| CanError::Unknown)?;
+    VersionNumber::parse(text.trim_matches(char::from(0))).ok_or(CanError::Unknown)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Version {
     pub device_driver_name_and_version: String,
@@ -49,126 +105,119 @@ pub trait ChannelVersion {
 impl<T: HasChannelVersion + Channel> ChannelVersion for T {
     fn channel_version(&self) -> Result<Version, CanError> {
         let mut data = [0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_VERSION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let newlines = s.lines().collect::<Vec<_>>();
-
-                    if newlines.len() == 3 {
-                        let newlines = newlines
-                            .iter()
-                            .map(|s| s.trim_matches(char::from(0)))
-                            .collect::<Vec<_>>();
-
-                        Ok(Version {
-                            device_driver_name_and_version: String::from(newlines[0]),
-                            year_of_copyright: String::from(newlines[1]),
-                            company_name_and_city: String::from(newlines[2]),
-                        })
-                    } else {
-                        Err(CanError::Unknown)
-                    }
+        self.backend().get_value(self.channel(), peak_can::PEAK_CHANNEL_VERSION as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let newlines = s.lines().collect::<Vec<_>>();
+
+                if newlines.len() == 3 {
+                    let newlines = newlines
+                        .iter()
+                        .map(|s| s.trim_matches(char::from(0)))
+                        .collect::<Vec<_>>();
+
+                    Ok(Version {
+                        device_driver_name_and_version: String::from(newlines[0]),
+                        year_of_copyright: String::from(newlines[1]),
+                        company_name_and_city: String::from(newlines[2]),
+                    })
+                } else {
+                    Err(CanError::Unknown)
                 }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+            }
             Err(_) => Err(CanError::Unknown),
         }
     }
 }
 
+/// The `FEATURE_*` flags of a channel.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+pub struct FeatureFlags(u32);
+
+impl FeatureFlags {
+    /// The channel can be initialized with CAN FD.
+    pub const FD_CAPABLE: FeatureFlags = FeatureFlags(peak_can::FEATURE_FD_CAPABLE);
+    /// The channel supports an interframe delay.
+    pub const DELAY_CAPABLE: FeatureFlags = FeatureFlags(peak_can::FEATURE_DELAY_CAPABLE);
+    /// The device has I/O pins.
+    pub const IO_CAPABLE: FeatureFlags = FeatureFlags(peak_can::FEATURE_IO_CAPABLE);
+
+    /// Keeps unknown bits, e.g. features of newer drivers.
+    pub const fn from_bits(bits: u32) -> Self {
+        FeatureFlags(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: FeatureFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for FeatureFlags {
+    type Output = FeatureFlags;
+
+    fn bitor(self, rhs: FeatureFlags) -> FeatureFlags {
+        FeatureFlags(self.0 | rhs.0)
+    }
+}
+
 /* ChannelFeatures trait */
 
 pub(crate) trait HasChannelFeatures {}
 
 pub trait ChannelFeatures {
+    /// All features, e.g. to check that a channel supports CAN FD before opening it with
+    /// [CanSocket::open_fd](crate::socket::CanSocket::open_fd).
+    fn channel_features(&self) -> Result<FeatureFlags, CanError>;
     fn is_fd_capable(&self) -> Result<bool, CanError>;
     fn is_delay_capable(&self) -> Result<bool, CanError>;
     fn is_io_capable(&self) -> Result<bool, CanError>;
 }
 
 impl<T: HasChannelFeatures + Channel> ChannelFeatures for T {
+    fn channel_features(&self) -> Result<FeatureFlags, CanError> {
+        let mut data = [0u8; 4];
+        self.backend().get_value(self.channel(), peak_can::PEAK_CHANNEL_FEATURES as u8, &mut data)?;
+        Ok(FeatureFlags::from_bits(u32::from_le_bytes(data)))
+    }
+
     fn is_fd_capable(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_FEATURES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::FEATURE_FD_CAPABLE == peak_can::FEATURE_FD_CAPABLE {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_CHANNEL_FEATURES as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::FEATURE_FD_CAPABLE == peak_can::FEATURE_FD_CAPABLE {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
     fn is_delay_capable(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_FEATURES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::FEATURE_DELAY_CAPABLE == peak_can::FEATURE_DELAY_CAPABLE {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_CHANNEL_FEATURES as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::FEATURE_DELAY_CAPABLE == peak_can::FEATURE_DELAY_CAPABLE {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
     fn is_io_capable(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_CHANNEL_FEATURES as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::FEATURE_IO_CAPABLE == peak_can::FEATURE_IO_CAPABLE {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_CHANNEL_FEATURES as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::FEATURE_IO_CAPABLE == peak_can::FEATURE_IO_CAPABLE {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -184,24 +233,10 @@ pub trait BitrateInfo {
 impl<T: HasBitrateInfo + Channel> BitrateInfo for T {
     fn bitrate_info(&self) -> Result<(u16, u16), CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BITRATE_INFO as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let btr0 = u16::from_le_bytes([data[0], data[1]]);
-                let btr1 = u16::from_le_bytes([data[2], data[3]]);
-                Ok((btr0, btr1))
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_BITRATE_INFO as u8, &mut data)?;
+        let btr0 = u16::from_le_bytes([data[0], data[1]]);
+        let btr1 = u16::from_le_bytes([data[2], data[3]]);
+        Ok((btr0, btr1))
     }
 }
 
@@ -216,24 +251,12 @@ pub trait BitrateInfoFd {
 impl<T: HasBitrateInfoFd + Channel> BitrateInfoFd for T {
     fn bitrate_info_fd(&self) -> Result<String, CanError> {
         let mut data = [0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BITRATE_INFO_FD as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    Ok(String::from(s))
-                }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+        self.backend().get_value(self.channel(), peak_can::PEAK_BITRATE_INFO_FD as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                Ok(String::from(s))
+            }
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -250,20 +273,8 @@ pub trait NominalBusSpeed {
 impl<T: HasNominalBusSpeed + Channel> NominalBusSpeed for T {
     fn nominal_bus_speed(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BUSSPEED_NOMINAL as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_BUSSPEED_NOMINAL as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -278,20 +289,8 @@ pub trait DataBusSpeed {
 impl<T: HasDataBusSpeed + Channel> DataBusSpeed for T {
     fn data_bus_speed(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BUSSPEED_DATA as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_BUSSPEED_DATA as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -352,31 +351,37 @@ pub fn lan_service_is_stopped() -> Result<bool, CanError> {
 pub(crate) trait HasFirmwareVersion {}
 
 pub trait FirmwareVersion {
-    fn firmware_version(&self) -> Result<String, CanError>;
+    fn firmware_version(&self) -> Result<VersionNumber, CanError>;
 }
 
 impl<T: HasFirmwareVersion + Channel> FirmwareVersion for T {
-    fn firmware_version(&self) -> Result<String, CanError> {
+    fn firmware_version(&self) -> Result<VersionNumber, CanError> {
         let mut data = [0u8; 18usize];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_FIRMWARE_VERSION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    Ok(String::from(s))
-                }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_FIRMWARE_VERSION as u8, &mut data)?;
+        parse_version(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_number_parse() {
+        let api = VersionNumber::parse("4.6.1.728").unwrap();
+        assert_eq!(api.build, Some(728));
+        assert_eq!(api.to_string(), "4.6.1.728");
+        assert_eq!(
+            VersionNumber::parse("3.2"),
+            Some(VersionNumber::new(3, 2, 0))
+        );
+        assert_eq!(
+            VersionNumber::parse("PCAN-USB FD v3.2.0 (beta)"),
+            Some(VersionNumber::new(3, 2, 0))
+        );
+        assert!(VersionNumber::new(3, 10, 0) > VersionNumber::new(3, 9, 7));
+        assert_eq!(VersionNumber::parse("4"), None);
+        assert_eq!(VersionNumber::parse(""), None);
+        assert_eq!(parse_version(b"4.6.1.728\0\0").unwrap(), api);
     }
 }
@@ -6,10 +6,8 @@
 /* IO DIGITAL CONFIGURATION trait */
 
 use crate::channel::Channel;
-use crate::error::{CanError, CanOkError};
-use crate::peak_lib;
+use crate::error::CanError;
 use crate::peak_can;
-use std::ffi::c_void;
 
 #[derive(PartialEq, Debug)]
 pub enum IOConfig {
@@ -48,47 +46,29 @@ pub trait DigitalConfiguration {
 impl<T: HasDigitalConfiguration + Channel> DigitalConfiguration for T {
     fn digital_mode(&self, pin: u8) -> Result<IOConfig, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let mode_word = u32::from_le_bytes(data);
-                let pin_enabled = mode_word & (1 << pin);
-
-                if pin_enabled == 0 {
-                    Ok(IOConfig::In)
-                } else {
-                    Ok(IOConfig::InOut)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
+            &mut data,
+        )?;
+        let mode_word = u32::from_le_bytes(data);
+        let pin_enabled = mode_word & (1 << pin);
+
+        if pin_enabled == 0 {
+            Ok(IOConfig::In)
+        } else {
+            Ok(IOConfig::InOut)
         }
     }
 
     fn digital_mode_word(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
+            &mut data,
+        )?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -102,59 +82,29 @@ pub trait SetDigitalConfiguration {
 impl<T: HasSetDigitalConfiguration + Channel> SetDigitalConfiguration for T {
     fn set_digital_mode(&self, pin: u8, mode: IOConfig) -> Result<(), CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        let mode_word = match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => u32::from_le_bytes(data),
-            Ok(CanOkError::Err(err)) => return Err(err),
-            Err(_) => return Err(CanError::Unknown),
-        };
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
+            &mut data,
+        )?;
+        let mode_word = u32::from_le_bytes(data);
 
         let mode_word = match mode {
             IOConfig::In => mode_word | !(1 << pin),
             IOConfig::InOut => mode_word | (1 << pin),
         };
-        let mut data = mode_word.to_le_bytes();
-
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        return match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        };
+        let data = mode_word.to_le_bytes();
+        self.backend()
+            .set_value(self.channel(), peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8, &data)
     }
 
     fn set_digital_mode_word(&self, mode_word: u32) -> Result<(), CanError> {
-        let mut data = mode_word.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = mode_word.to_le_bytes();
+        self.backend().set_value(
+            self.channel(),
+            peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
+            &data,
+        )
     }
 }
 
@@ -198,47 +148,21 @@ pub trait DigitalValue {
 impl<T: HasSetDigitalValue + Channel> DigitalValue for T {
     fn digital_value(&self, pin: u8) -> Result<IOValue, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_VALUE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let mode_word = u32::from_le_bytes(data);
-                let pin_enabled = mode_word & (1 << pin);
-
-                if pin_enabled == 0 {
-                    Ok(IOValue::Low)
-                } else {
-                    Ok(IOValue::High)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_IO_DIGITAL_VALUE as u8, &mut data)?;
+        let mode_word = u32::from_le_bytes(data);
+        let pin_enabled = mode_word & (1 << pin);
+
+        if pin_enabled == 0 {
+            Ok(IOValue::Low)
+        } else {
+            Ok(IOValue::High)
         }
     }
 
     fn digital_value_word(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_VALUE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_IO_DIGITAL_VALUE as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -252,59 +176,25 @@ pub trait SetDigitalValue {
 impl<T: HasSetDigitalValue + Channel> SetDigitalValue for T {
     fn set_digital_value(&self, pin: u8, value: IOValue) -> Result<(), CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        let mode_word = match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => u32::from_le_bytes(data),
-            Ok(CanOkError::Err(err)) => return Err(err),
-            Err(_) => return Err(CanError::Unknown),
-        };
+        self.backend().get_value(
+            self.channel(),
+            peak_can::PEAK_IO_DIGITAL_CONFIGURATION as u8,
+            &mut data,
+        )?;
+        let mode_word = u32::from_le_bytes(data);
 
         let mode_word = match value {
             IOValue::Low => mode_word | !(1 << pin),
             IOValue::High => mode_word | (1 << pin),
         };
-        let mut data = mode_word.to_le_bytes();
-
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_VALUE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        return match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        };
+        let data = mode_word.to_le_bytes();
+        self.backend()
+            .set_value(self.channel(), peak_can::PEAK_IO_DIGITAL_VALUE as u8, &data)
     }
 
     fn set_digital_value_word(&self, value_word: u32) -> Result<(), CanError> {
-        let mut data = value_word.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_VALUE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = value_word.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_IO_DIGITAL_VALUE as u8, &data)
     }
 }
 
@@ -318,21 +208,8 @@ pub trait SetDigitalSet {
 
 impl<T: HasSetDigitalSet + Channel> SetDigitalSet for T {
     fn digital_set(&self, mask: u32) -> Result<(), CanError> {
-        let mut data = mask.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_SET as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = mask.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_IO_DIGITAL_SET as u8, &data)
     }
 }
 
@@ -346,21 +223,8 @@ pub trait SetDigitalClear {
 
 impl<T: HasSetDigitalClear + Channel> SetDigitalClear for T {
     fn digital_clear(&self, mask: u32) -> Result<(), CanError> {
-        let mut data = mask.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_IO_DIGITAL_CLEAR as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = mask.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_IO_DIGITAL_CLEAR as u8, &data)
     }
 }
 
@@ -375,19 +239,7 @@ pub trait AnalogValue {
 impl<T: HasAnalogValue + Channel> AnalogValue for T {
     fn analog_value(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_IO_ANALOG_VALUE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_IO_ANALOG_VALUE as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
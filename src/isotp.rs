@@ -0,0 +1,666 @@
+//! ISO-TP (ISO 15765-2) transport of messages up to 4095 bytes over classic CAN or CAN FD
+//! frames.
+//!
+//! Messages of up to 7 bytes, 62 bytes over CAN FD, are sent as a single frame. Longer messages
+//! start with a first frame, the receiver answers with a flow control frame giving the block
+//! size and the minimum separation time, and the rest follows in consecutive frames.
+
+use crate::error::CanError;
+use crate::queue::BoundedQueue;
+use crate::socket::{
+    AnyFrame, BrsOverride, CanFdFrame, CanFrame, DlcPolicy, MessageType, RecvCan, RecvCanFd,
+    SendCan, SendCanFd,
+};
+
+use core::fmt;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Longest message with a 12 bit length in the first frame.
+pub const MAX_MESSAGE_LENGTH: usize = 4095;
+
+const SINGLE_FRAME: u8 = 0x00;
+const FIRST_FRAME: u8 = 0x10;
+const CONSECUTIVE_FRAME: u8 = 0x20;
+const FLOW_CONTROL: u8 = 0x30;
+
+const FLOW_CONTINUE: u8 = 0;
+const FLOW_WAIT: u8 = 1;
+const FLOW_OVERFLOW: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsoTpConfig {
+    pub msg_type: MessageType,
+    /// Consecutive frames the peer may send before waiting for the next flow control frame,
+    /// 0 for no limit.
+    pub block_size: u8,
+    /// Minimum time the peer has to wait between consecutive frames.
+    pub st_min: Duration,
+    /// Fills frames up to 8 bytes, `None` sends frames only as long as needed.
+    pub padding: Option<u8>,
+    /// How long to wait for the next flow control or consecutive frame.
+    pub timeout: Duration,
+    /// Bit rate switch of the frames sent over CAN FD, [BrsOverride::Inherit] switches.
+    pub brs: BrsOverride,
+    /// Flow control frames asking to wait that are accepted in a row before sending is
+    /// aborted, N_WFTmax in ISO 15765-2.
+    pub max_wait_frames: u8,
+}
+
+impl Default for IsoTpConfig {
+    fn default() -> Self {
+        IsoTpConfig {
+            msg_type: MessageType::Standard,
+            block_size: 0,
+            st_min: Duration::ZERO,
+            padding: Some(0xCC),
+            timeout: Duration::from_secs(1),
+            brs: BrsOverride::Inherit,
+            max_wait_frames: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum IsoTpError {
+    Can(CanError),
+    /// The message is longer than [MAX_MESSAGE_LENGTH].
+    TooLong(usize),
+    /// No flow control or consecutive frame arrived in time.
+    Timeout,
+    /// The peer asked to wait more often than [IsoTpConfig::max_wait_frames] in a row.
+    TooManyWaits,
+    /// The peer can't receive a message of this length.
+    Overflow,
+    /// A consecutive frame was lost or repeated.
+    WrongSequenceNumber {
+        expected: u8,
+        actual: u8,
+    },
+    /// A frame with an unexpected or malformed protocol control information.
+    InvalidFrame(CanFrame),
+    /// Like [IsoTpError::InvalidFrame], received over CAN FD.
+    InvalidFdFrame(CanFdFrame),
+}
+
+impl fmt::Display for IsoTpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsoTpError::Can(err) => write!(f, "{err}"),
+            IsoTpError::TooLong(len) => write!(f, "Message of {len} bytes is too long"),
+            IsoTpError::Timeout => write!(f, "Timeout waiting for the peer"),
+            IsoTpError::TooManyWaits => write!(f, "The peer asked to wait too often"),
+            IsoTpError::Overflow => write!(f, "Message too long for the peer"),
+            IsoTpError::WrongSequenceNumber { expected, actual } => {
+                write!(f, "Wrong sequence number {actual}, expected {expected}")
+            }
+            IsoTpError::InvalidFrame(frame) => write!(f, "Invalid frame {frame:?}"),
+            IsoTpError::InvalidFdFrame(frame) => write!(f, "Invalid frame {frame:?}"),
+        }
+    }
+}
+
+impl std::error::Error for IsoTpError {}
+
+impl From<CanError> for IsoTpError {
+    fn from(value: CanError) -> Self {
+        IsoTpError::Can(value)
+    }
+}
+
+/// An ISO-TP connection between `tx_id`, the ID of the frames sent, and `rx_id`, the ID of
+/// the frames received. Frames of other IDs are skipped, or handed to the queue set with
+/// [with_unrelated_frames](IsoTpSocket::with_unrelated_frames) so that other users of the
+/// channel still receive them.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::isotp::IsoTpSocket;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let isotp = IsoTpSocket::new(socket, 0x7E0, 0x7E8);
+/// isotp.send(&[0x22, 0xF1, 0x90])?;
+/// let response = isotp.recv()?;
+/// # Ok::<(), peak_can::isotp::IsoTpError>(())
+/// ```
+pub struct IsoTpSocket<S> {
+    socket: S,
+    tx_id: u32,
+    rx_id: u32,
+    config: IsoTpConfig,
+    unrelated: Option<Arc<BoundedQueue<AnyFrame>>>,
+}
+
+impl<S> IsoTpSocket<S> {
+    pub fn new(socket: S, tx_id: u32, rx_id: u32) -> Self {
+        Self::with_config(socket, tx_id, rx_id, IsoTpConfig::default())
+    }
+
+    pub fn with_config(socket: S, tx_id: u32, rx_id: u32, config: IsoTpConfig) -> Self {
+        IsoTpSocket {
+            socket,
+            tx_id,
+            rx_id,
+            config,
+            unrelated: None,
+        }
+    }
+
+    /// Pushes the frames received while waiting for a frame of the connection, which don't
+    /// belong to it, to `queue` instead of skipping them. The transfer never waits for the
+    /// queue: frames that don't fit into a full [OverflowPolicy::Block] queue are skipped.
+    ///
+    /// [OverflowPolicy::Block]: crate::queue::OverflowPolicy::Block
+    pub fn with_unrelated_frames(mut self, queue: Arc<BoundedQueue<AnyFrame>>) -> Self {
+        self.unrelated = Some(queue);
+        self
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    fn hand_back(&self, frame: impl Into<AnyFrame>) {
+        if let Some(queue) = &self.unrelated {
+            // waiting would let the timers of the peer expire
+            let _ = queue.push_timeout(frame.into(), Duration::ZERO);
+        }
+    }
+}
+
+impl<S: RecvCan + SendCan> IsoTpSocket<S> {
+    pub fn send(&self, data: &[u8]) -> Result<(), IsoTpError> {
+        Classic(self).send_message(data)
+    }
+
+    /// Waits for the next message without a time limit.
+    pub fn recv(&self) -> Result<Vec<u8>, IsoTpError> {
+        Classic(self).recv_message(None)
+    }
+
+    /// Waits up to `timeout` for the start of the next message.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Vec<u8>, IsoTpError> {
+        Classic(self).recv_message(Some(timeout))
+    }
+}
+
+impl<S: RecvCanFd + SendCanFd> IsoTpSocket<S> {
+    /// Like [send](IsoTpSocket::send) over CAN FD frames of up to 64 bytes, with the bit rate
+    /// switch set by [IsoTpConfig::brs].
+    pub fn send_fd(&self, data: &[u8]) -> Result<(), IsoTpError> {
+        Fd(self).send_message(data)
+    }
+
+    /// Like [recv](IsoTpSocket::recv) over CAN FD frames.
+    pub fn recv_fd(&self) -> Result<Vec<u8>, IsoTpError> {
+        Fd(self).recv_message(None)
+    }
+
+    /// Like [recv_timeout](IsoTpSocket::recv_timeout) over CAN FD frames.
+    pub fn recv_fd_timeout(&self, timeout: Duration) -> Result<Vec<u8>, IsoTpError> {
+        Fd(self).recv_message(Some(timeout))
+    }
+}
+
+/// The frames of a connection, classic CAN or CAN FD. The protocol is the same for both, only
+/// the frame length differs.
+trait Link {
+    type Frame;
+    /// Longest frame payload, TX_DL in ISO 15765-2.
+    const TX_DL: usize;
+
+    fn config(&self) -> &IsoTpConfig;
+    fn data(frame: &Self::Frame) -> &[u8];
+    fn invalid(frame: Self::Frame) -> IsoTpError;
+    fn send_frame(&self, data: Vec<u8>) -> Result<(), IsoTpError>;
+    /// Receives the next frame with the receive ID.
+    fn recv_frame(&self, timeout: Duration) -> Result<Self::Frame, IsoTpError>;
+
+    fn send_message(&self, data: &[u8]) -> Result<(), IsoTpError> {
+        if data.len() > MAX_MESSAGE_LENGTH {
+            return Err(IsoTpError::TooLong(data.len()));
+        }
+        if data.len() <= 7 {
+            let mut frame = vec![SINGLE_FRAME | data.len() as u8];
+            frame.extend_from_slice(data);
+            return self.send_frame(frame);
+        }
+        if data.len() <= Self::TX_DL - 2 {
+            // CAN FD single frame with the length in the second byte
+            let mut frame = vec![SINGLE_FRAME, data.len() as u8];
+            frame.extend_from_slice(data);
+            return self.send_frame(frame);
+        }
+
+        let (first, rest) = data.split_at(Self::TX_DL - 2);
+        let mut frame = vec![FIRST_FRAME | (data.len() >> 8) as u8, data.len() as u8];
+        frame.extend_from_slice(first);
+        self.send_frame(frame)?;
+
+        let mut sequence_number = 1u8;
+        let mut chunks = rest.chunks(Self::TX_DL - 1).peekable();
+        while chunks.peek().is_some() {
+            let (block_size, st_min) = self.wait_flow_control()?;
+            let mut sent = 0;
+            while let Some(chunk) = chunks.next() {
+                let mut frame = vec![CONSECUTIVE_FRAME | sequence_number];
+                frame.extend_from_slice(chunk);
+                self.send_frame(frame)?;
+                sequence_number = (sequence_number + 1) & 0x0F;
+                sent += 1;
+
+                if block_size != 0 && sent == block_size {
+                    break;
+                }
+                if chunks.peek().is_some() && !st_min.is_zero() {
+                    sleep(st_min);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives the next message, waiting up to `timeout` for its start or without a time
+    /// limit.
+    fn recv_message(&self, timeout: Option<Duration>) -> Result<Vec<u8>, IsoTpError> {
+        let frame = loop {
+            match self.recv_frame(timeout.unwrap_or(self.config().timeout)) {
+                Err(IsoTpError::Timeout) if timeout.is_none() => continue,
+                result => break result?,
+            }
+        };
+        let data = Self::data(&frame);
+        match data.first().map(|pci| pci & 0xF0) {
+            Some(SINGLE_FRAME) => {
+                let (len, offset) = match data[0] & 0x0F {
+                    0 if data.len() > 8 => (data[1] as usize, 2),
+                    len => (len as usize, 1),
+                };
+                match data.get(offset..offset + len) {
+                    Some(payload) if len > 0 => Ok(payload.to_vec()),
+                    _ => Err(Self::invalid(frame)),
+                }
+            }
+            Some(FIRST_FRAME) if (8..=Self::TX_DL).contains(&data.len()) => {
+                let len = ((data[0] & 0x0F) as usize) << 8 | data[1] as usize;
+                // a message that fits into a single frame, or the escape of a 32 bit length
+                if len <= (Self::TX_DL - 2).max(7) {
+                    return Err(Self::invalid(frame));
+                }
+                let first = data[2..data.len().min(2 + len)].to_vec();
+                self.recv_consecutive(len, &first)
+            }
+            _ => Err(Self::invalid(frame)),
+        }
+    }
+
+    fn recv_consecutive(&self, len: usize, first: &[u8]) -> Result<Vec<u8>, IsoTpError> {
+        let config = self.config();
+        let mut message = Vec::with_capacity(len);
+        message.extend_from_slice(first);
+        let mut sequence_number = 1u8;
+
+        while message.len() < len {
+            self.send_flow_control(FLOW_CONTINUE)?;
+            let mut received = 0;
+            while message.len() < len && (config.block_size == 0 || received < config.block_size) {
+                let frame = self.recv_frame(config.timeout)?;
+                let data = Self::data(&frame);
+                match data.first() {
+                    Some(pci) if pci & 0xF0 == CONSECUTIVE_FRAME => {
+                        if pci & 0x0F != sequence_number {
+                            return Err(IsoTpError::WrongSequenceNumber {
+                                expected: sequence_number,
+                                actual: pci & 0x0F,
+                            });
+                        }
+                    }
+                    _ => return Err(Self::invalid(frame)),
+                }
+                let remaining = len - message.len();
+                message.extend_from_slice(&data[1..data.len().min(1 + remaining)]);
+                sequence_number = (sequence_number + 1) & 0x0F;
+                received += 1;
+            }
+        }
+        Ok(message)
+    }
+
+    /// Returns the block size and separation time of the next clear-to-send flow control.
+    fn wait_flow_control(&self) -> Result<(u8, Duration), IsoTpError> {
+        let mut waits = 0u8;
+        loop {
+            let frame = self.recv_frame(self.config().timeout)?;
+            match Self::data(&frame) {
+                [pci, block_size, st_min, ..] if pci & 0xF0 == FLOW_CONTROL => match pci & 0x0F {
+                    FLOW_CONTINUE => return Ok((*block_size, decode_st_min(*st_min))),
+                    FLOW_WAIT if waits < self.config().max_wait_frames => waits += 1,
+                    FLOW_WAIT => return Err(IsoTpError::TooManyWaits),
+                    FLOW_OVERFLOW => return Err(IsoTpError::Overflow),
+                    _ => return Err(Self::invalid(frame)),
+                },
+                _ => return Err(Self::invalid(frame)),
+            }
+        }
+    }
+
+    fn send_flow_control(&self, status: u8) -> Result<(), IsoTpError> {
+        let config = self.config();
+        self.send_frame(vec![
+            FLOW_CONTROL | status,
+            config.block_size,
+            encode_st_min(config.st_min),
+        ])
+    }
+}
+
+struct Classic<'a, S>(&'a IsoTpSocket<S>);
+
+impl<S: RecvCan + SendCan> Link for Classic<'_, S> {
+    type Frame = CanFrame;
+    const TX_DL: usize = 8;
+
+    fn config(&self) -> &IsoTpConfig {
+        &self.0.config
+    }
+
+    fn data(frame: &CanFrame) -> &[u8] {
+        frame.data()
+    }
+
+    fn invalid(frame: CanFrame) -> IsoTpError {
+        IsoTpError::InvalidFrame(frame)
+    }
+
+    fn send_frame(&self, mut data: Vec<u8>) -> Result<(), IsoTpError> {
+        let isotp = self.0;
+        if let Some(padding) = isotp.config.padding {
+            data.resize(8, padding);
+        }
+        let frame = CanFrame::new(isotp.tx_id, isotp.config.msg_type, &data)
+            .map_err(|_| IsoTpError::TooLong(data.len()))?;
+        Ok(isotp.socket.send(frame)?)
+    }
+
+    fn recv_frame(&self, timeout: Duration) -> Result<CanFrame, IsoTpError> {
+        let isotp = self.0;
+        let deadline = Instant::now() + timeout;
+        let extended = isotp.config.msg_type == MessageType::Extended;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match isotp.socket.recv_timeout(remaining) {
+                Ok((frame, _)) => {
+                    if frame.can_id() == isotp.rx_id && frame.is_extended_frame() == extended {
+                        return Ok(frame);
+                    }
+                    isotp.hand_back(frame);
+                }
+                Err(CanError::QrcvEmpty) => return Err(IsoTpError::Timeout),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+struct Fd<'a, S>(&'a IsoTpSocket<S>);
+
+impl<S: RecvCanFd + SendCanFd> Link for Fd<'_, S> {
+    type Frame = CanFdFrame;
+    const TX_DL: usize = 64;
+
+    fn config(&self) -> &IsoTpConfig {
+        &self.0.config
+    }
+
+    fn data(frame: &CanFdFrame) -> &[u8] {
+        frame.data()
+    }
+
+    fn invalid(frame: CanFdFrame) -> IsoTpError {
+        IsoTpError::InvalidFdFrame(frame)
+    }
+
+    fn send_frame(&self, mut data: Vec<u8>) -> Result<(), IsoTpError> {
+        let isotp = self.0;
+        if let Some(padding) = isotp.config.padding {
+            data.resize(data.len().max(8), padding);
+        }
+        // lengths between the DLC steps are always padded
+        let padding = isotp.config.padding.unwrap_or(0xCC);
+        let msg_type = isotp.config.msg_type;
+        let mut frame = CanFdFrame::with_padding(
+            isotp.tx_id,
+            msg_type,
+            &data,
+            true,
+            true,
+            padding,
+            DlcPolicy::RoundUp,
+        )
+        .map_err(|_| IsoTpError::TooLong(data.len()))?;
+        isotp.config.brs.apply(&mut frame);
+        Ok(isotp.socket.send_fd(frame)?)
+    }
+
+    fn recv_frame(&self, timeout: Duration) -> Result<CanFdFrame, IsoTpError> {
+        let isotp = self.0;
+        let deadline = Instant::now() + timeout;
+        let extended = isotp.config.msg_type == MessageType::Extended;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match isotp.socket.recv_fd_timeout(remaining) {
+                Ok((frame, _)) => {
+                    if frame.can_id() == isotp.rx_id && frame.is_extended_frame() == extended {
+                        return Ok(frame);
+                    }
+                    isotp.hand_back(frame);
+                }
+                Err(CanError::QrcvEmpty) => return Err(IsoTpError::Timeout),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Decodes the STmin byte of a flow control frame. Reserved values are treated as the
+/// maximum of 127 ms.
+fn decode_st_min(value: u8) -> Duration {
+    match value {
+        0x00..=0x7F => Duration::from_millis(value as u64),
+        0xF1..=0xF9 => Duration::from_micros((value - 0xF0) as u64 * 100),
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+fn encode_st_min(st_min: Duration) -> u8 {
+    let micros = st_min.as_micros();
+    if micros > 0 && micros < 1000 {
+        0xF0 + micros.div_ceil(100) as u8
+    } else {
+        st_min.as_millis().min(0x7F) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::OverflowPolicy;
+    use crate::socket::{MockCanSocket, Timestamp};
+
+    fn queue(socket: &MockCanSocket, id: u32, data: &[u8]) {
+        let frame = CanFrame::new(id, MessageType::Standard, data).unwrap();
+        socket
+            .backend()
+            .push_rx(socket.channel(), frame, Timestamp::default());
+    }
+
+    #[test]
+    fn send_segmented() {
+        let socket = MockCanSocket::new();
+        let unrelated = Arc::new(BoundedQueue::new(4, OverflowPolicy::DropOldest));
+        let isotp = IsoTpSocket::new(socket.clone(), 0x7E0, 0x7E8)
+            .with_unrelated_frames(Arc::clone(&unrelated));
+        queue(&socket, 0x123, &[0x30, 0, 0]);
+        queue(&socket, 0x7E8, &[0x30, 1, 0]);
+        queue(&socket, 0x7E8, &[0x31, 0, 0]);
+        queue(&socket, 0x7E8, &[0x30, 0, 0]);
+
+        let message = (0..20).collect::<Vec<u8>>();
+        isotp.send(&message).unwrap();
+
+        let sent = socket.backend().sent(socket.channel());
+        let sent = sent.iter().map(|f| f.data()).collect::<Vec<_>>();
+        assert_eq!(
+            sent,
+            vec![
+                &[0x10, 20, 0, 1, 2, 3, 4, 5][..],
+                &[0x21, 6, 7, 8, 9, 10, 11, 12][..],
+                &[0x22, 13, 14, 15, 16, 17, 18, 19][..],
+            ]
+        );
+        assert!(matches!(socket.recv(), Err(CanError::QrcvEmpty)));
+        assert_eq!(unrelated.try_pop().map(|f| f.can_id()), Some(0x123));
+        assert!(unrelated.is_empty());
+    }
+
+    #[test]
+    fn first_frame_length_fits_no_single_frame() {
+        let socket = MockCanSocket::new();
+        let isotp = IsoTpSocket::new(socket.clone(), 0x7E0, 0x7E8);
+        // 5 bytes, and the escape of a 32 bit length
+        queue(&socket, 0x7E8, &[0x10, 0x05, 1, 2, 3, 4, 5, 6]);
+        queue(&socket, 0x7E8, &[0x10, 0x00, 0, 0, 0, 20, 1, 2]);
+        for _ in 0..2 {
+            assert!(matches!(
+                isotp.recv_timeout(Duration::ZERO),
+                Err(IsoTpError::InvalidFrame(_))
+            ));
+        }
+
+        let mut first = vec![0x10, 40];
+        first.extend(0..62);
+        let first = CanFdFrame::new(0x7E8, MessageType::Standard, &first, true, true);
+        socket
+            .backend()
+            .push_rx_fd(socket.channel(), first.unwrap(), 0);
+        assert!(matches!(
+            isotp.recv_fd_timeout(Duration::ZERO),
+            Err(IsoTpError::InvalidFdFrame(_))
+        ));
+        assert!(socket.backend().sent(socket.channel()).is_empty());
+    }
+
+    #[test]
+    fn unrelated_frames_never_block() {
+        let socket = MockCanSocket::new();
+        let unrelated = Arc::new(BoundedQueue::new(1, OverflowPolicy::Block));
+        let isotp = IsoTpSocket::new(socket.clone(), 0x7E0, 0x7E8)
+            .with_unrelated_frames(Arc::clone(&unrelated));
+        queue(&socket, 0x100, &[1]);
+        queue(&socket, 0x200, &[2]);
+        queue(&socket, 0x7E8, &[0x01, 0xAA]);
+
+        assert_eq!(isotp.recv().unwrap(), [0xAA]);
+        assert_eq!(unrelated.try_pop().map(|f| f.can_id()), Some(0x100));
+        assert!(unrelated.is_empty());
+    }
+
+    #[test]
+    fn wait_frame_limit() {
+        let config = IsoTpConfig {
+            max_wait_frames: 1,
+            ..Default::default()
+        };
+        let socket = MockCanSocket::new();
+        let isotp = IsoTpSocket::with_config(socket.clone(), 0x7E0, 0x7E8, config);
+        queue(&socket, 0x7E8, &[0x31, 0, 0]);
+        queue(&socket, 0x7E8, &[0x31, 0, 0]);
+
+        let message = (0..20).collect::<Vec<u8>>();
+        assert!(matches!(
+            isotp.send(&message),
+            Err(IsoTpError::TooManyWaits)
+        ));
+        assert_eq!(socket.backend().sent(socket.channel()).len(), 1);
+    }
+
+    #[test]
+    fn recv_single_and_segmented() {
+        let config = IsoTpConfig {
+            padding: None,
+            block_size: 2,
+            st_min: Duration::from_micros(300),
+            ..Default::default()
+        };
+        let socket = MockCanSocket::new();
+        let isotp = IsoTpSocket::with_config(socket.clone(), 0x7E0, 0x7E8, config);
+        queue(&socket, 0x7E8, &[0x03, 0x62, 0xF1, 0x90, 0xAA]);
+        queue(&socket, 0x7E8, &[0x10, 0x10, 1, 2, 3, 4, 5, 6]);
+        queue(&socket, 0x7E8, &[0x21, 7, 8, 9, 10, 11, 12, 13]);
+        queue(&socket, 0x7E8, &[0x22, 14, 15, 16, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        assert_eq!(isotp.recv().unwrap(), vec![0x62, 0xF1, 0x90]);
+        assert_eq!(isotp.recv().unwrap(), (1..=16).collect::<Vec<u8>>());
+        let flow_control = socket.backend().sent(socket.channel())[0];
+        assert_eq!(flow_control.data(), &[0x30, 2, 0xF3]);
+
+        queue(&socket, 0x7E8, &[0x10, 0x10, 1, 2, 3, 4, 5, 6]);
+        queue(&socket, 0x7E8, &[0x22, 7, 8, 9, 10, 11, 12, 13]);
+        assert!(matches!(
+            isotp.recv_timeout(Duration::ZERO),
+            Err(IsoTpError::WrongSequenceNumber {
+                expected: 1,
+                actual: 2
+            })
+        ));
+        assert!(matches!(
+            isotp.recv_timeout(Duration::ZERO),
+            Err(IsoTpError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn fd_consecutive_frames_with_brs_override() {
+        let config = IsoTpConfig {
+            brs: BrsOverride::Force(false),
+            ..Default::default()
+        };
+        let socket = MockCanSocket::new();
+        let isotp = IsoTpSocket::with_config(socket.clone(), 0x7E0, 0x7E8, config);
+        let flow_control = CanFdFrame::new(0x7E8, MessageType::Standard, &[0x30, 0, 0], true, true);
+        socket
+            .backend()
+            .push_rx_fd(socket.channel(), flow_control.unwrap(), 0);
+
+        let message = (0..100).collect::<Vec<u8>>();
+        isotp.send_fd(&message).unwrap();
+
+        let sent = socket.backend().sent_fd(socket.channel());
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|f| f.is_fd_frame() && !f.is_brs()));
+        assert_eq!(sent[0].data()[..3], [0x10, 100, 0]);
+        assert_eq!(sent[0].data().len(), 64);
+        assert_eq!(sent[1].data()[0], 0x21);
+        assert_eq!(sent[1].data()[1..39], message[62..]);
+        assert!(sent[1].data()[39..].iter().all(|&b| b == 0xCC));
+
+        // single frame with the length in the second byte
+        let mut single = vec![0x00, 20];
+        single.extend(0..20);
+        let single = CanFdFrame::new(0x7E8, MessageType::Standard, &single, true, true);
+        socket
+            .backend()
+            .push_rx_fd(socket.channel(), single.unwrap(), 0);
+        assert_eq!(
+            isotp.recv_fd_timeout(Duration::ZERO).unwrap(),
+            (0..20).collect::<Vec<u8>>()
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Latency and jitter instrumentation based on a log-linear (HDR style) histogram.
+//!
+//! [LatencyRecorder] measures the receive path (device timestamp against host receive time) and
+//! the transmit path (send call until the echo frame is received). Values are kept in
+//! nanoseconds with a relative precision of about 1%.
+
+use crate::socket::{CanFrame, ReceivedFrame, Timestamp};
+
+use core::fmt;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of bits of the linear sub-buckets, 2^(7-1) = 64 sub-buckets per power of two.
+const SUB_BUCKET_BITS: u32 = 7;
+const HALF_BUCKET: usize = 1 << (SUB_BUCKET_BITS - 1);
+/// Sent frames waiting for their echo are dropped beyond this count.
+const MAX_PENDING_ECHOS: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; (66 - SUB_BUCKET_BITS as usize) * HALF_BUCKET],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index(value: u64) -> usize {
+        let bits = 64 - value.leading_zeros();
+        if bits <= SUB_BUCKET_BITS {
+            value as usize
+        } else {
+            let shift = bits - SUB_BUCKET_BITS;
+            shift as usize * HALF_BUCKET + (value >> shift) as usize
+        }
+    }
+
+    /// Highest value that falls into the bucket with `index`.
+    fn highest_value(index: usize) -> u64 {
+        if index < 2 * HALF_BUCKET {
+            index as u64
+        } else {
+            let shift = index / HALF_BUCKET - 1;
+            let mantissa = (index - shift * HALF_BUCKET) as u64;
+            (mantissa << shift) + ((1u64 << shift) - 1)
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.counts[Self::index(value)] += 1;
+        self.count += 1;
+        self.sum += value as u128;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn record_duration(&mut self, value: Duration) {
+        self.record(value.as_nanos().min(u64::MAX as u128) as u64);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// Smallest recorded value that `percentile` percent (0-100) of all values are less than or
+    /// equal to, within the precision of the histogram.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.max(1);
+
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::highest_value(index).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Values at the given percentiles, e.g. `&[50.0, 99.0, 99.9]`.
+    pub fn percentiles(&self, percentiles: &[f64]) -> Vec<(f64, u64)> {
+        percentiles
+            .iter()
+            .filter_map(|p| self.value_at_percentile(*p).map(|v| (*p, v)))
+            .collect()
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        Some(LatencySummary {
+            count: self.count,
+            min: Duration::from_nanos(self.min()?),
+            mean: Duration::from_nanos(self.mean()? as u64),
+            p50: Duration::from_nanos(self.value_at_percentile(50.0)?),
+            p90: Duration::from_nanos(self.value_at_percentile(90.0)?),
+            p99: Duration::from_nanos(self.value_at_percentile(99.0)?),
+            p999: Duration::from_nanos(self.value_at_percentile(99.9)?),
+            max: Duration::from_nanos(self.max()?),
+        })
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n={} min={:?} mean={:?} p50={:?} p90={:?} p99={:?} p99.9={:?} max={:?}",
+            self.count, self.min, self.mean, self.p50, self.p90, self.p99, self.p999, self.max
+        )
+    }
+}
+
+struct PendingEcho {
+    can_id: u32,
+    data: Vec<u8>,
+    sent: Instant,
+}
+
+/// Records receive and send-to-echo latencies.
+///
+/// Device and host clocks are not synchronized, so the receive latency is the difference of
+/// both clocks relative to the lowest difference observed so far. It shows how long frames
+/// wait in the driver queue beyond the fastest observed delivery, i.e. the jitter of the
+/// receive loop.
+pub struct LatencyRecorder {
+    receive: Histogram,
+    echo: Histogram,
+    start: Instant,
+    min_offset: Option<i128>,
+    pending: VecDeque<PendingEcho>,
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        LatencyRecorder {
+            receive: Histogram::new(),
+            echo: Histogram::new(),
+            start: Instant::now(),
+            min_offset: None,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a frame as returned by [RecvCan::recv_with_host_time](crate::socket::RecvCan::recv_with_host_time).
+    pub fn record_receive(&mut self, timestamp: &Timestamp, host_time: Instant) {
+        self.record_receive_micros(timestamp.as_micros(), host_time);
+    }
+
+    /// Records a frame with a device timestamp in microseconds, e.g. from
+    /// [RecvCanFd::recv_fd_with_host_time](crate::socket::RecvCanFd::recv_fd_with_host_time).
+    pub fn record_receive_micros(&mut self, device_micros: u64, host_time: Instant) {
+        let host = host_time.saturating_duration_since(self.start).as_nanos() as i128;
+        let offset = host - device_micros as i128 * 1000;
+        let min_offset = *self
+            .min_offset
+            .insert(self.min_offset.map_or(offset, |min| min.min(offset)));
+        self.receive.record((offset - min_offset) as u64);
+    }
+
+    /// Records the receive latency of a frame.
+    pub fn record_received(&mut self, received: &ReceivedFrame) {
+        self.record_receive_micros(received.device_timestamp, received.host_timestamp);
+    }
+
+    /// Remembers a frame handed to the driver at `sent`, to be matched by [record_echo](LatencyRecorder::record_echo).
+    pub fn record_sent(&mut self, frame: &CanFrame, sent: Instant) {
+        if self.pending.len() == MAX_PENDING_ECHOS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(PendingEcho {
+            can_id: frame.can_id(),
+            data: frame.data().to_vec(),
+            sent,
+        });
+    }
+
+    /// Matches an echo frame against the oldest sent frame with the same ID and data. Returns
+    /// the latency if a sent frame was found.
+    pub fn record_echo(&mut self, frame: &CanFrame, host_time: Instant) -> Option<Duration> {
+        let position = self
+            .pending
+            .iter()
+            .position(|p| p.can_id == frame.can_id() && p.data == frame.data())?;
+        let pending = self.pending.remove(position)?;
+        let latency = host_time.saturating_duration_since(pending.sent);
+        self.echo.record_duration(latency);
+        Some(latency)
+    }
+
+    pub fn receive(&self) -> &Histogram {
+        &self.receive
+    }
+
+    pub fn echo(&self) -> &Histogram {
+        &self.echo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::MessageType;
+
+    #[test]
+    fn bucket_boundaries() {
+        for value in [0, 1, 63, 64, 127, 128, 129, 1000, 123_456_789, u64::MAX] {
+            let index = Histogram::index(value);
+            assert!(Histogram::highest_value(index) >= value);
+            if index > 0 {
+                assert!(Histogram::highest_value(index - 1) < value);
+            }
+        }
+    }
+
+    #[test]
+    fn percentiles_within_precision() {
+        let mut histogram = Histogram::new();
+        for value in 1..=10_000u64 {
+            histogram.record(value * 1000);
+        }
+
+        assert_eq!(histogram.min(), Some(1000));
+        assert_eq!(histogram.max(), Some(10_000_000));
+        for (percentile, value) in histogram.percentiles(&[50.0, 99.0]) {
+            let exact = percentile / 100.0 * 10_000_000.0;
+            assert!((value as f64 - exact).abs() / exact < 0.02);
+        }
+        assert_eq!(histogram.value_at_percentile(100.0), Some(10_000_000));
+    }
+
+    #[test]
+    fn echo_latency() {
+        let mut recorder = LatencyRecorder::new();
+        let frame = CanFrame::new(0x10, MessageType::Standard, &[1, 2]).unwrap();
+        let sent = Instant::now();
+
+        recorder.record_sent(&frame, sent);
+        assert_eq!(
+            recorder.record_echo(&frame, sent + Duration::from_micros(250)),
+            Some(Duration::from_micros(250))
+        );
+        assert_eq!(recorder.record_echo(&frame, sent), None);
+        assert_eq!(recorder.echo().count(), 1);
+    }
+}
@@ -1,28 +1,67 @@
 //!
 //!
 
+pub mod alias;
+#[cfg(feature = "bench")]
+pub mod bench;
 #[warn(dead_code)]
 pub mod bus;
+pub mod cache;
 mod channel;
+pub mod clock;
+#[cfg(feature = "dbc")]
+pub mod dbc;
 pub mod df;
+pub mod discover;
+pub mod dispatch;
 pub mod error;
+pub mod filter;
+pub mod flash;
 pub mod hw;
 pub mod info;
 pub mod io;
+pub mod isotp;
+pub mod latency;
+pub mod lock;
 pub mod log;
+pub mod message;
+pub mod parameter;
+pub mod payload;
+pub mod poller;
+pub mod queue;
+pub mod realtime;
+pub mod replay;
+pub mod report;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod selftest;
+pub mod sequence;
+pub mod shutdown;
+pub mod snapshot;
 pub mod socket;
 pub mod special;
+pub mod template;
 pub mod trace;
+pub mod trafficgen;
+pub mod watch;
 
 use peak_can_sys as peak_can;
 
-use std::sync::LazyLock;
+use std::sync::OnceLock;
 
-static PEAK_BASIC: LazyLock<Result<peak_can::Pcan, crate::error::CanError>> = LazyLock::new(|| {
-    let filename = libloading::library_filename("PCANBasic");
-    Ok(unsafe { peak_can::Pcan::new(filename) }?)
-});
+static PEAK_BASIC: OnceLock<Result<peak_can::Pcan, crate::error::CanError>> = OnceLock::new();
 
 pub(crate) fn peak_lib() -> Result<&'static peak_can::Pcan, crate::error::CanError> {
-    PEAK_BASIC.as_ref().map_err(|e| e.clone())
+    PEAK_BASIC
+        .get_or_init(|| {
+            let filename = libloading::library_filename("PCANBasic");
+            Ok(unsafe { peak_can::Pcan::new(filename) }?)
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+/// The library if an earlier call of [peak_lib] loaded it, without trying to load it.
+pub(crate) fn loaded_peak_lib() -> Option<&'static peak_can::Pcan> {
+    PEAK_BASIC.get()?.as_ref().ok()
 }
@@ -0,0 +1,169 @@
+//! Advisory locks that keep cooperating processes from opening the same channel twice.
+//!
+//! PCAN-Basic lets several processes initialize the same channel, which then share its bus
+//! parameters and receive queue in surprising ways. A [ChannelLock] is an exclusive file lock
+//! in the temporary directory, keyed by the channel handle. The holder writes its process ID
+//! and executable name next to the lock, so a process that finds the channel locked can report
+//! who holds it. The lock is released when the [ChannelLock] is dropped or the process exits.
+
+use crate::bus::Bus;
+
+use core::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The process holding a channel lock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub executable: String,
+}
+
+impl LockOwner {
+    fn current() -> LockOwner {
+        let executable = std::env::current_exe()
+            .ok()
+            .and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+        LockOwner {
+            pid: std::process::id(),
+            executable,
+        }
+    }
+
+    fn parse(s: &str) -> Option<LockOwner> {
+        let (pid, executable) = s.trim_end().split_once('\n')?;
+        Some(LockOwner {
+            pid: pid.parse().ok()?,
+            executable: executable.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {})", self.executable, self.pid)
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// Another process holds the lock. The owner is `None` if it could not be read, e.g.
+    /// while the holder is still writing it.
+    ChannelLocked(Option<LockOwner>),
+    Io(io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::ChannelLocked(Some(owner)) => write!(f, "Channel is locked by {owner}"),
+            LockError::ChannelLocked(None) => write!(f, "Channel is locked by another process"),
+            LockError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl From<io::Error> for LockError {
+    fn from(value: io::Error) -> Self {
+        LockError::Io(value)
+    }
+}
+
+/// An exclusive advisory lock of a channel, held until dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::lock::ChannelLock;
+/// let _lock = ChannelLock::acquire(&UsbBus::USB1)?;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct ChannelLock {
+    // keeps the lock, it is released when the file is closed
+    _file: File,
+    owner_path: PathBuf,
+}
+
+impl ChannelLock {
+    /// Locks the channel of `bus` in the temporary directory of the system.
+    pub fn acquire<T: Bus>(bus: &T) -> Result<ChannelLock, LockError> {
+        Self::acquire_in(std::env::temp_dir(), bus)
+    }
+
+    /// Locks the channel of `bus` in `dir`. All cooperating processes have to use the same
+    /// directory.
+    pub fn acquire_in<P: AsRef<Path>, T: Bus>(dir: P, bus: &T) -> Result<ChannelLock, LockError> {
+        let name = format!("peak-can-{:04x}", bus.channel());
+        let lock_path = dir.as_ref().join(format!("{name}.lock"));
+        // the owner is kept in a separate file, on Windows the locked file can't be read
+        let owner_path = dir.as_ref().join(format!("{name}.owner"));
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let owner = fs::read_to_string(&owner_path)
+                    .ok()
+                    .and_then(|s| LockOwner::parse(&s));
+                return Err(LockError::ChannelLocked(owner));
+            }
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+
+        let owner = LockOwner::current();
+        fs::write(
+            &owner_path,
+            format!("{}\n{}\n", owner.pid, owner.executable),
+        )?;
+        Ok(ChannelLock {
+            _file: file,
+            owner_path,
+        })
+    }
+}
+
+impl Drop for ChannelLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.owner_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::UsbBus;
+
+    #[test]
+    fn second_lock_reports_owner() {
+        let dir = std::env::temp_dir().join(format!("peak-can-lock-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let lock = ChannelLock::acquire_in(&dir, &UsbBus::USB1).unwrap();
+        let _other = ChannelLock::acquire_in(&dir, &UsbBus::USB2).unwrap();
+        match ChannelLock::acquire_in(&dir, &UsbBus::USB1) {
+            Err(LockError::ChannelLocked(Some(owner))) => {
+                assert_eq!(owner.pid, std::process::id())
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        drop(lock);
+        assert!(ChannelLock::acquire_in(&dir, &UsbBus::USB1).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,6 @@
 use crate::channel::Channel;
-use crate::error::{CanError, CanOkError};
-use crate::peak_lib;
+use crate::error::CanError;
 use crate::peak_can;
-use std::ffi::c_void;
 use std::path::{Path, PathBuf};
 
 /* TRACE LOCATION traits */
@@ -16,24 +14,12 @@ pub trait TraceLocation {
 impl<T: HasTraceLocation + Channel> TraceLocation for T {
     fn trace_location(&self) -> Result<PathBuf, CanError> {
         let mut data = [0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_LOCATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => match std::str::from_utf8(&data) {
-                Ok(s) => {
-                    let s = s.trim_matches(char::from(0));
-                    Ok(PathBuf::from(s))
-                }
-                Err(_) => Err(CanError::Unknown),
-            },
-            Ok(CanOkError::Err(err)) => Err(err),
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_LOCATION as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                Ok(PathBuf::from(s))
+            }
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -47,26 +33,17 @@ pub trait SetTraceLocation {
 
 impl<T: HasSetTraceLocation + Channel> SetTraceLocation for T {
     fn set_trace_location<P: AsRef<Path>>(&self, path: P) -> Result<(), CanError> {
-        let mut data = match path.as_ref().to_str() {
+        let data = match path.as_ref().to_str() {
             None => {
                 return Err(CanError::Unknown);
             }
             Some(s) => String::from(s),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_LOCATION as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(
+            self.channel(),
+            peak_can::PEAK_TRACE_LOCATION as u8,
+            data.as_bytes(),
+        )
     }
 }
 
@@ -85,28 +62,14 @@ pub trait TraceStatus {
 impl<T: HasTraceStatus + Channel> TraceStatus for T {
     fn is_tracing(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_STATUS as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                if code == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else if code == peak_can::PEAK_PARAMETER_OFF {
-                    Ok(false)
-                } else {
-                    Err(CanError::Unknown)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_STATUS as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
         }
     }
 }
@@ -119,24 +82,11 @@ pub trait SetTraceStatus {
 
 impl<T: HasSetTraceStatus + Channel> SetTraceStatus for T {
     fn set_tracing(&self, enable: bool) -> Result<(), CanError> {
-        let mut data = match enable {
+        let data = match enable {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_STATUS as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_STATUS as u8, &data)
     }
 }
 
@@ -151,20 +101,8 @@ pub trait TraceSize {
 impl<T: HasTraceSize + Channel> TraceSize for T {
     fn trace_size(&self) -> Result<u8, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_SIZE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(data[0]),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_SIZE as u8, &mut data)?;
+        Ok(data[0])
     }
 }
 
@@ -176,21 +114,8 @@ pub trait SetTraceSize {
 
 impl<T: HasSetTraceSize + Channel> SetTraceSize for T {
     fn set_trace_size(&self, size_mb: u8) -> Result<(), CanError> {
-        let mut data = [size_mb];
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_SIZE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = [size_mb];
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_SIZE as u8, &data)
     }
 }
 
@@ -245,24 +170,10 @@ pub trait TraceConfigure {
 impl<T: HasTraceConfigure + Channel> TraceConfigure for T {
     fn trace_configuration(&self) -> Result<TraceFile, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_CONFIGURE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let code = u32::from_le_bytes(data);
-                match TraceFile::try_from(code) {
-                    Ok(log_config) => Ok(log_config),
-                    Err(_) => Err(CanError::Unknown),
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_CONFIGURE as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        match TraceFile::try_from(code) {
+            Ok(log_config) => Ok(log_config),
             Err(_) => Err(CanError::Unknown),
         }
     }
@@ -276,20 +187,7 @@ pub trait SetTraceConfigure {
 
 impl<T: HasSetTraceConfigure + Channel> SetTraceConfigure for T {
     fn configure_trace(&self, config: TraceFile) -> Result<(), CanError> {
-        let mut data = u32::from(config).to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_TRACE_CONFIGURE as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = u32::from(config).to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_CONFIGURE as u8, &data)
     }
 }
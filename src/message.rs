@@ -0,0 +1,657 @@
+//! Typed messages of a fixed communication matrix.
+//!
+//! The [can_messages](crate::can_messages) macro generates a struct per message with one field
+//! per signal and implements [CanMessage] for it. Cyclic messages are sent by a
+//! [CyclicSender], responses to frames received by a [Dispatcher] by a [ResponseScheduler].
+//! Together they cover simple restbus simulations. Cyclic frames with live content are sent from a
+//! [FrameTemplate].
+
+use crate::dispatch::{CallbackId, Dispatcher};
+use crate::error::CanError;
+use crate::filter::{Action, FilterRule, FilterSet, IdMatch};
+use crate::payload::PayloadError;
+use crate::queue::TxFrame;
+use crate::socket::{AnyFrame, BrsOverride, CanFrame, Id, MessageType, SendCan, SendCanFd};
+use crate::template::FrameTemplate;
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub trait CanMessage: Sized {
+    const ID: u32;
+    const MSG_TYPE: MessageType;
+    const DLC: usize;
+    /// Cycle time of periodic messages.
+    const CYCLE: Option<Duration>;
+
+    fn encode(&self) -> Result<CanFrame, PayloadError>;
+    /// Decodes `frame`, returns `None` if it is not this message or too short.
+    fn decode(frame: &CanFrame) -> Option<Self>;
+
+    /// Whether `frame` has the ID and type of this message.
+    fn matches(frame: &CanFrame) -> bool {
+        let extended = Self::MSG_TYPE == MessageType::Extended;
+        frame.can_id() == Self::ID && frame.is_extended_frame() == extended
+    }
+}
+
+/// Conversion of signal field types from and to the raw bits of the payload.
+pub trait SignalValue: Copy {
+    fn from_raw(raw: u64, len: u8) -> Self;
+    /// The lowest `len` bits of the value, negative values in two's complement.
+    fn to_raw(self, len: u8) -> u64;
+}
+
+fn low_bits(value: u64, len: u8) -> u64 {
+    if len >= 64 {
+        value
+    } else {
+        value & ((1 << len) - 1)
+    }
+}
+
+macro_rules! unsigned_signal_value {
+    ($($ty:ty),*) => {
+        $(
+            impl SignalValue for $ty {
+                fn from_raw(raw: u64, _len: u8) -> Self {
+                    raw as $ty
+                }
+
+                fn to_raw(self, len: u8) -> u64 {
+                    low_bits(self as u64, len)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! signed_signal_value {
+    ($($ty:ty),*) => {
+        $(
+            impl SignalValue for $ty {
+                fn from_raw(raw: u64, len: u8) -> Self {
+                    let shift = 64 - len.clamp(1, 64) as u32;
+                    (((raw << shift) as i64) >> shift) as $ty
+                }
+
+                fn to_raw(self, len: u8) -> u64 {
+                    low_bits(self as i64 as u64, len)
+                }
+            }
+        )*
+    };
+}
+
+unsigned_signal_value!(u8, u16, u32, u64);
+signed_signal_value!(i8, i16, i32, i64);
+
+impl SignalValue for bool {
+    fn from_raw(raw: u64, _len: u8) -> Self {
+        raw != 0
+    }
+
+    fn to_raw(self, _len: u8) -> u64 {
+        self as u64
+    }
+}
+
+/// Declares messages of a communication matrix.
+///
+/// Each message is declared with its ID, an optional `extended` marker, its data length and an
+/// optional cycle time in milliseconds. Signals are declared as struct fields with their start
+/// bit, length and [ByteOrder](crate::payload::ByteOrder), using the bit numbering of the
+/// [payload](crate::payload) module.
+///
+/// # Examples
+///
+/// ```
+/// use peak_can::can_messages;
+/// use peak_can::message::CanMessage;
+///
+/// can_messages! {
+///     pub struct Speed: 0x100, 8, cycle 10 ms {
+///         pub vehicle_speed: u16 = 0, 16, LittleEndian;
+///         pub acceleration: i8 = 16, 8, LittleEndian;
+///     }
+///
+///     pub struct Diagnostics: extended 0x18DA_10F1, 2 {
+///         pub active: bool = 0, 1, LittleEndian;
+///     }
+/// }
+///
+/// let speed = Speed { vehicle_speed: 1234, acceleration: -3 };
+/// let frame = speed.encode()?;
+/// assert_eq!(Speed::decode(&frame), Some(speed));
+/// assert_eq!(Diagnostics::decode(&frame), None);
+/// # Ok::<(), peak_can::payload::PayloadError>(())
+/// ```
+#[macro_export]
+macro_rules! can_messages {
+    (@msg_type extended) => {
+        $crate::socket::MessageType::Extended
+    };
+    (@msg_type) => {
+        $crate::socket::MessageType::Standard
+    };
+    (@cycle $cycle:literal) => {
+        ::core::option::Option::Some(::std::time::Duration::from_millis($cycle))
+    };
+    (@cycle) => {
+        ::core::option::Option::None
+    };
+    ($(
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident: $($kind:ident)? $id:literal, $dlc:literal $(, cycle $cycle:literal ms)? {
+            $( $field_vis:vis $field:ident: $ty:ty = $start:literal, $len:literal, $order:ident; )*
+        }
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Default)]
+            $vis struct $name {
+                $( $field_vis $field: $ty, )*
+            }
+
+            impl $crate::message::CanMessage for $name {
+                const ID: u32 = $id;
+                const MSG_TYPE: $crate::socket::MessageType = $crate::can_messages!(@msg_type $($kind)?);
+                const DLC: usize = $dlc;
+                const CYCLE: ::core::option::Option<::std::time::Duration> =
+                    $crate::can_messages!(@cycle $($cycle)?);
+
+                fn encode(
+                    &self,
+                ) -> ::core::result::Result<$crate::socket::CanFrame, $crate::payload::PayloadError> {
+                    #[allow(unused_mut)]
+                    let mut data = [0u8; $dlc];
+                    $(
+                        $crate::payload::insert_bits(
+                            &mut data,
+                            $start,
+                            $len,
+                            $crate::message::SignalValue::to_raw(self.$field, $len),
+                            $crate::payload::ByteOrder::$order,
+                        )?;
+                    )*
+                    $crate::socket::CanFrame::new(Self::ID, Self::MSG_TYPE, &data)
+                        .map_err(|_| $crate::payload::PayloadError::OutOfRange)
+                }
+
+                fn decode(frame: &$crate::socket::CanFrame) -> ::core::option::Option<Self> {
+                    if !<Self as $crate::message::CanMessage>::matches(frame) {
+                        return ::core::option::Option::None;
+                    }
+                    #[allow(unused_variables)]
+                    let data = frame.data();
+                    ::core::option::Option::Some($name {
+                        $(
+                            $field: $crate::message::SignalValue::from_raw(
+                                $crate::payload::extract_bits(
+                                    data,
+                                    $start,
+                                    $len,
+                                    $crate::payload::ByteOrder::$order,
+                                )?,
+                                $len,
+                            ),
+                        )*
+                    })
+                }
+            }
+        )*
+    };
+}
+
+struct CyclicEntry {
+    frame: CanFrame,
+    /// Renders the frame for every transmission instead of sending `frame`.
+    template: Option<FrameTemplate>,
+    cycle: Option<Duration>,
+    next_send: Instant,
+}
+
+/// Sends registered messages at their cycle time.
+#[derive(Default)]
+pub struct CyclicSender {
+    entries: Vec<CyclicEntry>,
+}
+
+impl CyclicSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `message` or updates the signals of an already registered message of the
+    /// same type. New messages are sent on the next [poll](CyclicSender::poll), messages
+    /// without a cycle time only once.
+    pub fn set<M: CanMessage>(&mut self, message: &M) -> Result<(), PayloadError> {
+        let frame = message.encode()?;
+        match self.entries.iter_mut().find(|e| M::matches(&e.frame)) {
+            Some(entry) => entry.frame = frame,
+            None => self.entries.push(CyclicEntry {
+                frame,
+                template: None,
+                cycle: M::CYCLE,
+                next_send: Instant::now(),
+            }),
+        }
+        Ok(())
+    }
+
+    /// Registers `template` to be rendered and sent every `cycle`, or only once without a
+    /// cycle time. Replaces a registered message or template with the same ID.
+    pub fn set_template(&mut self, template: FrameTemplate, cycle: Option<Duration>) {
+        let id = template.id();
+        let frame = *template.frame();
+        match self.entries.iter_mut().find(|e| e.frame.id() == id) {
+            Some(entry) => {
+                entry.frame = frame;
+                entry.template = Some(template);
+                entry.cycle = cycle;
+            }
+            None => self.entries.push(CyclicEntry {
+                frame,
+                template: Some(template),
+                cycle,
+                next_send: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn remove<M: CanMessage>(&mut self) {
+        self.entries.retain(|e| !M::matches(&e.frame));
+    }
+
+    /// Removes the message or template with `id`.
+    pub fn remove_id(&mut self, id: impl Into<Id>) {
+        let id = id.into();
+        self.entries.retain(|e| e.frame.id() != id);
+    }
+
+    /// Sends all due messages and returns when the next message is due.
+    pub fn poll<S: SendCan>(&mut self, socket: &S) -> Result<Option<Instant>, CanError> {
+        let now = Instant::now();
+        for entry in self.entries.iter_mut().filter(|e| e.next_send <= now) {
+            let frame = match &mut entry.template {
+                Some(template) => template.render(),
+                None => entry.frame,
+            };
+            socket.send(frame)?;
+            if let Some(cycle) = entry.cycle {
+                entry.next_send += cycle;
+                if entry.next_send <= now {
+                    // fell behind by more than a cycle, don't send a burst to catch up
+                    entry.next_send = now + cycle;
+                }
+            }
+        }
+        self.entries
+            .retain(|e| e.cycle.is_some() || e.next_send > now);
+        Ok(self.entries.iter().map(|e| e.next_send).min())
+    }
+}
+
+/// Sends `response` `delay` after every reception of a frame with `trigger_id`.
+#[derive(Debug, Clone)]
+pub struct ResponseRule {
+    pub trigger_id: u32,
+    pub trigger_type: MessageType,
+    pub response: TxFrame,
+    pub delay: Duration,
+}
+
+impl ResponseRule {
+    pub fn new(
+        trigger_id: u32,
+        trigger_type: MessageType,
+        response: impl Into<TxFrame>,
+        delay: Duration,
+    ) -> Self {
+        ResponseRule {
+            trigger_id,
+            trigger_type,
+            response: response.into(),
+            delay,
+        }
+    }
+
+    /// Overrides the bit rate switch of a CAN FD response.
+    pub fn with_brs(mut self, brs: BrsOverride) -> Self {
+        self.response = self.response.with_brs(brs);
+        self
+    }
+
+    fn matches(&self, frame: &AnyFrame) -> bool {
+        let extended = self.trigger_type == MessageType::Extended;
+        frame.can_id() == self.trigger_id && frame.is_extended_frame() == extended
+    }
+}
+
+/// Sends frames at a fixed delay after the reception of trigger frames. The triggers are
+/// subscribed at a [Dispatcher] and timestamped with the monotonic host clock when it reads
+/// them, the responses are sent by a thread of the scheduler.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::dispatch::Dispatcher;
+/// # use peak_can::message::{ResponseRule, ResponseScheduler};
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType};
+/// # use peak_can::bus::UsbBus;
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// let socket = Arc::new(CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?);
+/// let response = CanFrame::new(0x7E8, MessageType::Standard, &[0x03, 0x41, 0x0D, 0x32]).unwrap();
+/// let mut scheduler = ResponseScheduler::new();
+/// scheduler.add(ResponseRule::new(
+///     0x7DF,
+///     MessageType::Standard,
+///     response,
+///     Duration::from_millis(10),
+/// ));
+///
+/// let mut dispatcher = Dispatcher::new();
+/// let responses = scheduler.spawn(&mut dispatcher, socket.clone());
+/// dispatcher.run(&socket);
+/// responses.stop();
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Default)]
+pub struct ResponseScheduler {
+    rules: Vec<ResponseRule>,
+}
+
+impl ResponseScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, rule: ResponseRule) {
+        self.rules.push(rule);
+    }
+
+    /// Subscribes the trigger frames at `dispatcher` and starts the thread sending the
+    /// responses through `socket`. The thread runs until the returned handle is stopped.
+    pub fn spawn<S>(self, dispatcher: &mut Dispatcher, socket: S) -> ResponseHandle<S>
+    where
+        S: SendCan + SendCanFd + Send + 'static,
+    {
+        let shared = Arc::new(Responses::default());
+
+        let mut triggers = FilterSet::deny_all();
+        for rule in &self.rules {
+            triggers.push(FilterRule::new(
+                Action::Allow,
+                Some(rule.trigger_type),
+                IdMatch::List(vec![rule.trigger_id]),
+            ));
+        }
+        let rules = self.rules;
+        let responses = shared.clone();
+        let callback = dispatcher.on_frame_matching(triggers, move |received| {
+            let received_at = received.host_timestamp;
+            let mut pending = responses.lock();
+            if pending.stopped {
+                return;
+            }
+            for rule in rules.iter().filter(|rule| rule.matches(&received.frame)) {
+                let due = received_at + rule.delay;
+                let index = pending.frames.partition_point(|(at, _)| *at <= due);
+                pending.frames.insert(index, (due, rule.response));
+            }
+            responses.changed.notify_one();
+        });
+
+        let responses = shared.clone();
+        let thread = thread::spawn(move || {
+            let mut pending = responses.lock();
+            while !pending.stopped {
+                let now = Instant::now();
+                match pending.frames.first() {
+                    Some((due, frame)) if *due <= now => {
+                        let frame = *frame;
+                        pending.frames.remove(0);
+                        drop(pending);
+                        let result = frame.send(&socket);
+                        pending = responses.lock();
+                        if let Err(err) = result {
+                            pending.last_error = Some(err);
+                        }
+                    }
+                    Some((due, _)) => {
+                        let timeout = *due - now;
+                        pending = responses
+                            .changed
+                            .wait_timeout(pending, timeout)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0;
+                    }
+                    None => {
+                        pending = responses
+                            .changed
+                            .wait(pending)
+                            .unwrap_or_else(|e| e.into_inner());
+                    }
+                }
+            }
+            drop(pending);
+            socket
+        });
+
+        ResponseHandle {
+            shared,
+            callback,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    // sorted by due time
+    frames: Vec<(Instant, TxFrame)>,
+    last_error: Option<CanError>,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Responses {
+    pending: Mutex<Pending>,
+    changed: Condvar,
+}
+
+impl Responses {
+    fn lock(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Handle of the sending thread of a [ResponseScheduler]. Dropping the handle stops the
+/// thread, responses that are not due yet are discarded.
+pub struct ResponseHandle<S> {
+    shared: Arc<Responses>,
+    callback: CallbackId,
+    thread: Option<JoinHandle<S>>,
+}
+
+impl<S> ResponseHandle<S> {
+    /// The dispatcher callback receiving the triggers, to remove it once the handle is stopped.
+    pub fn callback(&self) -> CallbackId {
+        self.callback
+    }
+
+    /// The error of the last response that failed to send. Failed responses are not retried.
+    pub fn last_error(&self) -> Option<CanError> {
+        self.shared.lock().last_error.clone()
+    }
+
+    /// Stops the thread and returns the socket.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the sending thread, if it panicked.
+    pub fn stop(mut self) -> S {
+        self.shutdown();
+        let thread = self.thread.take().expect("response thread joined twice");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    fn shutdown(&self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_one();
+    }
+}
+
+impl<S> Drop for ResponseHandle<S> {
+    fn drop(&mut self) {
+        self.shutdown();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{CanFdFrame, MockCanSocket, Timestamp};
+
+    crate::can_messages! {
+        struct Status: 0x200, 4, cycle 100 ms {
+            temperature: i16 = 0, 12, LittleEndian;
+            mode: u8 = 12, 4, LittleEndian;
+            enabled: bool = 16, 1, LittleEndian;
+        }
+
+        struct Command: extended 0x18FF_0010, 1 {
+            level: u8 = 7, 8, BigEndian;
+        }
+    }
+
+    #[test]
+    fn encode_decode_signals() {
+        let status = Status {
+            temperature: -40,
+            mode: 0xA,
+            enabled: true,
+        };
+        let frame = status.encode().unwrap();
+        assert_eq!(frame.can_id(), 0x200);
+        assert_eq!(frame.data(), &[0xD8, 0xAF, 0x01, 0x00]);
+        assert_eq!(Status::decode(&frame), Some(status));
+        assert_eq!(Command::decode(&frame), None);
+
+        let command = Command { level: 0x42 }.encode().unwrap();
+        assert!(command.is_extended_frame());
+        assert_eq!(Command::decode(&command), Some(Command { level: 0x42 }));
+        assert_eq!(Status::CYCLE, Some(Duration::from_millis(100)));
+        assert_eq!(Command::CYCLE, None);
+    }
+
+    #[test]
+    fn cyclic_sender_sends_once_per_cycle() {
+        let socket = MockCanSocket::new();
+        let mut sender = CyclicSender::new();
+        sender.set(&Status::default()).unwrap();
+        sender.set(&Command { level: 1 }).unwrap();
+
+        let next = sender.poll(&socket).unwrap();
+        assert_eq!(socket.backend().sent(socket.channel()).len(), 2);
+        assert!(next.is_some());
+
+        sender
+            .set(&Status {
+                mode: 3,
+                ..Default::default()
+            })
+            .unwrap();
+        sender.poll(&socket).unwrap();
+        // the status is not due yet and the command was sent only once
+        assert_eq!(socket.backend().sent(socket.channel()).len(), 2);
+        assert_eq!(sender.entries.len(), 1);
+        assert_eq!(sender.entries[0].frame.data()[1], 0x30);
+    }
+
+    #[test]
+    fn cyclic_sender_renders_templates() {
+        use crate::template::{BitField, FrameTemplate};
+
+        let socket = MockCanSocket::new();
+        let mut sender = CyclicSender::new();
+        let frame = CanFrame::new(0x300, MessageType::Standard, &[0xAA, 0x00]).unwrap();
+        let template = FrameTemplate::new(frame)
+            .with_counter(BitField::byte(1))
+            .unwrap();
+        sender.set_template(template, Some(Duration::ZERO));
+        sender.poll(&socket).unwrap();
+        sender.poll(&socket).unwrap();
+        assert_eq!(
+            socket.backend().sent(socket.channel())[1].data(),
+            [0xAA, 0x01]
+        );
+
+        sender.remove_id(frame.id());
+        sender.poll(&socket).unwrap();
+        assert_eq!(socket.backend().sent(socket.channel()).len(), 2);
+    }
+
+    #[test]
+    fn responses_after_delay() {
+        let socket = MockCanSocket::new();
+        let response = CanFrame::new(0x7E8, MessageType::Standard, &[0x41]).unwrap();
+        let mut scheduler = ResponseScheduler::new();
+        scheduler.add(ResponseRule::new(
+            0x7DF,
+            MessageType::Standard,
+            response,
+            Duration::from_secs(60),
+        ));
+        scheduler.add(ResponseRule::new(
+            0x7DF,
+            MessageType::Standard,
+            response,
+            Duration::from_millis(5),
+        ));
+        let fd_response =
+            CanFdFrame::new(0x7E8, MessageType::Standard, &[0x41], true, true).unwrap();
+        scheduler.add(
+            ResponseRule::new(
+                0x7DF,
+                MessageType::Standard,
+                fd_response,
+                Duration::from_millis(5),
+            )
+            .with_brs(BrsOverride::Force(false)),
+        );
+        let mut dispatcher = Dispatcher::new();
+        let responses = scheduler.spawn(&mut dispatcher, socket.clone());
+
+        let trigger = CanFrame::new(0x7DF, MessageType::Standard, &[0x01]).unwrap();
+        let extended = CanFrame::new(0x7DF, MessageType::Extended, &[0x01]).unwrap();
+        for frame in [trigger, extended] {
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::from_micros(0));
+        }
+        let received_at = Instant::now();
+        dispatcher.step(&socket);
+
+        let deadline = received_at + Duration::from_secs(5);
+        while socket.backend().sent(socket.channel()).is_empty() {
+            assert!(Instant::now() < deadline, "no response was sent");
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(received_at.elapsed() >= Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(socket.backend().sent(socket.channel()), [response]);
+        let sent_fd = socket.backend().sent_fd(socket.channel());
+        assert_eq!(sent_fd.len(), 1);
+        assert!(!sent_fd[0].is_brs());
+        assert!(responses.last_error().is_none());
+        responses.stop();
+    }
+}
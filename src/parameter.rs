@@ -0,0 +1,262 @@
+//! Typed access to the PCAN-Basic channel parameters.
+//!
+//! [Parameters::get_value] and [Parameters::set_value] cover the parameters that are plain
+//! switches, numbers or texts. Parameters with structured values, e.g. the acceptance filters,
+//! have dedicated traits in the other modules.
+
+use crate::channel::Channel;
+use crate::error::CanError;
+use crate::peak_can;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParameterKind {
+    /// `PCAN_PARAMETER_ON` / `PCAN_PARAMETER_OFF`
+    Bool,
+    U32,
+    Text,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Parameter {
+    DeviceId,
+    FiveVoltsPower,
+    /// 0 closed, 1 open, 2 custom.
+    MessageFilter,
+    BusOffAutoreset,
+    ListenOnly,
+    ChannelCondition,
+    HardwareName,
+    ReceiveStatus,
+    ControllerNumber,
+    TraceStatus,
+    TraceSize,
+    ChannelIdentifying,
+    ChannelFeatures,
+    BitrateAdapting,
+    BitrateInfoFd,
+    NominalBusSpeed,
+    DataBusSpeed,
+    IpAddress,
+    AllowStatusFrames,
+    AllowRtrFrames,
+    AllowErrorFrames,
+    InterframeDelay,
+    FirmwareVersion,
+    AllowEchoFrames,
+    DevicePartNumber,
+}
+
+impl Parameter {
+    fn id(&self) -> u32 {
+        match self {
+            Parameter::DeviceId => peak_can::PEAK_DEVICE_ID,
+            Parameter::FiveVoltsPower => peak_can::PEAK_5VOLTS_POWER,
+            Parameter::MessageFilter => peak_can::PEAK_MESSAGE_FILTER,
+            Parameter::BusOffAutoreset => peak_can::PEAK_BUSOFF_AUTORESET,
+            Parameter::ListenOnly => peak_can::PEAK_LISTEN_ONLY,
+            Parameter::ChannelCondition => peak_can::PEAK_CHANNEL_CONDITION,
+            Parameter::HardwareName => peak_can::PEAK_HARDWARE_NAME,
+            Parameter::ReceiveStatus => peak_can::PEAK_RECEIVE_STATUS,
+            Parameter::ControllerNumber => peak_can::PEAK_CONTROLLER_NUMBER,
+            Parameter::TraceStatus => peak_can::PEAK_TRACE_STATUS,
+            Parameter::TraceSize => peak_can::PEAK_TRACE_SIZE,
+            Parameter::ChannelIdentifying => peak_can::PEAK_CHANNEL_IDENTIFYING,
+            Parameter::ChannelFeatures => peak_can::PEAK_CHANNEL_FEATURES,
+            Parameter::BitrateAdapting => peak_can::PEAK_BITRATE_ADAPTING,
+            Parameter::BitrateInfoFd => peak_can::PEAK_BITRATE_INFO_FD,
+            Parameter::NominalBusSpeed => peak_can::PEAK_BUSSPEED_NOMINAL,
+            Parameter::DataBusSpeed => peak_can::PEAK_BUSSPEED_DATA,
+            Parameter::IpAddress => peak_can::PEAK_IP_ADDRESS,
+            Parameter::AllowStatusFrames => peak_can::PEAK_ALLOW_STATUS_FRAMES,
+            Parameter::AllowRtrFrames => peak_can::PEAK_ALLOW_RTR_FRAMES,
+            Parameter::AllowErrorFrames => peak_can::PEAK_ALLOW_ERROR_FRAMES,
+            Parameter::InterframeDelay => peak_can::PEAK_INTERFRAME_DELAY,
+            Parameter::FirmwareVersion => peak_can::PEAK_FIRMWARE_VERSION,
+            Parameter::AllowEchoFrames => peak_can::PEAK_ALLOW_ECHO_FRAMES,
+            Parameter::DevicePartNumber => peak_can::PEAK_DEVICE_PART_NUMBER,
+        }
+    }
+
+    pub fn kind(&self) -> ParameterKind {
+        match self {
+            Parameter::FiveVoltsPower
+            | Parameter::BusOffAutoreset
+            | Parameter::ListenOnly
+            | Parameter::ReceiveStatus
+            | Parameter::TraceStatus
+            | Parameter::ChannelIdentifying
+            | Parameter::BitrateAdapting
+            | Parameter::AllowStatusFrames
+            | Parameter::AllowRtrFrames
+            | Parameter::AllowErrorFrames
+            | Parameter::AllowEchoFrames => ParameterKind::Bool,
+            Parameter::DeviceId
+            | Parameter::MessageFilter
+            | Parameter::ChannelCondition
+            | Parameter::ControllerNumber
+            | Parameter::TraceSize
+            | Parameter::ChannelFeatures
+            | Parameter::NominalBusSpeed
+            | Parameter::DataBusSpeed
+            | Parameter::InterframeDelay => ParameterKind::U32,
+            Parameter::HardwareName
+            | Parameter::BitrateInfoFd
+            | Parameter::IpAddress
+            | Parameter::FirmwareVersion
+            | Parameter::DevicePartNumber => ParameterKind::Text,
+        }
+    }
+
+    /// Whether the driver accepts [Parameters::set_value] for the parameter.
+    pub fn is_writable(&self) -> bool {
+        !matches!(
+            self,
+            Parameter::ChannelCondition
+                | Parameter::HardwareName
+                | Parameter::ChannelFeatures
+                | Parameter::BitrateInfoFd
+                | Parameter::NominalBusSpeed
+                | Parameter::DataBusSpeed
+                | Parameter::FirmwareVersion
+                | Parameter::DevicePartNumber
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParameterValue {
+    Bool(bool),
+    U32(u32),
+    Text(String),
+}
+
+impl ParameterValue {
+    pub fn kind(&self) -> ParameterKind {
+        match self {
+            ParameterValue::Bool(_) => ParameterKind::Bool,
+            ParameterValue::U32(_) => ParameterKind::U32,
+            ParameterValue::Text(_) => ParameterKind::Text,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ParameterValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ParameterValue::U32(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ParameterValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn decode(kind: ParameterKind, data: &[u8]) -> Result<ParameterValue, CanError> {
+        match kind {
+            ParameterKind::Bool | ParameterKind::U32 => {
+                let value = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                if kind == ParameterKind::Bool {
+                    Ok(ParameterValue::Bool(
+                        value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON,
+                    ))
+                } else {
+                    Ok(ParameterValue::U32(value))
+                }
+            }
+            ParameterKind::Text => match std::str::from_utf8(data) {
+                Ok(s) => Ok(ParameterValue::Text(String::from(
+                    s.trim_matches(char::from(0)),
+                ))),
+                Err(_) => Err(CanError::Unknown),
+            },
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            ParameterValue::Bool(true) => peak_can::PEAK_PARAMETER_ON.to_le_bytes().to_vec(),
+            ParameterValue::Bool(false) => peak_can::PEAK_PARAMETER_OFF.to_le_bytes().to_vec(),
+            ParameterValue::U32(value) => value.to_le_bytes().to_vec(),
+            ParameterValue::Text(value) => {
+                let mut data = value.as_bytes().to_vec();
+                data.push(0);
+                data
+            }
+        }
+    }
+}
+
+/* Parameters trait */
+
+pub(crate) trait HasParameters {}
+
+pub trait Parameters {
+    fn get_value(&self, parameter: Parameter) -> Result<ParameterValue, CanError>;
+    /// Fails with [CanError::IllParamType] for read-only parameters and with
+    /// [CanError::IllParamVal] if the kind of `value` doesn't match the parameter.
+    fn set_value(&self, parameter: Parameter, value: ParameterValue) -> Result<(), CanError>;
+}
+
+impl<T: HasParameters + Channel> Parameters for T {
+    fn get_value(&self, parameter: Parameter) -> Result<ParameterValue, CanError> {
+        let mut data = match parameter.kind() {
+            ParameterKind::Bool | ParameterKind::U32 => vec![0u8; 4],
+            ParameterKind::Text => vec![0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize],
+        };
+        self.backend()
+            .get_value(self.channel(), parameter.id() as u8, &mut data)?;
+        ParameterValue::decode(parameter.kind(), &data)
+    }
+
+    fn set_value(&self, parameter: Parameter, value: ParameterValue) -> Result<(), CanError> {
+        if !parameter.is_writable() {
+            return Err(CanError::IllParamType);
+        }
+        if value.kind() != parameter.kind() {
+            return Err(CanError::IllParamVal);
+        }
+
+        let data = value.encode();
+        self.backend()
+            .set_value(self.channel(), parameter.id() as u8, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_round_trip() {
+        for value in [
+            ParameterValue::Bool(true),
+            ParameterValue::Bool(false),
+            ParameterValue::U32(0x1234_5678),
+        ] {
+            let mut data = value.encode();
+            data.resize(4, 0);
+            assert_eq!(
+                ParameterValue::decode(value.kind(), &data).ok(),
+                Some(value)
+            );
+        }
+
+        let mut data = ParameterValue::Text(String::from("PCAN-USB FD")).encode();
+        data.resize(peak_can::MAX_LENGTH_VERSION_STRING as usize, 0);
+        assert_eq!(
+            ParameterValue::decode(ParameterKind::Text, &data)
+                .unwrap()
+                .as_text(),
+            Some("PCAN-USB FD")
+        );
+    }
+}
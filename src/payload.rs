@@ -0,0 +1,307 @@
+//! Typed access to frame payloads.
+//!
+//! The [Payload] trait reads and writes integers at byte offsets and bit fields inside the data
+//! of [CanFrame](crate::socket::CanFrame) and [CanFdFrame](crate::socket::CanFdFrame).
+//!
+//! Bit positions follow the DBC convention: for [ByteOrder::LittleEndian] the start bit is the
+//! least significant bit of the field, for [ByteOrder::BigEndian] it is the most significant bit
+//! counted in the "sawtooth" numbering (bit 7 of byte 0 is followed by bit 6, ..., bit 0, then
+//! bit 15 of byte 1).
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType};
+
+#[cfg(feature = "derive")]
+pub use peak_can_derive::CanPayload;
+
+use core::fmt;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PayloadError {
+    /// The accessed bytes or bits are beyond the payload length.
+    OutOfRange,
+    /// The value does not fit into the bit field.
+    ValueTooLarge,
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::OutOfRange => write!(f, "Access beyond payload length"),
+            PayloadError::ValueTooLarge => write!(f, "Value does not fit into bit field"),
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {}
+
+pub trait Payload {
+    fn payload(&self) -> &[u8];
+    fn payload_mut(&mut self) -> &mut [u8];
+
+    fn u8_at(&self, offset: usize) -> Option<u8> {
+        self.payload().get(offset).copied()
+    }
+
+    fn u16_at(&self, offset: usize, order: ByteOrder) -> Option<u16> {
+        read_bytes::<2>(self.payload(), offset).map(|b| match order {
+            ByteOrder::LittleEndian => u16::from_le_bytes(b),
+            ByteOrder::BigEndian => u16::from_be_bytes(b),
+        })
+    }
+
+    fn u32_at(&self, offset: usize, order: ByteOrder) -> Option<u32> {
+        read_bytes::<4>(self.payload(), offset).map(|b| match order {
+            ByteOrder::LittleEndian => u32::from_le_bytes(b),
+            ByteOrder::BigEndian => u32::from_be_bytes(b),
+        })
+    }
+
+    fn u64_at(&self, offset: usize, order: ByteOrder) -> Option<u64> {
+        read_bytes::<8>(self.payload(), offset).map(|b| match order {
+            ByteOrder::LittleEndian => u64::from_le_bytes(b),
+            ByteOrder::BigEndian => u64::from_be_bytes(b),
+        })
+    }
+
+    fn set_u8_at(&mut self, offset: usize, value: u8) -> Result<(), PayloadError> {
+        write_bytes(self.payload_mut(), offset, &[value])
+    }
+
+    fn set_u16_at(&mut self, offset: usize, value: u16, order: ByteOrder) -> Result<(), PayloadError> {
+        let bytes = match order {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        };
+        write_bytes(self.payload_mut(), offset, &bytes)
+    }
+
+    fn set_u32_at(&mut self, offset: usize, value: u32, order: ByteOrder) -> Result<(), PayloadError> {
+        let bytes = match order {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        };
+        write_bytes(self.payload_mut(), offset, &bytes)
+    }
+
+    fn set_u64_at(&mut self, offset: usize, value: u64, order: ByteOrder) -> Result<(), PayloadError> {
+        let bytes = match order {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        };
+        write_bytes(self.payload_mut(), offset, &bytes)
+    }
+
+    /// Extracts the unsigned bit field of `len` bits (1-64) at `start_bit`.
+    fn bits(&self, start_bit: u16, len: u8, order: ByteOrder) -> Option<u64> {
+        extract_bits(self.payload(), start_bit, len, order)
+    }
+
+    /// Inserts `value` into the bit field of `len` bits (1-64) at `start_bit`.
+    fn set_bits(
+        &mut self,
+        start_bit: u16,
+        len: u8,
+        value: u64,
+        order: ByteOrder,
+    ) -> Result<(), PayloadError> {
+        insert_bits(self.payload_mut(), start_bit, len, value, order)
+    }
+}
+
+impl Payload for CanFrame {
+    fn payload(&self) -> &[u8] {
+        self.data()
+    }
+
+    fn payload_mut(&mut self) -> &mut [u8] {
+        self.mut_data()
+    }
+}
+
+impl Payload for CanFdFrame {
+    fn payload(&self) -> &[u8] {
+        self.data()
+    }
+
+    fn payload_mut(&mut self) -> &mut [u8] {
+        self.mut_data()
+    }
+}
+
+/* CanPayload trait */
+
+/// A struct packed into a frame payload, usually implemented with `#[derive(CanPayload)]` of
+/// the `derive` feature.
+pub trait CanPayload: Sized {
+    /// Length of the payload in bytes.
+    const LEN: usize;
+
+    fn write_payload(&self, data: &mut [u8]) -> Result<(), PayloadError>;
+    fn read_payload(data: &[u8]) -> Result<Self, PayloadError>;
+
+    fn to_frame(&self, can_id: u32, msg_type: MessageType) -> Result<CanFrame, PayloadError> {
+        let mut data = vec![0u8; Self::LEN];
+        self.write_payload(&mut data)?;
+        CanFrame::new(can_id, msg_type, &data).map_err(|_| PayloadError::OutOfRange)
+    }
+
+    fn from_frame<P: Payload>(frame: &P) -> Result<Self, PayloadError> {
+        Self::read_payload(frame.payload())
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+fn write_bytes(data: &mut [u8], offset: usize, bytes: &[u8]) -> Result<(), PayloadError> {
+    let end = offset
+        .checked_add(bytes.len())
+        .ok_or(PayloadError::OutOfRange)?;
+    match data.get_mut(offset..end) {
+        Some(target) => {
+            target.copy_from_slice(bytes);
+            Ok(())
+        }
+        None => Err(PayloadError::OutOfRange),
+    }
+}
+
+/// Bit positions (byte index, bit in byte) of a field, most significant bit first.
+fn bit_positions(start_bit: u16, len: u8, order: ByteOrder) -> impl Iterator<Item = (usize, u8)> {
+    let start = start_bit as usize;
+    let len = len as usize;
+    let mut motorola = start;
+
+    (0..len).map(move |i| match order {
+        ByteOrder::LittleEndian => {
+            let bit = start + len - 1 - i;
+            (bit / 8, (bit % 8) as u8)
+        }
+        ByteOrder::BigEndian => {
+            let position = (motorola / 8, (motorola % 8) as u8);
+            motorola = if motorola.is_multiple_of(8) {
+                motorola + 15
+            } else {
+                motorola - 1
+            };
+            position
+        }
+    })
+}
+
+/// Extracts an unsigned bit field from `data`, see the [module](self) documentation for the
+/// bit numbering.
+pub fn extract_bits(data: &[u8], start_bit: u16, len: u8, order: ByteOrder) -> Option<u64> {
+    if len == 0 || len > 64 {
+        return None;
+    }
+
+    let mut value = 0u64;
+    for (byte, bit) in bit_positions(start_bit, len, order) {
+        let byte = data.get(byte)?;
+        value = (value << 1) | ((*byte >> bit) & 1) as u64;
+    }
+    Some(value)
+}
+
+/// Inserts an unsigned bit field into `data`, see the [module](self) documentation for the bit
+/// numbering.
+pub fn insert_bits(
+    data: &mut [u8],
+    start_bit: u16,
+    len: u8,
+    value: u64,
+    order: ByteOrder,
+) -> Result<(), PayloadError> {
+    if len == 0 || len > 64 {
+        return Err(PayloadError::OutOfRange);
+    }
+    if len < 64 && value >> len != 0 {
+        return Err(PayloadError::ValueTooLarge);
+    }
+    if bit_positions(start_bit, len, order).any(|(byte, _)| byte >= data.len()) {
+        return Err(PayloadError::OutOfRange);
+    }
+
+    for (i, (byte, bit)) in bit_positions(start_bit, len, order).enumerate() {
+        let set = (value >> (len as usize - 1 - i)) & 1 == 1;
+        if set {
+            data[byte] |= 1 << bit;
+        } else {
+            data[byte] &= !(1 << bit);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_at_offsets() {
+        let mut frame =
+            CanFrame::new(0x100, MessageType::Standard, &[0x12, 0x34, 0x56, 0x78, 0, 0]).unwrap();
+
+        assert_eq!(frame.u16_at(0, ByteOrder::LittleEndian), Some(0x3412));
+        assert_eq!(frame.u16_at(0, ByteOrder::BigEndian), Some(0x1234));
+        assert_eq!(frame.u32_at(0, ByteOrder::BigEndian), Some(0x1234_5678));
+        assert_eq!(frame.u32_at(3, ByteOrder::BigEndian), None);
+        assert_eq!(frame.u64_at(0, ByteOrder::LittleEndian), None);
+
+        frame.set_u16_at(4, 0xBEEF, ByteOrder::BigEndian).unwrap();
+        assert_eq!(frame.data(), &[0x12, 0x34, 0x56, 0x78, 0xBE, 0xEF]);
+        assert_eq!(
+            frame.set_u32_at(4, 0, ByteOrder::LittleEndian),
+            Err(PayloadError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn intel_bit_fields() {
+        let data = [0b1010_0000, 0b0000_0011];
+        // 4 bits starting at bit 5 span both bytes: bits 5, 6, 7 of byte 0 and bit 0 of byte 1
+        assert_eq!(extract_bits(&data, 5, 4, ByteOrder::LittleEndian), Some(0b1101));
+
+        let mut data = [0u8; 2];
+        insert_bits(&mut data, 5, 4, 0b1101, ByteOrder::LittleEndian).unwrap();
+        assert_eq!(data, [0b1010_0000, 0b0000_0001]);
+    }
+
+    #[test]
+    fn motorola_bit_fields() {
+        // 12 bit signal with MSB at bit 7 of byte 0 ends at bit 4 of byte 1
+        let data = [0xAB, 0xC0];
+        assert_eq!(extract_bits(&data, 7, 12, ByteOrder::BigEndian), Some(0xABC));
+
+        let mut data = [0u8; 2];
+        insert_bits(&mut data, 7, 12, 0xABC, ByteOrder::BigEndian).unwrap();
+        assert_eq!(data, [0xAB, 0xC0]);
+
+        // 16 bit value big endian at byte 0 is the same as u16_at
+        let frame = CanFrame::new(0x100, MessageType::Standard, &[0x12, 0x34]).unwrap();
+        assert_eq!(frame.bits(7, 16, ByteOrder::BigEndian), Some(0x1234));
+        assert_eq!(frame.bits(0, 16, ByteOrder::LittleEndian), Some(0x3412));
+    }
+
+    #[test]
+    fn bit_field_errors() {
+        let mut data = [0u8; 1];
+        assert_eq!(extract_bits(&data, 4, 8, ByteOrder::LittleEndian), None);
+        assert_eq!(
+            insert_bits(&mut data, 0, 3, 8, ByteOrder::LittleEndian),
+            Err(PayloadError::ValueTooLarge)
+        );
+        assert_eq!(
+            insert_bits(&mut data, 0, 9, 0, ByteOrder::LittleEndian),
+            Err(PayloadError::OutOfRange)
+        );
+    }
+}
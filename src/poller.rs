@@ -0,0 +1,343 @@
+//! Periodic request/response polling, e.g. of OBD PIDs or proprietary status frames.
+
+use crate::error::CanError;
+use crate::shutdown::Shutdown;
+use crate::socket::wait::Waiter;
+use crate::socket::{CanFrame, RecvCan, SendCan, WaitStrategy};
+
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+type Decoder<T> = Box<dyn Fn(&CanFrame) -> Option<T>>;
+
+/// A request frame sent every `period` and the response that answers it.
+pub struct PollRequest<T> {
+    request: CanFrame,
+    response_id: u32,
+    response_match: Vec<(usize, u8)>,
+    period: Duration,
+    timeout: Duration,
+    decode: Decoder<T>,
+}
+
+impl<T> PollRequest<T> {
+    /// Creates a request answered by frames with `response_id`. Responses for which `decode`
+    /// returns `None` are ignored. The response timeout defaults to the period.
+    pub fn new<F>(request: CanFrame, response_id: u32, period: Duration, decode: F) -> Self
+    where
+        F: Fn(&CanFrame) -> Option<T> + 'static,
+    {
+        PollRequest {
+            request,
+            response_id,
+            response_match: Vec::new(),
+            period,
+            timeout: period,
+            decode: Box::new(decode),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Only accepts responses carrying `bytes` at `offset`, e.g. the echoed service and PID of
+    /// an OBD response.
+    pub fn with_response_match(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.response_match
+            .extend(bytes.iter().enumerate().map(|(i, b)| (offset + i, *b)));
+        self
+    }
+
+    fn matches(&self, frame: &CanFrame) -> bool {
+        frame.can_id() == self.response_id
+            && self
+                .response_match
+                .iter()
+                .all(|(offset, byte)| frame.data().get(*offset) == Some(byte))
+    }
+}
+
+#[derive(Debug)]
+pub enum PollEvent<T> {
+    /// Decoded response of the request with the given index.
+    Response {
+        request: usize,
+        value: T,
+        latency: Duration,
+        /// The channel set with [with_channel](Poller::with_channel).
+        channel: Option<u16>,
+    },
+    /// No response was received within the timeout of the request.
+    Timeout {
+        request: usize,
+    },
+    SendFailed {
+        request: usize,
+        error: CanError,
+    },
+    RecvFailed(CanError),
+}
+
+struct Schedule {
+    next_send: Instant,
+    sent: Option<Instant>,
+}
+
+/// Sends configured requests on their own schedules and reports decoded responses.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::poller::{PollEvent, PollRequest, Poller};
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType};
+/// # use peak_can::bus::UsbBus;
+/// # use std::ops::ControlFlow;
+/// # use std::time::Duration;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+///
+/// // OBD engine speed, PID 0x0C
+/// let request = CanFrame::new(0x7DF, MessageType::Standard, &[0x02, 0x01, 0x0C])?;
+/// let rpm = PollRequest::new(request, 0x7E8, Duration::from_millis(100), |frame| {
+///     let data = frame.data();
+///     Some(u16::from_be_bytes([*data.get(3)?, *data.get(4)?]) as f64 / 4.0)
+/// })
+/// .with_response_match(1, &[0x41, 0x0C]);
+///
+/// let mut poller = Poller::new();
+/// poller.add(rpm);
+/// poller.run(&socket, |event| {
+///     if let PollEvent::Response { value, .. } = event {
+///         println!("{value} rpm");
+///     }
+///     ControlFlow::Continue(())
+/// });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Poller<T> {
+    requests: Vec<PollRequest<T>>,
+    schedules: Vec<Schedule>,
+    wait_strategy: WaitStrategy,
+    shutdown: Option<Shutdown>,
+    channel: Option<u16>,
+}
+
+impl<T> Default for Poller<T> {
+    fn default() -> Self {
+        Poller {
+            requests: Vec::new(),
+            schedules: Vec::new(),
+            wait_strategy: WaitStrategy::default(),
+            shutdown: None,
+            channel: None,
+        }
+    }
+}
+
+impl<T> Poller<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how [run](Poller::run) waits between two steps. Waiting never delays a due request
+    /// by more than the configured interval.
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.wait_strategy = strategy;
+        self
+    }
+
+    /// Makes [run](Poller::run) return once `shutdown` is triggered.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Sets the channel of the reported responses, so that the events of pollers on several
+    /// buses can be told apart.
+    pub fn with_channel(mut self, channel: u16) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Adds a request which is sent on the next [step](Poller::step). Returns the index used
+    /// in [PollEvent]s of this request.
+    pub fn add(&mut self, request: PollRequest<T>) -> usize {
+        self.requests.push(request);
+        self.schedules.push(Schedule {
+            next_send: Instant::now(),
+            sent: None,
+        });
+        self.requests.len() - 1
+    }
+
+    /// Processes received frames, timeouts and due requests without blocking.
+    pub fn step<S, F>(&mut self, socket: &S, mut handler: F)
+    where
+        S: RecvCan + SendCan,
+        F: FnMut(PollEvent<T>),
+    {
+        loop {
+            match socket.recv_frame() {
+                Ok(frame) => self.dispatch(&frame, &mut handler),
+                Err(CanError::QrcvEmpty) => break,
+                Err(err) => {
+                    handler(PollEvent::RecvFailed(err));
+                    break;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for (index, (request, schedule)) in
+            self.requests.iter().zip(&mut self.schedules).enumerate()
+        {
+            if let Some(sent) = schedule.sent
+                && now.duration_since(sent) >= request.timeout
+            {
+                schedule.sent = None;
+                handler(PollEvent::Timeout { request: index });
+            }
+
+            if now >= schedule.next_send {
+                schedule.next_send += request.period;
+                if schedule.next_send <= now {
+                    // fell behind by more than a period, don't send a burst to catch up
+                    schedule.next_send = now + request.period;
+                }
+                match socket.send(request.request) {
+                    Ok(()) => schedule.sent = Some(now),
+                    Err(error) => handler(PollEvent::SendFailed {
+                        request: index,
+                        error,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Repeatedly calls [step](Poller::step) until `handler` returns [ControlFlow::Break] or
+    /// the shutdown token is triggered.
+    pub fn run<S, F>(&mut self, socket: &S, mut handler: F)
+    where
+        S: RecvCan + SendCan,
+        F: FnMut(PollEvent<T>) -> ControlFlow<()>,
+    {
+        let mut waiter = Waiter::new(self.wait_strategy);
+        let mut stop = false;
+        while !stop && !self.shutdown.as_ref().is_some_and(Shutdown::is_requested) {
+            let mut active = false;
+            self.step(socket, |event| {
+                active = true;
+                if !stop && handler(event).is_break() {
+                    stop = true;
+                }
+            });
+            if stop {
+                break;
+            }
+            if active {
+                waiter.reset();
+            }
+            let next_send = self.schedules.iter().map(|s| s.next_send).min();
+            waiter.wait(socket, next_send);
+        }
+    }
+
+    fn dispatch<F: FnMut(PollEvent<T>)>(&mut self, frame: &CanFrame, handler: &mut F) {
+        for (index, (request, schedule)) in
+            self.requests.iter().zip(&mut self.schedules).enumerate()
+        {
+            let Some(sent) = schedule.sent else {
+                continue;
+            };
+            if !request.matches(frame) {
+                continue;
+            }
+            if let Some(value) = (request.decode)(frame) {
+                schedule.sent = None;
+                handler(PollEvent::Response {
+                    request: index,
+                    value,
+                    latency: sent.elapsed(),
+                    channel: self.channel,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MessageType, MockCanSocket, Timestamp};
+
+    fn rpm_request() -> PollRequest<u16> {
+        let request = CanFrame::new(0x7DF, MessageType::Standard, &[0x02, 0x01, 0x0C]).unwrap();
+        PollRequest::new(request, 0x7E8, Duration::from_secs(10), |frame| {
+            frame
+                .data()
+                .get(3..5)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        })
+        .with_response_match(1, &[0x41, 0x0C])
+        .with_timeout(Duration::ZERO)
+    }
+
+    #[test]
+    fn response_is_decoded() {
+        let ecu = MockCanSocket::new();
+        let mut poller = Poller::new().with_channel(2);
+        poller.add(rpm_request().with_timeout(Duration::from_secs(10)));
+
+        let mut events = Vec::new();
+        poller.step(&ecu, |event| events.push(event));
+        assert_eq!(ecu.backend().sent(ecu.channel()).len(), 1);
+        assert!(events.is_empty());
+
+        let wrong_pid = CanFrame::new(
+            0x7E8,
+            MessageType::Standard,
+            &[0x04, 0x41, 0x0D, 0x00, 0x00],
+        )
+        .unwrap();
+        let response = CanFrame::new(
+            0x7E8,
+            MessageType::Standard,
+            &[0x04, 0x41, 0x0C, 0x1A, 0xF8],
+        )
+        .unwrap();
+        for frame in [wrong_pid, response] {
+            ecu.backend()
+                .push_rx(ecu.channel(), frame, Timestamp::default());
+        }
+        poller.step(&ecu, |event| events.push(event));
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            PollEvent::Response {
+                request: 0,
+                value: 0x1AF8,
+                channel: Some(2),
+                ..
+            }
+        ));
+        // the period has not elapsed yet
+        assert_eq!(ecu.backend().sent(ecu.channel()).len(), 1);
+    }
+
+    #[test]
+    fn missing_response_times_out() {
+        let ecu = MockCanSocket::new();
+        let mut poller = Poller::new();
+        poller.add(rpm_request());
+
+        let mut events = Vec::new();
+        poller.step(&ecu, |event| events.push(event));
+        poller.step(&ecu, |event| events.push(event));
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], PollEvent::Timeout { request: 0 }));
+    }
+}
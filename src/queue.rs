@@ -0,0 +1,452 @@
+//! Bounded queues with a selectable overflow behavior.
+//!
+//! Components that buffer frames or events between threads use a [BoundedQueue] so that their
+//! memory use stays predictable when the consumer falls behind. [TxQueue] orders classic and
+//! CAN FD frames for transmission on a single channel.
+
+use crate::error::CanError;
+use crate::socket::{AnyFrame, BrsOverride, Id, SendCan, SendCanFd};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// What happens when an item is pushed into a full queue.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum OverflowPolicy {
+    /// Wait until the consumer made room. Must not be used if the consumer runs on the
+    /// pushing thread.
+    Block,
+    /// Discard the pushed item.
+    DropNewest,
+    /// Discard the oldest queued item to make room.
+    #[default]
+    DropOldest,
+}
+
+struct Inner<T> {
+    items: VecDeque<T>,
+    dropped: u64,
+}
+
+/// A thread-safe FIFO queue of limited capacity. Discarded items are counted.
+pub struct BoundedQueue<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    inner: Mutex<Inner<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<T> BoundedQueue<T> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        assert!(capacity > 0, "queue capacity must not be 0");
+        BoundedQueue {
+            capacity,
+            policy,
+            inner: Mutex::new(Inner {
+                items: VecDeque::with_capacity(capacity),
+                dropped: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    /// Queues `item`, returns `false` if it was discarded by [OverflowPolicy::DropNewest].
+    pub fn push(&self, item: T) -> bool {
+        let mut inner = self.lock();
+        if self.policy == OverflowPolicy::Block {
+            while inner.items.len() >= self.capacity {
+                inner = self.not_full.wait(inner).unwrap_or_else(|e| e.into_inner());
+            }
+        }
+        self.insert(inner, item)
+    }
+
+    /// Like [push](BoundedQueue::push), but a full [OverflowPolicy::Block] queue is waited on
+    /// for at most `timeout`. Hands `item` back if there is still no room.
+    pub fn push_timeout(&self, item: T, timeout: Duration) -> Result<bool, T> {
+        let deadline = Instant::now() + timeout;
+        let mut inner = self.lock();
+        if self.policy == OverflowPolicy::Block {
+            while inner.items.len() >= self.capacity {
+                let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                    return Err(item);
+                };
+                inner = self
+                    .not_full
+                    .wait_timeout(inner, remaining)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+        Ok(self.insert(inner, item))
+    }
+
+    /// Queues `item` into the locked queue, which has room if the policy is
+    /// [OverflowPolicy::Block].
+    fn insert(&self, mut inner: MutexGuard<'_, Inner<T>>, item: T) -> bool {
+        if inner.items.len() >= self.capacity {
+            if self.policy == OverflowPolicy::DropNewest {
+                inner.dropped += 1;
+                return false;
+            }
+            inner.items.pop_front();
+            inner.dropped += 1;
+        }
+        inner.items.push_back(item);
+        self.not_empty.notify_one();
+        true
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let item = self.lock().items.pop_front();
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+
+    /// Waits up to `timeout` for an item.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let mut inner = self.lock();
+        loop {
+            if let Some(item) = inner.items.pop_front() {
+                self.not_full.notify_one();
+                return Some(item);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            inner = self
+                .not_empty
+                .wait_timeout(inner, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of items discarded because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        // the queue stays consistent even if a holder of the lock panicked
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A classic or CAN FD frame waiting in a [TxQueue].
+pub type TxFrame = AnyFrame;
+
+/// Sort key in the order the frames would win the bus arbitration: the base ID first, a
+/// standard frame before an extended frame with the same base ID.
+fn arbitration_key(frame: &TxFrame) -> (u32, bool, u32) {
+    let id = frame.can_id();
+    if frame.is_extended_frame() {
+        (id >> 18, true, id & 0x3_FFFF)
+    } else {
+        (id, false, 0)
+    }
+}
+
+struct TxEntry {
+    frame: TxFrame,
+    deadline: Option<Instant>,
+    seq: u64,
+}
+
+struct TxInner {
+    entries: Vec<TxEntry>,
+    next_seq: u64,
+    dropped: u64,
+    brs: HashMap<Id, BrsOverride>,
+}
+
+/// A thread-safe transmit queue shared by classic and CAN FD frames of one channel.
+///
+/// Frames leave the queue in the order they would win the bus arbitration, so a long CAN FD
+/// bulk transfer on a low priority ID doesn't hold back classic control frames queued after
+/// it. A frame whose deadline has passed is sent before all others, so low priority frames
+/// with a deadline are not starved either. Frames of the same priority keep their order.
+///
+/// When the queue is full, the frame with the lowest priority is discarded.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanFdFrame, CanFrame, CanSocket, MessageType};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::queue::TxQueue;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let queue = TxQueue::new(64);
+/// for block in [[0xAA; 64]; 4] {
+///     queue.push(CanFdFrame::new(0x7E0, MessageType::Standard, &block, true, true).unwrap());
+/// }
+/// queue.push(CanFrame::new(0x100, MessageType::Standard, &[0x01]).unwrap());
+/// // sends 0x100 first
+/// queue.flush(&socket)?;
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct TxQueue {
+    capacity: usize,
+    inner: Mutex<TxInner>,
+}
+
+impl TxQueue {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "queue capacity must not be 0");
+        TxQueue {
+            capacity,
+            inner: Mutex::new(TxInner {
+                entries: Vec::with_capacity(capacity),
+                next_seq: 0,
+                dropped: 0,
+                brs: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Queues `frame`, returns `false` if it was discarded because the queue is full of frames
+    /// with a higher priority.
+    pub fn push<F: Into<TxFrame>>(&self, frame: F) -> bool {
+        self.insert(frame.into(), None)
+    }
+
+    /// Queues `frame` to be sent before all frames without an elapsed deadline once `deadline`
+    /// has passed.
+    pub fn push_with_deadline<F: Into<TxFrame>>(&self, frame: F, deadline: Instant) -> bool {
+        self.insert(frame.into(), Some(deadline))
+    }
+
+    /// Overrides the bit rate switch of the CAN FD frames with `id` queued from now on, e.g. to
+    /// send them without BRS on a marginal bus.
+    pub fn set_brs(&self, id: impl Into<Id>, brs: BrsOverride) {
+        let mut inner = self.lock();
+        match brs {
+            BrsOverride::Inherit => inner.brs.remove(&id.into()),
+            brs => inner.brs.insert(id.into(), brs),
+        };
+    }
+
+    /// Removes the frame to be sent next.
+    pub fn pop(&self) -> Option<TxFrame> {
+        let mut inner = self.lock();
+        let next = Self::next_index(&inner.entries, Instant::now())?;
+        Some(inner.entries.remove(next).frame)
+    }
+
+    /// Sends queued frames in order until the queue is empty. If sending fails, e.g. because
+    /// the transmit queue of the driver is full, the frame stays queued and the error is
+    /// returned. Returns the number of sent frames.
+    pub fn flush<S: SendCan + SendCanFd>(&self, socket: &S) -> Result<usize, CanError> {
+        let mut sent = 0;
+        loop {
+            let mut inner = self.lock();
+            let Some(next) = Self::next_index(&inner.entries, Instant::now()) else {
+                return Ok(sent);
+            };
+            // the lock is held while sending, so the failed frame keeps its place
+            inner.entries[next].frame.send(socket)?;
+            inner.entries.remove(next);
+            sent += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of frames discarded because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    fn insert(&self, frame: TxFrame, deadline: Option<Instant>) -> bool {
+        let mut inner = self.lock();
+        let frame = match inner.brs.get(&frame.id()) {
+            Some(brs) => frame.with_brs(*brs),
+            None => frame,
+        };
+        if inner.entries.len() >= self.capacity {
+            let lowest = inner
+                .entries
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, entry)| (arbitration_key(&entry.frame), entry.seq))
+                .map(|(i, _)| i)
+                .unwrap_or_default();
+            inner.dropped += 1;
+            if arbitration_key(&inner.entries[lowest].frame) <= arbitration_key(&frame) {
+                return false;
+            }
+            inner.entries.remove(lowest);
+        }
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        inner.entries.push(TxEntry {
+            frame,
+            deadline,
+            seq,
+        });
+        true
+    }
+
+    fn next_index(entries: &[TxEntry], now: Instant) -> Option<usize> {
+        let overdue = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                entry
+                    .deadline
+                    .filter(|d| *d <= now)
+                    .map(|d| (d, entry.seq, i))
+            })
+            .min();
+        if let Some((_, _, i)) = overdue {
+            return Some(i);
+        }
+        entries
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| (arbitration_key(&entry.frame), entry.seq))
+            .map(|(i, _)| i)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TxInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{CanFdFrame, CanFrame, MessageType, MockCanSocket};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn overflow_policies() {
+        let queue = BoundedQueue::new(2, OverflowPolicy::DropOldest);
+        assert!(queue.push(1) && queue.push(2) && queue.push(3));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.try_pop(), Some(2));
+
+        let queue = BoundedQueue::new(2, OverflowPolicy::DropNewest);
+        assert!(queue.push(1) && queue.push(2));
+        assert!(!queue.push(3));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.try_pop(), Some(2));
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), None);
+    }
+
+    #[test]
+    fn block_until_consumed() {
+        let queue = Arc::new(BoundedQueue::new(1, OverflowPolicy::Block));
+        queue.push(1);
+        let producer = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.push(2))
+        };
+
+        assert_eq!(queue.pop_timeout(Duration::from_secs(1)), Some(1));
+        assert!(producer.join().unwrap());
+        assert_eq!(queue.pop_timeout(Duration::from_secs(1)), Some(2));
+        assert_eq!(queue.dropped(), 0);
+
+        assert!(queue.push(3));
+        assert_eq!(queue.push_timeout(4, Duration::from_millis(1)), Err(4));
+        assert_eq!(queue.try_pop(), Some(3));
+        assert_eq!(queue.push_timeout(4, Duration::from_millis(1)), Ok(true));
+    }
+
+    #[test]
+    fn tx_queue_order() {
+        let fd = |id| CanFdFrame::new(id, MessageType::Standard, &[0xAA; 64], true, true).unwrap();
+        let classic = |id| CanFrame::new(id, MessageType::Standard, &[0x01]).unwrap();
+
+        let queue = TxQueue::new(4);
+        queue.push(fd(0x7E0));
+        queue.push(fd(0x7E0));
+        queue.push(classic(0x100));
+        queue.push_with_deadline(fd(0x7E1), Instant::now());
+        assert_eq!(queue.pop().map(|f| f.can_id()), Some(0x7E1));
+        assert_eq!(queue.pop().map(|f| f.can_id()), Some(0x100));
+
+        // a full queue discards the lowest priority frame
+        queue.push(CanFrame::new(0x100, MessageType::Extended, &[]).unwrap());
+        queue.push(classic(0x101));
+        assert!(!queue.push(fd(0x7FF)));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.len(), 4);
+
+        let socket = MockCanSocket::new();
+        socket
+            .backend()
+            .fail_next_write(socket.channel(), CanError::XmtFull);
+        assert!(queue.flush(&socket).is_err());
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.flush(&socket).unwrap(), 4);
+
+        let sent = socket.backend().sent(socket.channel());
+        assert_eq!(sent[0].can_id(), 0x100);
+        assert!(sent[0].is_extended_frame());
+        assert_eq!(sent[1].can_id(), 0x101);
+        assert_eq!(socket.backend().sent_fd(socket.channel()).len(), 2);
+    }
+
+    #[test]
+    fn tx_queue_brs_override() {
+        let fd = |id| CanFdFrame::new(id, MessageType::Standard, &[0xAA; 64], true, true).unwrap();
+        let brs = |frame: Option<TxFrame>| match frame {
+            Some(TxFrame::CanFd(frame)) => frame.is_brs(),
+            _ => panic!("not a CAN FD frame"),
+        };
+
+        let queue = TxQueue::new(4);
+        queue.set_brs(
+            Id::new(0x7E0, MessageType::Standard).unwrap(),
+            BrsOverride::Force(false),
+        );
+        queue.push(fd(0x7E0));
+        queue.push(fd(0x7E1));
+        assert!(!brs(queue.pop()));
+        assert!(brs(queue.pop()));
+
+        queue.set_brs(fd(0x7E0).id(), BrsOverride::Inherit);
+        queue.push(fd(0x7E0));
+        assert!(brs(queue.pop()));
+    }
+}
@@ -0,0 +1,268 @@
+//! A receive and send path for control loops with a latency bound.
+//!
+//! The functions and types of this module neither allocate, lock nor format once they are set
+//! up: frames are read into and sent from caller-provided slices, and a [LockFreeRing] hands
+//! them between threads through a preallocated queue. The driver calls themselves are not
+//! affected, and the rare status codes combining several errors still allocate a
+//! [CanError::Multiple].
+
+use crate::error::CanError;
+use crate::socket::{CanFdFrame, CanFrame, RecvCan, RecvCanFd, SendCan, Timestamp};
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Reads queued frames into the front of `frames` until the receive queue is empty or the slice
+/// is full. Returns the number of frames read and the error which stopped reading, if any.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::realtime;
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, Timestamp};
+/// # use peak_can::bus::UsbBus;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut frames = [(CanFrame::default(), Timestamp::default()); 64];
+/// loop {
+///     let (count, error) = realtime::recv_into(&socket, &mut frames);
+///     for (frame, timestamp) in &frames[..count] {
+///         // control loop
+///     }
+///     if let Some(err) = error {
+///         return Err(err);
+///     }
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub fn recv_into<S: RecvCan + ?Sized>(
+    socket: &S,
+    frames: &mut [(CanFrame, Timestamp)],
+) -> (usize, Option<CanError>) {
+    for (count, slot) in frames.iter_mut().enumerate() {
+        match socket.recv() {
+            Ok(received) => *slot = received,
+            Err(CanError::QrcvEmpty) => return (count, None),
+            Err(err) => return (count, Some(err)),
+        }
+    }
+    (frames.len(), None)
+}
+
+/// Like [recv_into] for CAN FD frames with timestamps in microseconds.
+pub fn recv_fd_into<S: RecvCanFd + ?Sized>(
+    socket: &S,
+    frames: &mut [(CanFdFrame, u64)],
+) -> (usize, Option<CanError>) {
+    for (count, slot) in frames.iter_mut().enumerate() {
+        match socket.recv_fd() {
+            Ok(received) => *slot = received,
+            Err(CanError::QrcvEmpty) => return (count, None),
+            Err(err) => return (count, Some(err)),
+        }
+    }
+    (frames.len(), None)
+}
+
+/// Sends `frames` in order until one fails, e.g. with [CanError::XmtFull]. Returns the number of
+/// frames sent and the error of the first frame which was not.
+pub fn send_all<S: SendCan + ?Sized>(socket: &S, frames: &[CanFrame]) -> (usize, Option<CanError>) {
+    for (count, frame) in frames.iter().enumerate() {
+        if let Err(err) = socket.send(*frame) {
+            return (count, Some(err));
+        }
+    }
+    (frames.len(), None)
+}
+
+/// A fixed-capacity queue between one producing and one consuming thread, e.g. a receive
+/// thread and a control loop. Pushing and popping only use atomic loads and stores.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::realtime::LockFreeRing;
+/// # use peak_can::socket::{CanFrame, MessageType};
+/// let mut ring = LockFreeRing::<CanFrame, 256>::new();
+/// let (mut producer, mut consumer) = ring.split();
+/// std::thread::scope(|s| {
+///     s.spawn(move || {
+///         for id in 0..10 {
+///             let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+///             while producer.push(frame).is_err() {}
+///         }
+///     });
+///     let mut received = 0;
+///     while received < 10 {
+///         if let Some(frame) = consumer.pop() {
+///             assert_eq!(frame.can_id(), received);
+///             received += 1;
+///         }
+///     }
+/// });
+/// ```
+pub struct LockFreeRing<T: Copy, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    // both indices only grow and wrap around, the slot of an index is `index % N`
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// SAFETY: a slot is written only by the producer before publishing it with `tail` and read only
+// by the consumer before releasing it with `head`, `split` ensures one of each.
+unsafe impl<T: Copy + Send, const N: usize> Sync for LockFreeRing<T, N> {}
+
+impl<T: Copy, const N: usize> LockFreeRing<T, N> {
+    pub fn new() -> Self {
+        assert!(N > 0, "ring without capacity");
+        LockFreeRing {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// The two ends of the queue, the ring stays borrowed while they are alive.
+    pub fn split(&mut self) -> (RingProducer<'_, T, N>, RingConsumer<'_, T, N>) {
+        (RingProducer { ring: self }, RingConsumer { ring: self })
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T: Copy, const N: usize> Default for LockFreeRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RingProducer<'a, T: Copy, const N: usize> {
+    ring: &'a LockFreeRing<T, N>,
+}
+
+impl<T: Copy, const N: usize> RingProducer<'_, T, N> {
+    /// Appends `item`, or returns it if the ring is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == N {
+            return Err(item);
+        }
+        // SAFETY: the consumer doesn't read the slot before `tail` is advanced
+        unsafe { (*self.ring.slots[tail % N].get()).write(item) };
+        self.ring
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+pub struct RingConsumer<'a, T: Copy, const N: usize> {
+    ring: &'a LockFreeRing<T, N>,
+}
+
+impl<T: Copy, const N: usize> RingConsumer<'_, T, N> {
+    /// Removes the oldest item.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // SAFETY: the slot was initialized by the producer before `tail` was advanced past it
+        let item = unsafe { (*self.ring.slots[head % N].get()).assume_init() };
+        self.ring
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MessageType, MockCanSocket};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // SAFETY: forwards to the system allocator
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn hot_path_does_not_allocate() {
+        let socket = MockCanSocket::new();
+        let channel = socket.channel();
+        for n in (1..=5).rev() {
+            let frame = CanFrame::new(n, MessageType::Standard, &[1, 2, 3]).unwrap();
+            socket
+                .backend()
+                .push_rx(channel, frame, Timestamp::from_micros(n as u64));
+        }
+        // grows the queues of the mock, so that only the code under test could allocate below
+        socket.backend().fail_next_write(channel, CanError::XmtFull);
+        assert!(socket.send(CanFrame::default()).is_err());
+        for _ in 0..9 {
+            socket.send(CanFrame::default()).unwrap();
+        }
+        let mut frames = [(CanFrame::default(), Timestamp::default()); 4];
+        let mut ring = LockFreeRing::<CanFrame, 4>::new();
+        let (mut producer, mut consumer) = ring.split();
+
+        let before = ALLOCATIONS.with(Cell::get);
+        let (count, error) = recv_into(&socket, &mut frames);
+        assert!(count == 4 && error.is_none());
+        let (count, error) = recv_into(&socket, &mut frames);
+        assert!(count == 1 && error.is_none());
+
+        for (frame, _) in &frames {
+            assert!(producer.push(*frame).is_ok());
+        }
+        assert!(producer.push(frames[0].0).is_err());
+        let mut out = [CanFrame::default(); 6];
+        for slot in &mut out[..4] {
+            *slot = consumer.pop().unwrap();
+        }
+        assert!(consumer.pop().is_none());
+
+        let (sent, error) = send_all(&socket, &out[..4]);
+        assert!(sent == 4 && error.is_none());
+        socket.backend().fail_next_write(channel, CanError::XmtFull);
+        let (sent, error) = send_all(&socket, &out[4..]);
+        assert!(sent == 0 && matches!(error, Some(CanError::XmtFull)));
+        assert_eq!(ALLOCATIONS.with(Cell::get), before);
+
+        assert_eq!(socket.backend().sent(channel)[9..], out[..4]);
+
+        assert_eq!(out[0], frames[0].0);
+        assert_eq!(frames[0].0.can_id(), 1);
+    }
+}
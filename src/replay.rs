@@ -0,0 +1,258 @@
+//! Replay of recorded traffic with its original timing, e.g. for bench tests of an ECU.
+//!
+//! A [Player] sends the frames of [TraceRecord]s read from any supported trace format, keeping
+//! the gaps between their timestamps scaled by [ReplayConfig::speed].
+
+use crate::error::CanError;
+use crate::shutdown::Shutdown;
+use crate::socket::{CanFdFrame, CanFrame, MessageType, SendCan, SendCanFd};
+use crate::trace::{TraceFrame, TraceRecord};
+
+use std::collections::HashMap;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    /// Factor applied to the playback speed, `2.0` plays twice as fast. Zero or less sends the
+    /// frames back to back.
+    pub speed: f64,
+    /// IDs replaced before sending, the message type of a frame is kept.
+    pub id_map: HashMap<u32, u32>,
+    /// Only replay records of this channel. Records without a channel are always replayed.
+    pub channel: Option<u16>,
+    /// How often the recording is played, endlessly if `None`.
+    pub loops: Option<u32>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            speed: 1.0,
+            id_map: HashMap::new(),
+            channel: None,
+            loops: Some(1),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReplayStats {
+    pub frames_sent: u64,
+    pub loops_completed: u32,
+    /// Number of times the transmit queue was full and the frame had to be retried.
+    pub queue_full_retries: u64,
+    /// The largest delay of a frame behind its scheduled time.
+    pub max_lateness: Duration,
+    pub elapsed: Duration,
+}
+
+/// Sends recorded frames on a socket.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::replay::{Player, ReplayConfig};
+/// # use peak_can::trace;
+/// let records = trace::open("capture.trc")?.collect::<Result<Vec<_>, _>>()?;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+///
+/// let mut config = ReplayConfig {
+///     speed: 2.0,
+///     loops: None,
+///     ..Default::default()
+/// };
+/// config.id_map.insert(0x7E0, 0x7E1);
+/// Player::new(records, config).run(&socket)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Player {
+    records: Vec<TraceRecord>,
+    config: ReplayConfig,
+    shutdown: Option<Shutdown>,
+}
+
+impl Player {
+    /// Creates a player for `records`, which are sorted by their timestamp.
+    pub fn new<I: IntoIterator<Item = TraceRecord>>(records: I, config: ReplayConfig) -> Self {
+        let mut records = records
+            .into_iter()
+            .filter(|record| {
+                config.channel.is_none()
+                    || record.channel.is_none()
+                    || record.channel == config.channel
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|record| record.timestamp);
+        Player {
+            records,
+            config,
+            shutdown: None,
+        }
+    }
+
+    /// Stops the replay once `shutdown` is triggered.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn config(&self) -> &ReplayConfig {
+        &self.config
+    }
+
+    /// Duration of one pass through the recording at the configured speed.
+    pub fn duration(&self) -> Duration {
+        match (self.records.first(), self.records.last()) {
+            (Some(first), Some(last)) => self.scale(last.timestamp - first.timestamp),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Sends the recording until the configured number of loops is played or the shutdown is
+    /// triggered.
+    ///
+    /// A full transmit queue is not treated as an error; the frame is retried instead.
+    pub fn run<S: SendCan + SendCanFd>(&self, socket: &S) -> Result<ReplayStats, CanError> {
+        let mut stats = ReplayStats::default();
+        let start = Instant::now();
+        let Some(first) = self.records.first().map(|record| record.timestamp) else {
+            return Ok(stats);
+        };
+
+        let mut loop_start = start;
+        while self
+            .config
+            .loops
+            .is_none_or(|loops| stats.loops_completed < loops)
+        {
+            for record in &self.records {
+                if self.is_shutdown() {
+                    stats.elapsed = start.elapsed();
+                    return Ok(stats);
+                }
+
+                let scheduled = loop_start + self.scale(record.timestamp - first);
+                let now = Instant::now();
+                if scheduled > now {
+                    sleep(scheduled - now);
+                } else {
+                    stats.max_lateness = stats.max_lateness.max(now - scheduled);
+                }
+
+                let frame = self.remap(&record.frame);
+                loop {
+                    let result = match frame {
+                        TraceFrame::Can(frame) => socket.send(frame),
+                        TraceFrame::CanFd(frame) => socket.send_fd(frame),
+                    };
+                    match result {
+                        Ok(()) => break,
+                        Err(CanError::XmtFull) | Err(CanError::QxmtFull) => {
+                            stats.queue_full_retries += 1;
+                            std::thread::yield_now();
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                stats.frames_sent += 1;
+            }
+            stats.loops_completed += 1;
+            // the next pass starts right after the last frame of this one
+            loop_start = Instant::now();
+        }
+
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.as_ref().is_some_and(Shutdown::is_requested)
+    }
+
+    fn scale(&self, offset: Duration) -> Duration {
+        if self.config.speed > 0.0 {
+            offset.div_f64(self.config.speed)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn remap(&self, frame: &TraceFrame) -> TraceFrame {
+        let Some(can_id) = self.config.id_map.get(&frame.can_id()).copied() else {
+            return *frame;
+        };
+        let msg_type = if frame.is_extended_frame() {
+            MessageType::Extended
+        } else {
+            MessageType::Standard
+        };
+        let remapped = match frame {
+            TraceFrame::Can(can) if can.is_remote_frame() => {
+                CanFrame::new_remote(can_id, msg_type, can.dlc()).map(TraceFrame::Can)
+            }
+            TraceFrame::Can(can) => {
+                CanFrame::new(can_id, msg_type, can.data()).map(TraceFrame::Can)
+            }
+            TraceFrame::CanFd(fd) => {
+                CanFdFrame::new(can_id, msg_type, fd.data(), true, fd.is_brs())
+                    .map(TraceFrame::CanFd)
+            }
+        };
+        // the payload always fits, an ID which does not fit the frame format keeps the original
+        remapped.unwrap_or(*frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::MockCanSocket;
+    use crate::trace::test_util::record;
+
+    #[test]
+    fn replay_timing_and_remapping() {
+        let ms = Duration::from_millis;
+        let records = vec![
+            record(ms(1_030), Some(1), 0x300, &[1, 2]),
+            record(ms(1_000), Some(1), 0x100, &[1, 2]),
+            record(ms(1_010), Some(2), 0x200, &[1, 2]),
+            record(ms(1_020), Some(1), 0x100, &[1, 2]),
+        ];
+        let mut config = ReplayConfig {
+            speed: 2.0,
+            channel: Some(1),
+            loops: Some(2),
+            ..Default::default()
+        };
+        config.id_map.insert(0x100, 0x101);
+        let player = Player::new(records, config);
+        assert_eq!(player.duration(), Duration::from_millis(15));
+
+        let socket = MockCanSocket::new();
+        let stats = player.run(&socket).unwrap();
+        assert_eq!(stats.frames_sent, 6);
+        assert_eq!(stats.loops_completed, 2);
+        assert!(stats.elapsed >= Duration::from_millis(30));
+
+        let ids = socket
+            .backend()
+            .sent(socket.channel())
+            .iter()
+            .map(|frame| frame.can_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [0x101, 0x101, 0x300, 0x101, 0x101, 0x300]);
+
+        let shutdown = Shutdown::new();
+        shutdown.trigger();
+        let config = ReplayConfig {
+            loops: None,
+            ..Default::default()
+        };
+        let player = Player::new(vec![record(ms(0), Some(1), 0x100, &[1, 2])], config)
+            .with_shutdown(shutdown);
+        assert_eq!(player.run(&socket).unwrap().frames_sent, 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! A description of the PCAN installation to attach to bug reports.
+//!
+//! [environment_report] collects the library, driver and channel information that support
+//! usually asks for. Queries which fail are recorded in the report instead of aborting it, a
+//! missing library is the most common finding.
+
+use crate::discover::{self, ChannelInfo};
+use crate::info::{self, ChannelVersion};
+
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelReport {
+    pub handle: u16,
+    pub bus: String,
+    pub device_name: String,
+    pub device_type: u8,
+    pub device_id: u32,
+    pub controller_number: u8,
+    pub condition: String,
+    /// `FEATURE_*` flags of the channel.
+    pub features: u32,
+    pub fd_capable: bool,
+    pub driver_version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvironmentReport {
+    pub crate_version: String,
+    /// Operating system and architecture, e.g. `linux x86_64`.
+    pub platform: String,
+    /// File name of the PCAN-Basic library as searched by the loader.
+    pub library: String,
+    pub library_version: Option<String>,
+    /// Driver version of the first channel which reports one.
+    pub driver_version: Option<String>,
+    pub channels: Vec<ChannelReport>,
+    /// Queries which failed, e.g. `api version: ...`.
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for EnvironmentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".into());
+        writeln!(f, "peak-can {} on {}", self.crate_version, self.platform)?;
+        writeln!(
+            f,
+            "Library: {} {}",
+            self.library,
+            unknown(&self.library_version)
+        )?;
+        writeln!(f, "Driver: {}", unknown(&self.driver_version))?;
+        writeln!(f, "Channels: {}", self.channels.len())?;
+        for channel in &self.channels {
+            writeln!(
+                f,
+                "  {:#x} {} \"{}\" id {} controller {} {} features {:#x}{}",
+                channel.handle,
+                channel.bus,
+                channel.device_name,
+                channel.device_id,
+                channel.controller_number,
+                channel.condition,
+                channel.features,
+                if channel.fd_capable { " (FD)" } else { "" },
+            )?;
+        }
+        for error in &self.errors {
+            writeln!(f, "Error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&ChannelInfo> for ChannelReport {
+    fn from(info: &ChannelInfo) -> Self {
+        ChannelReport {
+            handle: info.channel_handle,
+            bus: format!("{:?}", info.bus),
+            device_name: info.device_name.clone(),
+            device_type: info.device_type,
+            device_id: info.device_id,
+            controller_number: info.controller_number,
+            condition: format!("{:?}", info.condition),
+            features: info.features,
+            fd_capable: info.is_fd_capable(),
+            driver_version: None,
+        }
+    }
+}
+
+/// Collects the installation details without opening any channel.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::report::environment_report;
+/// print!("{}", environment_report());
+/// ```
+pub fn environment_report() -> EnvironmentReport {
+    let mut report = EnvironmentReport {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        platform: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        library: libloading::library_filename("PCANBasic")
+            .to_string_lossy()
+            .into_owned(),
+        library_version: None,
+        driver_version: None,
+        channels: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    match info::api_version() {
+        Ok(version) => report.library_version = Some(version.to_string()),
+        Err(err) => report.errors.push(format!("api version: {err}")),
+    }
+
+    let channels = match discover::attached_channels() {
+        Ok(channels) => channels,
+        Err(err) => {
+            report.errors.push(format!("attached channels: {err}"));
+            Vec::new()
+        }
+    };
+    for info in &channels {
+        let mut channel = ChannelReport::from(info);
+        match info.bus.channel_version() {
+            Ok(version) => channel.driver_version = Some(version.device_driver_name_and_version),
+            Err(err) => report.errors.push(format!(
+                "version of channel {:#x}: {err}",
+                info.channel_handle
+            )),
+        }
+        report.channels.push(channel);
+    }
+    report.driver_version = report
+        .channels
+        .iter()
+        .find_map(|channel| channel.driver_version.clone());
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_text() {
+        let report = EnvironmentReport {
+            crate_version: "0.2.0".to_string(),
+            platform: "linux x86_64".to_string(),
+            library: "libPCANBasic.so".to_string(),
+            library_version: Some("4.8.0.5".to_string()),
+            driver_version: None,
+            channels: vec![ChannelReport {
+                handle: 0x51,
+                bus: "Usb(USB1)".to_string(),
+                device_name: "PCAN-USB FD".to_string(),
+                device_type: 5,
+                device_id: 3,
+                controller_number: 0,
+                condition: "Available".to_string(),
+                features: 1,
+                fd_capable: true,
+                driver_version: None,
+            }],
+            errors: vec!["version of channel 0x51: timeout".to_string()],
+        };
+        assert_eq!(
+            report.to_string(),
+            "peak-can 0.2.0 on linux x86_64\nLibrary: libPCANBasic.so 4.8.0.5\n\
+             Driver: unknown\nChannels: 1\n  \
+             0x51 Usb(USB1) \"PCAN-USB FD\" id 3 controller 0 Available features 0x1 (FD)\n\
+             Error: version of channel 0x51: timeout\n"
+        );
+    }
+}
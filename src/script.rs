@@ -0,0 +1,264 @@
+//! [Rhai](https://rhai.rs) scripts inspecting the frames received by a [Dispatcher] and sending
+//! responses, for quick experiments on the bus without recompiling.
+//!
+//! A script defines `fn on_frame(id, extended, data)`, which is called for every frame of the
+//! route with `data` as an array of bytes. It sends frames with `send(id, data)` and
+//! `send_extended(id, data)`:
+//!
+//! ```text
+//! fn on_frame(id, extended, data) {
+//!     if id == 0x7DF && data[1] == 0x01 {
+//!         send(0x7E8, [0x03, 0x41, data[2], 0x32]);
+//!     }
+//! }
+//! ```
+
+use crate::dispatch::{CallbackId, Dispatcher};
+use crate::error::CanError;
+use crate::filter::FilterSet;
+use crate::socket::{CanFrame, MessageType, ReceivedFrame, SendCan};
+
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, INT, Scope};
+
+use core::fmt;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// Operations a script may run per frame, so that an endless loop does not stall the
+/// dispatcher.
+const MAX_OPERATIONS: u64 = 100_000;
+
+#[derive(Debug, Clone)]
+pub enum ScriptError {
+    /// The script does not compile.
+    Compile(String),
+    /// `on_frame` failed, e.g. it is missing or sent an invalid frame.
+    Runtime(String),
+    /// A frame sent by the script failed to send.
+    Can(CanError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Compile(err) => write!(f, "Script does not compile: {err}"),
+            ScriptError::Runtime(err) => write!(f, "Script failed: {err}"),
+            ScriptError::Can(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<CanError> for ScriptError {
+    fn from(value: CanError) -> Self {
+        ScriptError::Can(value)
+    }
+}
+
+/// A compiled script, attached to a [Dispatcher] with [attach](FrameScript::attach).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::dispatch::Dispatcher;
+/// # use peak_can::filter::FilterSet;
+/// # use peak_can::script::FrameScript;
+/// # use peak_can::socket::{Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use std::sync::Arc;
+/// let socket = Arc::new(CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?);
+/// let script = FrameScript::compile(
+///     "fn on_frame(id, extended, data) { send(id + 8, data); }",
+/// )?;
+///
+/// let mut dispatcher = Dispatcher::new();
+/// let hook = script.attach(&mut dispatcher, FilterSet::allow_all(), socket.clone());
+/// dispatcher.run(&socket);
+/// if let Some(err) = hook.last_error() {
+///     eprintln!("{err}");
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FrameScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    outbox: Rc<RefCell<Vec<CanFrame>>>,
+}
+
+impl FrameScript {
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let outbox = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        for (name, msg_type) in [
+            ("send", MessageType::Standard),
+            ("send_extended", MessageType::Extended),
+        ] {
+            let outbox = outbox.clone();
+            engine.register_fn(
+                name,
+                move |id: INT, data: Array| -> Result<(), Box<EvalAltResult>> {
+                    let frame = script_frame(id, msg_type, &data)?;
+                    outbox.borrow_mut().push(frame);
+                    Ok(())
+                },
+            );
+        }
+        let ast = engine
+            .compile(source)
+            .map_err(|err| ScriptError::Compile(err.to_string()))?;
+        Ok(FrameScript {
+            engine,
+            ast,
+            scope: Scope::new(),
+            outbox,
+        })
+    }
+
+    /// Calls `on_frame` for `received` and returns the frames the script sent. On an error
+    /// the frames sent before are discarded.
+    pub fn on_frame(&mut self, received: &ReceivedFrame) -> Result<Vec<CanFrame>, ScriptError> {
+        let frame = &received.frame;
+        let data: Array = frame
+            .data()
+            .iter()
+            .map(|&byte| Dynamic::from_int(byte as INT))
+            .collect();
+        let args = (frame.can_id() as INT, frame.is_extended_frame(), data);
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, "on_frame", args);
+        let sent = self.outbox.take();
+        result.map_err(|err| ScriptError::Runtime(err.to_string()))?;
+        Ok(sent)
+    }
+
+    /// Registers the script for the frames accepted by `filter` at `dispatcher`, the frames it
+    /// sends go out through `socket` right away. A failing script or send is recorded in the
+    /// returned handle, the script keeps being called for the next frames.
+    pub fn attach<S>(self, dispatcher: &mut Dispatcher, filter: FilterSet, socket: S) -> ScriptHook
+    where
+        S: SendCan + 'static,
+    {
+        let mut script = self;
+        let last_error = Arc::new(Mutex::new(None));
+        let errors = last_error.clone();
+        let callback = dispatcher.on_frame_matching(filter, move |received| {
+            let result = script.on_frame(received).and_then(|frames| {
+                frames
+                    .into_iter()
+                    .try_for_each(|frame| socket.send(frame).map_err(ScriptError::from))
+            });
+            if let Err(err) = result {
+                *errors.lock().unwrap_or_else(|e| e.into_inner()) = Some(err);
+            }
+        });
+        ScriptHook {
+            callback,
+            last_error,
+        }
+    }
+}
+
+fn script_frame(
+    id: INT,
+    msg_type: MessageType,
+    data: &Array,
+) -> Result<CanFrame, Box<EvalAltResult>> {
+    let id = u32::try_from(id).map_err(|_| format!("invalid CAN ID {id}"))?;
+    let data = data
+        .iter()
+        .map(|byte| {
+            let byte = byte.as_int()?;
+            u8::try_from(byte).map_err(|_| "data bytes must be within 0..=255")
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    CanFrame::new(id, msg_type, &data).map_err(|err| err.to_string().into())
+}
+
+/// Handle of a [FrameScript] attached to a [Dispatcher].
+pub struct ScriptHook {
+    callback: CallbackId,
+    last_error: Arc<Mutex<Option<ScriptError>>>,
+}
+
+impl ScriptHook {
+    /// The dispatcher callback running the script, to remove it again.
+    pub fn callback(&self) -> CallbackId {
+        self.callback
+    }
+
+    /// The error of the last frame the script failed on.
+    pub fn last_error(&self) -> Option<ScriptError> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MockCanSocket, Timestamp};
+
+    #[test]
+    fn scripted_responses() {
+        let socket = MockCanSocket::new();
+        let script = FrameScript::compile(
+            r#"
+            fn on_frame(id, extended, data) {
+                if data[0] == 0xFF {
+                    send(id, [256]);
+                } else if extended {
+                    send_extended(id + 1, data);
+                } else {
+                    send(0x7E8, [0x03, 0x41, data[1], 0x32]);
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let mut dispatcher = Dispatcher::new();
+        let hook = script.attach(&mut dispatcher, FilterSet::allow_all(), socket.clone());
+
+        let requests = [
+            CanFrame::new(0x7DF, MessageType::Standard, &[0x01, 0x0D]).unwrap(),
+            CanFrame::new(0x18DA00F1, MessageType::Extended, &[1, 2]).unwrap(),
+        ];
+        for frame in requests {
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+            dispatcher.step(&socket);
+        }
+        assert!(hook.last_error().is_none());
+        let sent = socket.backend().take_sent(socket.channel());
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].can_id(), 0x7E8);
+        assert_eq!(sent[0].data(), [0x03, 0x41, 0x0D, 0x32]);
+        assert_eq!(sent[1].can_id(), 0x18DA00F2);
+        assert!(sent[1].is_extended_frame());
+
+        // an invalid frame fails the script, the dispatcher keeps running it
+        let invalid = CanFrame::new(0x100, MessageType::Standard, &[0xFF]).unwrap();
+        socket
+            .backend()
+            .push_rx(socket.channel(), invalid, Timestamp::default());
+        dispatcher.step(&socket);
+        assert!(matches!(hook.last_error(), Some(ScriptError::Runtime(_))));
+        assert!(socket.backend().take_sent(socket.channel()).is_empty());
+        assert!(dispatcher.panicked().is_empty());
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert!(matches!(
+            FrameScript::compile("fn on_frame(id, extended, data) {"),
+            Err(ScriptError::Compile(_))
+        ));
+    }
+}
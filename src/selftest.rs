@@ -0,0 +1,238 @@
+//! Startup self-test of a channel, e.g. for device provisioning scripts.
+//!
+//! [self_test] opens the channel and runs a fixed set of checks. A failing check doesn't stop
+//! the following ones, so the [SelfTestReport] shows everything that is wrong at once.
+
+use crate::bus::Bus;
+use crate::error::CanError;
+use crate::parameter::{Parameter, ParameterValue, Parameters};
+use crate::socket::{
+    Baudrate, BusState, BusStatus, CanFrame, CanSocket, MessageType, RecvCan, SendCan,
+};
+
+use core::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelfTestConfig {
+    pub baudrate: Baudrate,
+    /// ID of the standard frame sent for the echo check.
+    pub echo_id: u32,
+    /// How long to wait for the echo of the sent frame.
+    pub echo_timeout: Duration,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        SelfTestConfig {
+            baudrate: Baudrate::Baud500K,
+            echo_id: 0x7FF,
+            echo_timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckOutcome {
+    Passed,
+    /// The check could not run, e.g. because the device lacks the feature.
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestReport {
+    pub channel: u16,
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// Whether no check failed. Skipped checks don't count as failures.
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.outcome, CheckOutcome::Failed(_)))
+    }
+
+    fn push(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.checks.push(CheckResult { name, outcome });
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Self-test of channel {:#x}", self.channel)?;
+        for check in &self.checks {
+            match &check.outcome {
+                CheckOutcome::Passed => writeln!(f, "  {}: passed", check.name)?,
+                CheckOutcome::Skipped(reason) => {
+                    writeln!(f, "  {}: skipped ({reason})", check.name)?
+                }
+                CheckOutcome::Failed(reason) => writeln!(f, "  {}: FAILED ({reason})", check.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens the channel of `bus` and checks
+///
+/// - that the hardware name and the nominal bus speed can be read and match the configuration,
+/// - that the controller is not error passive or bus-off,
+/// - that a sent frame is echoed back, if the device supports echo frames. The frame is only
+///   echoed after it was acknowledged, so another node has to be on the bus,
+/// - that the channel closes without error.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::selftest::{SelfTestConfig, self_test};
+/// # use peak_can::bus::UsbBus;
+/// let report = self_test(UsbBus::USB1, &SelfTestConfig::default());
+/// print!("{report}");
+/// std::process::exit(if report.passed() { 0 } else { 1 });
+/// ```
+pub fn self_test<T: Bus>(bus: T, config: &SelfTestConfig) -> SelfTestReport {
+    let mut report = SelfTestReport {
+        channel: bus.channel(),
+        checks: Vec::new(),
+    };
+
+    let socket = match CanSocket::open(bus, config.baudrate) {
+        Ok(socket) => socket,
+        Err(err) => {
+            report.push("open", CheckOutcome::Failed(err.to_string()));
+            for name in ["parameters", "bus state", "echo", "close"] {
+                report.push(name, CheckOutcome::Skipped("channel not open".to_string()));
+            }
+            return report;
+        }
+    };
+    report.push("open", CheckOutcome::Passed);
+    report.push("parameters", outcome(check_parameters(&socket, config)));
+    report.push("bus state", outcome(check_bus_state(&socket)));
+    report.push("echo", outcome(check_echo(&socket, config)));
+    report.push(
+        "close",
+        outcome(socket.close().map(|_| CheckOutcome::Passed)),
+    );
+    report
+}
+
+fn outcome(result: Result<CheckOutcome, CanError>) -> CheckOutcome {
+    result.unwrap_or_else(|err| CheckOutcome::Failed(err.to_string()))
+}
+
+fn check_parameters(socket: &CanSocket, config: &SelfTestConfig) -> Result<CheckOutcome, CanError> {
+    if let ParameterValue::Text(name) = socket.get_value(Parameter::HardwareName)?
+        && name.is_empty()
+    {
+        return Ok(CheckOutcome::Failed("empty hardware name".to_string()));
+    }
+
+    let expected = config.baudrate.bits_per_second();
+    match socket.get_value(Parameter::NominalBusSpeed) {
+        Ok(ParameterValue::U32(speed)) if speed != expected => Ok(CheckOutcome::Failed(format!(
+            "nominal bus speed is {speed} bit/s instead of {expected} bit/s"
+        ))),
+        // older devices don't report the bus speed
+        Ok(_) | Err(CanError::IllParamType) => Ok(CheckOutcome::Passed),
+        Err(err) => Err(err),
+    }
+}
+
+fn check_bus_state(socket: &CanSocket) -> Result<CheckOutcome, CanError> {
+    Ok(match socket.bus_state()? {
+        BusState::ErrorActive | BusState::ErrorWarning => CheckOutcome::Passed,
+        state => CheckOutcome::Failed(format!("controller is {state:?}")),
+    })
+}
+
+fn check_echo(socket: &CanSocket, config: &SelfTestConfig) -> Result<CheckOutcome, CanError> {
+    if let Err(err) = socket.set_value(Parameter::AllowEchoFrames, ParameterValue::Bool(true)) {
+        return Ok(CheckOutcome::Skipped(format!("no echo frames: {err}")));
+    }
+    let result = send_and_await_echo(socket, config);
+    let reset = socket.set_value(Parameter::AllowEchoFrames, ParameterValue::Bool(false));
+    let outcome = result?;
+    reset?;
+    Ok(outcome)
+}
+
+fn send_and_await_echo(
+    socket: &CanSocket,
+    config: &SelfTestConfig,
+) -> Result<CheckOutcome, CanError> {
+    const PATTERN: [u8; 8] = [0x55, 0xAA, 0x00, 0xFF, 0x01, 0x02, 0x04, 0x08];
+    let frame = CanFrame::new(config.echo_id, MessageType::Standard, &PATTERN)
+        .map_err(|_| CanError::IllParamVal)?;
+    socket.send(frame)?;
+
+    let deadline = Instant::now() + config.echo_timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(CheckOutcome::Failed("no echo received".to_string()));
+        }
+        match socket.recv_timeout(remaining) {
+            Ok((received, _)) if received.is_echo_frame() => {
+                return Ok(if is_echo_of(&received, &frame) {
+                    CheckOutcome::Passed
+                } else {
+                    CheckOutcome::Failed(format!("echo differs from sent frame: {received:?}"))
+                });
+            }
+            Ok(_) => {}
+            Err(CanError::QrcvEmpty) => {
+                return Ok(CheckOutcome::Failed("no echo received".to_string()));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn is_echo_of(echo: &CanFrame, sent: &CanFrame) -> bool {
+    echo.can_id() == sent.can_id()
+        && echo.is_extended_frame() == sent.is_extended_frame()
+        && echo.data() == sent.data()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_failures() {
+        let mut report = SelfTestReport {
+            channel: 0x51,
+            checks: Vec::new(),
+        };
+        report.push("open", CheckOutcome::Passed);
+        report.push("echo", CheckOutcome::Skipped("unsupported".to_string()));
+        assert!(report.passed());
+
+        report.push("close", CheckOutcome::Failed("timeout".to_string()));
+        assert!(!report.passed());
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(
+            report.to_string(),
+            "Self-test of channel 0x51\n  open: passed\n  echo: skipped (unsupported)\n  \
+             close: FAILED (timeout)\n"
+        );
+
+        let sent = CanFrame::new(0x7FF, MessageType::Standard, &[1, 2]).unwrap();
+        let other = CanFrame::new(0x7FF, MessageType::Standard, &[1, 3]).unwrap();
+        assert!(is_echo_of(&sent, &sent));
+        assert!(!is_echo_of(&other, &sent));
+    }
+}
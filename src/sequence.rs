@@ -0,0 +1,263 @@
+//! Sequence number stamping of transmitted frames and order verification on reception.
+//!
+//! A [SequenceStamper] writes an incrementing counter into chosen payload bytes of each frame,
+//! a [SequenceVerifier] reads it back from echo frames or from the far side of a gateway and
+//! reports dropped, reordered and duplicated frames.
+
+use crate::error::CanError;
+use crate::payload::{ByteOrder, Payload, PayloadError};
+use crate::socket::{CanFrame, SendCan};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SequenceWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl SequenceWidth {
+    fn modulus(&self) -> u64 {
+        match self {
+            SequenceWidth::U8 => 1 << 8,
+            SequenceWidth::U16 => 1 << 16,
+            SequenceWidth::U32 => 1 << 32,
+        }
+    }
+}
+
+/// Location of the sequence number in the payload.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SequenceField {
+    pub offset: usize,
+    pub width: SequenceWidth,
+    pub order: ByteOrder,
+}
+
+impl SequenceField {
+    pub fn new(offset: usize, width: SequenceWidth, order: ByteOrder) -> Self {
+        SequenceField {
+            offset,
+            width,
+            order,
+        }
+    }
+
+    fn read<P: Payload>(&self, frame: &P) -> Option<u64> {
+        match self.width {
+            SequenceWidth::U8 => frame.u8_at(self.offset).map(u64::from),
+            SequenceWidth::U16 => frame.u16_at(self.offset, self.order).map(u64::from),
+            SequenceWidth::U32 => frame.u32_at(self.offset, self.order).map(u64::from),
+        }
+    }
+
+    fn write<P: Payload>(&self, frame: &mut P, value: u64) -> Result<(), PayloadError> {
+        match self.width {
+            SequenceWidth::U8 => frame.set_u8_at(self.offset, value as u8),
+            SequenceWidth::U16 => frame.set_u16_at(self.offset, value as u16, self.order),
+            SequenceWidth::U32 => frame.set_u32_at(self.offset, value as u32, self.order),
+        }
+    }
+}
+
+pub struct SequenceStamper {
+    field: SequenceField,
+    next: u64,
+}
+
+impl SequenceStamper {
+    pub fn new(field: SequenceField) -> Self {
+        SequenceStamper { field, next: 0 }
+    }
+
+    /// Writes the next sequence number into `frame` and returns it.
+    pub fn stamp<P: Payload>(&mut self, frame: &mut P) -> Result<u64, PayloadError> {
+        let sequence = self.next;
+        self.field.write(frame, sequence)?;
+        self.next = (sequence + 1) % self.field.width.modulus();
+        Ok(sequence)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SequenceCheck {
+    InOrder,
+    /// `missing` frames between the expected and the received sequence number were lost.
+    Dropped {
+        expected: u64,
+        received: u64,
+        missing: u64,
+    },
+    /// A frame older than the expected one arrived late.
+    Reordered {
+        expected: u64,
+        received: u64,
+    },
+    /// The last frame was received again.
+    Duplicate {
+        received: u64,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SequenceStats {
+    pub received: u64,
+    pub in_order: u64,
+    pub dropped: u64,
+    pub reordered: u64,
+    pub duplicates: u64,
+}
+
+pub struct SequenceVerifier {
+    field: SequenceField,
+    last: Option<u64>,
+    stats: SequenceStats,
+}
+
+impl SequenceVerifier {
+    pub fn new(field: SequenceField) -> Self {
+        SequenceVerifier {
+            field,
+            last: None,
+            stats: SequenceStats::default(),
+        }
+    }
+
+    /// Checks the sequence number of a received frame. The first frame is always in order.
+    pub fn check<P: Payload>(&mut self, frame: &P) -> Result<SequenceCheck, PayloadError> {
+        let received = self.field.read(frame).ok_or(PayloadError::OutOfRange)?;
+        let modulus = self.field.width.modulus();
+        self.stats.received += 1;
+
+        let Some(last) = self.last else {
+            self.last = Some(received);
+            self.stats.in_order += 1;
+            return Ok(SequenceCheck::InOrder);
+        };
+
+        let expected = (last + 1) % modulus;
+        let ahead = (received + modulus - expected) % modulus;
+        let check = if ahead == 0 {
+            self.stats.in_order += 1;
+            SequenceCheck::InOrder
+        } else if received == last {
+            self.stats.duplicates += 1;
+            return Ok(SequenceCheck::Duplicate { received });
+        } else if ahead < modulus / 2 {
+            self.stats.dropped += ahead;
+            SequenceCheck::Dropped {
+                expected,
+                received,
+                missing: ahead,
+            }
+        } else {
+            // late frames don't move the expected sequence number back
+            self.stats.reordered += 1;
+            return Ok(SequenceCheck::Reordered { expected, received });
+        };
+
+        self.last = Some(received);
+        Ok(check)
+    }
+
+    pub fn stats(&self) -> SequenceStats {
+        self.stats
+    }
+}
+
+/// Stamps every frame with the next sequence number before sending it.
+pub struct SequencedSender<'a, S: SendCan> {
+    socket: &'a S,
+    stamper: SequenceStamper,
+}
+
+impl<'a, S: SendCan> SequencedSender<'a, S> {
+    pub fn new(socket: &'a S, field: SequenceField) -> Self {
+        SequencedSender {
+            socket,
+            stamper: SequenceStamper::new(field),
+        }
+    }
+
+    /// Sends `frame` and returns the sequence number it was stamped with. The counter only
+    /// advances for frames that were accepted by the driver.
+    pub fn send(&mut self, mut frame: CanFrame) -> Result<u64, CanError> {
+        let next = self.stamper.next;
+        let sequence = self
+            .stamper
+            .stamp(&mut frame)
+            .map_err(|_| CanError::IllData)?;
+        if let Err(err) = self.socket.send(frame) {
+            self.stamper.next = next;
+            return Err(err);
+        }
+        Ok(sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::MessageType;
+
+    fn frames(sequences: &[u8]) -> Vec<CanFrame> {
+        sequences
+            .iter()
+            .map(|s| CanFrame::new(0x100, MessageType::Standard, &[0xAA, *s]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn stamp_wraps_around() {
+        let field = SequenceField::new(1, SequenceWidth::U8, ByteOrder::BigEndian);
+        let mut stamper = SequenceStamper::new(field);
+        stamper.next = 255;
+
+        let mut frame = CanFrame::new(0x100, MessageType::Standard, &[0xAA, 0]).unwrap();
+        assert_eq!(stamper.stamp(&mut frame), Ok(255));
+        assert_eq!(frame.data(), &[0xAA, 255]);
+        assert_eq!(stamper.stamp(&mut frame), Ok(0));
+
+        let mut short = CanFrame::new(0x100, MessageType::Standard, &[0xAA]).unwrap();
+        assert_eq!(stamper.stamp(&mut short), Err(PayloadError::OutOfRange));
+    }
+
+    #[test]
+    fn verify_order() {
+        let field = SequenceField::new(1, SequenceWidth::U8, ByteOrder::BigEndian);
+        let mut verifier = SequenceVerifier::new(field);
+
+        let checks = frames(&[254, 255, 0, 3, 2, 3, 4])
+            .iter()
+            .map(|f| verifier.check(f).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                SequenceCheck::InOrder,
+                SequenceCheck::InOrder,
+                SequenceCheck::InOrder,
+                SequenceCheck::Dropped {
+                    expected: 1,
+                    received: 3,
+                    missing: 2
+                },
+                SequenceCheck::Reordered {
+                    expected: 4,
+                    received: 2
+                },
+                SequenceCheck::Duplicate { received: 3 },
+                SequenceCheck::InOrder,
+            ]
+        );
+        assert_eq!(
+            verifier.stats(),
+            SequenceStats {
+                received: 7,
+                in_order: 4,
+                dropped: 2,
+                reordered: 1,
+                duplicates: 1,
+            }
+        );
+    }
+}
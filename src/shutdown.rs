@@ -0,0 +1,109 @@
+//! Coordinated shutdown of receive loops, recorders and channels.
+//!
+//! A [Shutdown] token is cloned into every component. Loops such as the frame iterators and
+//! the [Poller](crate::poller::Poller) end once it is triggered, and registered hooks run in
+//! the order of their [ShutdownStage]: first background tasks are stopped and joined, then
+//! trace writers are flushed and finally channels are uninitialized.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownStage {
+    StopTasks,
+    FlushWriters,
+    CloseChannels,
+}
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Inner {
+    requested: AtomicBool,
+    hooks: Mutex<Vec<(ShutdownStage, Hook)>>,
+}
+
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.inner.requested.load(Ordering::Acquire)
+    }
+
+    /// Registers a hook run by [trigger](Shutdown::trigger). Hooks of the same stage run in
+    /// registration order. A hook registered after the shutdown was triggered runs immediately.
+    pub fn on_shutdown<F>(&self, stage: ShutdownStage, hook: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut hooks = self.inner.hooks.lock().unwrap_or_else(|e| e.into_inner());
+        if self.is_requested() {
+            drop(hooks);
+            hook();
+        } else {
+            hooks.push((stage, Box::new(hook)));
+        }
+    }
+
+    /// Requests all components to stop and runs the registered hooks stage by stage. Only the
+    /// first call has an effect.
+    pub fn trigger(&self) {
+        let mut hooks = {
+            let mut hooks = self.inner.hooks.lock().unwrap_or_else(|e| e.into_inner());
+            if self.inner.requested.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            std::mem::take(&mut *hooks)
+        };
+
+        hooks.sort_by_key(|(stage, _)| *stage);
+        for (_, hook) in hooks {
+            hook();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_run_in_stage_order_once() {
+        let shutdown = Shutdown::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        for (stage, name) in [
+            (ShutdownStage::CloseChannels, "close"),
+            (ShutdownStage::StopTasks, "stop rx"),
+            (ShutdownStage::FlushWriters, "flush"),
+            (ShutdownStage::StopTasks, "stop tx"),
+        ] {
+            let order = order.clone();
+            shutdown.on_shutdown(stage, move || order.lock().unwrap().push(name));
+        }
+
+        let clone = shutdown.clone();
+        assert!(!clone.is_requested());
+        clone.trigger();
+        shutdown.trigger();
+
+        assert!(shutdown.is_requested());
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["stop rx", "stop tx", "flush", "close"]
+        );
+
+        let late = order.clone();
+        shutdown.on_shutdown(ShutdownStage::StopTasks, move || {
+            late.lock().unwrap().push("late")
+        });
+        assert_eq!(order.lock().unwrap().last(), Some(&"late"));
+    }
+}
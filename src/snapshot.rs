@@ -0,0 +1,229 @@
+//! A "current bus state" view of a channel for diagnostic tools.
+//!
+//! [BusSnapshot::capture] listens on a socket for a while and collects the last frame, count and
+//! rate of every ID together with the health of the bus in a single struct.
+
+use crate::error::CanError;
+use crate::socket::{BusState, BusStatusFrame, CanFrame, ErrorCounters, RecvCan, Timestamp};
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdSnapshot {
+    pub can_id: u32,
+    pub extended: bool,
+    /// The last frame received with this ID.
+    pub last_frame: CanFrame,
+    pub last_timestamp: Timestamp,
+    pub count: u64,
+    /// Frames per second over the capture duration.
+    pub rate: f64,
+    /// Mean time between two frames by device timestamps, `None` for a single frame.
+    pub period: Option<Duration>,
+}
+
+/// Health of the bus during the capture. Error counters and status changes are only reported
+/// with the parameters `AllowErrorFrames` and `AllowStatusFrames` enabled.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BusHealth {
+    /// The last bus state reported by a status frame, an error frame or a receive error.
+    pub bus_state: Option<BusState>,
+    pub error_counters: Option<ErrorCounters>,
+    pub error_frames: u64,
+    /// The controller or the receive queue of the driver lost frames.
+    pub overrun: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BusSnapshot {
+    pub duration: Duration,
+    /// Number of received data and remote frames.
+    pub frames: u64,
+    pub frames_per_second: f64,
+    /// Statistics per ID, ordered by ID with standard before extended IDs.
+    pub ids: Vec<IdSnapshot>,
+    pub health: BusHealth,
+}
+
+impl BusSnapshot {
+    /// Receives from `socket` for `duration` and returns the collected state. Receive errors
+    /// that report the bus state or an overrun are recorded in [BusSnapshot::health], other
+    /// errors end the capture.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket};
+    /// # use peak_can::bus::UsbBus;
+    /// # use peak_can::snapshot::BusSnapshot;
+    /// # use std::time::Duration;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// let snapshot = BusSnapshot::capture(&socket, Duration::from_secs(1))?;
+    /// for id in &snapshot.ids {
+    ///     println!("{:8x} {:6.1}/s {:02x?}", id.can_id, id.rate, id.last_frame.data());
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    pub fn capture<S: RecvCan>(socket: &S, duration: Duration) -> Result<BusSnapshot, CanError> {
+        let mut collector = Collector::default();
+        let start = Instant::now();
+        let deadline = start + duration;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            match socket.recv_timeout(remaining) {
+                Ok((frame, timestamp)) => collector.push(&frame, timestamp),
+                Err(CanError::QrcvEmpty) => {}
+                Err(err) => collector.push_error(err)?,
+            }
+        }
+        Ok(collector.finish(start.elapsed()))
+    }
+
+    /// The statistics of an ID, `None` if it was not seen.
+    pub fn id(&self, can_id: u32, extended: bool) -> Option<&IdSnapshot> {
+        self.ids
+            .iter()
+            .find(|id| id.can_id == can_id && id.extended == extended)
+    }
+}
+
+struct IdStats {
+    last_frame: CanFrame,
+    last_timestamp: Timestamp,
+    first_micros: u64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Collector {
+    ids: BTreeMap<(bool, u32), IdStats>,
+    frames: u64,
+    health: BusHealth,
+}
+
+impl Collector {
+    fn push(&mut self, frame: &CanFrame, timestamp: Timestamp) {
+        if let Some(status) = BusStatusFrame::decode(frame) {
+            self.health.bus_state = Some(status.bus_state);
+            self.health.overrun |= status.overrun || status.receive_queue_overrun;
+            return;
+        }
+        if let Some(counters) = frame.error_counters() {
+            self.health.error_frames += 1;
+            self.health.error_counters = Some(counters);
+            self.health.bus_state = Some(counters.bus_state());
+            return;
+        }
+        if frame.is_error_frame() {
+            self.health.error_frames += 1;
+            return;
+        }
+
+        self.frames += 1;
+        self.ids
+            .entry((frame.is_extended_frame(), frame.can_id()))
+            .and_modify(|stats| {
+                stats.last_frame = *frame;
+                stats.last_timestamp = timestamp;
+                stats.count += 1;
+            })
+            .or_insert(IdStats {
+                last_frame: *frame,
+                last_timestamp: timestamp,
+                first_micros: timestamp.as_micros(),
+                count: 1,
+            });
+    }
+
+    fn push_error(&mut self, err: CanError) -> Result<(), CanError> {
+        let bus_state = match err {
+            CanError::BusLight | CanError::BusHeavy => BusState::ErrorWarning,
+            CanError::BusPassive => BusState::ErrorPassive,
+            CanError::BusOff => BusState::BusOff,
+            CanError::Overrun | CanError::QOverrun => {
+                self.health.overrun = true;
+                return Ok(());
+            }
+            err => return Err(err),
+        };
+        self.health.bus_state = Some(bus_state);
+        Ok(())
+    }
+
+    fn finish(self, duration: Duration) -> BusSnapshot {
+        let secs = duration.as_secs_f64();
+        let per_second = |count: u64| {
+            if secs > 0.0 { count as f64 / secs } else { 0.0 }
+        };
+
+        let ids = self
+            .ids
+            .into_iter()
+            .map(|((extended, can_id), stats)| {
+                let span = stats
+                    .last_timestamp
+                    .as_micros()
+                    .saturating_sub(stats.first_micros);
+                IdSnapshot {
+                    can_id,
+                    extended,
+                    last_frame: stats.last_frame,
+                    last_timestamp: stats.last_timestamp,
+                    count: stats.count,
+                    rate: per_second(stats.count),
+                    period: (stats.count > 1)
+                        .then(|| Duration::from_micros(span / (stats.count - 1))),
+                }
+            })
+            .collect();
+
+        BusSnapshot {
+            duration,
+            frames: self.frames,
+            frames_per_second: per_second(self.frames),
+            ids,
+            health: self.health,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MessageType, MockCanSocket};
+
+    #[test]
+    fn capture_mock_traffic() {
+        let socket = MockCanSocket::new();
+        let backend = socket.backend();
+        for i in 0..5u64 {
+            let frame = CanFrame::new(0x200, MessageType::Standard, &[i as u8]).unwrap();
+            backend.push_rx(socket.channel(), frame, Timestamp::from_micros(i * 10_000));
+        }
+        let frame = CanFrame::new(0x100, MessageType::Extended, &[0xFF]).unwrap();
+        backend.push_rx(socket.channel(), frame, Timestamp::from_micros(5_000));
+        backend.push_rx_error(socket.channel(), CanError::BusPassive);
+
+        let snapshot = BusSnapshot::capture(&socket, Duration::from_millis(20)).unwrap();
+        assert_eq!(snapshot.frames, 6);
+        assert_eq!(snapshot.ids.len(), 2);
+        // standard IDs come first
+        assert_eq!(snapshot.ids[0].can_id, 0x200);
+
+        let id = snapshot.id(0x200, false).unwrap();
+        assert_eq!(id.count, 5);
+        assert_eq!(id.last_frame.data(), &[4]);
+        assert_eq!(id.period, Some(Duration::from_millis(10)));
+        assert!(id.rate > 0.0);
+        assert_eq!(snapshot.id(0x100, true).unwrap().period, None);
+        assert_eq!(snapshot.health.bus_state, Some(BusState::ErrorPassive));
+
+        backend.push_rx_error(socket.channel(), CanError::Unknown);
+        assert!(BusSnapshot::capture(&socket, Duration::from_millis(20)).is_err());
+    }
+}
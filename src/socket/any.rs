@@ -0,0 +1,157 @@
+//! A single frame type for code handling classic and CAN FD frames alike.
+//!
+//! Traces and the TX queue use the same type under the names
+//! [TraceFrame](crate::trace::TraceFrame) and [TxFrame](crate::queue::TxFrame).
+
+use crate::error::CanError;
+use crate::peak_can;
+use crate::socket::{BrsOverride, CanFdFrame, CanFrame, Id, SendCan, SendCanFd};
+
+/// A classic or CAN FD frame.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::socket::{AnyFrame, CanSocket, RecvCanFd};
+/// let socket = CanSocket::open_fd_with_bitrate(UsbBus::USB1, "f_clock_mhz=80, nom_brp=10, \
+///     nom_tseg1=12, nom_tseg2=3, nom_sjw=1, data_brp=4, data_tseg1=7, data_tseg2=2, data_sjw=1")?;
+/// let (frame, micros) = socket.recv_any()?;
+/// match frame {
+///     AnyFrame::Can(frame) => println!("{micros} classic {}", frame.id()),
+///     AnyFrame::CanFd(frame) => {
+///         println!("{micros} CAN FD {} {} bytes", frame.id(), frame.data().len())
+///     }
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnyFrame {
+    Can(CanFrame),
+    CanFd(CanFdFrame),
+}
+
+impl AnyFrame {
+    pub fn id(&self) -> Id {
+        match self {
+            AnyFrame::Can(frame) => frame.id(),
+            AnyFrame::CanFd(frame) => frame.id(),
+        }
+    }
+
+    pub fn can_id(&self) -> u32 {
+        match self {
+            AnyFrame::Can(frame) => frame.can_id(),
+            AnyFrame::CanFd(frame) => frame.can_id(),
+        }
+    }
+
+    pub fn is_extended_frame(&self) -> bool {
+        match self {
+            AnyFrame::Can(frame) => frame.is_extended_frame(),
+            AnyFrame::CanFd(frame) => frame.is_extended_frame(),
+        }
+    }
+
+    pub fn is_fd(&self) -> bool {
+        matches!(self, AnyFrame::CanFd(_))
+    }
+
+    pub fn is_echo_frame(&self) -> bool {
+        match self {
+            AnyFrame::Can(frame) => frame.is_echo_frame(),
+            AnyFrame::CanFd(frame) => frame.is_echo_frame(),
+        }
+    }
+
+    pub fn is_error_frame(&self) -> bool {
+        match self {
+            AnyFrame::Can(frame) => frame.is_error_frame(),
+            AnyFrame::CanFd(frame) => frame.is_error_frame(),
+        }
+    }
+
+    pub fn dlc(&self) -> u8 {
+        match self {
+            AnyFrame::Can(frame) => frame.dlc(),
+            AnyFrame::CanFd(frame) => frame.dlc(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            AnyFrame::Can(frame) => frame.data(),
+            AnyFrame::CanFd(frame) => frame.data(),
+        }
+    }
+
+    /// Applies `brs` to a CAN FD frame, classic frames have no bit rate switch.
+    pub fn with_brs(mut self, brs: BrsOverride) -> Self {
+        if let AnyFrame::CanFd(frame) = &mut self {
+            brs.apply(frame);
+        }
+        self
+    }
+
+    /// Sends the frame with [SendCan::send] or [SendCanFd::send_fd].
+    pub fn send<S: SendCan + SendCanFd + ?Sized>(&self, socket: &S) -> Result<(), CanError> {
+        match self {
+            AnyFrame::Can(frame) => socket.send(*frame),
+            AnyFrame::CanFd(frame) => socket.send_fd(*frame),
+        }
+    }
+
+    /// Sorts a frame read from a CAN FD channel: frames without the FD flag are classic frames.
+    pub(crate) fn from_received(frame: CanFdFrame) -> AnyFrame {
+        if frame.is_fd_frame() || frame.dlc() > 8 {
+            return AnyFrame::CanFd(frame);
+        }
+        AnyFrame::Can(CanFrame {
+            frame: peak_can::TPEAKMsg {
+                ID: frame.frame.ID,
+                MSGTYPE: frame.frame.MSGTYPE,
+                LEN: frame.frame.DLC,
+                DATA: frame.frame.DATA[..8].try_into().unwrap(),
+            },
+        })
+    }
+}
+
+impl From<CanFrame> for AnyFrame {
+    fn from(frame: CanFrame) -> Self {
+        AnyFrame::Can(frame)
+    }
+}
+
+impl From<CanFdFrame> for AnyFrame {
+    fn from(frame: CanFdFrame) -> Self {
+        AnyFrame::CanFd(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::VirtualPair;
+    use crate::socket::{CanSocket, MessageType, RecvCanFd};
+
+    #[test]
+    fn recv_any_sorts_frames() {
+        let pair = VirtualPair::new().unwrap();
+        let a = CanSocket::open_fd_with_bitrate(pair.a(), "f_clock_mhz=80").unwrap();
+        let b = CanSocket::open_fd_with_bitrate(pair.b(), "f_clock_mhz=80").unwrap();
+
+        let classic = CanFrame::new(0x123, MessageType::Extended, &[1, 2]).unwrap();
+        let fd = CanFdFrame::new(0x123, MessageType::Extended, &[3; 12], true, true).unwrap();
+        AnyFrame::from(classic).send(&a).unwrap();
+        AnyFrame::from(fd).send(&a).unwrap();
+
+        let (received, _) = b.recv_any().unwrap();
+        assert_eq!(received, AnyFrame::Can(classic));
+        assert!(received.is_extended_frame() && !received.is_fd());
+        assert_eq!(received.id(), classic.id());
+        let (received, _) = b.recv_any().unwrap();
+        assert_eq!(received, AnyFrame::CanFd(fd));
+        assert_eq!(received.data(), [3; 12]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Tokio integration of [CanSocket] based on the driver receive event.
+//!
+//! The receive event file descriptor is registered with the tokio reactor, so waiting for
+//! frames doesn't block a runtime thread. Only available on Linux, where the driver exposes the
+//! receive event as a pollable file descriptor. Backends without such a descriptor, e.g.
+//! [MockBackend](crate::socket::MockBackend), are polled: a waiting task wakes itself again
+//! until a frame is queued.
+
+use crate::error::CanError;
+use crate::socket::{
+    Backend, CanFrame, CanSocket, PcanBackend, ReceiveEvent, RecvCan, SendCan, Socket, Timestamp,
+};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::future::poll_fn;
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+
+/// A [CanSocket] usable as [Stream] of received frames and [Sink] of frames to send.
+///
+/// Sending never waits: the driver transmit queue is not observable, so a full queue is
+/// reported as [CanError::QxmtFull] by [start_send](Sink::start_send) and [send](AsyncCanSocket::send).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{AsyncCanSocket, Baudrate, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # async fn example() -> Result<(), peak_can::error::CanError> {
+/// let socket = AsyncCanSocket::new(CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?)?;
+/// loop {
+///     let (frame, timestamp) = socket.recv().await?;
+///     println!("{:?} {:?}", frame, timestamp);
+/// }
+/// # }
+/// ```
+pub struct AsyncCanSocket<B: Backend = PcanBackend> {
+    socket: CanSocket<B>,
+    /// `None` for backends without a pollable receive event.
+    event: Option<AsyncFd<EventFd>>,
+}
+
+/// The file descriptor of a driver receive event, kept registered while the reactor polls it.
+struct EventFd {
+    fd: RawFd,
+    _event: Arc<ReceiveEvent>,
+}
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl<B: Backend> AsyncCanSocket<B> {
+    /// Registers the receive event of `socket` with the reactor of the current tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime, if the backend has a pollable receive
+    /// event.
+    pub fn new(socket: CanSocket<B>) -> Result<Self, CanError> {
+        let event = socket.receive_event()?;
+        let event = match event.as_raw_fd() {
+            Some(fd) => {
+                let event = EventFd { fd, _event: event };
+                let event = AsyncFd::with_interest(event, Interest::READABLE)
+                    .map_err(|_| CanError::Resource)?;
+                Some(event)
+            }
+            None => None,
+        };
+        Ok(AsyncCanSocket { socket, event })
+    }
+
+    pub fn get_ref(&self) -> &CanSocket<B> {
+        &self.socket
+    }
+
+    pub fn into_inner(self) -> CanSocket<B> {
+        self.socket
+    }
+
+    /// Waits for the next received frame.
+    pub async fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub async fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        self.socket.send(frame)
+    }
+
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<(CanFrame, Timestamp), CanError>> {
+        loop {
+            match self.socket.recv() {
+                Err(CanError::QrcvEmpty) => {}
+                result => return Poll::Ready(result),
+            }
+
+            let Some(event) = &self.event else {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            };
+            let mut guard = ready!(event.poll_read_ready(cx)).map_err(|_| CanError::Resource)?;
+            // the event may have been signaled before the queue was read above
+            match self.socket.recv() {
+                Err(CanError::QrcvEmpty) => guard.clear_ready(),
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
+
+impl<B: Backend> Stream for AsyncCanSocket<B> {
+    type Item = Result<(CanFrame, Timestamp), CanError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(Some)
+    }
+}
+
+impl<B: Backend> Sink<CanFrame> for AsyncCanSocket<B> {
+    type Error = CanError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), CanError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: CanFrame) -> Result<(), CanError> {
+        self.socket.send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), CanError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), CanError>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MessageType, MockCanSocket};
+    use std::pin::pin;
+    use std::task::Waker;
+
+    #[test]
+    fn receive_from_mock() {
+        let mock = MockCanSocket::new();
+        let socket = AsyncCanSocket::new(mock.clone()).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(socket.poll_recv(&mut cx).is_pending());
+
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap();
+        mock.backend()
+            .push_rx(mock.channel(), frame, Timestamp::from_micros(10));
+        match pin!(socket.recv()).poll(&mut cx) {
+            Poll::Ready(Ok((received, timestamp))) => {
+                assert_eq!(received, frame);
+                assert_eq!(timestamp.as_micros(), 10);
+            }
+            _ => panic!("frame not received"),
+        }
+
+        mock.backend()
+            .push_rx_error(mock.channel(), CanError::BusOff);
+        let mut socket = pin!(socket);
+        assert!(matches!(
+            socket.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some(Err(CanError::BusOff)))
+        ));
+    }
+
+    #[test]
+    fn send_to_mock() {
+        let mock = MockCanSocket::new();
+        let socket = AsyncCanSocket::new(mock.clone()).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        let frame = CanFrame::new(0x456, MessageType::Extended, &[3]).unwrap();
+
+        assert!(matches!(
+            pin!(socket.send(frame)).poll(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+
+        let mut socket = pin!(socket);
+        assert!(matches!(
+            socket.as_mut().poll_ready(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        mock.backend()
+            .fail_next_write(mock.channel(), CanError::QxmtFull);
+        assert!(matches!(
+            socket.as_mut().start_send(frame),
+            Err(CanError::QxmtFull)
+        ));
+        socket.as_mut().start_send(frame).unwrap();
+        assert_eq!(mock.backend().sent(mock.channel()), [frame, frame]);
+    }
+}
@@ -0,0 +1,223 @@
+//! The channel calls of the driver behind a trait.
+//!
+//! [CanSocket](crate::socket::CanSocket) initializes, configures, reads and writes its channel
+//! through a [Backend], [PcanBackend] by default. Other implementations, e.g.
+//! [MockBackend](crate::socket::MockBackend) or [VirtualBackend](crate::socket::VirtualBackend),
+//! run the same frame handling without a PEAK adapter.
+
+use crate::error::{CanError, CanOkError};
+use crate::peak_can;
+use crate::peak_lib;
+use crate::socket::event::ReceiveEvent;
+use crate::socket::vcan::{self, VirtualBackend};
+use crate::socket::{BusState, CanFdFrame, CanFrame, Timestamp};
+
+use std::ffi::{CString, c_void};
+
+pub trait Backend {
+    /// Initializes `channel` with the BTR0/BTR1 register value of a
+    /// [Baudrate](crate::socket::Baudrate) or a custom bit timing.
+    fn initialize(&self, channel: u16, btr0btr1: u16) -> Result<(), CanError>;
+    /// Initializes `channel` in CAN FD mode with a PCAN-Basic bitrate string.
+    fn initialize_fd(&self, channel: u16, bitrate: &str) -> Result<(), CanError>;
+    fn uninitialize(&self, channel: u16) -> Result<(), CanError>;
+    /// Reads the next frame of `channel`, [CanError::QrcvEmpty] if there is none.
+    fn read(&self, channel: u16) -> Result<(CanFrame, Timestamp), CanError>;
+    fn write(&self, channel: u16, frame: &CanFrame) -> Result<(), CanError>;
+    /// Reads the next frame of `channel` and its timestamp in microseconds.
+    fn read_fd(&self, channel: u16) -> Result<(CanFdFrame, u64), CanError>;
+    fn write_fd(&self, channel: u16, frame: &CanFdFrame) -> Result<(), CanError>;
+    /// Reads the `PEAK_*` parameter `parameter` of `channel` into `data`.
+    fn get_value(&self, channel: u16, parameter: u8, data: &mut [u8]) -> Result<(), CanError>;
+    fn set_value(&self, channel: u16, parameter: u8, data: &[u8]) -> Result<(), CanError>;
+    /// Lets the IDs from `from_id` to `to_id` pass, `mode` is `PEAK_MODE_STANDARD` or
+    /// `PEAK_MODE_EXTENDED`.
+    fn filter_messages(&self, channel: u16, from_id: u32, to_id: u32, mode: u8)
+    -> Result<(), CanError>;
+    fn bus_state(&self, channel: u16) -> Result<BusState, CanError>;
+    /// The event signaled when frames are queued at `channel`. Polls by default.
+    fn receive_event(&self, channel: u16) -> Result<ReceiveEvent, CanError> {
+        let _ = channel;
+        Ok(ReceiveEvent::poll())
+    }
+}
+
+/// The PCAN-Basic library. The handles of [VirtualBus](crate::bus::VirtualBus) channels are
+/// served by [VirtualBackend] instead, so that they open through the same constructors.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PcanBackend;
+
+fn check(code: u32) -> Result<(), CanError> {
+    match CanOkError::try_from(code) {
+        Ok(CanOkError::Ok) => Ok(()),
+        Ok(CanOkError::Err(err)) => Err(err),
+        Err(_) => Err(CanError::Unknown),
+    }
+}
+
+/// [VirtualBackend] for the handles of virtual channels, which the driver doesn't know.
+fn virtual_backend(channel: u16) -> Option<VirtualBackend> {
+    vcan::is_virtual(channel).then_some(VirtualBackend)
+}
+
+impl Backend for PcanBackend {
+    fn initialize(&self, channel: u16, btr0btr1: u16) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.initialize(channel, btr0btr1);
+        }
+        let code = unsafe { peak_lib()?.CAN_Initialize(channel, btr0btr1, 0, 0, 0) };
+        check(code)
+    }
+
+    fn initialize_fd(&self, channel: u16, bitrate: &str) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.initialize_fd(channel, bitrate);
+        }
+        let mut bitrate_bytes = CString::new(bitrate)
+            .map_err(|_| CanError::IllParamVal)?
+            .into_bytes_with_nul();
+
+        let code =
+            unsafe { peak_lib()?.CAN_InitializeFD(channel, bitrate_bytes.as_mut_ptr().cast()) };
+        check(code)
+    }
+
+    fn uninitialize(&self, channel: u16) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.uninitialize(channel);
+        }
+        let code = unsafe { peak_lib()?.CAN_Uninitialize(channel) };
+        check(code)
+    }
+
+    fn read(&self, channel: u16) -> Result<(CanFrame, Timestamp), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.read(channel);
+        }
+        let mut frame = CanFrame::default();
+        let mut timestamp = peak_can::TPEAKTimestamp {
+            micros: 0,
+            millis: 0,
+            millis_overflow: 0,
+        };
+
+        let code = unsafe {
+            peak_lib()?.CAN_Read(
+                channel,
+                &mut frame.frame as *mut peak_can::TPEAKMsg,
+                &mut timestamp as *mut peak_can::TPEAKTimestamp,
+            )
+        };
+        check(code)?;
+        Ok((frame, Timestamp { timestamp }))
+    }
+
+    fn write(&self, channel: u16, frame: &CanFrame) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.write(channel, frame);
+        }
+        let mut frame = *frame;
+        let code =
+            unsafe { peak_lib()?.CAN_Write(channel, &mut frame.frame as *mut peak_can::TPEAKMsg) };
+        check(code)
+    }
+
+    fn read_fd(&self, channel: u16) -> Result<(CanFdFrame, u64), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.read_fd(channel);
+        }
+        let mut frame = CanFdFrame::default();
+        let mut timestamp = 0u64;
+
+        let code = unsafe {
+            peak_lib()?.CAN_ReadFD(
+                channel,
+                &mut frame.frame as *mut peak_can::TPEAKMsgFD,
+                &mut timestamp as *mut u64,
+            )
+        };
+        check(code)?;
+        Ok((frame, timestamp))
+    }
+
+    fn write_fd(&self, channel: u16, frame: &CanFdFrame) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.write_fd(channel, frame);
+        }
+        let mut frame = *frame;
+        let code = unsafe {
+            peak_lib()?.CAN_WriteFD(channel, &mut frame.frame as *mut peak_can::TPEAKMsgFD)
+        };
+        check(code)
+    }
+
+    fn get_value(&self, channel: u16, parameter: u8, data: &mut [u8]) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.get_value(channel, parameter, data);
+        }
+        let code = unsafe {
+            peak_lib()?.CAN_GetValue(
+                channel,
+                parameter,
+                data.as_mut_ptr() as *mut c_void,
+                data.len() as u32,
+            )
+        };
+        check(code)
+    }
+
+    fn set_value(&self, channel: u16, parameter: u8, data: &[u8]) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.set_value(channel, parameter, data);
+        }
+        // the driver only reads the buffer
+        let mut data = data.to_vec();
+        let code = unsafe {
+            peak_lib()?.CAN_SetValue(
+                channel,
+                parameter,
+                data.as_mut_ptr() as *mut c_void,
+                data.len() as u32,
+            )
+        };
+        check(code)
+    }
+
+    fn filter_messages(
+        &self,
+        channel: u16,
+        from_id: u32,
+        to_id: u32,
+        mode: u8,
+    ) -> Result<(), CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.filter_messages(channel, from_id, to_id, mode);
+        }
+        let code = unsafe { peak_lib()?.CAN_FilterMessages(channel, from_id, to_id, mode) };
+        check(code)
+    }
+
+    fn bus_state(&self, channel: u16) -> Result<BusState, CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.bus_state(channel);
+        }
+        let code = unsafe { peak_lib()?.CAN_GetStatus(channel) };
+
+        let bus_bits = peak_can::PEAK_ERROR_BUSLIGHT
+            | peak_can::PEAK_ERROR_BUSWARNING
+            | peak_can::PEAK_ERROR_BUSPASSIVE
+            | peak_can::PEAK_ERROR_BUSOFF;
+        if code & bus_bits != 0 {
+            return Ok(BusState::from_status(code));
+        }
+        check(code)?;
+        Ok(BusState::ErrorActive)
+    }
+
+    fn receive_event(&self, channel: u16) -> Result<ReceiveEvent, CanError> {
+        if let Some(backend) = virtual_backend(channel) {
+            return backend.receive_event(channel);
+        }
+        ReceiveEvent::register(channel)
+    }
+}
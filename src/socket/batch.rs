@@ -0,0 +1,107 @@
+//! Reading the received frames of a control-loop slot without blocking past its deadline.
+
+use crate::error::CanError;
+use crate::socket::{CanFrame, RecvCan, Timestamp};
+
+use std::time::Instant;
+
+/// Why [recv_until](RecvCan::recv_until) stopped reading.
+#[derive(Debug, Clone)]
+pub enum BatchEnd {
+    /// The receive queue is empty, all frames received before the call are in the batch.
+    Drained,
+    /// The deadline passed before the queue was empty, the remaining frames stay queued for
+    /// the next slot.
+    DeadlineExpired,
+    /// Reading failed after the frames of the batch, e.g. with a bus error.
+    Error(CanError),
+}
+
+/// Frames read by [recv_until](RecvCan::recv_until).
+#[derive(Debug, Clone)]
+pub struct FrameBatch {
+    pub frames: Vec<(CanFrame, Timestamp)>,
+    pub end: BatchEnd,
+}
+
+impl FrameBatch {
+    /// Whether the loop fell behind and frames were left in the queue.
+    pub fn deadline_expired(&self) -> bool {
+        matches!(self.end, BatchEnd::DeadlineExpired)
+    }
+}
+
+/// Appends the queued frames to `frames` until the queue is empty or `deadline` passed.
+pub(crate) fn recv_until<S: RecvCan + ?Sized>(
+    socket: &S,
+    deadline: Instant,
+    frames: &mut Vec<(CanFrame, Timestamp)>,
+) -> BatchEnd {
+    loop {
+        if Instant::now() >= deadline {
+            return BatchEnd::DeadlineExpired;
+        }
+        match socket.recv() {
+            Ok(received) => frames.push(received),
+            Err(CanError::QrcvEmpty) => return BatchEnd::Drained,
+            Err(err) => return BatchEnd::Error(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MessageType, MockCanSocket};
+    use std::time::Duration;
+
+    #[test]
+    fn batch_until_drained_or_deadline() {
+        let socket = MockCanSocket::new();
+        for id in 0..3 {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+        socket
+            .backend()
+            .push_rx_error(socket.channel(), CanError::BusPassive);
+
+        let expired = socket.recv_until(Instant::now());
+        assert!(expired.deadline_expired() && expired.frames.is_empty());
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let batch = socket.recv_until(deadline);
+        assert_eq!(batch.frames.len(), 3);
+        assert!(matches!(batch.end, BatchEnd::Error(CanError::BusPassive)));
+
+        let mut frames = Vec::new();
+        assert!(matches!(
+            socket.recv_until_into(deadline, &mut frames),
+            BatchEnd::Drained
+        ));
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn drain_up_to_max() {
+        let socket = MockCanSocket::new();
+        for id in 0..5 {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::default());
+        }
+
+        let mut frames = Vec::new();
+        assert_eq!(socket.drain(&mut frames, 3).unwrap(), 3);
+        assert_eq!(socket.drain(&mut frames, 3).unwrap(), 2);
+        assert_eq!(socket.drain(&mut frames, 3).unwrap(), 0);
+        let ids = frames
+            .iter()
+            .map(|(frame, _)| frame.can_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,313 @@
+//! Opening a [CanSocket] with its whole configuration at once.
+
+use crate::bus::Bus;
+use crate::channel::Channel;
+use crate::df::{
+    AcceptanceFilter, FilterMessages, SetAllowEchoFrames, SetAllowErrorFrames, SetAllowRTRFrames,
+    SetAllowStatusFrames,
+};
+use crate::error::CanError;
+use crate::socket::event::EventSlot;
+use crate::socket::probe::RawChannel;
+use crate::socket::usb::calculate_btr0btr1;
+use crate::socket::{
+    Backend, Baudrate, CanBitTiming, CanFdBitTiming, CanSocket, EXTENDED_MASK, MessageType,
+    PcanBackend, STANDARD_MASK, build_timing_string,
+};
+use crate::special::{SetBusOffAutoreset, SetListenOnly};
+
+use core::fmt;
+
+#[derive(Debug)]
+pub enum BuilderError {
+    /// Neither a baudrate nor a bit timing was configured.
+    MissingBitrate,
+    /// A listen-only node sends nothing that could be echoed.
+    EchoWhileListenOnly,
+    /// A filter range is empty or exceeds the IDs of its message type.
+    InvalidFilterRange {
+        from_id: u32,
+        to_id: u32,
+        msg_type: MessageType,
+    },
+    /// The driver rejected the configuration.
+    Can(CanError),
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::MissingBitrate => write!(f, "No baudrate or bit timing configured"),
+            BuilderError::EchoWhileListenOnly => {
+                write!(f, "Echo frames cannot be received in listen-only mode")
+            }
+            BuilderError::InvalidFilterRange {
+                from_id,
+                to_id,
+                msg_type,
+            } => write!(
+                f,
+                "Invalid {msg_type:?} filter range {from_id:#X}..={to_id:#X}"
+            ),
+            BuilderError::Can(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {}
+
+impl From<CanError> for BuilderError {
+    fn from(value: CanError) -> Self {
+        BuilderError::Can(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Bitrate {
+    Baudrate(Baudrate),
+    Btr0Btr1(u16),
+    Fd(String),
+}
+
+/// Configures a [CanSocket] before the channel is initialized, instead of setting parameters
+/// one by one after opening it. The combination is validated first, and a socket whose
+/// configuration fails is closed again.
+///
+/// Options left unset keep the defaults of the driver.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocketBuilder, MessageType};
+/// # use peak_can::bus::UsbBus;
+/// let socket = CanSocketBuilder::new(UsbBus::USB1)
+///     .with_baudrate(Baudrate::Baud500K)
+///     .with_listen_only(true)
+///     .with_filter(0x700, 0x7FF, MessageType::Standard)
+///     .with_error_frames(true)
+///     .open()?;
+/// # Ok::<(), peak_can::socket::BuilderError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CanSocketBuilder {
+    handle: u16,
+    bitrate: Option<Bitrate>,
+    listen_only: bool,
+    bus_off_autoreset: Option<bool>,
+    status_frames: Option<bool>,
+    rtr_frames: Option<bool>,
+    error_frames: Option<bool>,
+    echo_frames: Option<bool>,
+    filters: Vec<(u32, u32, MessageType)>,
+    acceptance_filters: Vec<AcceptanceFilter>,
+}
+
+impl CanSocketBuilder {
+    pub fn new<T: Bus>(bus: T) -> Self {
+        CanSocketBuilder {
+            handle: bus.channel(),
+            bitrate: None,
+            listen_only: false,
+            bus_off_autoreset: None,
+            status_frames: None,
+            rtr_frames: None,
+            error_frames: None,
+            echo_frames: None,
+            filters: Vec::new(),
+            acceptance_filters: Vec::new(),
+        }
+    }
+
+    pub fn with_baudrate(mut self, baud: Baudrate) -> Self {
+        self.bitrate = Some(Bitrate::Baudrate(baud));
+        self
+    }
+
+    /// Custom timing of a classic CAN channel, replacing a configured baudrate.
+    pub fn with_bit_timing(mut self, timing: &CanBitTiming) -> Self {
+        self.bitrate = Some(Bitrate::Btr0Btr1(calculate_btr0btr1(timing)));
+        self
+    }
+
+    /// Opens the channel in FD mode with custom timing for nominal and data phases.
+    pub fn with_fd_timing(mut self, timing: &CanFdBitTiming) -> Self {
+        self.bitrate = Some(Bitrate::Fd(build_timing_string(timing)));
+        self
+    }
+
+    /// Opens the channel in FD mode with a PCAN-Basic bitrate string.
+    pub fn with_fd_bitrate(mut self, bitrate: &str) -> Self {
+        self.bitrate = Some(Bitrate::Fd(bitrate.to_string()));
+        self
+    }
+
+    pub fn with_listen_only(mut self, enable: bool) -> Self {
+        self.listen_only = enable;
+        self
+    }
+
+    pub fn with_bus_off_autoreset(mut self, enable: bool) -> Self {
+        self.bus_off_autoreset = Some(enable);
+        self
+    }
+
+    pub fn with_status_frames(mut self, enable: bool) -> Self {
+        self.status_frames = Some(enable);
+        self
+    }
+
+    pub fn with_rtr_frames(mut self, enable: bool) -> Self {
+        self.rtr_frames = Some(enable);
+        self
+    }
+
+    pub fn with_error_frames(mut self, enable: bool) -> Self {
+        self.error_frames = Some(enable);
+        self
+    }
+
+    pub fn with_echo_frames(mut self, enable: bool) -> Self {
+        self.echo_frames = Some(enable);
+        self
+    }
+
+    /// Lets frames with IDs from `from_id` to `to_id` pass, see
+    /// [filter](crate::df::FilterMessages::filter). Further ranges widen the filter.
+    pub fn with_filter(mut self, from_id: u32, to_id: u32, msg_type: MessageType) -> Self {
+        self.filters.push((from_id, to_id, msg_type));
+        self
+    }
+
+    /// Programs the hardware acceptance filter of its message type, the last one wins.
+    pub fn with_acceptance_filter(mut self, filter: AcceptanceFilter) -> Self {
+        self.acceptance_filters.push(filter);
+        self
+    }
+
+    /// Checks the combination of options without touching the channel.
+    pub fn validate(&self) -> Result<(), BuilderError> {
+        if self.bitrate.is_none() {
+            return Err(BuilderError::MissingBitrate);
+        }
+        if self.listen_only && self.echo_frames == Some(true) {
+            return Err(BuilderError::EchoWhileListenOnly);
+        }
+        for &(from_id, to_id, msg_type) in &self.filters {
+            let max_id = match msg_type {
+                MessageType::Standard => STANDARD_MASK,
+                MessageType::Extended => EXTENDED_MASK,
+            };
+            if from_id > to_id || to_id > max_id {
+                return Err(BuilderError::InvalidFilterRange {
+                    from_id,
+                    to_id,
+                    msg_type,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the configuration and opens the socket with it.
+    pub fn open(self) -> Result<CanSocket, BuilderError> {
+        self.open_with_backend(PcanBackend)
+    }
+
+    /// Validates the configuration and opens the socket with it on `backend`. The builder is
+    /// kept, e.g. to open the channel again with the same configuration.
+    pub fn open_with_backend<B: Backend>(&self, backend: B) -> Result<CanSocket<B>, BuilderError> {
+        self.validate()?;
+
+        if self.listen_only {
+            RawChannel(self.handle, &backend).set_listen_only(true)?;
+        }
+        if let Err(err) = self.initialize(&backend) {
+            self.reset_listen_only(&backend);
+            return Err(err.into());
+        }
+        let socket = CanSocket {
+            handle: self.handle,
+            backend,
+            event: EventSlot::default(),
+            closed: false,
+        };
+        if let Err(err) = self.configure(&socket) {
+            // reset while the channel is still initialized, dropping the socket closes it
+            self.reset_listen_only(Channel::backend(&socket));
+            return Err(err.into());
+        }
+        Ok(socket)
+    }
+
+    fn initialize(&self, backend: &dyn Backend) -> Result<(), CanError> {
+        match self.bitrate.as_ref().ok_or(CanError::IllParamVal)? {
+            Bitrate::Baudrate(baud) => backend.initialize(self.handle, (*baud).into()),
+            Bitrate::Btr0Btr1(btr0btr1) => backend.initialize(self.handle, *btr0btr1),
+            Bitrate::Fd(bitrate) => backend.initialize_fd(self.handle, bitrate),
+        }
+    }
+
+    fn configure<B: Backend>(&self, socket: &CanSocket<B>) -> Result<(), CanError> {
+        let channel = RawChannel(socket.channel(), Channel::backend(socket));
+        if let Some(enable) = self.bus_off_autoreset {
+            socket.set_bus_off_autoreset(enable)?;
+        }
+        if let Some(enable) = self.status_frames {
+            channel.allow_status_frames(enable)?;
+        }
+        if let Some(enable) = self.rtr_frames {
+            channel.allow_rtr_frames(enable)?;
+        }
+        if let Some(enable) = self.error_frames {
+            channel.allow_error_frames(enable)?;
+        }
+        if let Some(enable) = self.echo_frames {
+            channel.allow_echo_frames(enable)?;
+        }
+        for &(from_id, to_id, msg_type) in &self.filters {
+            socket.filter(from_id, to_id, msg_type)?;
+        }
+        for filter in &self.acceptance_filters {
+            socket.set_acceptance_filter(filter)?;
+        }
+        Ok(())
+    }
+
+    /// Leaves the uninitialized channel as it was before a failed open.
+    fn reset_listen_only(&self, backend: &dyn Backend) {
+        if self.listen_only {
+            let _ = RawChannel(self.handle, backend).set_listen_only(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::UsbBus;
+
+    #[test]
+    fn validate_combination() {
+        let builder = CanSocketBuilder::new(UsbBus::USB1).with_listen_only(true);
+        assert!(matches!(
+            builder.validate(),
+            Err(BuilderError::MissingBitrate)
+        ));
+
+        let builder = builder
+            .with_baudrate(Baudrate::Baud500K)
+            .with_error_frames(true)
+            .with_filter(0x100, 0x1FF, MessageType::Standard);
+        assert!(builder.validate().is_ok());
+        assert!(matches!(
+            builder.clone().with_echo_frames(true).validate(),
+            Err(BuilderError::EchoWhileListenOnly)
+        ));
+        assert!(matches!(
+            builder
+                .with_filter(0x700, 0x800, MessageType::Standard)
+                .validate(),
+            Err(BuilderError::InvalidFilterRange { to_id: 0x800, .. })
+        ));
+    }
+}
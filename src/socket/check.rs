@@ -0,0 +1,290 @@
+//! Consistency checks of received frames, to catch driver or binding bugs in the field.
+
+use crate::error::CanError;
+use crate::peak_can;
+use crate::queue::BoundedQueue;
+use crate::socket::{
+    CanFdFrame, CanFrame, EXTENDED_MASK, RecvCan, RecvCanFd, STANDARD_MASK, Timestamp,
+};
+
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Invariant of a received frame that did not hold. `id` is the raw ID as delivered by the
+/// driver.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Violation {
+    /// The length is beyond the maximum of the frame format: 8 for classic frames, DLC 15 for
+    /// CAN FD frames.
+    InvalidDlc { id: u32, dlc: u8 },
+    /// The ID has bits set beyond the 11 or 29 bits of its frame type.
+    IdOutOfRange { id: u32 },
+    /// Contradicting flags, e.g. BRS without FD or a remote CAN FD frame.
+    InvalidFlags { id: u32, msg_type: u8 },
+    /// The device timestamp went backwards, in microseconds.
+    TimestampRegression { previous: u64, timestamp: u64 },
+}
+
+fn check_id(id: u32, msg_type: u8, violations: &mut Vec<Violation>) {
+    let mask = if msg_type & peak_can::PEAK_MESSAGE_EXTENDED as u8 != 0 {
+        EXTENDED_MASK
+    } else {
+        STANDARD_MASK
+    };
+    if id & !mask != 0 {
+        violations.push(Violation::IdOutOfRange { id });
+    }
+}
+
+pub(crate) fn can_violations(frame: &CanFrame) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let raw = &frame.frame;
+    check_id(raw.ID, raw.MSGTYPE, &mut violations);
+
+    if raw.LEN as usize > CanFrame::MAX_DLC {
+        violations.push(Violation::InvalidDlc {
+            id: raw.ID,
+            dlc: raw.LEN,
+        });
+    }
+    let fd_flags =
+        peak_can::PEAK_MESSAGE_FD | peak_can::PEAK_MESSAGE_BRS | peak_can::PEAK_MESSAGE_ESI;
+    if raw.MSGTYPE & fd_flags as u8 != 0 {
+        violations.push(Violation::InvalidFlags {
+            id: raw.ID,
+            msg_type: raw.MSGTYPE,
+        });
+    }
+    violations
+}
+
+pub(crate) fn can_fd_violations(frame: &CanFdFrame) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let raw = &frame.frame;
+    check_id(raw.ID, raw.MSGTYPE, &mut violations);
+
+    let fd = raw.MSGTYPE & peak_can::PEAK_MESSAGE_FD as u8 != 0;
+    let max_dlc = if fd { 15 } else { CanFrame::MAX_DLC as u8 };
+    if raw.DLC > max_dlc {
+        violations.push(Violation::InvalidDlc {
+            id: raw.ID,
+            dlc: raw.DLC,
+        });
+    }
+    let fd_only = (peak_can::PEAK_MESSAGE_BRS | peak_can::PEAK_MESSAGE_ESI) as u8;
+    let remote = raw.MSGTYPE & peak_can::PEAK_MESSAGE_RTR as u8 != 0;
+    if (!fd && raw.MSGTYPE & fd_only != 0) || (fd && remote) {
+        violations.push(Violation::InvalidFlags {
+            id: raw.ID,
+            msg_type: raw.MSGTYPE,
+        });
+    }
+    violations
+}
+
+/// A socket wrapper that validates every received frame and queues violations into a
+/// diagnostics queue. The frames are passed on unchanged.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket, CheckedSocket, RecvCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::queue::{BoundedQueue, OverflowPolicy};
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// let violations = Arc::new(BoundedQueue::new(256, OverflowPolicy::DropOldest));
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let socket = CheckedSocket::new(socket, violations.clone());
+/// std::thread::spawn(move || {
+///     loop {
+///         if let Some(violation) = violations.pop_timeout(Duration::from_secs(1)) {
+///             eprintln!("{violation:?}");
+///         }
+///     }
+/// });
+/// for received in socket.iter() {
+///     let (frame, _) = received?;
+///     println!("{:?}", frame);
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct CheckedSocket<S> {
+    socket: S,
+    diagnostics: Arc<BoundedQueue<Violation>>,
+    last_timestamp: Cell<Option<u64>>,
+}
+
+impl<S> CheckedSocket<S> {
+    pub fn new(socket: S, diagnostics: Arc<BoundedQueue<Violation>>) -> Self {
+        CheckedSocket {
+            socket,
+            diagnostics,
+            last_timestamp: Cell::new(None),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    fn report(&self, violations: Vec<Violation>, timestamp: Option<u64>) {
+        for violation in violations {
+            self.diagnostics.push(violation);
+        }
+        if let Some(timestamp) = timestamp {
+            if let Some(previous) = self.last_timestamp.get()
+                && timestamp < previous
+            {
+                self.diagnostics.push(Violation::TimestampRegression {
+                    previous,
+                    timestamp,
+                });
+            }
+            self.last_timestamp.set(Some(timestamp));
+        }
+    }
+
+    fn check_can<T>(
+        &self,
+        result: Result<T, CanError>,
+        frame: impl Fn(&T) -> (&CanFrame, Option<u64>),
+    ) -> Result<T, CanError> {
+        if let Ok(received) = &result {
+            let (frame, timestamp) = frame(received);
+            self.report(can_violations(frame), timestamp);
+        }
+        result
+    }
+
+    fn check_fd<T>(
+        &self,
+        result: Result<T, CanError>,
+        frame: impl Fn(&T) -> (&CanFdFrame, Option<u64>),
+    ) -> Result<T, CanError> {
+        if let Ok(received) = &result {
+            let (frame, timestamp) = frame(received);
+            self.report(can_fd_violations(frame), timestamp);
+        }
+        result
+    }
+}
+
+impl<S: RecvCan> RecvCan for CheckedSocket<S> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        self.check_can(self.socket.recv(), |(frame, timestamp)| {
+            (frame, Some(timestamp.as_micros()))
+        })
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.check_can(self.socket.recv_frame(), |frame| (frame, None))
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        self.check_can(
+            self.socket.recv_with_host_time(),
+            |(frame, timestamp, _)| (frame, Some(timestamp.as_micros())),
+        )
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        self.check_can(self.socket.recv_timeout(timeout), |(frame, timestamp)| {
+            (frame, Some(timestamp.as_micros()))
+        })
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        self.socket.wait_readable(timeout)
+    }
+}
+
+impl<S: RecvCanFd> RecvCanFd for CheckedSocket<S> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        self.check_fd(self.socket.recv_fd(), |(frame, timestamp)| {
+            (frame, Some(*timestamp))
+        })
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.check_fd(self.socket.recv_fd_frame(), |frame| (frame, None))
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        self.check_fd(
+            self.socket.recv_fd_with_host_time(),
+            |(frame, timestamp, _)| (frame, Some(*timestamp)),
+        )
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        self.check_fd(
+            self.socket.recv_fd_timeout(timeout),
+            |(frame, timestamp)| (frame, Some(*timestamp)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::OverflowPolicy;
+    use crate::socket::MessageType;
+
+    #[test]
+    fn detect_violations() {
+        let mut frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap();
+        assert!(can_violations(&frame).is_empty());
+        frame.frame.ID = 0x923;
+        frame.frame.LEN = 9;
+        frame.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_BRS as u8;
+        assert_eq!(
+            can_violations(&frame),
+            vec![
+                Violation::IdOutOfRange { id: 0x923 },
+                Violation::InvalidDlc { id: 0x923, dlc: 9 },
+                Violation::InvalidFlags {
+                    id: 0x923,
+                    msg_type: peak_can::PEAK_MESSAGE_BRS as u8
+                },
+            ]
+        );
+
+        let mut frame =
+            CanFdFrame::new(0x123, MessageType::Standard, &[0; 12], true, true).unwrap();
+        assert!(can_fd_violations(&frame).is_empty());
+        frame.frame.MSGTYPE &= !(peak_can::PEAK_MESSAGE_FD as u8);
+        assert_eq!(
+            can_fd_violations(&frame),
+            vec![
+                Violation::InvalidDlc { id: 0x123, dlc: 9 },
+                Violation::InvalidFlags {
+                    id: 0x123,
+                    msg_type: peak_can::PEAK_MESSAGE_BRS as u8
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_timestamp_regression() {
+        let violations = Arc::new(BoundedQueue::new(4, OverflowPolicy::DropOldest));
+        let socket = CheckedSocket::new((), violations.clone());
+        socket.report(Vec::new(), Some(200));
+        socket.report(Vec::new(), None);
+        socket.report(Vec::new(), Some(100));
+
+        assert_eq!(
+            std::iter::from_fn(|| violations.try_pop()).collect::<Vec<_>>(),
+            vec![Violation::TimestampRegression {
+                previous: 200,
+                timestamp: 100
+            }]
+        );
+    }
+}
@@ -6,11 +6,11 @@ use crate::bus::DngBus;
 use crate::channel::Channel;
 use crate::df::{
     HasAcceptanceFilter11Bit, HasAcceptanceFilter29Bit, HasAllowErrorFrames, HasAllowRTRFrames,
-    HasAllowStatusFrames, HasMessageFilter, HasReceiveStatus, HasSetAcceptanceFilter11Bit,
-    HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames, HasSetAllowRTRFrames,
-    HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
+    HasAllowStatusFrames, HasFilterMessages, HasMessageFilter, HasReceiveStatus,
+    HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames,
+    HasSetAllowRTRFrames, HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
 };
-use crate::error::{CanError, CanOkError};
+use crate::error::CanError;
 use crate::hw::{
     HasControllerNumber, HasDevicePartNumber, HasHardwareName, HasSetControllerNumber,
 };
@@ -18,8 +18,10 @@ use crate::info::{
     HasBitrateInfo, HasChannelFeatures, HasChannelVersion, HasDataBusSpeed, HasFirmwareVersion,
     HasNominalBusSpeed,
 };
-use crate::peak_lib;
-use crate::socket::{Baudrate, HasRecvCan, HasSendCan, Socket};
+use crate::parameter::HasParameters;
+use crate::socket::event::EventSlot;
+use crate::socket::status::HasBusStatus;
+use crate::socket::{Backend, Baudrate, HasRecvCan, HasSendCan, PcanBackend, Socket};
 use crate::trace::{
     HasSetTraceConfigure, HasSetTraceLocation, HasSetTraceSize, HasSetTraceStatus,
     HasTraceConfigure, HasTraceLocation, HasTraceSize, HasTraceStatus,
@@ -28,18 +30,17 @@ use crate::trace::{
 #[derive(Debug, PartialEq)]
 pub struct DngCanSocket {
     handle: u16,
+    event: EventSlot,
 }
 
 impl DngCanSocket {
     pub fn open(bus: DngBus, baud: Baudrate) -> Result<DngCanSocket, CanError> {
         let handle = bus.into();
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, baud.into(), 0, 0, 0) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(DngCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize(handle, baud.into())?;
+        Ok(DngCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
     }
 }
 
@@ -47,10 +48,8 @@ impl DngCanSocket {
 
 impl Drop for DngCanSocket {
     fn drop(&mut self) {
-        let Ok(peak_lib) = peak_lib() else {
-            return;
-        };
-        unsafe { peak_lib.CAN_Uninitialize(self.handle) };
+        self.event.clear();
+        let _ = PcanBackend.uninitialize(self.handle);
     }
 }
 
@@ -60,6 +59,10 @@ impl Socket for DngCanSocket {
     fn handle(&self) -> u16 {
         self.handle
     }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
 }
 
 /* Channel trait implementation */
@@ -78,6 +81,14 @@ impl HasSendCan for DngCanSocket {}
 // impl HasRecvCanFd for DngCanSocket {}
 // impl HasSendCanFd for DngCanSocket {}
 
+/* TYPED PARAMETERS */
+
+impl HasParameters for DngCanSocket {}
+
+/* BUS STATUS */
+
+impl HasBusStatus for DngCanSocket {}
+
 /* HARDWARE IDENTIFICATION */
 
 impl HasHardwareName for DngCanSocket {}
@@ -108,6 +119,8 @@ impl HasFirmwareVersion for DngCanSocket {}
 impl HasMessageFilter for DngCanSocket {}
 impl HasSetMessageFilter for DngCanSocket {}
 
+impl HasFilterMessages for DngCanSocket {}
+
 impl HasReceiveStatus for DngCanSocket {}
 impl HasSetReceiveStatus for DngCanSocket {}
 
@@ -0,0 +1,205 @@
+//! Transmitting without touching the bus, to verify a new deployment against a live vehicle
+//! before it goes active.
+
+use crate::error::CanError;
+use crate::queue::BoundedQueue;
+use crate::socket::check::{can_fd_violations, can_violations};
+use crate::socket::{
+    AnyFrame, CanFdFrame, CanFrame, RecvCan, RecvCanFd, SendCan, SendCanFd, Timestamp,
+};
+use crate::trace::TraceRecord;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A socket wrapper whose sends are validated but not transmitted until it is activated.
+/// Receiving is passed through, so the application runs against the live bus.
+///
+/// A frame the driver would reject fails with [CanError::IllParamVal] in dry-run mode. Every
+/// frame passed to a send, transmitted or not, is queued into the tap, timestamped relative to
+/// the creation of the socket.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, DryRunSocket, MessageType, SendCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::queue::{BoundedQueue, OverflowPolicy};
+/// # use std::sync::Arc;
+/// let sent = Arc::new(BoundedQueue::new(1024, OverflowPolicy::DropOldest));
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let socket = DryRunSocket::new(socket).with_tap(sent.clone());
+///
+/// socket.send(CanFrame::new(0x7DF, MessageType::Standard, &[0x02, 0x01, 0x0D]).unwrap())?;
+/// println!("would have sent {:?}", sent.try_pop().unwrap().frame);
+///
+/// // verified, go active
+/// socket.set_active(true);
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct DryRunSocket<S> {
+    socket: S,
+    active: AtomicBool,
+    tap: Option<Arc<BoundedQueue<TraceRecord>>>,
+    started: Instant,
+    suppressed: AtomicU64,
+}
+
+impl<S> DryRunSocket<S> {
+    /// Wraps `socket` in dry-run mode.
+    pub fn new(socket: S) -> Self {
+        DryRunSocket {
+            socket,
+            active: AtomicBool::new(false),
+            tap: None,
+            started: Instant::now(),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Queues every frame passed to a send into `tap`. With
+    /// [OverflowPolicy::Block](crate::queue::OverflowPolicy::Block) a send waits until the
+    /// consumer made room.
+    pub fn with_tap(mut self, tap: Arc<BoundedQueue<TraceRecord>>) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    /// Switches between dry-run mode and transmitting, e.g. from another thread.
+    pub fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::Release);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Number of frames accepted in dry-run mode.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    fn tap(&self, frame: AnyFrame) {
+        if let Some(tap) = &self.tap {
+            tap.push(TraceRecord {
+                timestamp: self.started.elapsed(),
+                channel: None,
+                frame,
+            });
+        }
+    }
+
+    /// Whether the frame has to be transmitted, `valid` is only evaluated in dry-run mode.
+    fn transmit(&self, frame: AnyFrame, valid: impl FnOnce() -> bool) -> Result<bool, CanError> {
+        if self.is_active() {
+            self.tap(frame);
+            return Ok(true);
+        }
+        if !valid() {
+            return Err(CanError::IllParamVal);
+        }
+        self.tap(frame);
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        Ok(false)
+    }
+}
+
+impl<S: RecvCan> RecvCan for DryRunSocket<S> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        self.socket.recv()
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.socket.recv_frame()
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        self.socket.recv_with_host_time()
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        self.socket.recv_timeout(timeout)
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        self.socket.wait_readable(timeout)
+    }
+}
+
+impl<S: RecvCanFd> RecvCanFd for DryRunSocket<S> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        self.socket.recv_fd()
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.socket.recv_fd_frame()
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        self.socket.recv_fd_with_host_time()
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        self.socket.recv_fd_timeout(timeout)
+    }
+}
+
+impl<S: SendCan> SendCan for DryRunSocket<S> {
+    fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        if self.transmit(AnyFrame::Can(frame), || can_violations(&frame).is_empty())? {
+            self.socket.send(frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: SendCanFd> SendCanFd for DryRunSocket<S> {
+    fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
+        if self.transmit(AnyFrame::CanFd(frame), || {
+            can_fd_violations(&frame).is_empty()
+        })? {
+            self.socket.send_fd(frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::OverflowPolicy;
+    use crate::socket::{MessageType, MockCanSocket};
+
+    #[test]
+    fn dry_run_until_activated() {
+        let mock = MockCanSocket::new();
+        let tapped = Arc::new(BoundedQueue::new(4, OverflowPolicy::DropOldest));
+        let socket = DryRunSocket::new(mock.clone()).with_tap(tapped.clone());
+
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap();
+        socket.send(frame).unwrap();
+        assert!(mock.backend().sent(mock.channel()).is_empty());
+        assert_eq!(socket.suppressed(), 1);
+        assert_eq!(tapped.try_pop().unwrap().frame, AnyFrame::Can(frame));
+
+        let mut invalid = frame;
+        invalid.frame.LEN = 9;
+        assert!(matches!(socket.send(invalid), Err(CanError::IllParamVal)));
+        assert!(tapped.is_empty());
+
+        socket.set_active(true);
+        socket.send(frame).unwrap();
+        assert_eq!(mock.backend().sent(mock.channel()), [frame]);
+        assert_eq!(socket.suppressed(), 1);
+        assert!(tapped.try_pop().is_some());
+    }
+}
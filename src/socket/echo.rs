@@ -0,0 +1,186 @@
+//! Confirmation that sent frames reached the bus by matching them with their echoes.
+
+use crate::error::CanError;
+use crate::queue::TxFrame;
+use crate::socket::{CanFdFrame, CanFrame, SendCan, SendCanFd};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Sent frames waiting for their echo are dropped beyond this count.
+const MAX_PENDING: usize = 1024;
+
+struct Pending {
+    seq: u64,
+    frame: TxFrame,
+    sent: Instant,
+}
+
+/// Sends frames with an echo request and numbers them, so that received echoes can be traced
+/// back to the send call. Echoes carry no reference to the sent frame, an echo is matched with
+/// the oldest pending frame of the same ID and data.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::df::SetAllowEchoFrames;
+/// # use peak_can::socket::usb::UsbCanSocket;
+/// # use peak_can::socket::{Baudrate, CanFrame, EchoTracker, MessageType, RecvCan};
+/// let socket = UsbCanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// socket.allow_echo_frames(true)?;
+///
+/// let mut tracker = EchoTracker::new();
+/// let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2, 3]).unwrap();
+/// let seq = tracker.send(&socket, frame)?;
+/// while tracker.is_pending(seq) {
+///     let (received, _) = socket.recv()?;
+///     if let Some(confirmed) = tracker.confirm(&received) {
+///         println!("frame {confirmed} was sent");
+///     }
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Default)]
+pub struct EchoTracker {
+    pending: VecDeque<Pending>,
+    next_seq: u64,
+}
+
+impl EchoTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `frame` with an echo request and returns its sequence number.
+    pub fn send<S: SendCan + ?Sized>(
+        &mut self,
+        socket: &S,
+        frame: CanFrame,
+    ) -> Result<u64, CanError> {
+        socket.send_with_echo(frame)?;
+        Ok(self.track(TxFrame::Can(frame)))
+    }
+
+    /// Like [send](EchoTracker::send) for CAN FD frames.
+    pub fn send_fd<S: SendCanFd + ?Sized>(
+        &mut self,
+        socket: &S,
+        frame: CanFdFrame,
+    ) -> Result<u64, CanError> {
+        socket.send_fd_with_echo(frame)?;
+        Ok(self.track(TxFrame::CanFd(frame)))
+    }
+
+    /// The sequence number of the sent frame `frame` is the echo of, `None` for frames which
+    /// are no echo or match no pending frame.
+    pub fn confirm(&mut self, frame: &CanFrame) -> Option<u64> {
+        if !frame.is_echo_frame() {
+            return None;
+        }
+        self.take(|pending| match pending {
+            TxFrame::Can(sent) => same_frame(sent.id(), sent.data(), frame.id(), frame.data()),
+            TxFrame::CanFd(_) => false,
+        })
+    }
+
+    /// Like [confirm](EchoTracker::confirm) for CAN FD frames, which may also echo classic
+    /// frames sent on the same channel.
+    pub fn confirm_fd(&mut self, frame: &CanFdFrame) -> Option<u64> {
+        if !frame.is_echo_frame() {
+            return None;
+        }
+        self.take(|pending| match pending {
+            TxFrame::Can(sent) => same_frame(sent.id(), sent.data(), frame.id(), frame.data()),
+            TxFrame::CanFd(sent) => same_frame(sent.id(), sent.data(), frame.id(), frame.data()),
+        })
+    }
+
+    pub fn is_pending(&self, seq: u64) -> bool {
+        self.pending.iter().any(|pending| pending.seq == seq)
+    }
+
+    /// Number of sent frames without echo.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Forgets the frames sent more than `max_age` ago without echo and returns their sequence
+    /// numbers, e.g. to report frames which never made it onto the bus.
+    pub fn expire(&mut self, max_age: Duration) -> Vec<u64> {
+        let mut expired = Vec::new();
+        while let Some(oldest) = self.pending.front() {
+            if oldest.sent.elapsed() <= max_age {
+                break;
+            }
+            expired.push(oldest.seq);
+            self.pending.pop_front();
+        }
+        expired
+    }
+
+    fn track(&mut self, frame: TxFrame) -> u64 {
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.pending.push_back(Pending {
+            seq,
+            frame,
+            sent: Instant::now(),
+        });
+        seq
+    }
+
+    fn take(&mut self, matches: impl Fn(&TxFrame) -> bool) -> Option<u64> {
+        let position = self
+            .pending
+            .iter()
+            .position(|pending| matches(&pending.frame))?;
+        self.pending.remove(position).map(|pending| pending.seq)
+    }
+}
+
+fn same_frame<I: PartialEq>(a: I, a_data: &[u8], b: I, b_data: &[u8]) -> bool {
+    a == b && a_data == b_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::VirtualPair;
+    use crate::socket::{Baudrate, CanSocket, MessageType, RecvCan};
+
+    #[test]
+    fn echoes_of_sent_frames() {
+        let pair = VirtualPair::new().unwrap();
+        let a = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let b = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+
+        let frame = |data: &[u8]| CanFrame::new(0x123, MessageType::Standard, data).unwrap();
+        let mut tracker = EchoTracker::new();
+        let first = tracker.send(&a, frame(&[1])).unwrap();
+        let second = tracker.send(&a, frame(&[2])).unwrap();
+        a.send(frame(&[1])).unwrap();
+        assert_eq!(tracker.pending(), 2);
+
+        // the peer receives plain frames
+        let (received, _) = b.recv().unwrap();
+        assert!(!received.is_echo_frame());
+        assert_eq!(tracker.confirm(&received), None);
+
+        let (echo, _) = a.recv().unwrap();
+        assert!(echo.is_echo_frame());
+        assert_eq!(echo.data(), [1]);
+        assert_eq!(tracker.confirm(&echo), Some(first));
+        assert_eq!(tracker.confirm(&echo), None);
+        assert!(tracker.is_pending(second));
+        assert_eq!(tracker.confirm(&a.recv().unwrap().0), Some(second));
+        assert!(matches!(a.recv(), Err(CanError::QrcvEmpty)));
+
+        tracker.send(&a, frame(&[3])).unwrap();
+        assert!(tracker.expire(Duration::from_secs(60)).is_empty());
+        assert_eq!(tracker.expire(Duration::ZERO).len(), 1);
+    }
+}
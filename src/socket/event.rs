@@ -0,0 +1,304 @@
+//! Blocking on the driver receive event instead of polling the receive queue.
+//!
+//! On Linux the driver exposes a file descriptor that becomes readable when frames are queued,
+//! on Windows an event object is registered with the channel and signaled by the driver.
+//! Backends without such an event, e.g. [MockBackend](crate::socket::MockBackend), poll.
+
+use crate::error::{CanError, CanOkError};
+use crate::peak_can;
+use crate::peak_lib;
+use crate::socket::vcan;
+
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Interval of a polling [ReceiveEvent], the default [WaitStrategy](crate::socket::WaitStrategy).
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_int, c_short};
+
+    #[cfg(target_os = "linux")]
+    pub type NfdsT = std::ffi::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    pub type NfdsT = std::ffi::c_uint;
+
+    pub const POLLIN: c_short = 0x1;
+
+    #[repr(C)]
+    pub struct PollFd {
+        pub fd: c_int,
+        pub events: c_short,
+        pub revents: c_short,
+    }
+
+    unsafe extern "C" {
+        pub fn poll(fds: *mut PollFd, nfds: NfdsT, timeout: c_int) -> c_int;
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+
+    pub type Handle = *mut c_void;
+
+    pub const WAIT_FAILED: u32 = 0xFF_FF_FF_FF;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        pub fn CreateEventW(
+            attributes: *mut c_void,
+            manual_reset: i32,
+            initial_state: i32,
+            name: *const u16,
+        ) -> Handle;
+        pub fn WaitForSingleObject(handle: Handle, milliseconds: u32) -> u32;
+        pub fn CloseHandle(handle: Handle) -> i32;
+    }
+}
+
+fn check(code: u32) -> Result<(), CanError> {
+    match CanOkError::try_from(code) {
+        Ok(CanOkError::Ok) => Ok(()),
+        Ok(CanOkError::Err(err)) => Err(err),
+        Err(_) => Err(CanError::Unknown),
+    }
+}
+
+/// Milliseconds to wait, rounded up so that a short remaining timeout doesn't turn into a
+/// busy loop.
+fn timeout_millis(timeout: Duration) -> u32 {
+    timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as u32
+}
+
+/// The receive event of a channel, see [Backend::receive_event](crate::socket::Backend). A
+/// driver event is registered for the lifetime of this value.
+///
+/// Register before the first read of the receive queue, otherwise a frame queued between the
+/// read and the registration is only noticed after the timeout.
+pub struct ReceiveEvent {
+    kind: Kind,
+}
+
+enum Kind {
+    #[cfg(unix)]
+    Driver {
+        fd: std::ffi::c_int,
+    },
+    #[cfg(windows)]
+    Driver {
+        handle: u16,
+        event: sys::Handle,
+    },
+    Virtual(u16),
+    Poll,
+}
+
+impl ReceiveEvent {
+    /// An event that never signals, waiting sleeps in short intervals until the timeout.
+    pub fn poll() -> Self {
+        ReceiveEvent { kind: Kind::Poll }
+    }
+
+    pub(crate) fn virtual_channel(handle: u16) -> Self {
+        ReceiveEvent {
+            kind: Kind::Virtual(handle),
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn register(handle: u16) -> Result<Self, CanError> {
+        let mut fd: std::ffi::c_int = -1;
+        let code = unsafe {
+            peak_lib()?.CAN_GetValue(
+                handle,
+                peak_can::PEAK_RECEIVE_EVENT as u8,
+                &mut fd as *mut std::ffi::c_int as *mut c_void,
+                size_of::<std::ffi::c_int>() as u32,
+            )
+        };
+        check(code)?;
+        Ok(ReceiveEvent {
+            kind: Kind::Driver { fd },
+        })
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn register(handle: u16) -> Result<Self, CanError> {
+        // auto-reset event, the driver signals it whenever a frame is queued
+        let event = unsafe { sys::CreateEventW(std::ptr::null_mut(), 0, 0, std::ptr::null()) };
+        if event.is_null() {
+            return Err(CanError::Resource);
+        }
+        let mut event_value = event;
+        let code = unsafe {
+            peak_lib()?.CAN_SetValue(
+                handle,
+                peak_can::PEAK_RECEIVE_EVENT as u8,
+                &mut event_value as *mut sys::Handle as *mut c_void,
+                size_of::<sys::Handle>() as u32,
+            )
+        };
+        if let Err(err) = check(code) {
+            unsafe { sys::CloseHandle(event) };
+            return Err(err);
+        }
+        Ok(ReceiveEvent {
+            kind: Kind::Driver { handle, event },
+        })
+    }
+
+    /// Waits until a frame was queued or `timeout` elapsed. Spurious wake-ups are possible, the
+    /// caller has to read the queue and wait again if it is still empty.
+    pub(crate) fn wait(&self, timeout: Duration) -> Result<(), CanError> {
+        match &self.kind {
+            Kind::Driver { .. } => self.wait_driver(timeout),
+            Kind::Virtual(handle) => vcan::wait(*handle, timeout),
+            Kind::Poll => {
+                sleep(timeout.min(POLL_INTERVAL));
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn wait_driver(&self, timeout: Duration) -> Result<(), CanError> {
+        let Kind::Driver { fd } = self.kind else {
+            return Ok(());
+        };
+        let mut poll_fd = sys::PollFd {
+            fd,
+            events: sys::POLLIN,
+            revents: 0,
+        };
+        let result = unsafe { sys::poll(&mut poll_fd, 1, timeout_millis(timeout) as i32) };
+        if result < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return Err(CanError::Resource);
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn wait_driver(&self, timeout: Duration) -> Result<(), CanError> {
+        let Kind::Driver { event, .. } = self.kind else {
+            return Ok(());
+        };
+        let result = unsafe { sys::WaitForSingleObject(event, timeout_millis(timeout)) };
+        if result == sys::WAIT_FAILED {
+            return Err(CanError::Resource);
+        }
+        Ok(())
+    }
+
+    /// The file descriptor of a driver event, readable while frames are queued.
+    #[cfg(all(feature = "tokio", target_os = "linux"))]
+    pub(crate) fn as_raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        match self.kind {
+            Kind::Driver { fd } => Some(fd),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ReceiveEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            Kind::Driver { .. } => "Driver",
+            Kind::Virtual(_) => "Virtual",
+            Kind::Poll => "Poll",
+        };
+        f.debug_struct("ReceiveEvent").field("kind", &kind).finish()
+    }
+}
+
+// the driver event handle may be waited on and closed from any thread
+#[cfg(windows)]
+unsafe impl Send for ReceiveEvent {}
+#[cfg(windows)]
+unsafe impl Sync for ReceiveEvent {}
+
+#[cfg(windows)]
+impl Drop for ReceiveEvent {
+    fn drop(&mut self) {
+        let Kind::Driver { handle, event } = self.kind else {
+            return;
+        };
+        let mut no_event: sys::Handle = std::ptr::null_mut();
+        if let Ok(lib) = peak_lib() {
+            unsafe {
+                lib.CAN_SetValue(
+                    handle,
+                    peak_can::PEAK_RECEIVE_EVENT as u8,
+                    &mut no_event as *mut sys::Handle as *mut c_void,
+                    size_of::<sys::Handle>() as u32,
+                );
+            }
+        }
+        unsafe { sys::CloseHandle(event) };
+    }
+}
+
+/// The receive event of a socket, registered on the first wait and shared by all threads
+/// waiting on the socket afterwards. A second registration would replace the event of a waiter
+/// that is still blocked on the first one.
+#[derive(Default)]
+pub(crate) struct EventSlot(Mutex<Option<Arc<ReceiveEvent>>>);
+
+impl EventSlot {
+    pub(crate) fn get_or_register(
+        &self,
+        register: impl FnOnce() -> Result<ReceiveEvent, CanError>,
+    ) -> Result<Arc<ReceiveEvent>, CanError> {
+        let mut slot = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(event) = slot.as_ref() {
+            return Ok(Arc::clone(event));
+        }
+        let event = Arc::new(register()?);
+        *slot = Some(Arc::clone(&event));
+        Ok(event)
+    }
+
+    /// Releases the registration, before the channel is uninitialized.
+    pub(crate) fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+    }
+}
+
+impl std::fmt::Debug for EventSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let registered = self.0.lock().map(|slot| slot.is_some()).unwrap_or(false);
+        f.debug_struct("EventSlot")
+            .field("registered", &registered)
+            .finish()
+    }
+}
+
+/// Sockets compare by their channel, whether the event is registered yet doesn't matter.
+impl PartialEq for EventSlot {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_is_rounded_up_to_millis() {
+        assert_eq!(timeout_millis(Duration::ZERO), 0);
+        assert_eq!(timeout_millis(Duration::from_micros(1)), 1);
+        assert_eq!(timeout_millis(Duration::from_micros(1500)), 2);
+        assert_eq!(
+            timeout_millis(Duration::from_secs(u64::MAX)),
+            i32::MAX as u32
+        );
+    }
+}
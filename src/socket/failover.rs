@@ -0,0 +1,411 @@
+//! Redundant channels on the same bus, e.g. two adapters of a logging or gateway appliance.
+//!
+//! A [FailoverSocket] sends and receives on its primary channel until the primary fails, then
+//! continues on the standby channel and reports the switch as a [FailoverEvent]. A channel
+//! fails when its handle is lost, e.g. when the adapter is unplugged, or when it reports
+//! bus-off too often within a time window.
+
+use crate::error::CanError;
+use crate::queue::{BoundedQueue, OverflowPolicy};
+use crate::socket::{
+    BrsOverride, CanFdFrame, CanFrame, RecvCan, RecvCanFd, SendCan, SendCanFd, Timestamp,
+};
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of failover events kept until they are polled.
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Primary,
+    Standby,
+}
+
+#[derive(Debug, Clone)]
+pub enum FailoverReason {
+    /// The handle of the primary channel became invalid with the given error.
+    HandleLost(CanError),
+    /// The primary channel reported bus-off this many times within the configured window.
+    BusOffStorm(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum FailoverEvent {
+    SwitchedToStandby(FailoverReason),
+    /// Switched back by [switch_to_primary](FailoverSocket::switch_to_primary).
+    SwitchedToPrimary,
+}
+
+/// A pair of sockets on the same bus which fails over from the primary to the standby.
+///
+/// Both channels receive the traffic of the bus. Frames the standby received before the switch
+/// were delivered by the primary already and are discarded on the next receive call. The
+/// standby stays active until [switch_to_primary](FailoverSocket::switch_to_primary) is called.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, FailoverSocket, SendCan};
+/// # use std::time::Duration;
+/// let primary = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let standby = CanSocket::open(UsbBus::USB2, Baudrate::Baud500K)?;
+/// let socket =
+///     FailoverSocket::new(primary, standby).with_bus_off_limit(3, Duration::from_secs(10));
+///
+/// socket.send(CanFrame::default())?;
+/// while let Some(event) = socket.poll_event() {
+///     println!("{event:?}");
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct FailoverSocket<P, S> {
+    primary: P,
+    standby: S,
+    active: Cell<Role>,
+    /// Set on a switch to the standby until its receive queue was cleared.
+    stale_standby: Cell<bool>,
+    bus_off_limit: usize,
+    bus_off_window: Duration,
+    bus_offs: RefCell<VecDeque<Instant>>,
+    events: BoundedQueue<FailoverEvent>,
+    standby_brs: BrsOverride,
+}
+
+impl<P, S> FailoverSocket<P, S> {
+    /// Fails over when the primary loses its handle or reports bus-off 3 times within 10 s.
+    pub fn new(primary: P, standby: S) -> Self {
+        FailoverSocket {
+            primary,
+            standby,
+            active: Cell::new(Role::Primary),
+            stale_standby: Cell::new(false),
+            bus_off_limit: 3,
+            bus_off_window: Duration::from_secs(10),
+            bus_offs: RefCell::new(VecDeque::new()),
+            events: BoundedQueue::new(EVENT_CAPACITY, OverflowPolicy::DropOldest),
+            standby_brs: BrsOverride::Inherit,
+        }
+    }
+
+    /// Fails over after `count` bus-off reports of the primary within `window`.
+    pub fn with_bus_off_limit(mut self, count: usize, window: Duration) -> Self {
+        self.bus_off_limit = count.max(1);
+        self.bus_off_window = window;
+        self
+    }
+
+    /// Overrides the bit rate switch of CAN FD frames sent by the standby, e.g. when the
+    /// standby adapter is attached by a stub that doesn't sustain the data bit rate.
+    pub fn with_standby_brs(mut self, brs: BrsOverride) -> Self {
+        self.standby_brs = brs;
+        self
+    }
+
+    pub fn active(&self) -> Role {
+        self.active.get()
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn standby(&self) -> &S {
+        &self.standby
+    }
+
+    /// Returns the oldest failover event that was not polled yet.
+    pub fn poll_event(&self) -> Option<FailoverEvent> {
+        self.events.try_pop()
+    }
+
+    /// Continues on the primary, e.g. after the adapter was replaced or the bus was repaired.
+    pub fn switch_to_primary(&self) {
+        if self.active.replace(Role::Primary) == Role::Standby {
+            self.bus_offs.borrow_mut().clear();
+            self.stale_standby.set(false);
+            self.events.push(FailoverEvent::SwitchedToPrimary);
+        }
+    }
+
+    /// Records the outcome of an operation on the primary and fails over if it failed.
+    fn check_primary<R>(&self, result: &Result<R, CanError>) -> bool {
+        let Err(err) = result else {
+            return false;
+        };
+        let reason = if err.is_handle_lost() {
+            FailoverReason::HandleLost(err.clone())
+        } else if err.contains(&CanError::BusOff) {
+            let now = Instant::now();
+            let mut bus_offs = self.bus_offs.borrow_mut();
+            bus_offs.push_back(now);
+            while bus_offs
+                .front()
+                .is_some_and(|&t| now.duration_since(t) > self.bus_off_window)
+            {
+                bus_offs.pop_front();
+            }
+            if bus_offs.len() < self.bus_off_limit {
+                return false;
+            }
+            FailoverReason::BusOffStorm(bus_offs.len())
+        } else {
+            return false;
+        };
+        self.active.set(Role::Standby);
+        self.stale_standby.set(true);
+        self.events.push(FailoverEvent::SwitchedToStandby(reason));
+        true
+    }
+
+    fn send_with<R>(
+        &self,
+        primary: impl FnOnce(&P) -> Result<R, CanError>,
+        standby: impl FnOnce(&S) -> Result<R, CanError>,
+    ) -> Result<R, CanError> {
+        if self.active() == Role::Primary {
+            let result = primary(&self.primary);
+            if !self.check_primary(&result) {
+                return result;
+            }
+        }
+        standby(&self.standby)
+    }
+
+    /// Like [send_with](FailoverSocket::send_with), `discard` reads one stale frame of the
+    /// standby and returns whether there was one.
+    fn recv_with<R>(
+        &self,
+        primary: impl FnOnce(&P) -> Result<R, CanError>,
+        standby: impl FnOnce(&S) -> Result<R, CanError>,
+        discard: impl Fn(&S) -> bool,
+    ) -> Result<R, CanError> {
+        if self.active() == Role::Primary {
+            let result = primary(&self.primary);
+            if !self.check_primary(&result) {
+                return result;
+            }
+        }
+        if self.stale_standby.replace(false) {
+            // stops at the first error, which is reported by the next read again
+            while discard(&self.standby) {}
+        }
+        standby(&self.standby)
+    }
+}
+
+impl<P: SendCan, S: SendCan> SendCan for FailoverSocket<P, S> {
+    fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        self.send_with(|p| p.send(frame), |s| s.send(frame))
+    }
+}
+
+impl<P: SendCanFd, S: SendCanFd> SendCanFd for FailoverSocket<P, S> {
+    fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
+        self.send_with(
+            |p| p.send_fd(frame),
+            |s| {
+                let mut frame = frame;
+                self.standby_brs.apply(&mut frame);
+                s.send_fd(frame)
+            },
+        )
+    }
+}
+
+impl<P: RecvCan, S: RecvCan> RecvCan for FailoverSocket<P, S> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        self.recv_with(|p| p.recv(), |s| s.recv(), discard)
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.recv_with(|p| p.recv_frame(), |s| s.recv_frame(), discard)
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        self.recv_with(
+            |p| p.recv_with_host_time(),
+            |s| s.recv_with_host_time(),
+            discard,
+        )
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.recv_with(
+            |p| p.recv_timeout(timeout),
+            |s| s.recv_timeout(remaining(deadline, timeout)),
+            discard,
+        )
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.recv_with(
+            |p| p.wait_readable(timeout),
+            |s| s.wait_readable(remaining(deadline, timeout)),
+            discard,
+        )
+    }
+}
+
+impl<P: RecvCanFd, S: RecvCanFd> RecvCanFd for FailoverSocket<P, S> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        self.recv_with(|p| p.recv_fd(), |s| s.recv_fd(), discard_fd)
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.recv_with(|p| p.recv_fd_frame(), |s| s.recv_fd_frame(), discard_fd)
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        self.recv_with(
+            |p| p.recv_fd_with_host_time(),
+            |s| s.recv_fd_with_host_time(),
+            discard_fd,
+        )
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.recv_with(
+            |p| p.recv_fd_timeout(timeout),
+            |s| s.recv_fd_timeout(remaining(deadline, timeout)),
+            discard_fd,
+        )
+    }
+}
+
+/// Time left for the standby after the primary failed within a wait of `timeout`.
+fn remaining(deadline: Option<Instant>, timeout: Duration) -> Duration {
+    deadline.map_or(timeout, |deadline| {
+        deadline.saturating_duration_since(Instant::now())
+    })
+}
+
+fn discard<S: RecvCan>(socket: &S) -> bool {
+    socket.recv_frame().is_ok()
+}
+
+fn discard_fd<S: RecvCanFd>(socket: &S) -> bool {
+    socket.recv_fd_frame().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::VirtualPair;
+    use crate::socket::{Baudrate, CanSocket, MessageType, MockCanSocket};
+
+    #[test]
+    fn fails_over_to_standby() {
+        let pair = VirtualPair::new().unwrap();
+        let standby = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let bus = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+        let frame = CanFrame::new(0x10, MessageType::Standard, &[1]).unwrap();
+
+        let primary = MockCanSocket::new();
+        primary
+            .backend()
+            .fail_next_write(primary.channel(), CanError::BusOff);
+        primary
+            .backend()
+            .fail_next_write(primary.channel(), CanError::BusOff);
+        let socket =
+            FailoverSocket::new(primary, standby).with_bus_off_limit(2, Duration::from_secs(60));
+        assert!(matches!(socket.send(frame), Err(CanError::BusOff)));
+        assert_eq!(socket.active(), Role::Primary);
+        // the second bus-off switches over and the frame is sent by the standby
+        socket.send(frame).unwrap();
+        assert_eq!(socket.active(), Role::Standby);
+        assert_eq!(bus.recv().unwrap().0, frame);
+        assert!(matches!(
+            socket.poll_event(),
+            Some(FailoverEvent::SwitchedToStandby(
+                FailoverReason::BusOffStorm(2)
+            ))
+        ));
+
+        socket.switch_to_primary();
+        assert!(matches!(
+            socket.poll_event(),
+            Some(FailoverEvent::SwitchedToPrimary)
+        ));
+        assert!(socket.poll_event().is_none());
+
+        let primary = MockCanSocket::new();
+        primary
+            .backend()
+            .fail_next_write(primary.channel(), CanError::IllClient);
+        let socket = FailoverSocket::new(primary, socket.standby);
+        socket.send(frame).unwrap();
+        assert!(matches!(
+            socket.poll_event(),
+            Some(FailoverEvent::SwitchedToStandby(
+                FailoverReason::HandleLost(CanError::IllClient)
+            ))
+        ));
+        assert!(bus.recv().is_ok());
+    }
+
+    #[test]
+    fn standby_brs_override() {
+        let standby = MockCanSocket::new();
+        let frame = CanFdFrame::new(0x10, MessageType::Standard, &[0xAA; 16], true, true).unwrap();
+
+        let primary = MockCanSocket::new();
+        primary
+            .backend()
+            .fail_next_write(primary.channel(), CanError::IllClient);
+        let socket = FailoverSocket::new(primary, standby.clone())
+            .with_standby_brs(BrsOverride::Force(false));
+        socket.send_fd(frame).unwrap();
+        assert_eq!(socket.active(), Role::Standby);
+        let sent = standby.backend().sent_fd(standby.channel());
+        assert!(!sent[0].is_brs());
+        assert_eq!(sent[0].data(), frame.data());
+    }
+
+    #[test]
+    fn timeouts_wait_on_the_active_socket() {
+        let primary = MockCanSocket::new();
+        let standby = MockCanSocket::new();
+        let socket = FailoverSocket::new(primary.clone(), standby.clone());
+        let frame = CanFrame::new(0x10, MessageType::Standard, &[1]).unwrap();
+
+        primary
+            .backend()
+            .push_rx(primary.channel(), frame, Timestamp::from_micros(1));
+        assert_eq!(
+            socket.recv_timeout(Duration::from_secs(1)).unwrap().0,
+            frame
+        );
+        assert!(matches!(
+            socket.recv_timeout(Duration::from_millis(1)),
+            Err(CanError::QrcvEmpty)
+        ));
+        assert!(socket.wait_readable(Duration::from_millis(1)).is_ok());
+        assert_eq!(socket.active(), Role::Primary);
+
+        primary
+            .backend()
+            .push_rx_error(primary.channel(), CanError::IllHw);
+        // the stale frame of the standby is discarded
+        standby
+            .backend()
+            .push_rx(standby.channel(), frame, Timestamp::from_micros(1));
+        assert!(matches!(
+            socket.recv_timeout(Duration::from_millis(1)),
+            Err(CanError::QrcvEmpty)
+        ));
+        assert_eq!(socket.active(), Role::Standby);
+        standby
+            .backend()
+            .push_rx(standby.channel(), frame, Timestamp::from_micros(2));
+        let (received, timestamp) = socket.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(received == frame && timestamp.as_micros() == 2);
+    }
+}
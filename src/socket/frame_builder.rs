@@ -0,0 +1,269 @@
+//! Building frames from named settings instead of positional flags.
+
+use crate::socket::{CanFdFrame, CanFrame, DlcPolicy, FrameConstructionError, MessageType};
+
+fn msg_type(extended: bool) -> MessageType {
+    if extended {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    }
+}
+
+/// Builds a [CanFrame], validating the combination in [build](CanFrameBuilder::build).
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::CanFrame;
+/// let frame = CanFrame::builder()
+///     .with_id(0x18DA00F1)
+///     .with_extended(true)
+///     .with_data(&[0x02, 0x10, 0x03])
+///     .build()?;
+/// assert!(frame.is_extended_frame());
+///
+/// let request = CanFrame::builder().with_id(0x7DF).with_rtr(8).build()?;
+/// assert!(request.is_remote_frame());
+/// # Ok::<(), peak_can::socket::FrameConstructionError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CanFrameBuilder {
+    can_id: u32,
+    extended: bool,
+    rtr: Option<u8>,
+    echo: bool,
+    data: Vec<u8>,
+}
+
+impl CanFrameBuilder {
+    /// A standard data frame with ID 0 and no data.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_id(mut self, can_id: u32) -> Self {
+        self.can_id = can_id;
+        self
+    }
+
+    pub fn with_extended(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    /// Makes the frame a remote transmission request for `dlc` bytes.
+    pub fn with_rtr(mut self, dlc: u8) -> Self {
+        self.rtr = Some(dlc);
+        self
+    }
+
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    pub fn with_data(mut self, data: &[u8]) -> Self {
+        self.data = data.to_vec();
+        self
+    }
+
+    pub fn build(&self) -> Result<CanFrame, FrameConstructionError> {
+        let msg_type = msg_type(self.extended);
+        let mut frame = match self.rtr {
+            Some(_) if !self.data.is_empty() => {
+                return Err(FrameConstructionError::RemoteFrameWithData);
+            }
+            Some(dlc) => CanFrame::new_remote(self.can_id, msg_type, dlc)?,
+            None => CanFrame::new(self.can_id, msg_type, &self.data)?,
+        };
+        frame.set_echo(self.echo);
+        Ok(frame)
+    }
+}
+
+/// Builds a [CanFdFrame], validating the combination in [build](CanFdFrameBuilder::build).
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::CanFdFrame;
+/// let frame = CanFdFrame::builder()
+///     .with_id(0x123)
+///     .with_brs(true)
+///     .with_data(&[1; 10])
+///     .with_padding(0xCC)
+///     .build()?;
+/// assert_eq!(frame.data()[10..], [0xCC, 0xCC]);
+///
+/// let classic = CanFdFrame::builder().with_fd(false).with_brs(true).build();
+/// assert!(classic.is_err());
+/// # Ok::<(), peak_can::socket::FrameConstructionError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CanFdFrameBuilder {
+    can_id: u32,
+    extended: bool,
+    fd: bool,
+    brs: bool,
+    esi: bool,
+    echo: bool,
+    data: Vec<u8>,
+    padding: u8,
+    policy: DlcPolicy,
+}
+
+impl Default for CanFdFrameBuilder {
+    fn default() -> Self {
+        CanFdFrameBuilder {
+            can_id: 0,
+            extended: false,
+            fd: true,
+            brs: false,
+            esi: false,
+            echo: false,
+            data: Vec::new(),
+            padding: 0x00,
+            policy: DlcPolicy::RoundUp,
+        }
+    }
+}
+
+impl CanFdFrameBuilder {
+    /// A standard CAN FD frame with ID 0, no data and no bit rate switch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_id(mut self, can_id: u32) -> Self {
+        self.can_id = can_id;
+        self
+    }
+
+    pub fn with_extended(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    /// Without the FD flag the frame is sent as a classic frame of up to 8 bytes.
+    pub fn with_fd(mut self, fd: bool) -> Self {
+        self.fd = fd;
+        self
+    }
+
+    pub fn with_brs(mut self, brs: bool) -> Self {
+        self.brs = brs;
+        self
+    }
+
+    pub fn with_esi(mut self, esi: bool) -> Self {
+        self.esi = esi;
+        self
+    }
+
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    pub fn with_data(mut self, data: &[u8]) -> Self {
+        self.data = data.to_vec();
+        self
+    }
+
+    /// Fills the bytes up to the next DLC length, see [CanFdFrame::with_padding].
+    pub fn with_padding(mut self, padding: u8) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_dlc_policy(mut self, policy: DlcPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn build(&self) -> Result<CanFdFrame, FrameConstructionError> {
+        if !self.fd {
+            if self.brs || self.esi {
+                return Err(FrameConstructionError::FdFlagsWithoutFd);
+            }
+            if self.data.len() > 8 {
+                return Err(FrameConstructionError::TooMuchData);
+            }
+        }
+        let mut frame = CanFdFrame::with_padding(
+            self.can_id,
+            msg_type(self.extended),
+            &self.data,
+            self.fd,
+            self.brs,
+            self.padding,
+            self.policy,
+        )?;
+        frame.set_esi(self.esi);
+        frame.set_echo(self.echo);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_validates() {
+        let frame = CanFrame::builder()
+            .with_id(0x7FF)
+            .with_data(&[1, 2])
+            .with_echo(true)
+            .build()
+            .unwrap();
+        assert_eq!(frame.can_id(), 0x7FF);
+        assert_eq!(frame.data(), [1, 2]);
+        assert!(frame.is_echo_frame() && !frame.is_remote_frame());
+        assert_eq!(
+            CanFrame::builder().with_id(0x800).build(),
+            Err(FrameConstructionError::CanIdMessageTypeMismatch)
+        );
+        assert_eq!(
+            CanFrame::builder().with_data(&[0; 9]).build(),
+            Err(FrameConstructionError::TooMuchData)
+        );
+        assert_eq!(
+            CanFrame::builder().with_rtr(2).with_data(&[1]).build(),
+            Err(FrameConstructionError::RemoteFrameWithData)
+        );
+        assert_eq!(
+            CanFrame::builder().with_rtr(9).build(),
+            Err(FrameConstructionError::TooMuchData)
+        );
+
+        let frame = CanFdFrame::builder()
+            .with_id(0x800)
+            .with_extended(true)
+            .with_esi(true)
+            .with_data(&[0; 22])
+            .build()
+            .unwrap();
+        assert!(frame.is_fd_frame() && frame.is_esi() && !frame.is_brs());
+        assert_eq!(frame.len(), 24);
+        assert_eq!(
+            CanFdFrame::builder()
+                .with_data(&[0; 22])
+                .with_dlc_policy(DlcPolicy::Exact)
+                .build(),
+            Err(FrameConstructionError::InexactDataLength)
+        );
+        assert_eq!(
+            CanFdFrame::builder().with_fd(false).with_esi(true).build(),
+            Err(FrameConstructionError::FdFlagsWithoutFd)
+        );
+        assert_eq!(
+            CanFdFrame::builder()
+                .with_fd(false)
+                .with_data(&[0; 12])
+                .build(),
+            Err(FrameConstructionError::TooMuchData)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! CAN identifiers which are known to fit their frame format.
+
+use crate::socket::{EXTENDED_MASK, MessageType, STANDARD_MASK};
+
+use core::fmt;
+
+/// An 11-bit identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StandardId(u16);
+
+impl StandardId {
+    pub const ZERO: StandardId = StandardId(0);
+    pub const MAX: StandardId = StandardId(STANDARD_MASK as u16);
+
+    /// `None` if `raw` has more than 11 bits.
+    pub const fn new(raw: u16) -> Option<StandardId> {
+        if raw as u32 <= STANDARD_MASK {
+            Some(StandardId(raw))
+        } else {
+            None
+        }
+    }
+
+    pub const fn as_raw(&self) -> u16 {
+        self.0
+    }
+}
+
+/// A 29-bit identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtendedId(u32);
+
+impl ExtendedId {
+    pub const ZERO: ExtendedId = ExtendedId(0);
+    pub const MAX: ExtendedId = ExtendedId(EXTENDED_MASK);
+
+    /// `None` if `raw` has more than 29 bits.
+    pub const fn new(raw: u32) -> Option<ExtendedId> {
+        if raw <= EXTENDED_MASK {
+            Some(ExtendedId(raw))
+        } else {
+            None
+        }
+    }
+
+    pub const fn as_raw(&self) -> u32 {
+        self.0
+    }
+
+    /// The 11 most significant bits, which are sent in the position of a standard identifier.
+    pub const fn standard_id(&self) -> StandardId {
+        StandardId((self.0 >> 18) as u16)
+    }
+}
+
+/// A standard or extended identifier.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::{CanFrame, ExtendedId, Id, StandardId};
+/// let id = StandardId::new(0x123).unwrap();
+/// let frame = CanFrame::from_id(id, &[1, 2, 3])?;
+/// assert_eq!(frame.id(), Id::Standard(id));
+///
+/// assert_eq!(StandardId::new(0x800), None);
+/// assert_eq!(Id::extended(0x1234_5678).map(|id| id.as_raw()), Some(0x1234_5678));
+/// # Ok::<(), peak_can::socket::FrameConstructionError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Id {
+    Standard(StandardId),
+    Extended(ExtendedId),
+}
+
+impl Id {
+    pub const fn standard(raw: u16) -> Option<Id> {
+        match StandardId::new(raw) {
+            Some(id) => Some(Id::Standard(id)),
+            None => None,
+        }
+    }
+
+    pub const fn extended(raw: u32) -> Option<Id> {
+        match ExtendedId::new(raw) {
+            Some(id) => Some(Id::Extended(id)),
+            None => None,
+        }
+    }
+
+    /// An identifier of the format `msg_type`, `None` if `raw` does not fit it.
+    pub fn new(raw: u32, msg_type: MessageType) -> Option<Id> {
+        match msg_type {
+            MessageType::Standard => Id::standard(u16::try_from(raw).ok()?),
+            MessageType::Extended => Id::extended(raw),
+        }
+    }
+
+    pub const fn as_raw(&self) -> u32 {
+        match self {
+            Id::Standard(id) => id.as_raw() as u32,
+            Id::Extended(id) => id.as_raw(),
+        }
+    }
+
+    pub const fn msg_type(&self) -> MessageType {
+        match self {
+            Id::Standard(_) => MessageType::Standard,
+            Id::Extended(_) => MessageType::Extended,
+        }
+    }
+
+    pub const fn is_extended(&self) -> bool {
+        matches!(self, Id::Extended(_))
+    }
+}
+
+impl From<StandardId> for Id {
+    fn from(id: StandardId) -> Self {
+        Id::Standard(id)
+    }
+}
+
+impl From<ExtendedId> for Id {
+    fn from(id: ExtendedId) -> Self {
+        Id::Extended(id)
+    }
+}
+
+impl fmt::Display for StandardId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03X}", self.0)
+    }
+}
+
+impl fmt::Display for ExtendedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08X}", self.0)
+    }
+}
+
+/// Formats the identifier as in candump notation, 3 hex digits for standard and 8 for extended
+/// identifiers.
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Standard(id) => id.fmt(f),
+            Id::Extended(id) => id.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_ranges() {
+        assert_eq!(StandardId::new(0x7FF), Some(StandardId::MAX));
+        assert_eq!(StandardId::new(0x800), None);
+        assert_eq!(ExtendedId::new(0x1FFF_FFFF), Some(ExtendedId::MAX));
+        assert_eq!(ExtendedId::new(0x2000_0000), None);
+
+        assert_eq!(Id::new(0x1_0123, MessageType::Standard), None);
+        let id = Id::new(0x123, MessageType::Extended).unwrap();
+        assert!(id.is_extended());
+        assert_eq!(id.as_raw(), 0x123);
+        assert_eq!(id.to_string(), "00000123");
+        assert_eq!(Id::standard(0x12).unwrap().to_string(), "012");
+        assert_eq!(ExtendedId::MAX.standard_id(), StandardId::MAX);
+    }
+}
@@ -6,11 +6,11 @@ use crate::bus::IsaBus;
 use crate::channel::Channel;
 use crate::df::{
     HasAcceptanceFilter11Bit, HasAcceptanceFilter29Bit, HasAllowErrorFrames, HasAllowRTRFrames,
-    HasAllowStatusFrames, HasMessageFilter, HasReceiveStatus, HasSetAcceptanceFilter11Bit,
-    HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames, HasSetAllowRTRFrames,
-    HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
+    HasAllowStatusFrames, HasFilterMessages, HasMessageFilter, HasReceiveStatus,
+    HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames,
+    HasSetAllowRTRFrames, HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
 };
-use crate::error::{CanError, CanOkError};
+use crate::error::CanError;
 use crate::hw::{
     HasControllerNumber, HasDevicePartNumber, HasHardwareName, HasSetControllerNumber,
 };
@@ -18,8 +18,10 @@ use crate::info::{
     HasBitrateInfo, HasChannelFeatures, HasChannelVersion, HasDataBusSpeed, HasFirmwareVersion,
     HasNominalBusSpeed,
 };
-use crate::peak_lib;
-use crate::socket::{Baudrate, HasRecvCan, HasSendCan, Socket};
+use crate::parameter::HasParameters;
+use crate::socket::event::EventSlot;
+use crate::socket::status::HasBusStatus;
+use crate::socket::{Backend, Baudrate, HasRecvCan, HasSendCan, PcanBackend, Socket};
 use crate::trace::{
     HasSetTraceConfigure, HasSetTraceLocation, HasSetTraceSize, HasSetTraceStatus,
     HasTraceConfigure, HasTraceLocation, HasTraceSize, HasTraceStatus,
@@ -28,17 +30,16 @@ use crate::trace::{
 #[derive(Debug, PartialEq)]
 pub struct IsaCanSocket {
     handle: u16,
+    event: EventSlot,
 }
 
 impl IsaCanSocket {
     pub fn open(bus: IsaBus, baud: Baudrate) -> Result<IsaCanSocket, CanError> {
-        let code = unsafe { peak_lib()?.CAN_Initialize(bus.into(), baud.into(), 0, 0, 0) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(IsaCanSocket { handle: bus.into() }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize(bus.into(), baud.into())?;
+        Ok(IsaCanSocket {
+            handle: bus.into(),
+            event: EventSlot::default(),
+        })
     }
 }
 
@@ -46,10 +47,8 @@ impl IsaCanSocket {
 
 impl Drop for IsaCanSocket {
     fn drop(&mut self) {
-        let Ok(peak_lib) = peak_lib() else {
-            return;
-        };
-        unsafe { peak_lib.CAN_Uninitialize(self.handle) };
+        self.event.clear();
+        let _ = PcanBackend.uninitialize(self.handle);
     }
 }
 
@@ -59,6 +58,10 @@ impl Socket for IsaCanSocket {
     fn handle(&self) -> u16 {
         self.handle
     }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
 }
 
 /* Channel trait implementation */
@@ -77,6 +80,14 @@ impl HasSendCan for IsaCanSocket {}
 // impl HasRecvCanFd for IsaCanSocket {}
 // impl HasSendCanFd for IsaCanSocket {}
 
+/* TYPED PARAMETERS */
+
+impl HasParameters for IsaCanSocket {}
+
+/* BUS STATUS */
+
+impl HasBusStatus for IsaCanSocket {}
+
 /* HARDWARE IDENTIFICATION */
 
 impl HasHardwareName for IsaCanSocket {}
@@ -107,6 +118,8 @@ impl HasFirmwareVersion for IsaCanSocket {}
 impl HasMessageFilter for IsaCanSocket {}
 impl HasSetMessageFilter for IsaCanSocket {}
 
+impl HasFilterMessages for IsaCanSocket {}
+
 impl HasReceiveStatus for IsaCanSocket {}
 impl HasSetReceiveStatus for IsaCanSocket {}
 
@@ -0,0 +1,112 @@
+//! Blocking iterators over received frames.
+
+use crate::error::CanError;
+use crate::shutdown::Shutdown;
+use crate::socket::wait::Waiter;
+use crate::socket::{CanFrame, RecvCan, Timestamp, WaitStrategy};
+
+use std::time::{Duration, Instant};
+
+/// Iterator returned by [RecvCan::iter].
+pub struct Frames<'a, S: RecvCan> {
+    socket: &'a S,
+    waiter: Waiter,
+    shutdown: Option<Shutdown>,
+}
+
+impl<'a, S: RecvCan> Frames<'a, S> {
+    pub(crate) fn new(socket: &'a S) -> Self {
+        Frames {
+            socket,
+            waiter: Waiter::new(WaitStrategy::default()),
+            shutdown: None,
+        }
+    }
+
+    /// Sets how the iterator waits while the receive queue is empty.
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.waiter = Waiter::new(strategy);
+        self
+    }
+
+    /// Ends the iteration once `shutdown` is triggered.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+}
+
+impl<S: RecvCan> Iterator for Frames<'_, S> {
+    type Item = Result<(CanFrame, Timestamp), CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.shutdown.as_ref().is_some_and(Shutdown::is_requested) {
+                return None;
+            }
+            match self.socket.recv() {
+                Err(CanError::QrcvEmpty) => self.waiter.wait(self.socket, None),
+                result => {
+                    self.waiter.reset();
+                    return Some(result);
+                }
+            }
+        }
+    }
+}
+
+/// Iterator returned by [RecvCan::iter_timeout].
+pub struct FramesTimeout<'a, S: RecvCan> {
+    socket: &'a S,
+    timeout: Duration,
+    waiter: Waiter,
+    shutdown: Option<Shutdown>,
+}
+
+impl<'a, S: RecvCan> FramesTimeout<'a, S> {
+    pub(crate) fn new(socket: &'a S, timeout: Duration) -> Self {
+        FramesTimeout {
+            socket,
+            timeout,
+            waiter: Waiter::new(WaitStrategy::default()),
+            shutdown: None,
+        }
+    }
+
+    /// Sets how the iterator waits while the receive queue is empty.
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.waiter = Waiter::new(strategy);
+        self
+    }
+
+    /// Ends the iteration once `shutdown` is triggered.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+}
+
+impl<S: RecvCan> Iterator for FramesTimeout<'_, S> {
+    type Item = Result<(CanFrame, Timestamp), CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if self.shutdown.as_ref().is_some_and(Shutdown::is_requested) {
+                return None;
+            }
+            match self.socket.recv() {
+                Err(CanError::QrcvEmpty) => {
+                    if Instant::now() >= deadline {
+                        return None;
+                    }
+                    self.waiter.wait(self.socket, Some(deadline));
+                }
+                result => {
+                    self.waiter.reset();
+                    return Some(result);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! A scriptable backend to test CAN logic without a PEAK adapter.
+
+use crate::error::CanError;
+use crate::socket::event::EventSlot;
+use crate::socket::{Backend, BusState, CanFdFrame, CanFrame, CanSocket, Timestamp};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+struct MockChannel {
+    rx: VecDeque<Result<(CanFrame, Timestamp), CanError>>,
+    rx_fd: VecDeque<Result<(CanFdFrame, u64), CanError>>,
+    tx: Vec<CanFrame>,
+    tx_fd: Vec<CanFdFrame>,
+    write_errors: VecDeque<CanError>,
+    read_error: Option<CanError>,
+    values: HashMap<u8, Vec<u8>>,
+    filters: Vec<(u32, u32, u8)>,
+    bus_state: BusState,
+}
+
+impl Default for MockChannel {
+    fn default() -> Self {
+        MockChannel {
+            rx: VecDeque::new(),
+            rx_fd: VecDeque::new(),
+            tx: Vec::new(),
+            tx_fd: Vec::new(),
+            write_errors: VecDeque::new(),
+            read_error: None,
+            values: HashMap::new(),
+            filters: Vec::new(),
+            bus_state: BusState::ErrorActive,
+        }
+    }
+}
+
+/// A backend whose channels read scripted frames and capture written frames. Clones share the
+/// same channels, so a test keeps a clone to script and inspect the traffic of the code under
+/// test.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    channels: Arc<Mutex<HashMap<u16, MockChannel>>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn socket(&self, channel: u16) -> MockCanSocket {
+        CanSocket {
+            handle: channel,
+            backend: self.clone(),
+            event: EventSlot::default(),
+            closed: false,
+        }
+    }
+
+    /// Queues a frame to be read from `channel`.
+    pub fn push_rx(&self, channel: u16, frame: CanFrame, timestamp: Timestamp) {
+        self.with_channel(channel, |ch| ch.rx.push_back(Ok((frame, timestamp))));
+    }
+
+    /// Queues an error to be returned by a read instead of a frame.
+    pub fn push_rx_error(&self, channel: u16, err: CanError) {
+        self.with_channel(channel, |ch| ch.rx.push_back(Err(err)));
+    }
+
+    /// Queues a CAN FD frame with a timestamp in microseconds to be read from `channel`.
+    pub fn push_rx_fd(&self, channel: u16, frame: CanFdFrame, timestamp: u64) {
+        self.with_channel(channel, |ch| ch.rx_fd.push_back(Ok((frame, timestamp))));
+    }
+
+    /// Lets the next write to `channel` fail with `err`, the frame is not captured.
+    pub fn fail_next_write(&self, channel: u16, err: CanError) {
+        self.with_channel(channel, |ch| ch.write_errors.push_back(err));
+    }
+
+    /// Lets every read of `channel` fail with `err` until it is cleared with `None`, e.g. to
+    /// script a fault that persists.
+    pub fn set_read_error(&self, channel: u16, err: Option<CanError>) {
+        self.with_channel(channel, |ch| ch.read_error = err);
+    }
+
+    /// The frames written to `channel` so far.
+    pub fn sent(&self, channel: u16) -> Vec<CanFrame> {
+        self.with_channel(channel, |ch| ch.tx.clone())
+    }
+
+    pub fn sent_fd(&self, channel: u16) -> Vec<CanFdFrame> {
+        self.with_channel(channel, |ch| ch.tx_fd.clone())
+    }
+
+    /// Removes and returns the frames written to `channel` so far.
+    pub fn take_sent(&self, channel: u16) -> Vec<CanFrame> {
+        self.with_channel(channel, |ch| std::mem::take(&mut ch.tx))
+    }
+
+    /// The data last set for the `PEAK_*` parameter `parameter` of `channel`.
+    pub fn value(&self, channel: u16, parameter: u8) -> Option<Vec<u8>> {
+        self.with_channel(channel, |ch| ch.values.get(&parameter).cloned())
+    }
+
+    /// The ID ranges passed to the message filter of `channel` so far, with their mode.
+    pub fn filters(&self, channel: u16) -> Vec<(u32, u32, u8)> {
+        self.with_channel(channel, |ch| ch.filters.clone())
+    }
+
+    pub fn set_bus_state(&self, channel: u16, bus_state: BusState) {
+        self.with_channel(channel, |ch| ch.bus_state = bus_state);
+    }
+
+    fn with_channel<R>(&self, channel: u16, f: impl FnOnce(&mut MockChannel) -> R) -> R {
+        f(self.lock().entry(channel).or_default())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u16, MockChannel>> {
+        // the scripted state stays usable even if a test thread panicked
+        self.channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend for MockBackend {
+    fn initialize(&self, _channel: u16, _btr0btr1: u16) -> Result<(), CanError> {
+        Ok(())
+    }
+
+    fn initialize_fd(&self, _channel: u16, _bitrate: &str) -> Result<(), CanError> {
+        Ok(())
+    }
+
+    fn uninitialize(&self, _channel: u16) -> Result<(), CanError> {
+        Ok(())
+    }
+
+    fn read(&self, channel: u16) -> Result<(CanFrame, Timestamp), CanError> {
+        self.with_channel(channel, |ch| match &ch.read_error {
+            Some(err) => Some(Err(err.clone())),
+            None => ch.rx.pop_front(),
+        })
+        .unwrap_or(Err(CanError::QrcvEmpty))
+    }
+
+    fn write(&self, channel: u16, frame: &CanFrame) -> Result<(), CanError> {
+        self.with_channel(channel, |ch| match ch.write_errors.pop_front() {
+            Some(err) => Err(err),
+            None => {
+                ch.tx.push(*frame);
+                Ok(())
+            }
+        })
+    }
+
+    fn read_fd(&self, channel: u16) -> Result<(CanFdFrame, u64), CanError> {
+        self.with_channel(channel, |ch| match &ch.read_error {
+            Some(err) => Some(Err(err.clone())),
+            None => ch.rx_fd.pop_front(),
+        })
+        .unwrap_or(Err(CanError::QrcvEmpty))
+    }
+
+    fn write_fd(&self, channel: u16, frame: &CanFdFrame) -> Result<(), CanError> {
+        self.with_channel(channel, |ch| match ch.write_errors.pop_front() {
+            Some(err) => Err(err),
+            None => {
+                ch.tx_fd.push(*frame);
+                Ok(())
+            }
+        })
+    }
+
+    /// [CanError::IllParamType] for parameters that were never set.
+    fn get_value(&self, channel: u16, parameter: u8, data: &mut [u8]) -> Result<(), CanError> {
+        let value = self
+            .value(channel, parameter)
+            .ok_or(CanError::IllParamType)?;
+        let len = value.len().min(data.len());
+        data[..len].copy_from_slice(&value[..len]);
+        Ok(())
+    }
+
+    fn set_value(&self, channel: u16, parameter: u8, data: &[u8]) -> Result<(), CanError> {
+        self.with_channel(channel, |ch| ch.values.insert(parameter, data.to_vec()));
+        Ok(())
+    }
+
+    fn filter_messages(
+        &self,
+        channel: u16,
+        from_id: u32,
+        to_id: u32,
+        mode: u8,
+    ) -> Result<(), CanError> {
+        self.with_channel(channel, |ch| ch.filters.push((from_id, to_id, mode)));
+        Ok(())
+    }
+
+    fn bus_state(&self, channel: u16) -> Result<BusState, CanError> {
+        Ok(self.with_channel(channel, |ch| ch.bus_state))
+    }
+}
+
+/// A socket on a channel of a [MockBackend]. Waiting receive calls poll the scripted queue.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::{CanFrame, MessageType, MockCanSocket, RecvCan, SendCan, Timestamp};
+/// let socket = MockCanSocket::new();
+/// let request = CanFrame::new(0x7DF, MessageType::Standard, &[0x01, 0x0D]).unwrap();
+/// socket.backend().push_rx(socket.channel(), request, Timestamp::from_micros(100));
+///
+/// let (frame, _) = socket.recv()?;
+/// socket.send(CanFrame::new(0x7E8, MessageType::Standard, &frame.data()[..1]).unwrap())?;
+/// assert_eq!(socket.backend().sent(socket.channel())[0].can_id(), 0x7E8);
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub type MockCanSocket = CanSocket<MockBackend>;
+
+impl CanSocket<MockBackend> {
+    /// A socket on channel 0 of a new backend.
+    pub fn new() -> Self {
+        MockBackend::new().socket(0)
+    }
+
+    pub fn backend(&self) -> &MockBackend {
+        &self.backend
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.handle
+    }
+}
+
+impl Default for CanSocket<MockBackend> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones share the channel, closing a mock channel has no effect.
+impl Clone for CanSocket<MockBackend> {
+    fn clone(&self) -> Self {
+        self.backend.socket(self.handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::df::FilterMessages;
+    use crate::peak_can;
+    use crate::socket::{BusStatus, MessageType, RecvCan, SendCan};
+    use crate::special::{ListenOnly, SetListenOnly};
+    use std::time::Duration;
+
+    #[test]
+    fn scripted_traffic() {
+        let backend = MockBackend::new();
+        let socket = backend.socket(0x51);
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2, 3]).unwrap();
+
+        backend.push_rx(
+            0x51,
+            frame,
+            Timestamp::from_micros(0x1_0000_0000 * 1000 + 1500),
+        );
+        backend.push_rx_error(0x51, CanError::BusOff);
+        let (received, timestamp) = socket.recv().unwrap();
+        assert_eq!(received.data(), &[1, 2, 3]);
+        assert_eq!(timestamp.as_micros(), 0x1_0000_0000 * 1000 + 1500);
+        assert!(matches!(socket.recv(), Err(CanError::BusOff)));
+        assert!(matches!(socket.try_recv(), Ok(None)));
+        backend.push_rx_error(0x51, CanError::BusOff);
+        assert!(matches!(socket.try_recv(), Err(CanError::BusOff)));
+        assert!(matches!(
+            socket.recv_timeout(Duration::from_millis(5)),
+            Err(CanError::QrcvEmpty)
+        ));
+        // other channels are independent
+        assert!(backend.socket(0x52).recv().is_err());
+
+        backend.fail_next_write(0x51, CanError::XmtFull);
+        assert!(socket.send(frame).is_err());
+        socket.send(frame).unwrap();
+        assert_eq!(backend.take_sent(0x51).len(), 1);
+        assert!(backend.sent(0x51).is_empty());
+    }
+
+    #[test]
+    fn parameters_go_through_the_backend() {
+        let socket = MockCanSocket::new();
+        let backend = socket.backend();
+        assert!(matches!(socket.listen_only(), Err(CanError::IllParamType)));
+
+        socket.set_listen_only(true).unwrap();
+        assert!(socket.listen_only().unwrap());
+        assert_eq!(
+            backend.value(0, peak_can::PEAK_LISTEN_ONLY as u8),
+            Some(peak_can::PEAK_PARAMETER_ON.to_le_bytes().to_vec())
+        );
+
+        socket.filter(0x100, 0x1FF, MessageType::Standard).unwrap();
+        assert_eq!(
+            backend.filters(0),
+            vec![(0x100, 0x1FF, peak_can::PEAK_MODE_STANDARD as u8)]
+        );
+
+        backend.set_bus_state(0, BusState::BusOff);
+        assert_eq!(socket.bus_state().unwrap(), BusState::BusOff);
+    }
+}
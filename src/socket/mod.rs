@@ -2,34 +2,140 @@
 //!
 //!
 
+mod any;
+#[cfg(all(feature = "tokio", target_os = "linux"))]
+mod async_socket;
+mod backend;
+mod batch;
+mod builder;
+mod check;
 pub mod dng;
+mod dry_run;
+mod echo;
+mod failover;
+mod frame_builder;
+mod event;
+mod id;
 pub mod isa;
+mod iter;
+mod mock;
+mod received;
+pub(crate) mod wait;
 pub mod lan;
 pub mod pcc;
 pub mod pci;
+mod probe;
+mod reader;
+mod resume;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+mod socketcan_interop;
+mod status;
+mod transform;
 pub mod usb;
+pub(crate) mod vcan;
+
+pub use any::AnyFrame;
+#[cfg(all(feature = "tokio", target_os = "linux"))]
+pub use async_socket::AsyncCanSocket;
+pub use backend::{Backend, PcanBackend};
+pub use batch::{BatchEnd, FrameBatch};
+pub use builder::{BuilderError, CanSocketBuilder};
+pub use check::{CheckedSocket, Violation};
+pub use dry_run::DryRunSocket;
+pub use echo::EchoTracker;
+pub use event::ReceiveEvent;
+pub use failover::{FailoverEvent, FailoverReason, FailoverSocket, Role};
+pub use frame_builder::{CanFdFrameBuilder, CanFrameBuilder};
+pub use id::{ExtendedId, Id, StandardId};
+pub use iter::{Frames, FramesTimeout};
+pub use mock::{MockBackend, MockCanSocket};
+pub use probe::{BitrateProbe, ProbeConfig, ProbeError, detect_bitrate, probe_bitrates};
+pub use reader::{FrameReceiver, ReaderHandle, spawn_channel_reader, spawn_reader};
+pub use received::{Direction, ReceivedFrame};
+pub use resume::{BusOffRecovery, PowerEvent, ResumingSocket};
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+pub use socketcan_interop::SocketCanConversionError;
+pub use status::{BusState, BusStatus, BusStatusFrame, ErrorCounters};
+pub use transform::{FrameTransform, TransformSocket};
+pub use vcan::VirtualBackend;
+pub use wait::WaitStrategy;
 
 use crate::bus::Bus;
-use crate::error::{CanError, CanOkError};
-use crate::peak_lib;
+use crate::channel::Channel;
+use crate::df::{AcceptanceFilter, HasFilterMessages};
+use crate::error::CanError;
+use crate::hw::{
+    ChannelIdentifying, HasChannelIdentifying, HasControllerNumber, HasDeviceId, HasHardwareName,
+    HasSetDeviceId,
+};
+use crate::info::HasFirmwareVersion;
+use crate::parameter::HasParameters;
 use crate::peak_can;
+use crate::queue::OverflowPolicy;
+use crate::special::{
+    HasBusOffAutoreset, HasListenOnly, HasSetBusOffAutoreset, HasSetListenOnly, SetListenOnly,
+};
+use event::EventSlot;
+use probe::RawChannel;
+use status::HasBusStatus;
 
+use core::cmp::Ordering;
 use core::fmt;
-use std::ops::Deref;
+use std::ops::{Deref, Sub};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub const STANDARD_MASK: u32 = 0x07_FF;
 pub const EXTENDED_MASK: u32 = 0x1F_FF_FF_FF;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageType {
     Standard,
     Extended,
 }
 
+/// Bit rate switch setting applied to outgoing frames by higher layers, e.g. to disable BRS for
+/// specific IDs on marginal buses.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BrsOverride {
+    /// Keep the BRS flag of the frame.
+    #[default]
+    Inherit,
+    Force(bool),
+}
+
+impl BrsOverride {
+    pub fn apply(&self, frame: &mut CanFdFrame) {
+        if let BrsOverride::Force(brs) = self {
+            frame.set_brs(*brs);
+        }
+    }
+}
+
+/// How [CanFdFrame::with_padding] treats data lengths that a CAN FD DLC cannot encode
+/// (9-11, 13-15, 17-19, ... bytes).
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DlcPolicy {
+    /// Round up to the next DLC length and fill the remainder with the padding byte.
+    #[default]
+    RoundUp,
+    /// Reject the data with [FrameConstructionError::InexactDataLength].
+    Exact,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum FrameConstructionError {
     TooMuchData,
     CanIdMessageTypeMismatch,
+    /// The data length is not one of the lengths a CAN FD DLC can encode.
+    InexactDataLength,
+    /// A remote frame carries no data, only the requested length.
+    RemoteFrameWithData,
+    /// Bit rate switch or error state indicator set on a frame without the FD flag.
+    FdFlagsWithoutFd,
 }
 impl fmt::Display for FrameConstructionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -38,6 +144,15 @@ impl fmt::Display for FrameConstructionError {
             FrameConstructionError::CanIdMessageTypeMismatch => {
                 write!(f, "CAN ID does not match message type")
             }
+            FrameConstructionError::InexactDataLength => {
+                write!(f, "Data length does not match a CAN FD DLC")
+            }
+            FrameConstructionError::RemoteFrameWithData => {
+                write!(f, "Remote frame must not carry data")
+            }
+            FrameConstructionError::FdFlagsWithoutFd => {
+                write!(f, "BRS and ESI require a CAN FD frame")
+            }
         }
     }
 }
@@ -51,6 +166,8 @@ pub struct CanFrame {
 impl CanFrame {
     const MAX_DLC: usize = 8;
 
+    /// Creates a frame from a raw ID, [FrameConstructionError::CanIdMessageTypeMismatch] if
+    /// `can_id` does not fit `msg_type`.
     pub fn new(
         can_id: u32,
         msg_type: MessageType,
@@ -58,6 +175,8 @@ impl CanFrame {
     ) -> Result<CanFrame, FrameConstructionError> {
         if data.len() > Self::MAX_DLC {
             Err(FrameConstructionError::TooMuchData)
+        } else if Id::new(can_id, msg_type).is_none() {
+            Err(FrameConstructionError::CanIdMessageTypeMismatch)
         } else {
             let mut frame_data: [u8; 8] = [0; 8];
             for (i, v) in data.into_iter().enumerate() {
@@ -67,7 +186,7 @@ impl CanFrame {
             match msg_type {
                 MessageType::Standard => Ok(CanFrame {
                     frame: peak_can::TPEAKMsg {
-                        ID: can_id & STANDARD_MASK,
+                        ID: can_id,
                         MSGTYPE: peak_can::PEAK_MESSAGE_STANDARD as u8,
                         LEN: data.len() as u8,
                         DATA: frame_data,
@@ -75,7 +194,7 @@ impl CanFrame {
                 }),
                 MessageType::Extended => Ok(CanFrame {
                     frame: peak_can::TPEAKMsg {
-                        ID: can_id & EXTENDED_MASK,
+                        ID: can_id,
                         MSGTYPE: peak_can::PEAK_MESSAGE_EXTENDED as u8,
                         LEN: data.len() as u8,
                         DATA: frame_data,
@@ -85,6 +204,31 @@ impl CanFrame {
         }
     }
 
+    pub fn from_id(id: impl Into<Id>, data: &[u8]) -> Result<CanFrame, FrameConstructionError> {
+        let id = id.into();
+        CanFrame::new(id.as_raw(), id.msg_type(), data)
+    }
+
+    pub fn builder() -> CanFrameBuilder {
+        CanFrameBuilder::new()
+    }
+
+    /// Creates a remote transmission request for `dlc` bytes of data. The frame carries no
+    /// data, [data](CanFrame::data) returns `dlc` zero bytes.
+    pub fn new_remote(
+        can_id: u32,
+        msg_type: MessageType,
+        dlc: u8,
+    ) -> Result<CanFrame, FrameConstructionError> {
+        if dlc as usize > Self::MAX_DLC {
+            return Err(FrameConstructionError::TooMuchData);
+        }
+
+        let mut frame = CanFrame::new(can_id, msg_type, &[0; Self::MAX_DLC][..dlc as usize])?;
+        frame.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_RTR as u8;
+        Ok(frame)
+    }
+
     pub fn is_standard_frame(&self) -> bool {
         // PEAK_MESSAGE_STANDARD flag is denoted as 0, so check for extended frame flag instead
         !self.is_extended_frame()
@@ -94,6 +238,10 @@ impl CanFrame {
         self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_EXTENDED as u8 != 0
     }
 
+    pub fn is_remote_frame(&self) -> bool {
+        self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_RTR as u8 != 0
+    }
+
     pub fn is_error_frame(&self) -> bool {
         self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_ERRFRAME as u8 != 0
     }
@@ -102,6 +250,30 @@ impl CanFrame {
         self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_ECHO as u8 != 0
     }
 
+    /// Requests an echo of the frame when it is sent on a channel which
+    /// [allows echo frames](crate::df::SetAllowEchoFrames).
+    pub fn set_echo(&mut self, echo: bool) {
+        if echo {
+            self.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_ECHO as u8;
+        } else {
+            self.frame.MSGTYPE &= !(peak_can::PEAK_MESSAGE_ECHO as u8);
+        }
+    }
+
+    pub fn is_status_frame(&self) -> bool {
+        self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_STATUS as u8 != 0
+    }
+
+    /// Decodes a status frame, returns `None` for all other frames.
+    pub fn as_status(&self) -> Option<BusStatusFrame> {
+        BusStatusFrame::decode(self)
+    }
+
+    /// The error counters reported by an error frame, `None` for all other frames.
+    pub fn error_counters(&self) -> Option<ErrorCounters> {
+        ErrorCounters::decode(self)
+    }
+
     pub fn can_id(&self) -> u32 {
         if self.is_standard_frame() {
             self.frame.ID & STANDARD_MASK
@@ -110,10 +282,33 @@ impl CanFrame {
         }
     }
 
+    pub fn id(&self) -> Id {
+        if self.is_extended_frame() {
+            Id::Extended(ExtendedId::new(self.can_id()).unwrap())
+        } else {
+            Id::Standard(StandardId::new((self.frame.ID & STANDARD_MASK) as u16).unwrap())
+        }
+    }
+
     pub fn dlc(&self) -> u8 {
         self.frame.LEN
     }
 
+    /// Whether the ID matches `id` in all bits set in `mask`.
+    pub fn matches(&self, id: u32, mask: u32) -> bool {
+        (self.can_id() ^ id) & mask == 0
+    }
+
+    /// Whether the frame passes `filter` in the same way as with the hardware acceptance filter.
+    pub fn matches_filter(&self, filter: &AcceptanceFilter) -> bool {
+        let msg_type = if self.is_extended_frame() {
+            MessageType::Extended
+        } else {
+            MessageType::Standard
+        };
+        filter.accepts(msg_type, self.can_id())
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.frame.DATA[0..self.dlc() as usize]
     }
@@ -160,17 +355,51 @@ pub struct CanFdFrame {
 impl CanFdFrame {
     const MAX_DATA_LENGTH: usize = 64;
 
+    /// Creates a frame from a raw ID, [FrameConstructionError::CanIdMessageTypeMismatch] if
+    /// `can_id` does not fit `msg_type`.
     pub fn new(
         can_id: u32,
         msg_type: MessageType,
         data: &[u8],
         fd: bool,
         brs: bool,
+    ) -> Result<CanFdFrame, FrameConstructionError> {
+        Self::with_padding(can_id, msg_type, data, fd, brs, 0x00, DlcPolicy::RoundUp)
+    }
+
+    pub fn from_id(
+        id: impl Into<Id>,
+        data: &[u8],
+        fd: bool,
+        brs: bool,
+    ) -> Result<CanFdFrame, FrameConstructionError> {
+        let id = id.into();
+        CanFdFrame::new(id.as_raw(), id.msg_type(), data, fd, brs)
+    }
+
+    pub fn builder() -> CanFdFrameBuilder {
+        CanFdFrameBuilder::new()
+    }
+
+    /// Creates a frame whose unused bytes up to the next DLC length are filled with `padding`,
+    /// e.g. 0xAA or 0xCC for ECUs that validate the padding content.
+    pub fn with_padding(
+        can_id: u32,
+        msg_type: MessageType,
+        data: &[u8],
+        fd: bool,
+        brs: bool,
+        padding: u8,
+        policy: DlcPolicy,
     ) -> Result<CanFdFrame, FrameConstructionError> {
         if data.len() > Self::MAX_DATA_LENGTH {
             Err(FrameConstructionError::TooMuchData)
+        } else if Id::new(can_id, msg_type).is_none() {
+            Err(FrameConstructionError::CanIdMessageTypeMismatch)
+        } else if policy == DlcPolicy::Exact && Self::dlc_to_len(Self::calc_dlc(data.len())) != data.len() {
+            Err(FrameConstructionError::InexactDataLength)
         } else {
-            let mut frame_data: [u8; Self::MAX_DATA_LENGTH] = [0; Self::MAX_DATA_LENGTH];
+            let mut frame_data: [u8; Self::MAX_DATA_LENGTH] = [padding; Self::MAX_DATA_LENGTH];
             for (i, v) in data.into_iter().enumerate() {
                 frame_data[i] = *v;
             }
@@ -178,7 +407,7 @@ impl CanFdFrame {
             match msg_type {
                 MessageType::Standard => Ok(CanFdFrame {
                     frame: peak_can::TPEAKMsgFD {
-                        ID: can_id & STANDARD_MASK,
+                        ID: can_id,
                         MSGTYPE: peak_can::PEAK_MESSAGE_STANDARD as u8 | 
                             if fd { peak_can::PEAK_MESSAGE_FD as u8 } else { 0 } |
                             if brs { peak_can::PEAK_MESSAGE_BRS as u8 } else { 0 },
@@ -188,7 +417,7 @@ impl CanFdFrame {
                 }),
                 MessageType::Extended => Ok(CanFdFrame {
                     frame: peak_can::TPEAKMsgFD {
-                        ID: can_id & EXTENDED_MASK,
+                        ID: can_id,
                         MSGTYPE: peak_can::PEAK_MESSAGE_EXTENDED as u8 |
                             if fd { peak_can::PEAK_MESSAGE_FD as u8 } else { 0 } |
                             if brs { peak_can::PEAK_MESSAGE_BRS as u8 } else { 0 },
@@ -220,10 +449,56 @@ impl CanFdFrame {
         self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_ECHO as u8 != 0
     }
 
+    /// Requests an echo of the frame when it is sent on a channel which
+    /// [allows echo frames](crate::df::SetAllowEchoFrames).
+    pub fn set_echo(&mut self, echo: bool) {
+        if echo {
+            self.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_ECHO as u8;
+        } else {
+            self.frame.MSGTYPE &= !(peak_can::PEAK_MESSAGE_ECHO as u8);
+        }
+    }
+
+    pub fn is_status_frame(&self) -> bool {
+        self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_STATUS as u8 != 0
+    }
+
     pub fn is_fd_frame(&self) -> bool {
         self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_FD as u8 != 0
     }
 
+    pub fn is_brs(&self) -> bool {
+        self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_BRS as u8 != 0
+    }
+
+    pub fn set_brs(&mut self, brs: bool) {
+        if brs {
+            self.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_BRS as u8;
+        } else {
+            self.frame.MSGTYPE &= !(peak_can::PEAK_MESSAGE_BRS as u8);
+        }
+    }
+
+    /// Whether the error state indicator is set, i.e. the sender was error passive.
+    pub fn is_esi(&self) -> bool {
+        self.frame.MSGTYPE & peak_can::PEAK_MESSAGE_ESI as u8 != 0
+    }
+
+    pub fn set_esi(&mut self, esi: bool) {
+        if esi {
+            self.frame.MSGTYPE |= peak_can::PEAK_MESSAGE_ESI as u8;
+        } else {
+            self.frame.MSGTYPE &= !(peak_can::PEAK_MESSAGE_ESI as u8);
+        }
+    }
+
+    /// The frame with the error state indicator set to `esi`, e.g. to emulate an error passive
+    /// node.
+    pub fn with_esi(mut self, esi: bool) -> Self {
+        self.set_esi(esi);
+        self
+    }
+
     pub fn can_id(&self) -> u32 {
         if self.is_standard_frame() {
             self.frame.ID & STANDARD_MASK
@@ -232,10 +507,33 @@ impl CanFdFrame {
         }
     }
 
+    pub fn id(&self) -> Id {
+        if self.is_extended_frame() {
+            Id::Extended(ExtendedId::new(self.can_id()).unwrap())
+        } else {
+            Id::Standard(StandardId::new((self.frame.ID & STANDARD_MASK) as u16).unwrap())
+        }
+    }
+
     pub fn dlc(&self) -> u8 {
         self.frame.DLC
     }
 
+    /// Whether the ID matches `id` in all bits set in `mask`.
+    pub fn matches(&self, id: u32, mask: u32) -> bool {
+        (self.can_id() ^ id) & mask == 0
+    }
+
+    /// Whether the frame passes `filter` in the same way as with the hardware acceptance filter.
+    pub fn matches_filter(&self, filter: &AcceptanceFilter) -> bool {
+        let msg_type = if self.is_extended_frame() {
+            MessageType::Extended
+        } else {
+            MessageType::Standard
+        };
+        filter.accepts(msg_type, self.can_id())
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.frame.DATA[0..self.len() as usize]
     }
@@ -245,6 +543,26 @@ impl CanFdFrame {
         &mut self.frame.DATA[0..len as usize]
     }
 
+    /// The first 8 bytes of the payload, i.e. the part a classic CAN frame can carry.
+    pub fn classic_data(&self) -> &[u8] {
+        let data = self.data();
+        &data[..data.len().min(CanFrame::MAX_DLC)]
+    }
+
+    /// The payload beyond the first 8 bytes, empty for frames of up to 8 bytes.
+    pub fn extension_data(&self) -> &[u8] {
+        self.data().get(CanFrame::MAX_DLC..).unwrap_or(&[])
+    }
+
+    /// Iterates over the payload in chunks of `size` bytes, the last chunk may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn data_chunks(&self, size: usize) -> std::slice::Chunks<'_, u8> {
+        self.data().chunks(size)
+    }
+
     fn calc_dlc(len: usize) -> u8 {
         match len {
             0..=8 => len as u8,
@@ -260,8 +578,12 @@ impl CanFdFrame {
     }
 
     pub fn len(&self) -> usize {
-        match self.dlc() {
-            0..=8 => self.dlc() as usize,
+        Self::dlc_to_len(self.dlc())
+    }
+
+    fn dlc_to_len(dlc: u8) -> usize {
+        match dlc {
+            0..=8 => dlc as usize,
             9 => 12,
             10 => 16,
             11 => 20,
@@ -311,6 +633,48 @@ pub struct Timestamp {
     timestamp: peak_can::TPEAKTimestamp,
 }
 
+impl Timestamp {
+    /// Device time in microseconds, the same unit as the timestamp of [RecvCanFd::recv_fd].
+    pub fn as_micros(&self) -> u64 {
+        self.timestamp.micros as u64
+            + 1000 * self.timestamp.millis as u64
+            + 0x1_0000_0000 * 1000 * self.timestamp.millis_overflow as u64
+    }
+
+    /// Inverse of [as_micros](Timestamp::as_micros), e.g. for frames of a mock backend.
+    pub fn from_micros(micros: u64) -> Timestamp {
+        let millis = micros / 1000;
+        Timestamp {
+            timestamp: peak_can::TPEAKTimestamp {
+                micros: (micros % 1000) as u16,
+                millis: millis as u32,
+                millis_overflow: (millis >> 32) as u16,
+            },
+        }
+    }
+
+    /// Device time since the device started, see [as_micros](Timestamp::as_micros).
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_micros(self.as_micros())
+    }
+
+    /// The time elapsed from `earlier` to this timestamp, `None` if `earlier` is later.
+    pub fn checked_duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        self.as_micros()
+            .checked_sub(earlier.as_micros())
+            .map(Duration::from_micros)
+    }
+}
+
+/// The time elapsed between two timestamps, zero if the right hand side is later.
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Timestamp) -> Duration {
+        self.checked_duration_since(rhs).unwrap_or_default()
+    }
+}
+
 impl Deref for Timestamp {
     type Target = peak_can::TPEAKTimestamp;
 
@@ -349,29 +713,479 @@ impl PartialEq for Timestamp {
     }
 }
 
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_micros().cmp(&other.as_micros())
+    }
+}
+
+/// CAN FD controller clock frequency in Hz for PEAK USB devices.
+/// 
+/// This represents the base clock frequency (80 MHz) used by the CAN FD controller
+/// on PEAK USB hardware. This value is used when configuring custom bit timing
+/// parameters for CAN FD communication.
+const CANFD_CLOCK_HZ: u32 = 80_000_000;
+
+/// Helper function to build timing string
+pub(crate) fn build_timing_string(timing: &CanFdBitTiming) -> String {
+    format!(
+        "f_clock={},nom_brp={},nom_tseg1={},nom_tseg2={},nom_sjw={},data_brp={},data_tseg1={},data_tseg2={},data_sjw={}",
+        CANFD_CLOCK_HZ,
+        timing.nom_prescaler,
+        timing.nom_tseg1,
+        timing.nom_tseg2,
+        timing.nom_sjw,
+        timing.data_prescaler,
+        timing.data_tseg1,
+        timing.data_tseg2,
+        timing.data_sjw,
+    )
+}
+
+/// A channel of any PCAN-Basic device, opened through a [Backend].
+///
+/// The constructors without a backend argument use the PCAN-Basic library, [open_with_backend]
+/// runs the same socket on e.g. a [VirtualBackend] or [MockBackend].
+///
+/// [open_with_backend]: CanSocket::open_with_backend
 #[derive(Debug, PartialEq)]
-pub struct CanSocket {
+pub struct CanSocket<B: Backend = PcanBackend> {
     handle: u16,
+    backend: B,
+    event: EventSlot,
+    /// Set once the channel was uninitialized, so that it is uninitialized only once.
+    closed: bool,
 }
 
 impl CanSocket {
     pub fn open<T: Bus>(bus: T, baud: Baudrate) -> Result<CanSocket, CanError> {
+        Self::open_with_backend(bus, baud, PcanBackend)
+    }
+
+    /// Configures the socket with a [CanSocketBuilder] before opening it.
+    pub fn builder<T: Bus>(bus: T) -> CanSocketBuilder {
+        CanSocketBuilder::new(bus)
+    }
+
+    /// Opens a socket in listen-only mode, which neither acknowledges frames nor sends error
+    /// frames, so a monitoring tool can attach to a live bus without affecting it. The mode is
+    /// set before the channel is initialized and can be changed later with
+    /// [set_listen_only](crate::special::SetListenOnly::set_listen_only).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+    /// # use peak_can::bus::UsbBus;
+    /// let socket = CanSocket::open_listen_only(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// for received in socket.iter() {
+    ///     let (frame, timestamp) = received?;
+    ///     println!("{frame:?} {timestamp:?}");
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    pub fn open_listen_only<T: Bus>(bus: T, baud: Baudrate) -> Result<CanSocket, CanError> {
+        let handle = bus.channel();
+        let channel = RawChannel(handle, &PcanBackend);
+        channel.set_listen_only(true)?;
+        if let Err(err) = PcanBackend.initialize(handle, baud.into()) {
+            // leave the uninitialized channel as it was
+            let _ = channel.set_listen_only(false);
+            return Err(err);
+        }
+        Ok(CanSocket {
+            handle,
+            backend: PcanBackend,
+            event: EventSlot::default(),
+            closed: false,
+        })
+    }
+
+    /// Probes the `candidates` in order listen-only and opens the socket actively with the
+    /// first plausible baudrate, so that a wrong baudrate never disturbs the bus.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, ProbeConfig};
+    /// # use peak_can::bus::UsbBus;
+    /// let (socket, baudrate) = CanSocket::open_probe_first(
+    ///     UsbBus::USB1,
+    ///     &[Baudrate::Baud500K, Baudrate::Baud250K],
+    ///     &ProbeConfig::default(),
+    /// )?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn open_probe_first<T: Bus>(
+        bus: T,
+        candidates: &[Baudrate],
+        config: &ProbeConfig,
+    ) -> Result<(CanSocket, Baudrate), ProbeError> {
+        let handle = bus.channel();
+        let mut probes = Vec::with_capacity(candidates.len());
+        for baudrate in candidates {
+            let result = probe::probe(handle, *baudrate, config)?;
+            if result.is_plausible(config) {
+                PcanBackend.initialize(handle, (*baudrate).into())?;
+                let socket = CanSocket {
+                    handle,
+                    backend: PcanBackend,
+                    event: EventSlot::default(),
+                    closed: false,
+                };
+                return Ok((socket, *baudrate));
+            }
+            probes.push(result);
+        }
+        Err(ProbeError::NoPlausibleBitrate(probes))
+    }
+
+    /// Opens a CAN FD socket with custom timing for nominal and data phases.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{CanFdBitTiming, CanSocket};
+    /// # use peak_can::bus::UsbBus;
+    /// let timing = CanFdBitTiming::new(
+    ///     10, 4, 13, 2,  // Nominal phase
+    ///     5, 2, 6, 1     // Data phase
+    /// )?;
+    /// let socket = CanSocket::open_fd(UsbBus::USB1, &timing)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn open_fd<T: Bus>(bus: T, timing: &CanFdBitTiming) -> Result<CanSocket, CanError> {
+        Self::open_fd_with_bitrate(bus, &build_timing_string(timing))
+    }
+
+    /// Opens a CAN FD socket with a PCAN-Basic bitrate string, e.g.
+    /// `"f_clock_mhz=80,nom_brp=10,nom_tseg1=12,nom_tseg2=3,nom_sjw=1,data_brp=4,data_tseg1=7,data_tseg2=2,data_sjw=1"`.
+    pub fn open_fd_with_bitrate<T: Bus>(bus: T, bitrate: &str) -> Result<CanSocket, CanError> {
+        Self::open_fd_with_backend(bus, bitrate, PcanBackend)
+    }
+}
+
+impl<B: Backend> CanSocket<B> {
+    /// Opens the channel of `bus` on `backend`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use peak_can::bus::VirtualPair;
+    /// # use peak_can::socket::{Baudrate, CanSocket, VirtualBackend};
+    /// let pair = VirtualPair::new()?;
+    /// let socket = CanSocket::open_with_backend(pair.a(), Baudrate::Baud500K, VirtualBackend)?;
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    pub fn open_with_backend<T: Bus>(bus: T, baud: Baudrate, backend: B) -> Result<Self, CanError> {
         let handle = bus.channel();
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, baud.into(), 0, 0, 0) };
+        backend.initialize(handle, baud.into())?;
+        Ok(CanSocket {
+            handle,
+            backend,
+            event: EventSlot::default(),
+            closed: false,
+        })
+    }
+
+    /// Opens the channel of `bus` on `backend` in CAN FD mode with a PCAN-Basic bitrate string.
+    pub fn open_fd_with_backend<T: Bus>(
+        bus: T,
+        bitrate: &str,
+        backend: B,
+    ) -> Result<Self, CanError> {
+        let handle = bus.channel();
+        backend.initialize_fd(handle, bitrate)?;
+        Ok(CanSocket {
+            handle,
+            backend,
+            event: EventSlot::default(),
+            closed: false,
+        })
+    }
 
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(CanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+    /// Uninitializes the channel, reporting errors that dropping the socket would ignore.
+    pub fn close(mut self) -> Result<(), CanError> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), CanError> {
+        if self.closed {
+            return Ok(());
         }
+        self.closed = true;
+        self.event.clear();
+        self.backend.uninitialize(self.handle)
+    }
+
+    /// Blinks the LED of the channel while `on`, e.g. to find one of several identical
+    /// adapters. Only PCAN-USB devices have the LED, others return an error.
+    pub fn identify(&self, on: bool) -> Result<(), CanError> {
+        self.set_channel_identifying(on)
+    }
+}
+
+/* Drop trait implementation */
+
+impl<B: Backend> Drop for CanSocket<B> {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+/* Socket trait implementation */
+
+impl<B: Backend> Socket for CanSocket<B> {
+    fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
+}
+
+/* Channel trait implementation */
+
+impl<B: Backend> Channel for CanSocket<B> {
+    fn channel(&self) -> u16 {
+        self.handle
+    }
+
+    fn backend(&self) -> &dyn Backend {
+        &self.backend
     }
 }
 
+/* CAN trait implementations */
+
+impl<B: Backend> HasRecvCan for CanSocket<B> {}
+impl<B: Backend> HasSendCan for CanSocket<B> {}
+
+impl<B: Backend> HasRecvCanFd for CanSocket<B> {}
+impl<B: Backend> HasSendCanFd for CanSocket<B> {}
+
+/* TYPED PARAMETERS */
+
+impl<B: Backend> HasParameters for CanSocket<B> {}
+
+/* BUS STATUS */
+
+impl<B: Backend> HasBusStatus for CanSocket<B> {}
+
+/* HARDWARE IDENTIFICATION */
+
+impl<B: Backend> HasChannelIdentifying for CanSocket<B> {}
+
+impl<B: Backend> HasDeviceId for CanSocket<B> {}
+impl<B: Backend> HasSetDeviceId for CanSocket<B> {}
+
+impl<B: Backend> HasHardwareName for CanSocket<B> {}
+
+impl<B: Backend> HasControllerNumber for CanSocket<B> {}
+
+/* INFORMATIONAL PARAMETER */
+
+impl<B: Backend> HasFirmwareVersion for CanSocket<B> {}
+
+/* CONTROLLING DATA FLOW */
+
+impl<B: Backend> HasFilterMessages for CanSocket<B> {}
+
+/* SPECIAL BEHAVIOR */
+
+impl<B: Backend> HasBusOffAutoreset for CanSocket<B> {}
+impl<B: Backend> HasSetBusOffAutoreset for CanSocket<B> {}
+
+impl<B: Backend> HasListenOnly for CanSocket<B> {}
+impl<B: Backend> HasSetListenOnly for CanSocket<B> {}
+
 trait HasRecvCan {}
 
 pub trait RecvCan {
     fn recv(&self) -> Result<(CanFrame, Timestamp), CanError>;
     fn recv_frame(&self) -> Result<CanFrame, CanError>;
+    /// Like [recv](RecvCan::recv), additionally returning the host monotonic time captured
+    /// right after the frame was read from the driver.
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        let (frame, timestamp) = self.recv()?;
+        Ok((frame, timestamp, Instant::now()))
+    }
+
+    /// Like [recv](RecvCan::recv), returning `Ok(None)` instead of [CanError::QrcvEmpty]
+    /// while the receive queue is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+    /// # use peak_can::bus::UsbBus;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// while let Some((frame, timestamp)) = socket.try_recv()? {
+    ///     println!("{frame:?} {timestamp:?}");
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    fn try_recv(&self) -> Result<Option<(CanFrame, Timestamp)>, CanError> {
+        match self.recv() {
+            Ok(received) => Ok(Some(received)),
+            Err(CanError::QrcvEmpty) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns a blocking iterator over received frames.
+    ///
+    /// The iterator waits while the receive queue is empty and never ends on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+    /// # use peak_can::bus::UsbBus;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+    /// for received in socket.iter() {
+    ///     let (frame, timestamp) = received?;
+    ///     println!("{:?} {:?}", frame, timestamp);
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    fn iter(&self) -> Frames<'_, Self>
+    where
+        Self: Sized,
+    {
+        Frames::new(self)
+    }
+
+    /// Returns a blocking iterator over received frames that ends once no frame was received
+    /// for `timeout`.
+    fn iter_timeout(&self, timeout: Duration) -> FramesTimeout<'_, Self>
+    where
+        Self: Sized,
+    {
+        FramesTimeout::new(self, timeout)
+    }
+
+    /// Moves the socket to a background thread delivering received frames through a bounded queue,
+    /// see [spawn_reader].
+    fn spawn_reader(
+        self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> (FrameReceiver, ReaderHandle<Self>)
+    where
+        Self: Sized + Send + 'static,
+    {
+        spawn_reader(self, capacity, policy)
+    }
+
+    /// Blocks until a frame is received or `timeout` elapsed, returning
+    /// [CanError::QrcvEmpty] on timeout. Sockets wait on the driver receive event, other
+    /// implementations poll [recv](RecvCan::recv) with the default [WaitStrategy].
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        let mut waiter = wait::Waiter::new(WaitStrategy::default());
+        wait::read_timeout(timeout, || self.recv(), |remaining| {
+            waiter.wait(self, Instant::now().checked_add(remaining));
+            Ok(())
+        })
+    }
+
+    /// Blocks until a frame may have been queued or `timeout` elapsed, used by
+    /// [WaitStrategy::Event]. Wake-ups without a frame are possible. Sockets wait on the driver
+    /// receive event, other implementations sleep a millisecond.
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        std::thread::sleep(timeout.min(Duration::from_millis(1)));
+        Ok(())
+    }
+
+    /// Reads the queued frames without waiting, stopping once the queue is empty or
+    /// `deadline` passed, so a control loop never overruns its slot. [FrameBatch::end] tells
+    /// whether frames were left queued.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+    /// # use peak_can::bus::UsbBus;
+    /// # use std::time::{Duration, Instant};
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud1M)?;
+    /// let period = Duration::from_millis(1);
+    /// let mut slot = Instant::now();
+    /// loop {
+    ///     // leave half of the slot for the control law
+    ///     let batch = socket.recv_until(slot + period / 2);
+    ///     if batch.deadline_expired() {
+    ///         eprintln!("overrun, frames left queued");
+    ///     }
+    ///     for (frame, _) in &batch.frames {
+    ///         println!("{frame:?}");
+    ///     }
+    ///     slot += period;
+    ///     std::thread::sleep(slot.saturating_duration_since(Instant::now()));
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    fn recv_until(&self, deadline: Instant) -> FrameBatch {
+        let mut frames = Vec::new();
+        let end = batch::recv_until(self, deadline, &mut frames);
+        FrameBatch { frames, end }
+    }
+
+    /// Appends up to `max` queued frames to `frames` without waiting and returns their number,
+    /// reusing the storage of the caller. An error other than an empty receive queue ends the
+    /// drain, the frames read before it stay in `frames`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+    /// # use peak_can::bus::UsbBus;
+    /// # use std::time::Duration;
+    /// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud1M)?;
+    /// let mut frames = Vec::with_capacity(1024);
+    /// loop {
+    ///     frames.clear();
+    ///     if socket.drain(&mut frames, 1024)? == 0 {
+    ///         std::thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///     for (frame, timestamp) in &frames {
+    ///         println!("{frame:?} {timestamp:?}");
+    ///     }
+    /// }
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    fn drain(
+        &self,
+        frames: &mut Vec<(CanFrame, Timestamp)>,
+        max: usize,
+    ) -> Result<usize, CanError> {
+        for count in 0..max {
+            match self.recv() {
+                Ok(received) => frames.push(received),
+                Err(CanError::QrcvEmpty) => return Ok(count),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(max)
+    }
+
+    /// Like [recv_until](RecvCan::recv_until), appending the frames to `frames` so that the
+    /// buffer can be reused from slot to slot.
+    fn recv_until_into(
+        &self,
+        deadline: Instant,
+        frames: &mut Vec<(CanFrame, Timestamp)>,
+    ) -> BatchEnd {
+        batch::recv_until(self, deadline, frames)
+    }
 }
 
 trait HasRecvCanFd {}
@@ -379,27 +1193,97 @@ trait HasRecvCanFd {}
 pub trait RecvCanFd {
     fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError>;
     fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError>;
+    /// Like [recv_fd](RecvCanFd::recv_fd), additionally returning the host monotonic time
+    /// captured right after the frame was read from the driver.
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        let (frame, timestamp) = self.recv_fd()?;
+        Ok((frame, timestamp, Instant::now()))
+    }
+
+    /// Like [recv_fd](RecvCanFd::recv_fd), returning `Ok(None)` instead of
+    /// [CanError::QrcvEmpty] while the receive queue is empty.
+    fn try_recv_fd(&self) -> Result<Option<(CanFdFrame, u64)>, CanError> {
+        match self.recv_fd() {
+            Ok(received) => Ok(Some(received)),
+            Err(CanError::QrcvEmpty) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Like [recv_fd](RecvCanFd::recv_fd), returning frames without the FD flag as
+    /// [AnyFrame::Can].
+    fn recv_any(&self) -> Result<(AnyFrame, u64), CanError> {
+        let (frame, micros) = self.recv_fd()?;
+        Ok((AnyFrame::from_received(frame), micros))
+    }
+
+    /// Like [drain](RecvCan::drain), appending up to `max` queued FD frames to `frames`.
+    fn drain_fd(
+        &self,
+        frames: &mut Vec<(CanFdFrame, u64)>,
+        max: usize,
+    ) -> Result<usize, CanError> {
+        for count in 0..max {
+            match self.recv_fd() {
+                Ok(received) => frames.push(received),
+                Err(CanError::QrcvEmpty) => return Ok(count),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(max)
+    }
+
+    /// Blocks until a frame is received or `timeout` elapsed, returning
+    /// [CanError::QrcvEmpty] on timeout. Sockets wait on the driver receive event, other
+    /// implementations poll [recv_fd](RecvCanFd::recv_fd) every millisecond.
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        wait::read_timeout(timeout, || self.recv_fd(), |remaining| {
+            std::thread::sleep(remaining.min(Duration::from_millis(1)));
+            Ok(())
+        })
+    }
 }
 
 trait HasSendCan {}
 
 pub trait SendCan {
     fn send(&self, frame: CanFrame) -> Result<(), CanError>;
+
+    /// Sends `frame` with an echo request, the echo is received once the frame was sent on the
+    /// bus. See [EchoTracker] to match echoes with sent frames.
+    fn send_with_echo(&self, mut frame: CanFrame) -> Result<(), CanError> {
+        frame.set_echo(true);
+        self.send(frame)
+    }
 }
 
 trait HasSendCanFd {}
 
 pub trait SendCanFd {
     fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError>;
+
+    /// Like [SendCan::send_with_echo] for CAN FD frames.
+    fn send_fd_with_echo(&self, mut frame: CanFdFrame) -> Result<(), CanError> {
+        frame.set_echo(true);
+        self.send_fd(frame)
+    }
 }
 
-trait Socket {
+trait Socket: Channel {
     fn handle(&self) -> u16;
+    fn event_slot(&self) -> &EventSlot;
+
+    /// The receive event of the channel, registered once and shared by all waiting threads.
+    fn receive_event(&self) -> Result<Arc<ReceiveEvent>, CanError> {
+        self.event_slot()
+            .get_or_register(|| self.backend().receive_event(self.handle()))
+    }
 }
 
 /* Baudrate */
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Baudrate {
     Baud1M,
     Baud800K,
@@ -439,6 +1323,28 @@ impl From<Baudrate> for u16 {
     }
 }
 
+impl Baudrate {
+    /// Nominal bit rate in bits per second.
+    pub fn bits_per_second(&self) -> u32 {
+        match self {
+            Baudrate::Baud1M => 1_000_000,
+            Baudrate::Baud800K => 800_000,
+            Baudrate::Baud500K => 500_000,
+            Baudrate::Baud250K => 250_000,
+            Baudrate::Baud125K => 125_000,
+            Baudrate::Baud100K => 100_000,
+            Baudrate::Baud95K => 95_238,
+            Baudrate::Baud83K => 83_333,
+            Baudrate::Baud50K => 50_000,
+            Baudrate::Baud47K => 47_619,
+            Baudrate::Baud33K => 33_333,
+            Baudrate::Baud20K => 20_000,
+            Baudrate::Baud10K => 10_000,
+            Baudrate::Baud5K => 5_000,
+        }
+    }
+}
+
 /// Hardware-specific timing parameter boundaries for classical CAN 2.0 bit timing.
 ///
 /// These boundaries define the valid ranges for CAN bit timing parameters and are
@@ -722,40 +1628,53 @@ impl CanFdBitTiming {
 
 impl<T: HasRecvCan + Socket> RecvCan for T {
     fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
-        let mut frame = CanFrame::default();
-        let mut timestamp = Timestamp::default();
-
-        let error_code = unsafe {
-            peak_lib()?.CAN_Read(
-                self.handle(),
-                &mut frame.frame as *mut peak_can::TPEAKMsg,
-                &mut timestamp.timestamp as *mut peak_can::TPEAKTimestamp,
-            )
-        };
+        self.backend().read(self.handle())
+    }
 
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok((frame, timestamp)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.recv().map(|(frame, _)| frame)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        let event = self.receive_event()?;
+        wait::read_timeout(timeout, || self.recv(), |remaining| event.wait(remaining))
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        self.receive_event()?.wait(timeout)
+    }
+}
+
+/// A socket shared between threads, e.g. moved to [spawn_reader] while other threads keep
+/// sending through it. Outside of this crate the impl isn't possible, as neither [Arc] nor the
+/// trait would be local.
+impl<S: RecvCan + ?Sized> RecvCan for Arc<S> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        (**self).recv()
     }
 
     fn recv_frame(&self) -> Result<CanFrame, CanError> {
-        let mut frame = CanFrame::default();
-
-        let error_code = unsafe {
-            peak_lib()?.CAN_Read(
-                self.handle(),
-                &mut frame.frame as *mut peak_can::TPEAKMsg,
-                0 as *mut peak_can::TPEAKTimestamp,
-            )
-        };
+        (**self).recv_frame()
+    }
 
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok(frame),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        (**self).recv_with_host_time()
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        (**self).recv_timeout(timeout)
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        (**self).wait_readable(timeout)
+    }
+}
+
+/// A socket shared between threads, e.g. sending the responses of a
+/// [ResponseScheduler](crate::message::ResponseScheduler) while a dispatcher reads it.
+impl<S: SendCan + ?Sized> SendCan for Arc<S> {
+    fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        (**self).send(frame)
     }
 }
 
@@ -763,40 +1682,16 @@ impl<T: HasRecvCan + Socket> RecvCan for T {
 
 impl<T: HasRecvCanFd + Socket> RecvCanFd for T {
     fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
-        let mut frame = CanFdFrame::default();
-        let mut timestamp = 0u64;
-
-        let error_code = unsafe {
-            peak_lib()?.CAN_ReadFD(
-                self.handle(),
-                &mut frame.frame as *mut peak_can::TPEAKMsgFD,
-                &mut timestamp as *mut u64,
-            )
-        };
-
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok((frame, timestamp)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().read_fd(self.handle())
     }
 
     fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
-        let mut frame = CanFdFrame::default();
-
-        let error_code = unsafe {
-            peak_lib()?.CAN_ReadFD(
-                self.handle(),
-                &mut frame.frame as *mut peak_can::TPEAKMsgFD,
-                0 as *mut u64,
-            )
-        };
+        self.recv_fd().map(|(frame, _)| frame)
+    }
 
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok(frame),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        let event = self.receive_event()?;
+        wait::read_timeout(timeout, || self.recv_fd(), |remaining| event.wait(remaining))
     }
 }
 
@@ -804,16 +1699,7 @@ impl<T: HasRecvCanFd + Socket> RecvCanFd for T {
 
 impl<T: HasSendCan + Socket> SendCan for T {
     fn send(&self, frame: CanFrame) -> Result<(), CanError> {
-        let mut frame = frame;
-        let error_code = unsafe {
-            peak_lib()?.CAN_Write(self.handle(), &mut frame.frame as *mut peak_can::TPEAKMsg)
-        };
-
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().write(self.handle(), &frame)
     }
 }
 
@@ -821,16 +1707,13 @@ impl<T: HasSendCan + Socket> SendCan for T {
 
 impl<T: HasSendCanFd + Socket> SendCanFd for T {
     fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
-        let mut frame = frame;
-        let error_code = unsafe {
-            peak_lib()?.CAN_WriteFD(self.handle(), &mut frame.frame as *mut peak_can::TPEAKMsgFD)
-        };
+        self.backend().write_fd(self.handle(), &frame)
+    }
+}
 
-        match CanOkError::try_from(error_code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+impl<S: SendCanFd + ?Sized> SendCanFd for Arc<S> {
+    fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
+        (**self).send_fd(frame)
     }
 }
 
@@ -877,11 +1760,12 @@ mod tests {
     #[test]
     fn can_frame_new_005() {
         let extended_id = 0x1E_C5_7E_D0;
-        // Extended id bitwise and with standard mask
-        let standard_id = 0x06_D0;
 
-        let can_frame_1 = CanFrame::new(extended_id, MessageType::Standard, &[0, 1, 2]).unwrap();
-        assert_eq!(can_frame_1.can_id(), standard_id);
+        let can_frame_1 = CanFrame::new(extended_id, MessageType::Standard, &[0, 1, 2]);
+        assert_eq!(
+            can_frame_1.err(),
+            Some(FrameConstructionError::CanIdMessageTypeMismatch)
+        );
 
         let can_frame_2 = CanFrame::new(extended_id, MessageType::Extended, &[0, 1, 2]).unwrap();
         assert_eq!(can_frame_2.can_id(), extended_id);
@@ -896,6 +1780,61 @@ mod tests {
         assert!(can_frame_2.is_extended_frame());
     }
 
+    #[test]
+    fn can_frame_new_remote_001() {
+        let can_frame_1 = CanFrame::new_remote(0x7DF, MessageType::Standard, 4).unwrap();
+        assert!(can_frame_1.is_remote_frame());
+        assert_eq!(can_frame_1.dlc(), 4);
+        assert_eq!(can_frame_1.can_id(), 0x7DF);
+
+        let can_frame_2 = CanFrame::new(0x7DF, MessageType::Standard, &[0, 0, 0, 0]).unwrap();
+        assert!(!can_frame_2.is_remote_frame());
+        assert_ne!(can_frame_1, can_frame_2);
+
+        let can_frame_3 = CanFrame::new_remote(0x1f_ff_00_ff, MessageType::Extended, 0).unwrap();
+        assert!(can_frame_3.is_remote_frame());
+        assert!(can_frame_3.is_extended_frame());
+    }
+
+    #[test]
+    fn can_frame_new_remote_002() {
+        let can_frame = CanFrame::new_remote(0x7DF, MessageType::Standard, 9);
+        assert_eq!(can_frame.err(), Some(FrameConstructionError::TooMuchData));
+    }
+
+    #[test]
+    fn can_frame_as_status() {
+        let mut can_frame =
+            CanFrame::new(0, MessageType::Standard, &[0x00, 0x04, 0x00, 0x40]).unwrap();
+        assert_eq!(can_frame.as_status(), None);
+
+        can_frame.frame.MSGTYPE = peak_can::PEAK_MESSAGE_STATUS as u8;
+        let status = can_frame.as_status().unwrap();
+        assert_eq!(status.bus_state, BusState::ErrorPassive);
+        assert!(status.receive_queue_overrun);
+        assert!(!status.transmit_queue_full);
+
+        can_frame.mut_data().copy_from_slice(&[0x00, 0x00, 0x00, 0x18]);
+        assert_eq!(can_frame.as_status().unwrap().bus_state, BusState::BusOff);
+        can_frame.mut_data().copy_from_slice(&[0x00, 0x00, 0x00, 0x08]);
+        assert_eq!(can_frame.as_status().unwrap().bus_state, BusState::ErrorWarning);
+    }
+
+    #[test]
+    fn can_frame_error_counters() {
+        let mut can_frame = CanFrame::new(0x4, MessageType::Standard, &[1, 0x88, 96, 130]).unwrap();
+        assert_eq!(can_frame.error_counters(), None);
+
+        can_frame.frame.MSGTYPE = peak_can::PEAK_MESSAGE_ERRFRAME as u8;
+        let counters = can_frame.error_counters().unwrap();
+        assert_eq!(counters, ErrorCounters { tx: 130, rx: 96 });
+        assert_eq!(counters.bus_state(), BusState::ErrorPassive);
+        assert_eq!(
+            ErrorCounters { tx: 0, rx: 96 }.bus_state(),
+            BusState::ErrorWarning
+        );
+    }
+
     /* CAN FD FRAME */
 
     #[test]
@@ -937,8 +1876,6 @@ mod tests {
     #[test]
     fn can_fd_frame_new_005() {
         let extended_id = 0x1E_C5_7E_D0;
-        // Extended id bitwise and with standard mask
-        let standard_id = 0x06_D0;
 
         let can_frame_1 = CanFdFrame::new(
             extended_id,
@@ -946,9 +1883,11 @@ mod tests {
             &(0..64u8).collect::<Vec<_>>(),
             false,
             false,
-        )
-        .unwrap();
-        assert_eq!(can_frame_1.can_id(), standard_id);
+        );
+        assert_eq!(
+            can_frame_1.err(),
+            Some(FrameConstructionError::CanIdMessageTypeMismatch)
+        );
 
         let can_frame_2 = CanFdFrame::new(
             extended_id,
@@ -962,6 +1901,85 @@ mod tests {
         assert_eq!(can_frame_2.can_id(), extended_id);
     }
 
+    #[test]
+    fn can_fd_frame_with_padding_001() {
+        let can_frame =
+            CanFdFrame::with_padding(0x20, MessageType::Standard, &[1; 10], true, false, 0xCC, DlcPolicy::RoundUp)
+                .unwrap();
+
+        assert_eq!(can_frame.dlc(), 9);
+        assert_eq!(can_frame.data(), &[1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0xCC, 0xCC]);
+    }
+
+    #[test]
+    fn can_fd_frame_with_padding_002() {
+        let inexact =
+            CanFdFrame::with_padding(0x20, MessageType::Standard, &[1; 10], true, false, 0xAA, DlcPolicy::Exact);
+        assert_eq!(inexact, Err(FrameConstructionError::InexactDataLength));
+
+        let exact =
+            CanFdFrame::with_padding(0x20, MessageType::Standard, &[1; 12], true, false, 0xAA, DlcPolicy::Exact);
+        assert!(exact.is_ok());
+    }
+
+    #[test]
+    fn can_fd_frame_brs_override() {
+        let mut can_frame = CanFdFrame::new(0x20, MessageType::Standard, &[1, 2], true, true).unwrap();
+
+        BrsOverride::Inherit.apply(&mut can_frame);
+        assert!(can_frame.is_brs());
+        BrsOverride::Force(false).apply(&mut can_frame);
+        assert!(!can_frame.is_brs());
+        assert!(can_frame.is_fd_frame());
+    }
+
+    #[test]
+    fn can_fd_frame_esi() {
+        let can_frame = CanFdFrame::new(0x20, MessageType::Standard, &[1, 2], true, true).unwrap();
+        assert!(!can_frame.is_esi());
+
+        let mut esi_frame = can_frame.with_esi(true);
+        assert!(esi_frame.is_esi());
+        assert!(esi_frame.is_brs());
+        assert_ne!(esi_frame, can_frame);
+        esi_frame.set_esi(false);
+        assert_eq!(esi_frame, can_frame);
+    }
+
+    #[test]
+    fn can_fd_frame_data_regions() {
+        let can_frame =
+            CanFdFrame::new(0x20, MessageType::Standard, &(0..12u8).collect::<Vec<_>>(), true, false).unwrap();
+
+        assert_eq!(can_frame.classic_data(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(can_frame.extension_data(), &[8, 9, 10, 11]);
+        assert_eq!(can_frame.data_chunks(5).map(|c| c.len()).collect::<Vec<_>>(), vec![5, 5, 2]);
+
+        let short_frame = CanFdFrame::new(0x20, MessageType::Standard, &[1, 2], true, false).unwrap();
+        assert_eq!(short_frame.classic_data(), &[1, 2]);
+        assert!(short_frame.extension_data().is_empty());
+    }
+
+    #[test]
+    fn can_frame_matches_filter() {
+        let filter = AcceptanceFilter::from_ids(MessageType::Standard, &[0x100, 0x101]);
+        assert_eq!(filter, AcceptanceFilter::new(MessageType::Standard, 0x100, 0x001));
+
+        let accepted = CanFrame::new(0x101, MessageType::Standard, &[]).unwrap();
+        let rejected = CanFrame::new(0x102, MessageType::Standard, &[]).unwrap();
+        let extended = CanFrame::new(0x102, MessageType::Extended, &[]).unwrap();
+        assert!(accepted.matches_filter(&filter));
+        assert!(!rejected.matches_filter(&filter));
+        assert!(extended.matches_filter(&filter));
+
+        assert!(rejected.matches(0x100, 0x7F0));
+        assert!(!rejected.matches(0x100, 0x7FF));
+
+        let fd_frame = CanFdFrame::new(0x101, MessageType::Standard, &[], true, false).unwrap();
+        assert!(fd_frame.matches_filter(&filter));
+        assert!(fd_frame.matches(0x101, STANDARD_MASK));
+    }
+
     /* calc_dlc TESTS */
 
     #[test]
@@ -1217,4 +2235,73 @@ mod tests {
         assert!(CanFdBitTiming::new(1, 1, 1, 1, 1, 1, 1, 0).is_err());
         assert!(CanFdBitTiming::new(1, 1, 1, 1, 1, 1, 1, 17).is_err());
     }
+
+    #[test]
+    fn identify_virtual_channel() {
+        let pair = crate::bus::VirtualPair::new().unwrap();
+        // the PCAN-Basic constructors route virtual handles to the virtual backend
+        let socket = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let peer = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+        assert!(matches!(socket.identify(true), Err(CanError::IllParamType)));
+
+        let frame = CanFrame::new(0x100, MessageType::Standard, &[1]).unwrap();
+        socket.send(frame).unwrap();
+        assert_eq!(peer.recv().unwrap().0, frame);
+    }
+
+    #[test]
+    fn close_uninitializes_once() {
+        let pair = crate::bus::VirtualPair::new().unwrap();
+        let socket = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        socket.close().unwrap();
+
+        // the closed socket was not uninitialized a second time by its drop
+        let reopened = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        assert!(matches!(
+            CanSocket::open(pair.a(), Baudrate::Baud500K),
+            Err(CanError::Initialize)
+        ));
+        reopened.close().unwrap();
+    }
+
+    #[test]
+    fn waiters_share_the_receive_event() {
+        let pair = crate::bus::VirtualPair::new().unwrap();
+        let a = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let b = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+
+        let ids = std::thread::scope(|scope| {
+            let waiters: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| b.recv_timeout(Duration::from_secs(5))))
+                .collect();
+            for id in [0x100, 0x200] {
+                a.send(CanFrame::new(id, MessageType::Standard, &[]).unwrap())
+                    .unwrap();
+            }
+            let mut ids: Vec<_> = waiters
+                .into_iter()
+                .map(|waiter| waiter.join().unwrap().unwrap().0.can_id())
+                .collect();
+            ids.sort();
+            ids
+        });
+        assert_eq!(ids, [0x100, 0x200]);
+        assert!(Arc::ptr_eq(
+            &b.receive_event().unwrap(),
+            &b.receive_event().unwrap()
+        ));
+    }
+
+    #[test]
+    fn timestamp_arithmetic() {
+        // across the 32 bit millisecond wrap
+        let before = Timestamp::from_micros(0xFFFF_FFFF * 1000 + 999);
+        let after = Timestamp::from_micros(0x1_0000_0000 * 1000 + 1);
+        assert_eq!(after.millis_overflow, 1);
+        assert_eq!(after - before, Duration::from_micros(2));
+        assert_eq!(before - after, Duration::ZERO);
+        assert_eq!(before.checked_duration_since(after), None);
+        assert!(before < after);
+        assert_eq!(after.as_duration(), Duration::from_micros(after.as_micros()));
+    }
 }
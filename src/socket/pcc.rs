@@ -6,11 +6,11 @@ use crate::bus::PccBus;
 use crate::channel::Channel;
 use crate::df::{
     HasAcceptanceFilter11Bit, HasAcceptanceFilter29Bit, HasAllowErrorFrames, HasAllowRTRFrames,
-    HasAllowStatusFrames, HasMessageFilter, HasReceiveStatus, HasSetAcceptanceFilter11Bit,
-    HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames, HasSetAllowRTRFrames,
-    HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
+    HasAllowStatusFrames, HasFilterMessages, HasMessageFilter, HasReceiveStatus,
+    HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit, HasSetAllowErrorFrames,
+    HasSetAllowRTRFrames, HasSetAllowStatusFrames, HasSetMessageFilter, HasSetReceiveStatus,
 };
-use crate::error::{CanError, CanOkError};
+use crate::error::CanError;
 use crate::hw::{
     HasControllerNumber, HasDevicePartNumber, HasHardwareName, HasSetControllerNumber,
 };
@@ -18,8 +18,10 @@ use crate::info::{
     HasBitrateInfo, HasChannelFeatures, HasChannelVersion, HasDataBusSpeed, HasFirmwareVersion,
     HasNominalBusSpeed,
 };
-use crate::peak_lib;
-use crate::socket::{Baudrate, HasRecvCan, HasSendCan, Socket};
+use crate::parameter::HasParameters;
+use crate::socket::event::EventSlot;
+use crate::socket::status::HasBusStatus;
+use crate::socket::{Backend, Baudrate, HasRecvCan, HasSendCan, PcanBackend, Socket};
 use crate::special::{HasFiveVoltsPower, HasSetFiveVoltsPower};
 use crate::trace::{
     HasSetTraceConfigure, HasSetTraceLocation, HasSetTraceSize, HasSetTraceStatus,
@@ -29,18 +31,17 @@ use crate::trace::{
 #[derive(Debug, PartialEq)]
 pub struct PccCanSocket {
     handle: u16,
+    event: EventSlot,
 }
 
 impl PccCanSocket {
     pub fn open(bus: PccBus, baud: Baudrate) -> Result<PccCanSocket, CanError> {
         let handle = bus.into();
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, baud.into(), 0, 0, 0) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(PccCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize(handle, baud.into())?;
+        Ok(PccCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
     }
 }
 
@@ -48,10 +49,8 @@ impl PccCanSocket {
 
 impl Drop for PccCanSocket {
     fn drop(&mut self) {
-        let Ok(peak_lib) = peak_lib() else {
-            return;
-        };
-        unsafe { peak_lib.CAN_Uninitialize(self.handle) };
+        self.event.clear();
+        let _ = PcanBackend.uninitialize(self.handle);
     }
 }
 
@@ -61,6 +60,10 @@ impl Socket for PccCanSocket {
     fn handle(&self) -> u16 {
         self.handle
     }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
 }
 
 /* Channel trait implementation */
@@ -79,6 +82,14 @@ impl HasSendCan for PccCanSocket {}
 // impl HasRecvCanFd for PccCanSocket {}
 // impl HasSendCanFd for PccCanSocket {}
 
+/* TYPED PARAMETERS */
+
+impl HasParameters for PccCanSocket {}
+
+/* BUS STATUS */
+
+impl HasBusStatus for PccCanSocket {}
+
 /* HARDWARE IDENTIFICATION */
 
 impl HasHardwareName for PccCanSocket {}
@@ -112,6 +123,8 @@ impl HasSetFiveVoltsPower for PccCanSocket {}
 impl HasMessageFilter for PccCanSocket {}
 impl HasSetMessageFilter for PccCanSocket {}
 
+impl HasFilterMessages for PccCanSocket {}
+
 impl HasReceiveStatus for PccCanSocket {}
 impl HasSetReceiveStatus for PccCanSocket {}
 
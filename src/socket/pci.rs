@@ -6,12 +6,12 @@ use crate::bus::PciBus;
 use crate::channel::Channel;
 use crate::df::{
     HasAcceptanceFilter11Bit, HasAcceptanceFilter29Bit, HasAllowEchoFrames, HasAllowErrorFrames,
-    HasAllowRTRFrames, HasAllowStatusFrames, HasMessageFilter, HasReceiveStatus,
+    HasAllowRTRFrames, HasAllowStatusFrames, HasFilterMessages, HasMessageFilter, HasReceiveStatus,
     HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit, HasSetAllowEchoFrames,
     HasSetAllowErrorFrames, HasSetAllowRTRFrames, HasSetAllowStatusFrames, HasSetMessageFilter,
     HasSetReceiveStatus,
 };
-use crate::error::{CanError, CanOkError};
+use crate::error::CanError;
 use crate::hw::{
     HasControllerNumber, HasDeviceId, HasDevicePartNumber, HasHardwareName, HasSetControllerNumber,
     HasSetDeviceId,
@@ -20,8 +20,10 @@ use crate::info::{
     HasBitrateInfo, HasChannelFeatures, HasChannelVersion, HasDataBusSpeed, HasFirmwareVersion,
     HasNominalBusSpeed,
 };
-use crate::peak_lib;
-use crate::socket::{Baudrate, HasRecvCan, HasSendCan, Socket};
+use crate::parameter::HasParameters;
+use crate::socket::event::EventSlot;
+use crate::socket::status::HasBusStatus;
+use crate::socket::{Backend, Baudrate, HasRecvCan, HasSendCan, PcanBackend, Socket};
 use crate::trace::{
     HasSetTraceConfigure, HasSetTraceLocation, HasSetTraceSize, HasSetTraceStatus,
     HasTraceConfigure, HasTraceLocation, HasTraceSize, HasTraceStatus,
@@ -30,18 +32,17 @@ use crate::trace::{
 #[derive(Debug, PartialEq)]
 pub struct PciCanSocket {
     handle: u16,
+    event: EventSlot,
 }
 
 impl PciCanSocket {
     pub fn open(bus: PciBus, baud: Baudrate) -> Result<PciCanSocket, CanError> {
         let handle = bus.into();
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, baud.into(), 0, 0, 0) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(PciCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize(handle, baud.into())?;
+        Ok(PciCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
     }
 }
 
@@ -49,10 +50,8 @@ impl PciCanSocket {
 
 impl Drop for PciCanSocket {
     fn drop(&mut self) {
-        let Ok(peak_lib) = peak_lib() else {
-            return;
-        };
-        unsafe { peak_lib.CAN_Uninitialize(self.handle) };
+        self.event.clear();
+        let _ = PcanBackend.uninitialize(self.handle);
     }
 }
 
@@ -62,6 +61,10 @@ impl Socket for PciCanSocket {
     fn handle(&self) -> u16 {
         self.handle
     }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
 }
 
 /* Channel trait implementation */
@@ -80,6 +83,14 @@ impl HasSendCan for PciCanSocket {}
 // impl HasRecvCanFd for PciCanSocket {}
 // impl HasSendCanFd for PciCanSocket {}
 
+/* TYPED PARAMETERS */
+
+impl HasParameters for PciCanSocket {}
+
+/* BUS STATUS */
+
+impl HasBusStatus for PciCanSocket {}
+
 /* HARDWARE IDENTIFICATION */
 
 impl HasDeviceId for PciCanSocket {}
@@ -113,6 +124,8 @@ impl HasFirmwareVersion for PciCanSocket {}
 impl HasMessageFilter for PciCanSocket {}
 impl HasSetMessageFilter for PciCanSocket {}
 
+impl HasFilterMessages for PciCanSocket {}
+
 impl HasReceiveStatus for PciCanSocket {}
 impl HasSetReceiveStatus for PciCanSocket {}
 
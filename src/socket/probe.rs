@@ -0,0 +1,238 @@
+//! Listen-only probing of a bus before joining it actively.
+//!
+//! A node with the wrong bitrate destroys every frame on the bus with error frames. Probing
+//! opens the channel listen-only first, so a wrong bitrate only shows up as receive errors on
+//! the probing side.
+
+use crate::bus::Bus;
+use crate::channel::Channel;
+use crate::df::{
+    HasSetAllowEchoFrames, HasSetAllowErrorFrames, HasSetAllowRTRFrames, HasSetAllowStatusFrames,
+    SetAllowErrorFrames,
+};
+use crate::error::CanError;
+use crate::socket::event::EventSlot;
+use crate::socket::{Backend, Baudrate, CanSocket, PcanBackend, RecvCan};
+use crate::special::{HasSetListenOnly, SetListenOnly};
+
+use core::fmt;
+use std::time::{Duration, Instant};
+
+/// A channel handle on a backend that is not (or not yet) owned by a socket, e.g. to set
+/// parameters which have to be configured before initialization.
+pub(crate) struct RawChannel<'a>(pub(crate) u16, pub(crate) &'a dyn Backend);
+
+impl Channel for RawChannel<'_> {
+    fn channel(&self) -> u16 {
+        self.0
+    }
+
+    fn backend(&self) -> &dyn Backend {
+        self.1
+    }
+}
+
+impl HasSetListenOnly for RawChannel<'_> {}
+impl HasSetAllowErrorFrames for RawChannel<'_> {}
+impl HasSetAllowStatusFrames for RawChannel<'_> {}
+impl HasSetAllowRTRFrames for RawChannel<'_> {}
+impl HasSetAllowEchoFrames for RawChannel<'_> {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeConfig {
+    /// How long to listen per bitrate.
+    pub listen_window: Duration,
+    /// Valid frames required for a bitrate to be plausible. With `0` a silent bus is accepted.
+    pub min_frames: usize,
+    /// Error frames and bus errors tolerated for a bitrate to be plausible.
+    pub max_errors: usize,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        ProbeConfig {
+            listen_window: Duration::from_millis(500),
+            min_frames: 1,
+            max_errors: 0,
+        }
+    }
+}
+
+/// What was observed while listening with one bitrate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitrateProbe {
+    pub baudrate: Baudrate,
+    pub frames: usize,
+    pub errors: usize,
+}
+
+impl BitrateProbe {
+    pub fn is_plausible(&self, config: &ProbeConfig) -> bool {
+        self.frames >= config.min_frames && self.errors <= config.max_errors
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ProbeError {
+    Can(CanError),
+    /// None of the candidates was plausible, the probes are in candidate order.
+    NoPlausibleBitrate(Vec<BitrateProbe>),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Can(err) => write!(f, "{err}"),
+            ProbeError::NoPlausibleBitrate(probes) => {
+                write!(f, "No plausible bitrate among {} candidates", probes.len())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl From<CanError> for ProbeError {
+    fn from(value: CanError) -> Self {
+        ProbeError::Can(value)
+    }
+}
+
+/// Listens listen-only on the uninitialized channel `handle` and leaves it uninitialized with
+/// listen-only mode turned off again.
+pub(crate) fn probe(
+    handle: u16,
+    baudrate: Baudrate,
+    config: &ProbeConfig,
+) -> Result<BitrateProbe, CanError> {
+    let channel = RawChannel(handle, &PcanBackend);
+    channel.set_listen_only(true)?;
+    let result = listen(handle, baudrate, config);
+    let reset = channel.set_listen_only(false);
+    let probe = result?;
+    reset?;
+    Ok(probe)
+}
+
+fn listen(handle: u16, baudrate: Baudrate, config: &ProbeConfig) -> Result<BitrateProbe, CanError> {
+    PcanBackend.initialize(handle, baudrate.into())?;
+    // uninitializes the channel on all paths
+    let socket = CanSocket {
+        handle,
+        backend: PcanBackend,
+        event: EventSlot::default(),
+        closed: false,
+    };
+    RawChannel(handle, &PcanBackend).allow_error_frames(true)?;
+
+    let mut probe = BitrateProbe {
+        baudrate,
+        frames: 0,
+        errors: 0,
+    };
+    let deadline = Instant::now() + config.listen_window;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        match socket.recv_timeout(remaining) {
+            Ok((frame, _)) if frame.is_error_frame() => probe.errors += 1,
+            Ok(_) => probe.frames += 1,
+            Err(CanError::QrcvEmpty) => break,
+            Err(
+                CanError::BusLight
+                | CanError::BusHeavy
+                | CanError::BusPassive
+                | CanError::BusOff
+                | CanError::AnyBusErr,
+            ) => probe.errors += 1,
+            Err(err) => return Err(err),
+        }
+        if probe.errors > config.max_errors {
+            break;
+        }
+    }
+    socket.close()?;
+    Ok(probe)
+}
+
+/// Probes every candidate listen-only for `config.listen_window` each.
+pub fn probe_bitrates<T: Bus>(
+    bus: T,
+    candidates: &[Baudrate],
+    config: &ProbeConfig,
+) -> Result<Vec<BitrateProbe>, CanError> {
+    let handle = bus.channel();
+    candidates
+        .iter()
+        .map(|baudrate| probe(handle, *baudrate, config))
+        .collect()
+}
+
+/// Detects the baudrate of a bus by listening to it with every candidate. Returns the candidate
+/// that received the most valid frames without any error, or `None` if every candidate saw
+/// errors or the bus was silent.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, detect_bitrate};
+/// # use peak_can::bus::UsbBus;
+/// # use std::time::Duration;
+/// let candidates = [Baudrate::Baud1M, Baudrate::Baud500K, Baudrate::Baud250K, Baudrate::Baud125K];
+/// match detect_bitrate(UsbBus::USB1, &candidates, Duration::from_millis(300))? {
+///     Some(baudrate) => println!("bus runs at {baudrate:?}"),
+///     None => println!("no traffic or unknown baudrate"),
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub fn detect_bitrate<T: Bus>(
+    bus: T,
+    candidates: &[Baudrate],
+    listen_window: Duration,
+) -> Result<Option<Baudrate>, CanError> {
+    let config = ProbeConfig {
+        listen_window,
+        min_frames: 1,
+        max_errors: 0,
+    };
+    let probes = probe_bitrates(bus, candidates, &config)?;
+    Ok(best_candidate(&probes, &config))
+}
+
+fn best_candidate(probes: &[BitrateProbe], config: &ProbeConfig) -> Option<Baudrate> {
+    probes
+        .iter()
+        .filter(|probe| probe.is_plausible(config))
+        // the first candidate wins a tie
+        .rev()
+        .max_by_key(|probe| probe.frames)
+        .map(|probe| probe.baudrate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_candidate_without_errors() {
+        let config = ProbeConfig::default();
+        let probe = |baudrate, frames, errors| BitrateProbe {
+            baudrate,
+            frames,
+            errors,
+        };
+
+        let probes = [
+            probe(Baudrate::Baud1M, 0, 12),
+            probe(Baudrate::Baud500K, 40, 0),
+            probe(Baudrate::Baud250K, 80, 3),
+            probe(Baudrate::Baud125K, 40, 0),
+        ];
+        assert_eq!(best_candidate(&probes, &config), Some(Baudrate::Baud500K));
+
+        let silent = [probe(Baudrate::Baud500K, 0, 0)];
+        assert_eq!(best_candidate(&silent, &config), None);
+    }
+}
@@ -0,0 +1,338 @@
+//! A background thread reading a socket and delivering the frames through a bounded queue.
+
+use crate::error::CanError;
+use crate::queue::{BoundedQueue, OverflowPolicy};
+use crate::shutdown::Shutdown;
+use crate::socket::wait::Waiter;
+use crate::socket::{ReceivedFrame, RecvCan, WaitStrategy};
+
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Longest time the reader thread waits for a frame, or for room in a full
+/// [OverflowPolicy::Block] queue, before checking whether it was stopped.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Wait after a receive error before reading again, so that a persisting fault neither keeps a
+/// core busy nor floods the queue. Doubles with every further error.
+const ERROR_BACKOFF: WaitStrategy = WaitStrategy::Backoff {
+    min: Duration::from_millis(1),
+    max: STOP_CHECK_INTERVAL,
+};
+
+type Received = Result<ReceivedFrame, CanError>;
+
+/// Frames and receive errors delivered by a reader thread, see [spawn_reader].
+pub struct FrameReceiver {
+    queue: Arc<BoundedQueue<Received>>,
+}
+
+impl FrameReceiver {
+    /// Waits for the next frame or receive error. `None` once the reader thread ended and all
+    /// queued frames were taken.
+    pub fn recv(&self) -> Option<Received> {
+        loop {
+            if let Some(received) = self.queue.pop_timeout(STOP_CHECK_INTERVAL) {
+                return Some(received);
+            }
+            if self.is_disconnected() {
+                return self.queue.try_pop();
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for the next frame or receive error.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Received> {
+        self.queue.pop_timeout(timeout)
+    }
+
+    pub fn try_recv(&self) -> Option<Received> {
+        self.queue.try_pop()
+    }
+
+    /// Blocking iterator over the delivered frames and errors, ends with the reader thread.
+    pub fn iter(&self) -> impl Iterator<Item = Received> + '_ {
+        std::iter::from_fn(|| self.recv())
+    }
+
+    /// Number of frames and errors discarded because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// Whether the reader thread ended, it holds the only other reference to the queue.
+    fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.queue) == 1
+    }
+}
+
+/// Handle of a reader thread started by [spawn_reader]. Dropping the handle stops the thread.
+pub struct ReaderHandle<S> {
+    shutdown: Shutdown,
+    thread: Option<JoinHandle<S>>,
+}
+
+impl<S> ReaderHandle<S> {
+    /// Stops the thread and returns the socket.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the reader thread, if it panicked.
+    pub fn stop(mut self) -> S {
+        self.shutdown.trigger();
+        let thread = self.thread.take().expect("reader thread joined twice");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Whether the thread ended, because it was stopped or all receivers were dropped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+impl<S> Drop for ReaderHandle<S> {
+    fn drop(&mut self) {
+        self.shutdown.trigger();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Moves `socket` to a new thread that receives frames until it is stopped or the returned
+/// receiver is dropped. Receive errors other than an empty queue are delivered as well. After
+/// an error the thread waits before reading again, up to 50 ms while the error persists, and it
+/// ends after an error that [lost the handle](CanError::is_handle_lost). The frames are
+/// numbered in receive order and have no channel, see [spawn_channel_reader], the host
+/// timestamp is taken right after each read.
+///
+/// At most `capacity` frames and errors are queued, `policy` decides what happens when a
+/// consumer falls behind. With [OverflowPolicy::Block] the thread stops reading until there is
+/// room, so frames are lost in the driver receive queue instead.
+///
+/// A socket shared through an [Arc] stays usable for sending while the thread reads.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType, RecvCan, SendCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::queue::OverflowPolicy;
+/// # use std::sync::Arc;
+/// let socket = Arc::new(CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?);
+/// let (frames, reader) = socket.clone().spawn_reader(1024, OverflowPolicy::DropOldest);
+///
+/// socket.send(CanFrame::new(0x7DF, MessageType::Standard, &[0x02, 0x01, 0x0D]).unwrap())?;
+/// for received in frames.iter().take(10) {
+///     let received = received?;
+///     println!("{} {:?}", received.sequence, received.frame);
+/// }
+/// reader.stop();
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub fn spawn_reader<S>(
+    socket: S,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (FrameReceiver, ReaderHandle<S>)
+where
+    S: RecvCan + Send + 'static,
+{
+    spawn(socket, None, capacity, policy)
+}
+
+/// Like [spawn_reader], but tags the delivered frames with `channel`, so that the frames of
+/// several readers merged into one pipeline keep their origin.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn spawn_channel_reader<S>(
+    socket: S,
+    channel: u16,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (FrameReceiver, ReaderHandle<S>)
+where
+    S: RecvCan + Send + 'static,
+{
+    spawn(socket, Some(channel), capacity, policy)
+}
+
+fn spawn<S>(
+    socket: S,
+    channel: Option<u16>,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (FrameReceiver, ReaderHandle<S>)
+where
+    S: RecvCan + Send + 'static,
+{
+    let receiver = FrameReceiver {
+        queue: Arc::new(BoundedQueue::new(capacity, policy)),
+    };
+    let shutdown = Shutdown::new();
+
+    let queue = receiver.queue.clone();
+    let stop = shutdown.clone();
+    let thread = thread::spawn(move || {
+        // the receiver holds the only other reference to the queue
+        let receiver_dropped = |queue: &Arc<_>| Arc::strong_count(queue) == 1;
+        let mut sequence = 0;
+        let mut backoff = Waiter::new(ERROR_BACKOFF);
+        while !stop.is_requested() && !receiver_dropped(&queue) {
+            let result = socket.recv_timeout(STOP_CHECK_INTERVAL);
+            let handle_lost = matches!(&result, Err(err) if err.is_handle_lost());
+            let mut received = match result {
+                Ok((frame, timestamp)) => {
+                    let host_time = Instant::now();
+                    backoff.reset();
+                    sequence += 1;
+                    Ok(ReceivedFrame::from_can(
+                        frame,
+                        &timestamp,
+                        host_time,
+                        channel,
+                        sequence - 1,
+                    ))
+                }
+                Err(CanError::QrcvEmpty) => continue,
+                Err(err) => Err(err),
+            };
+            let failed = received.is_err();
+            while let Err(back) = queue.push_timeout(received, STOP_CHECK_INTERVAL) {
+                if stop.is_requested() || receiver_dropped(&queue) {
+                    return socket;
+                }
+                received = back;
+            }
+            if handle_lost {
+                break;
+            }
+            if failed {
+                backoff.wait(&socket, None);
+            }
+        }
+        socket
+    });
+
+    (
+        receiver,
+        ReaderHandle {
+            shutdown,
+            thread: Some(thread),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{CanFrame, MessageType, MockCanSocket, Timestamp};
+
+    #[test]
+    fn deliver_frames_and_errors() {
+        let socket = MockCanSocket::new();
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap();
+        socket
+            .backend()
+            .push_rx(socket.channel(), frame, Timestamp::from_micros(10));
+        socket
+            .backend()
+            .push_rx_error(socket.channel(), CanError::BusOff);
+
+        let (frames, reader) = spawn_reader(socket.clone(), 16, OverflowPolicy::DropOldest);
+        let received = frames
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.frame.data(), &[1, 2]);
+        assert_eq!(received.device_timestamp, 10);
+        assert_eq!(received.sequence, 0);
+        assert_eq!(received.channel, None);
+        assert!(matches!(
+            frames.recv_timeout(Duration::from_secs(1)).unwrap(),
+            Err(CanError::BusOff)
+        ));
+
+        socket
+            .backend()
+            .push_rx(socket.channel(), frame, Timestamp::from_micros(20));
+        let received = frames.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(received.unwrap().sequence, 1);
+        assert!(!reader.is_finished());
+        assert_eq!(reader.stop().channel(), socket.channel());
+        assert!(frames.recv().is_none());
+    }
+
+    #[test]
+    fn back_off_from_persisting_errors() {
+        let socket = MockCanSocket::new();
+        socket
+            .backend()
+            .set_read_error(socket.channel(), Some(CanError::BusOff));
+
+        let (frames, reader) = spawn_reader(socket.clone(), 64, OverflowPolicy::DropNewest);
+        thread::sleep(Duration::from_millis(200));
+        reader.stop();
+        // 1 + 2 + 4 + ... ms and then every 50 ms
+        let errors = frames.iter().filter(Result::is_err).count();
+        assert!((1..16).contains(&errors), "{errors} errors");
+        assert_eq!(frames.dropped(), 0);
+
+        socket
+            .backend()
+            .set_read_error(socket.channel(), Some(CanError::IllHw));
+        let (frames, reader) = spawn_reader(socket, 64, OverflowPolicy::DropNewest);
+        assert!(matches!(frames.recv(), Some(Err(CanError::IllHw))));
+        // the thread ended after the lost handle
+        assert!(frames.recv().is_none());
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn overflow_policy_of_a_slow_consumer() {
+        let socket = MockCanSocket::new();
+        for id in 1..=3 {
+            let frame = CanFrame::new(id, MessageType::Standard, &[]).unwrap();
+            socket
+                .backend()
+                .push_rx(socket.channel(), frame, Timestamp::from_micros(0));
+        }
+
+        let (frames, reader) = spawn_reader(socket.clone(), 2, OverflowPolicy::DropNewest);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while frames.dropped() == 0 {
+            assert!(Instant::now() < deadline, "no frame was dropped");
+            thread::yield_now();
+        }
+        reader.stop();
+        let ids: Vec<_> = frames.iter().map(|r| r.unwrap().frame.can_id()).collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn tag_frames_with_the_channel() {
+        let socket = MockCanSocket::new();
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[]).unwrap();
+        socket
+            .backend()
+            .push_rx(socket.channel(), frame, Timestamp::default());
+
+        let (frames, reader) =
+            spawn_channel_reader(socket.clone(), 7, 16, OverflowPolicy::DropOldest);
+        let received = frames
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.channel, Some(7));
+        reader.stop();
+    }
+}
@@ -0,0 +1,80 @@
+//! Received frames together with their provenance.
+
+use crate::socket::{AnyFrame, CanFdFrame, CanFrame, Timestamp};
+use crate::trace::TraceRecord;
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rx,
+    /// Echo of a frame sent by this channel.
+    Tx,
+}
+
+/// A frame bundled with where and when it was received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedFrame {
+    pub frame: AnyFrame,
+    /// Device timestamp in microseconds.
+    pub device_timestamp: u64,
+    /// Host monotonic time right after the frame was read from the driver.
+    pub host_timestamp: Instant,
+    pub channel: Option<u16>,
+    pub direction: Direction,
+    /// Position of the frame in the receive order of its source.
+    pub sequence: u64,
+}
+
+impl ReceivedFrame {
+    /// Bundles a frame as returned by [RecvCan::recv_with_host_time](crate::socket::RecvCan::recv_with_host_time).
+    pub fn from_can(
+        frame: CanFrame,
+        timestamp: &Timestamp,
+        host_timestamp: Instant,
+        channel: Option<u16>,
+        sequence: u64,
+    ) -> Self {
+        ReceivedFrame {
+            direction: direction(frame.is_echo_frame()),
+            frame: AnyFrame::Can(frame),
+            device_timestamp: timestamp.as_micros(),
+            host_timestamp,
+            channel,
+            sequence,
+        }
+    }
+
+    /// Bundles a frame as returned by [RecvCanFd::recv_fd_with_host_time](crate::socket::RecvCanFd::recv_fd_with_host_time).
+    pub fn from_can_fd(
+        frame: CanFdFrame,
+        timestamp: u64,
+        host_timestamp: Instant,
+        channel: Option<u16>,
+        sequence: u64,
+    ) -> Self {
+        ReceivedFrame {
+            direction: direction(frame.is_echo_frame()),
+            frame: AnyFrame::CanFd(frame),
+            device_timestamp: timestamp,
+            host_timestamp,
+            channel,
+            sequence,
+        }
+    }
+}
+
+fn direction(echo: bool) -> Direction {
+    if echo { Direction::Tx } else { Direction::Rx }
+}
+
+impl From<&ReceivedFrame> for TraceRecord {
+    /// Uses the device timestamp as trace time.
+    fn from(value: &ReceivedFrame) -> Self {
+        TraceRecord {
+            timestamp: Duration::from_micros(value.device_timestamp),
+            channel: value.channel,
+            frame: value.frame,
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! Surviving system sleep and device re-plugging.
+//!
+//! After a resume from sleep the driver often invalidates all channel handles. A
+//! [ResumingSocket] notices this from the returned error codes, reports it as
+//! [PowerEvent::Suspended] and initializes the channel again once it is available, with the
+//! whole configuration of its [CanSocketBuilder].
+
+use crate::bus::Bus;
+use crate::error::CanError;
+use crate::queue::{BoundedQueue, OverflowPolicy};
+use crate::socket::{
+    Backend, Baudrate, BuilderError, BusState, BusStatus, CanFdFrame, CanFrame, CanSocket,
+    CanSocketBuilder, PcanBackend, RecvCan, RecvCanFd, SendCan, SendCanFd, Timestamp,
+};
+
+use std::cell::RefCell;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub enum PowerEvent {
+    /// The channel handle became invalid with the given error.
+    Suspended(CanError),
+    /// The channel was initialized again.
+    Resumed,
+}
+
+/// Outcome of [ResumingSocket::recover_from_bus_off].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusOffRecovery {
+    /// The channel was not bus-off and was left alone.
+    NotBusOff,
+    /// The channel was initialized again and reported this state afterwards, which is still
+    /// [BusState::BusOff] if the fault on the bus persists.
+    Recovered(BusState),
+}
+
+/// Default number of power events kept until they are polled.
+const EVENT_CAPACITY: usize = 64;
+
+struct State<B: Backend> {
+    socket: Option<CanSocket<B>>,
+    next_attempt: Instant,
+}
+
+/// A socket that reinitializes its channel after the handle was lost.
+///
+/// While the channel is suspended, receiving reports an empty queue so that receive loops keep
+/// waiting, and sending fails with [CanError::Initialize].
+///
+/// Every initialization applies the configuration of the [CanSocketBuilder] the socket was
+/// opened with, so bitrate, FD timing, filters and frame options survive a resume.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, PowerEvent, RecvCan, ResumingSocket};
+/// # use peak_can::bus::UsbBus;
+/// let socket = ResumingSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// for received in socket.iter() {
+///     while let Some(event) = socket.poll_event() {
+///         println!("{event:?}");
+///     }
+///     let (frame, timestamp) = received?;
+///     println!("{:?} {:?}", frame, timestamp);
+/// }
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct ResumingSocket<B: Backend + Clone = PcanBackend> {
+    builder: CanSocketBuilder,
+    backend: B,
+    auto_reinitialize: bool,
+    retry_interval: Duration,
+    state: RefCell<State<B>>,
+    events: BoundedQueue<PowerEvent>,
+}
+
+impl ResumingSocket {
+    pub fn open<T: Bus>(bus: T, baud: Baudrate) -> Result<ResumingSocket, CanError> {
+        Self::open_with_builder(CanSocketBuilder::new(bus).with_baudrate(baud))
+            .map_err(into_can_error)
+    }
+
+    /// Opens a CAN FD socket with a PCAN-Basic bitrate string.
+    pub fn open_fd<T: Bus>(bus: T, bitrate: &str) -> Result<ResumingSocket, CanError> {
+        Self::open_with_builder(CanSocketBuilder::new(bus).with_fd_bitrate(bitrate))
+            .map_err(into_can_error)
+    }
+
+    /// Opens the socket with the configuration of `builder`, which is applied again after
+    /// every resume.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::{Baudrate, CanSocketBuilder, MessageType, ResumingSocket};
+    /// # use peak_can::bus::UsbBus;
+    /// let builder = CanSocketBuilder::new(UsbBus::USB1)
+    ///     .with_baudrate(Baudrate::Baud500K)
+    ///     .with_filter(0x700, 0x7FF, MessageType::Standard);
+    /// let socket = ResumingSocket::open_with_builder(builder)?;
+    /// # Ok::<(), peak_can::socket::BuilderError>(())
+    /// ```
+    pub fn open_with_builder(builder: CanSocketBuilder) -> Result<ResumingSocket, BuilderError> {
+        Self::open_with_backend(builder, PcanBackend)
+    }
+}
+
+impl<B: Backend + Clone> ResumingSocket<B> {
+    /// Opens the socket with the configuration of `builder` on `backend`.
+    pub fn open_with_backend(
+        builder: CanSocketBuilder,
+        backend: B,
+    ) -> Result<ResumingSocket<B>, BuilderError> {
+        let socket = builder.open_with_backend(backend.clone())?;
+        Ok(ResumingSocket {
+            builder,
+            backend,
+            auto_reinitialize: true,
+            retry_interval: Duration::from_secs(1),
+            state: RefCell::new(State {
+                socket: Some(socket),
+                next_attempt: Instant::now(),
+            }),
+            events: BoundedQueue::new(EVENT_CAPACITY, OverflowPolicy::DropOldest),
+        })
+    }
+
+    /// Whether the channel is initialized again automatically, on by default. Without it
+    /// the channel stays suspended until [reinitialize](ResumingSocket::reinitialize) is called.
+    pub fn with_auto_reinitialize(mut self, enable: bool) -> Self {
+        self.auto_reinitialize = enable;
+        self
+    }
+
+    /// Minimum time between two automatic reinitialization attempts, 1 s by default.
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Number of power events kept until they are polled, 64 by default. The oldest events
+    /// are discarded when the queue is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        self.events = BoundedQueue::new(capacity, OverflowPolicy::DropOldest);
+        self
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.state.borrow().socket.is_none()
+    }
+
+    /// Returns the oldest power event that was not polled yet.
+    pub fn poll_event(&self) -> Option<PowerEvent> {
+        self.events.try_pop()
+    }
+
+    /// Number of power events discarded because they were not polled in time.
+    pub fn dropped_events(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    /// Initializes the suspended channel again. Does nothing if the channel is not suspended.
+    pub fn reinitialize(&self) -> Result<(), CanError> {
+        let mut state = self.state.borrow_mut();
+        if state.socket.is_some() {
+            return Ok(());
+        }
+        state.next_attempt = Instant::now() + self.retry_interval;
+        let socket = self.initialize()?;
+        state.socket = Some(socket);
+        self.events.push(PowerEvent::Resumed);
+        Ok(())
+    }
+
+    /// Initializes the channel again if it is bus-off, e.g. for channels without
+    /// [bus-off autoreset](crate::special::SetBusOffAutoreset). Frames still queued are lost.
+    ///
+    /// If the initialization fails the channel is suspended and initialized again like after
+    /// a lost handle.
+    pub fn recover_from_bus_off(&self) -> Result<BusOffRecovery, CanError> {
+        if self.with_socket(|socket| socket.bus_state())? != BusState::BusOff {
+            return Ok(BusOffRecovery::NotBusOff);
+        }
+
+        let mut state = self.state.borrow_mut();
+        // uninitializes the channel before initializing it again
+        state.socket = None;
+        let socket = match self.initialize() {
+            Ok(socket) => socket,
+            Err(err) => {
+                state.next_attempt = Instant::now() + self.retry_interval;
+                self.events.push(PowerEvent::Suspended(err.clone()));
+                return Err(err);
+            }
+        };
+        let bus_state = socket.bus_state();
+        state.socket = Some(socket);
+        Ok(BusOffRecovery::Recovered(bus_state?))
+    }
+
+    fn initialize(&self) -> Result<CanSocket<B>, CanError> {
+        self.builder
+            .open_with_backend(self.backend.clone())
+            .map_err(into_can_error)
+    }
+
+    /// Runs `op` on the socket, suspending the channel if the handle was lost.
+    fn with_socket<R, F>(&self, op: F) -> Result<R, CanError>
+    where
+        F: FnOnce(&CanSocket<B>) -> Result<R, CanError>,
+    {
+        if self.is_suspended() {
+            let due = Instant::now() >= self.state.borrow().next_attempt;
+            if !self.auto_reinitialize || !due || self.reinitialize().is_err() {
+                return Err(CanError::Initialize);
+            }
+        }
+
+        let mut state = self.state.borrow_mut();
+        let Some(socket) = state.socket.as_ref() else {
+            return Err(CanError::Initialize);
+        };
+        let result = op(socket);
+        if let Err(err) = &result
+            && err.is_handle_lost()
+        {
+            // dropping the socket uninitializes whatever is left of the channel
+            state.socket = None;
+            state.next_attempt = Instant::now() + self.retry_interval;
+            self.events.push(PowerEvent::Suspended(err.clone()));
+        }
+        result
+    }
+
+    fn recv_with<R, F>(&self, op: F) -> Result<R, CanError>
+    where
+        F: FnOnce(&CanSocket<B>) -> Result<R, CanError>,
+    {
+        match self.with_socket(op) {
+            Err(err) if err.is_handle_lost() => Err(CanError::QrcvEmpty),
+            result => result,
+        }
+    }
+
+    /// Like [recv_with](Self::recv_with) for an `op` waiting up to `timeout`. While the channel
+    /// is suspended, waits until the next reinitialization attempt is due instead.
+    fn recv_timeout_with<R, F>(&self, timeout: Duration, op: F) -> Result<R, CanError>
+    where
+        F: FnOnce(&CanSocket<B>) -> Result<R, CanError>,
+    {
+        let result = self.recv_with(op);
+        if matches!(result, Err(CanError::QrcvEmpty)) && self.is_suspended() {
+            let due = if self.auto_reinitialize {
+                let next_attempt = self.state.borrow().next_attempt;
+                next_attempt.saturating_duration_since(Instant::now())
+            } else {
+                timeout
+            };
+            sleep(due.min(timeout));
+        }
+        result
+    }
+}
+
+impl<B: Backend + Clone> RecvCan for ResumingSocket<B> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        self.recv_with(|socket| socket.recv())
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.recv_with(|socket| socket.recv_frame())
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        self.recv_with(|socket| socket.recv_with_host_time())
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        self.recv_timeout_with(timeout, |socket| socket.recv_timeout(timeout))
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        match self.recv_timeout_with(timeout, |socket| socket.wait_readable(timeout)) {
+            Err(CanError::QrcvEmpty) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl<B: Backend + Clone> RecvCanFd for ResumingSocket<B> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        self.recv_with(|socket| socket.recv_fd())
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.recv_with(|socket| socket.recv_fd_frame())
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        self.recv_with(|socket| socket.recv_fd_with_host_time())
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        self.recv_timeout_with(timeout, |socket| socket.recv_fd_timeout(timeout))
+    }
+}
+
+impl<B: Backend + Clone> SendCan for ResumingSocket<B> {
+    fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        self.with_socket(|socket| socket.send(frame))
+    }
+}
+
+impl<B: Backend + Clone> SendCanFd for ResumingSocket<B> {
+    fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
+        self.with_socket(|socket| socket.send_fd(frame))
+    }
+}
+
+/// The configuration was validated when the socket was opened, so only the driver can reject
+/// it later.
+fn into_can_error(err: BuilderError) -> CanError {
+    match err {
+        BuilderError::Can(err) => err,
+        _ => CanError::IllParamVal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::UsbBus;
+    use crate::socket::{MessageType, MockBackend};
+
+    #[test]
+    fn lost_handle_suspends() {
+        let backend = MockBackend::new();
+        let channel = UsbBus::USB1.channel();
+        let builder = CanSocketBuilder::new(UsbBus::USB1)
+            .with_baudrate(Baudrate::Baud500K)
+            .with_filter(0x100, 0x1FF, MessageType::Standard);
+        let socket = ResumingSocket::open_with_backend(builder, backend.clone())
+            .unwrap()
+            .with_retry_interval(Duration::from_secs(3600))
+            .with_event_capacity(1);
+        assert_eq!(backend.filters(channel).len(), 1);
+
+        backend.push_rx_error(channel, CanError::IllClient);
+        assert!(matches!(socket.recv(), Err(CanError::QrcvEmpty)));
+        assert!(socket.is_suspended());
+        assert!(matches!(
+            socket.poll_event(),
+            Some(PowerEvent::Suspended(CanError::IllClient))
+        ));
+        assert!(socket.poll_event().is_none());
+
+        // the next attempt is not due yet
+        assert!(matches!(
+            socket.send(CanFrame::default()),
+            Err(CanError::Initialize)
+        ));
+        assert!(socket.poll_event().is_none());
+
+        // the filter is configured again
+        socket.reinitialize().unwrap();
+        assert!(matches!(socket.poll_event(), Some(PowerEvent::Resumed)));
+        let filters = backend.filters(channel);
+        assert!(filters.len() == 2 && filters[0] == filters[1]);
+        socket.send(CanFrame::default()).unwrap();
+        assert_eq!(backend.sent(channel).len(), 1);
+    }
+
+    #[test]
+    fn timeouts_wait_on_the_socket() {
+        let backend = MockBackend::new();
+        let channel = UsbBus::USB1.channel();
+        let builder = CanSocketBuilder::new(UsbBus::USB1).with_baudrate(Baudrate::Baud500K);
+        let socket = ResumingSocket::open_with_backend(builder, backend.clone())
+            .unwrap()
+            .with_retry_interval(Duration::from_millis(20));
+
+        let frame = CanFrame::new(0x100, MessageType::Standard, &[1]).unwrap();
+        backend.push_rx(channel, frame, Timestamp::from_micros(1));
+        let received = socket.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(received.0, frame);
+
+        // a suspended channel waits until the next attempt instead of returning at once
+        backend.push_rx_error(channel, CanError::IllHw);
+        let start = Instant::now();
+        assert!(matches!(
+            socket.recv_timeout(Duration::from_secs(1)),
+            Err(CanError::QrcvEmpty)
+        ));
+        let waited = start.elapsed();
+        assert!(socket.is_suspended());
+        assert!(waited >= Duration::from_millis(20) && waited < Duration::from_secs(1));
+
+        backend.push_rx(channel, frame, Timestamp::from_micros(2));
+        assert!(socket.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(!socket.is_suspended());
+    }
+}
@@ -0,0 +1,107 @@
+//! Serde support for frames and timestamps.
+//!
+//! Frames are written as structs of their ID, type flags and data, e.g.
+//! `{"id":291,"extended":false,"remote":false,"data":[222,173]}`, so that they stay readable in
+//! logs and configuration files. The error, status and echo flags of received frames are not
+//! kept. Timestamps are written as device time in microseconds.
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType, Timestamp};
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+struct CanFrameRepr {
+    id: u32,
+    extended: bool,
+    #[serde(default)]
+    remote: bool,
+    /// `dlc` zero bytes for remote frames.
+    data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanFdFrameRepr {
+    id: u32,
+    extended: bool,
+    fd: bool,
+    brs: bool,
+    #[serde(default)]
+    esi: bool,
+    data: Vec<u8>,
+}
+
+fn msg_type(extended: bool) -> MessageType {
+    if extended {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    }
+}
+
+impl Serialize for CanFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CanFrameRepr {
+            id: self.can_id(),
+            extended: self.is_extended_frame(),
+            remote: self.is_remote_frame(),
+            data: self.data().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CanFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = CanFrameRepr::deserialize(deserializer)?;
+        let msg_type = msg_type(repr.extended);
+        let frame = if repr.remote {
+            let dlc = u8::try_from(repr.data.len()).map_err(D::Error::custom)?;
+            CanFrame::new_remote(repr.id, msg_type, dlc)
+        } else {
+            CanFrame::new(repr.id, msg_type, &repr.data)
+        };
+        frame.map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for CanFdFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CanFdFrameRepr {
+            id: self.can_id(),
+            extended: self.is_extended_frame(),
+            fd: self.is_fd_frame(),
+            brs: self.is_brs(),
+            esi: self.is_esi(),
+            data: self.data().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CanFdFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = CanFdFrameRepr::deserialize(deserializer)?;
+        CanFdFrame::new(
+            repr.id,
+            msg_type(repr.extended),
+            &repr.data,
+            repr.fd,
+            repr.brs,
+        )
+        .map(|frame| frame.with_esi(repr.esi))
+        .map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_micros())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Timestamp::from_micros)
+    }
+}
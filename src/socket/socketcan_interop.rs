@@ -0,0 +1,167 @@
+//! Conversions between the frames of this crate and those of the `socketcan` crate, e.g. to
+//! bridge a SocketCAN interface and a PCAN channel.
+
+use crate::socket::{CanFdFrame, CanFrame, FrameConstructionError, MessageType};
+
+use socketcan::{CanDataFrame, CanRemoteFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum SocketCanConversionError {
+    /// Error frames carry different information in PCAN and SocketCAN.
+    ErrorFrame,
+    /// PCAN status frames have no SocketCAN counterpart.
+    StatusFrame,
+    /// A classic frame received through the CAN FD API.
+    NotFd,
+    Frame(FrameConstructionError),
+}
+
+impl fmt::Display for SocketCanConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketCanConversionError::ErrorFrame => write!(f, "error frames cannot be converted"),
+            SocketCanConversionError::StatusFrame => {
+                write!(f, "status frames cannot be converted")
+            }
+            SocketCanConversionError::NotFd => write!(f, "frame is not a CAN FD frame"),
+            SocketCanConversionError::Frame(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SocketCanConversionError {}
+
+impl From<FrameConstructionError> for SocketCanConversionError {
+    fn from(value: FrameConstructionError) -> Self {
+        SocketCanConversionError::Frame(value)
+    }
+}
+
+fn split_id(id: Id) -> (u32, MessageType) {
+    match id {
+        Id::Standard(id) => (id.as_raw() as u32, MessageType::Standard),
+        Id::Extended(id) => (id.as_raw(), MessageType::Extended),
+    }
+}
+
+fn join_id(can_id: u32, extended: bool) -> Id {
+    // the IDs of this crate are masked to their valid range on construction
+    if extended {
+        Id::Extended(ExtendedId::new(can_id).expect("extended ID out of range"))
+    } else {
+        Id::Standard(StandardId::new(can_id as u16).expect("standard ID out of range"))
+    }
+}
+
+impl From<&CanDataFrame> for CanFrame {
+    fn from(value: &CanDataFrame) -> Self {
+        let (can_id, msg_type) = split_id(value.id());
+        CanFrame::new(can_id, msg_type, value.data()).expect("classic frame data fits")
+    }
+}
+
+impl From<&CanRemoteFrame> for CanFrame {
+    fn from(value: &CanRemoteFrame) -> Self {
+        let (can_id, msg_type) = split_id(value.id());
+        CanFrame::new_remote(can_id, msg_type, value.dlc() as u8).expect("classic frame DLC fits")
+    }
+}
+
+impl TryFrom<&socketcan::CanFrame> for CanFrame {
+    type Error = SocketCanConversionError;
+
+    fn try_from(value: &socketcan::CanFrame) -> Result<Self, SocketCanConversionError> {
+        match value {
+            socketcan::CanFrame::Data(frame) => Ok(frame.into()),
+            socketcan::CanFrame::Remote(frame) => Ok(frame.into()),
+            socketcan::CanFrame::Error(_) => Err(SocketCanConversionError::ErrorFrame),
+        }
+    }
+}
+
+impl TryFrom<&CanFrame> for socketcan::CanFrame {
+    type Error = SocketCanConversionError;
+
+    fn try_from(value: &CanFrame) -> Result<Self, SocketCanConversionError> {
+        if value.is_error_frame() {
+            return Err(SocketCanConversionError::ErrorFrame);
+        }
+        if value.is_status_frame() {
+            return Err(SocketCanConversionError::StatusFrame);
+        }
+        let id = join_id(value.can_id(), value.is_extended_frame());
+        let frame = if value.is_remote_frame() {
+            CanRemoteFrame::new_remote(id, value.dlc() as usize).map(socketcan::CanFrame::Remote)
+        } else {
+            CanDataFrame::new(id, value.data()).map(socketcan::CanFrame::Data)
+        };
+        Ok(frame.expect("classic frame data fits"))
+    }
+}
+
+impl TryFrom<&socketcan::CanFdFrame> for CanFdFrame {
+    type Error = SocketCanConversionError;
+
+    fn try_from(value: &socketcan::CanFdFrame) -> Result<Self, SocketCanConversionError> {
+        let (can_id, msg_type) = split_id(value.id());
+        Ok(CanFdFrame::new(
+            can_id,
+            msg_type,
+            value.data(),
+            true,
+            value.is_brs(),
+        )?
+        .with_esi(value.is_esi()))
+    }
+}
+
+impl TryFrom<&CanFdFrame> for socketcan::CanFdFrame {
+    type Error = SocketCanConversionError;
+
+    fn try_from(value: &CanFdFrame) -> Result<Self, SocketCanConversionError> {
+        if value.is_error_frame() {
+            return Err(SocketCanConversionError::ErrorFrame);
+        }
+        if value.is_status_frame() {
+            return Err(SocketCanConversionError::StatusFrame);
+        }
+        if !value.is_fd_frame() {
+            return Err(SocketCanConversionError::NotFd);
+        }
+        let id = join_id(value.can_id(), value.is_extended_frame());
+        let mut frame = socketcan::CanFdFrame::new(id, value.data()).expect("CAN FD data fits");
+        frame.set_brs(value.is_brs());
+        frame.set_esi(value.is_esi());
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let classic = CanFrame::new(0x1234, MessageType::Extended, &[1, 2, 3]).unwrap();
+        let converted = socketcan::CanFrame::try_from(&classic).unwrap();
+        assert!(matches!(converted, socketcan::CanFrame::Data(_)));
+        assert_eq!(CanFrame::try_from(&converted).unwrap(), classic);
+
+        let remote = CanFrame::new_remote(0x12, MessageType::Standard, 4).unwrap();
+        let converted = socketcan::CanFrame::try_from(&remote).unwrap();
+        assert!(matches!(converted, socketcan::CanFrame::Remote(_)));
+        assert_eq!(CanFrame::try_from(&converted).unwrap(), remote);
+
+        let fd = CanFdFrame::new(0x7FF, MessageType::Standard, &[0xAA; 12], true, true).unwrap();
+        let converted = socketcan::CanFdFrame::try_from(&fd).unwrap();
+        assert!(converted.is_brs());
+        assert_eq!(CanFdFrame::try_from(&converted).unwrap(), fd);
+
+        let classic_fd = CanFdFrame::new(0x7FF, MessageType::Standard, &[1], false, false).unwrap();
+        assert_eq!(
+            socketcan::CanFdFrame::try_from(&classic_fd),
+            Err(SocketCanConversionError::NotFd)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Bus status and error counters.
+//!
+//! The current [BusState] is queried with [BusStatus::bus_state]. With `PEAK_ALLOW_STATUS_FRAMES`
+//! the driver also reports changes of the bus status as frames flagged with
+//! `PEAK_MESSAGE_STATUS`. Their 4 data bytes hold the `PEAK_ERROR_*` status bits in big endian
+//! byte order.
+//!
+//! PCAN-Basic has no parameter to read the error counters on demand, they are reported in the
+//! error frames delivered with `PEAK_ALLOW_ERROR_FRAMES` and decoded with
+//! [CanFrame::error_counters].
+
+use crate::channel::Channel;
+use crate::error::CanError;
+use crate::peak_can;
+use crate::socket::CanFrame;
+
+/// Fault confinement state of the CAN controller.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BusState {
+    ErrorActive,
+    /// An error counter exceeded the warning limit of 96.
+    ErrorWarning,
+    /// An error counter exceeded 127, the controller only sends passive error flags.
+    ErrorPassive,
+    BusOff,
+}
+
+impl BusState {
+    /// The most severe state in the `PEAK_ERROR_*` bits of `status`.
+    pub(crate) fn from_status(status: u32) -> BusState {
+        if status & peak_can::PEAK_ERROR_BUSOFF != 0 {
+            BusState::BusOff
+        } else if status & peak_can::PEAK_ERROR_BUSPASSIVE != 0 {
+            BusState::ErrorPassive
+        } else if status & (peak_can::PEAK_ERROR_BUSWARNING | peak_can::PEAK_ERROR_BUSLIGHT) != 0 {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BusStatusFrame {
+    pub bus_state: BusState,
+    /// The controller lost frames because they were not read fast enough.
+    pub overrun: bool,
+    /// The receive queue of the driver overflowed.
+    pub receive_queue_overrun: bool,
+    /// The transmit queue of the driver is full.
+    pub transmit_queue_full: bool,
+    /// The raw `PEAK_ERROR_*` status bits.
+    pub status: u32,
+}
+
+impl BusStatusFrame {
+    /// Decodes `frame`, returns `None` if it is not a status frame.
+    pub fn decode(frame: &CanFrame) -> Option<BusStatusFrame> {
+        if !frame.is_status_frame() {
+            return None;
+        }
+        let data: [u8; 4] = frame.data().get(..4)?.try_into().ok()?;
+        let status = u32::from_be_bytes(data);
+
+        Some(BusStatusFrame {
+            bus_state: BusState::from_status(status),
+            overrun: status & peak_can::PEAK_ERROR_OVERRUN != 0,
+            receive_queue_overrun: status & peak_can::PEAK_ERROR_QOVERRUN != 0,
+            transmit_queue_full: status & peak_can::PEAK_ERROR_QXMTFULL != 0,
+            status,
+        })
+    }
+}
+
+/// Transmit and receive error counters of the CAN controller.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ErrorCounters {
+    pub tx: u8,
+    pub rx: u8,
+}
+
+impl ErrorCounters {
+    /// The bus state implied by the counters. Bus-off can't be told from the counters alone and
+    /// is reported as [BusState::ErrorPassive].
+    pub fn bus_state(&self) -> BusState {
+        let max = self.tx.max(self.rx);
+        if max > 127 {
+            BusState::ErrorPassive
+        } else if max >= 96 {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        }
+    }
+
+    /// Decodes the counters of an error frame: byte 2 holds the receive and byte 3 the
+    /// transmit error counter.
+    pub(crate) fn decode(frame: &CanFrame) -> Option<ErrorCounters> {
+        if !frame.is_error_frame() {
+            return None;
+        }
+        match frame.data() {
+            [_, _, rx, tx, ..] => Some(ErrorCounters { tx: *tx, rx: *rx }),
+            _ => None,
+        }
+    }
+}
+
+/* BusStatus trait */
+
+pub(crate) trait HasBusStatus {}
+
+pub trait BusStatus {
+    fn bus_state(&self) -> Result<BusState, CanError>;
+}
+
+impl<T: HasBusStatus + Channel> BusStatus for T {
+    fn bus_state(&self) -> Result<BusState, CanError> {
+        self.backend().bus_state(self.channel())
+    }
+}
@@ -0,0 +1,194 @@
+//! Frame transformations on the send and receive path, e.g. to encrypt or obfuscate payloads
+//! of proprietary links.
+
+use crate::error::CanError;
+use crate::socket::{CanFdFrame, CanFrame, RecvCan, RecvCanFd, SendCan, SendCanFd, Timestamp};
+
+use std::time::{Duration, Instant};
+
+/// A pair of hooks applied to every sent (`outbound`) and received (`inbound`) frame. CAN FD
+/// frames pass unchanged unless the FD hooks are implemented.
+pub trait FrameTransform {
+    fn outbound(&self, frame: CanFrame) -> CanFrame;
+    fn inbound(&self, frame: CanFrame) -> CanFrame;
+
+    fn outbound_fd(&self, frame: CanFdFrame) -> CanFdFrame {
+        frame
+    }
+
+    fn inbound_fd(&self, frame: CanFdFrame) -> CanFdFrame {
+        frame
+    }
+}
+
+impl<T: FrameTransform + ?Sized> FrameTransform for Box<T> {
+    fn outbound(&self, frame: CanFrame) -> CanFrame {
+        (**self).outbound(frame)
+    }
+
+    fn inbound(&self, frame: CanFrame) -> CanFrame {
+        (**self).inbound(frame)
+    }
+
+    fn outbound_fd(&self, frame: CanFdFrame) -> CanFdFrame {
+        (**self).outbound_fd(frame)
+    }
+
+    fn inbound_fd(&self, frame: CanFdFrame) -> CanFdFrame {
+        (**self).inbound_fd(frame)
+    }
+}
+
+/// A socket wrapper that applies a [FrameTransform]. Components that take a socket by its
+/// [RecvCan] and [SendCan] traits run unchanged on a transformed link.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, FrameTransform, TransformSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::payload::Payload;
+/// struct Xor(u8);
+///
+/// impl FrameTransform for Xor {
+///     fn outbound(&self, mut frame: CanFrame) -> CanFrame {
+///         frame.payload_mut().iter_mut().for_each(|b| *b ^= self.0);
+///         frame
+///     }
+///
+///     fn inbound(&self, frame: CanFrame) -> CanFrame {
+///         self.outbound(frame)
+///     }
+/// }
+///
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let socket = TransformSocket::new(socket, Xor(0x5A));
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct TransformSocket<S, T> {
+    socket: S,
+    transform: T,
+}
+
+impl<S, T: FrameTransform> TransformSocket<S, T> {
+    pub fn new(socket: S, transform: T) -> Self {
+        TransformSocket { socket, transform }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    pub fn transform(&self) -> &T {
+        &self.transform
+    }
+
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+}
+
+impl<S: RecvCan, T: FrameTransform> RecvCan for TransformSocket<S, T> {
+    fn recv(&self) -> Result<(CanFrame, Timestamp), CanError> {
+        let (frame, timestamp) = self.socket.recv()?;
+        Ok((self.transform.inbound(frame), timestamp))
+    }
+
+    fn recv_frame(&self) -> Result<CanFrame, CanError> {
+        self.socket
+            .recv_frame()
+            .map(|frame| self.transform.inbound(frame))
+    }
+
+    fn recv_with_host_time(&self) -> Result<(CanFrame, Timestamp, Instant), CanError> {
+        let (frame, timestamp, host_time) = self.socket.recv_with_host_time()?;
+        Ok((self.transform.inbound(frame), timestamp, host_time))
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<(CanFrame, Timestamp), CanError> {
+        let (frame, timestamp) = self.socket.recv_timeout(timeout)?;
+        Ok((self.transform.inbound(frame), timestamp))
+    }
+
+    fn wait_readable(&self, timeout: Duration) -> Result<(), CanError> {
+        self.socket.wait_readable(timeout)
+    }
+}
+
+impl<S: RecvCanFd, T: FrameTransform> RecvCanFd for TransformSocket<S, T> {
+    fn recv_fd(&self) -> Result<(CanFdFrame, u64), CanError> {
+        let (frame, timestamp) = self.socket.recv_fd()?;
+        Ok((self.transform.inbound_fd(frame), timestamp))
+    }
+
+    fn recv_fd_frame(&self) -> Result<CanFdFrame, CanError> {
+        self.socket
+            .recv_fd_frame()
+            .map(|frame| self.transform.inbound_fd(frame))
+    }
+
+    fn recv_fd_with_host_time(&self) -> Result<(CanFdFrame, u64, Instant), CanError> {
+        let (frame, timestamp, host_time) = self.socket.recv_fd_with_host_time()?;
+        Ok((self.transform.inbound_fd(frame), timestamp, host_time))
+    }
+
+    fn recv_fd_timeout(&self, timeout: Duration) -> Result<(CanFdFrame, u64), CanError> {
+        let (frame, timestamp) = self.socket.recv_fd_timeout(timeout)?;
+        Ok((self.transform.inbound_fd(frame), timestamp))
+    }
+}
+
+impl<S: SendCan, T: FrameTransform> SendCan for TransformSocket<S, T> {
+    fn send(&self, frame: CanFrame) -> Result<(), CanError> {
+        self.socket.send(self.transform.outbound(frame))
+    }
+}
+
+impl<S: SendCanFd, T: FrameTransform> SendCanFd for TransformSocket<S, T> {
+    fn send_fd(&self, frame: CanFdFrame) -> Result<(), CanError> {
+        self.socket.send_fd(self.transform.outbound_fd(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::socket::{MessageType, MockCanSocket};
+
+    struct AddOne;
+
+    impl FrameTransform for AddOne {
+        fn outbound(&self, mut frame: CanFrame) -> CanFrame {
+            frame
+                .payload_mut()
+                .iter_mut()
+                .for_each(|b| *b = b.wrapping_add(1));
+            frame
+        }
+
+        fn inbound(&self, mut frame: CanFrame) -> CanFrame {
+            frame
+                .payload_mut()
+                .iter_mut()
+                .for_each(|b| *b = b.wrapping_sub(1));
+            frame
+        }
+    }
+
+    #[test]
+    fn transform_both_directions() {
+        let mock = MockCanSocket::new();
+        let socket =
+            TransformSocket::new(mock.clone(), Box::new(AddOne) as Box<dyn FrameTransform>);
+        let frame = CanFrame::new(0x100, MessageType::Standard, &[0x00, 0xFF]).unwrap();
+
+        socket.send(frame).unwrap();
+        let sent = mock.backend().take_sent(mock.channel());
+        assert_eq!(sent[0].data(), &[0x01, 0x00]);
+
+        mock.backend()
+            .push_rx(mock.channel(), sent[0], Timestamp::default());
+        assert_eq!(socket.recv_frame().unwrap().data(), &[0x00, 0xFF]);
+    }
+}
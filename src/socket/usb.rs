@@ -2,18 +2,17 @@
 //!
 //!
 
-use std::ffi::CString;
-
 use crate::bus::UsbBus;
 use crate::channel::Channel;
 use crate::df::{
     HasAcceptanceFilter11Bit, HasAcceptanceFilter29Bit, HasAllowEchoFrames, HasAllowErrorFrames,
-    HasAllowRTRFrames, HasAllowStatusFrames, HasMessageFilter, HasReceiveStatus,
-    HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit, HasSetAllowEchoFrames,
-    HasSetAllowErrorFrames, HasSetAllowRTRFrames, HasSetAllowStatusFrames, HasSetMessageFilter,
-    HasSetReceiveStatus,
+    HasAllowRTRFrames, HasAllowStatusFrames, HasFilterMessages, HasMessageFilter,
+    HasReceiveStatus, HasSetAcceptanceFilter11Bit, HasSetAcceptanceFilter29Bit,
+    HasSetAllowEchoFrames, HasSetAllowErrorFrames, HasSetAllowRTRFrames, HasSetAllowStatusFrames,
+    HasSetMessageFilter, HasSetReceiveStatus,
 };
-use crate::error::{CanError, CanOkError};
+use crate::discover::{self, ChannelBus, ChannelInfo};
+use crate::error::CanError;
 use crate::hw::{
     HasChannelIdentifying, HasControllerNumber, HasDeviceId, HasDevicePartNumber, HasHardwareName,
     HasSetControllerNumber, HasSetDeviceId,
@@ -26,8 +25,14 @@ use crate::io::{
     HasAnalogValue, HasDigitalConfiguration, HasDigitalValue, HasSetDigitalClear,
     HasSetDigitalConfiguration, HasSetDigitalSet, HasSetDigitalValue,
 };
-use crate::peak_lib;
-use crate::socket::{Baudrate, CanBitTiming, CanFdBitTiming, HasRecvCan, HasRecvCanFd, HasSendCan, HasSendCanFd, Socket};
+use crate::parameter::HasParameters;
+use crate::socket::event::EventSlot;
+use crate::socket::status::HasBusStatus;
+use crate::socket::{
+    build_timing_string, Backend, Baudrate, CanBitTiming, CanFdBitTiming, HasRecvCan, HasRecvCanFd,
+    HasSendCan,
+    HasSendCanFd, PcanBackend, Socket,
+};
 use crate::special::{
     HasBusOffAutoreset, HasFiveVoltsPower, HasInterframeDelay, HasListenOnly,
     HasSetBusOffAutoreset, HasSetFiveVoltsPower, HasSetInterframeDelay, HasSetListenOnly,
@@ -37,40 +42,28 @@ use crate::trace::{
     HasTraceConfigure, HasTraceLocation, HasTraceSize, HasTraceStatus,
 };
 
-/// CAN FD controller clock frequency in Hz for PEAK USB devices.
-/// 
-/// This represents the base clock frequency (80 MHz) used by the CAN FD controller
-/// on PEAK USB hardware. This value is used when configuring custom bit timing
-/// parameters for CAN FD communication.
-const CANFD_CLOCK_HZ: u32 = 80_000_000;
+fn usb_bus_with_device_id(channels: &[ChannelInfo], id: u32) -> Result<UsbBus, CanError> {
+    channels
+        .iter()
+        .find_map(|info| match info.bus {
+            ChannelBus::Usb(bus) if info.device_id == id => Some(bus),
+            _ => None,
+        })
+        .ok_or(CanError::DeviceNotFound(id))
+}
 
 /// Helper function to calculate BTR0BTR1 value
-fn calculate_btr0btr1(timing: &CanBitTiming) -> u16 {
+pub(crate) fn calculate_btr0btr1(timing: &CanBitTiming) -> u16 {
     ((((timing.tseg2 - 1) & 0x07) as u16) << 4)
         | (((timing.tseg1 - 1) & 0x0F) as u16)
         | ((((timing.prescaler - 1) & 0x3F) as u16) << 8)
         | ((((timing.sjw - 1) & 0x03) as u16) << 14)
 }
 
-/// Helper function to build timing string
-fn build_timing_string(timing: &CanFdBitTiming) -> String {
-    format!(
-        "f_clock={},nom_brp={},nom_tseg1={},nom_tseg2={},nom_sjw={},data_brp={},data_tseg1={},data_tseg2={},data_sjw={}",
-        CANFD_CLOCK_HZ,
-        timing.nom_prescaler,
-        timing.nom_tseg1,
-        timing.nom_tseg2,
-        timing.nom_sjw,
-        timing.data_prescaler,
-        timing.data_tseg1,
-        timing.data_tseg2,
-        timing.data_sjw,
-    )
-}
-
 #[derive(Debug, PartialEq)]
 pub struct UsbCanSocket {
     handle: u16,
+    event: EventSlot,
 }
 
 impl UsbCanSocket {
@@ -92,18 +85,37 @@ impl UsbCanSocket {
     /// ```
     pub fn open(bus: UsbBus, baud: Baudrate) -> Result<UsbCanSocket, CanError> {
         let handle = bus.into();
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, baud.into(), 0, 0, 0) };
+        PcanBackend.initialize(handle, baud.into())?;
+        Ok(UsbCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
+    }
 
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(UsbCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+    /// Opens the attached USB channel with the device ID `id`, set before with
+    /// [SetDeviceId](crate::hw::SetDeviceId), so that the channel does not depend on the order
+    /// the adapters were plugged in. [CanError::DeviceNotFound] if no attached USB channel has
+    /// the ID.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use peak_can::socket::usb::UsbCanSocket;
+    /// # use peak_can::socket::Baudrate;
+    /// let socket = UsbCanSocket::open_by_device_id(42, Baudrate::Baud500K)?;
+    /// # Ok::<(), peak_can::error::CanError>(())
+    /// ```
+    pub fn open_by_device_id(id: u32, baud: Baudrate) -> Result<UsbCanSocket, CanError> {
+        let bus = usb_bus_with_device_id(&discover::attached_channels()?, id)?;
+        Self::open(bus, baud)
     }
 
     pub fn open_with_usb_bus(bus: UsbBus) -> UsbCanSocket {
         let handle = bus.into();
-        UsbCanSocket { handle }
+        UsbCanSocket {
+            handle,
+            event: EventSlot::default(),
+        }
     }
 
     /// Opens a CAN socket with custom bit timing.
@@ -123,13 +135,11 @@ impl UsbCanSocket {
     pub fn open_with_timing(bus: UsbBus, timing: &CanBitTiming) -> Result<UsbCanSocket, CanError> {
         let handle = bus.into();
         let btr0btr1 = calculate_btr0btr1(timing);
-        let code = unsafe { peak_lib()?.CAN_Initialize(handle, btr0btr1, 0, 0, 0) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(UsbCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize(handle, btr0btr1)?;
+        Ok(UsbCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
     }
 
     /// Opens a CAN FD socket with custom timing for nominal and data phases.
@@ -149,19 +159,11 @@ impl UsbCanSocket {
     /// ```
     pub fn open_fd_with_timing(bus: UsbBus, timing: &CanFdBitTiming) -> Result<UsbCanSocket, CanError> {
         let handle = bus.into();
-        let timing_str = build_timing_string(timing);
-
-        let mut timing_bytes = CString::new(timing_str)
-            .map_err(|_| CanError::Unknown)?
-            .into_bytes_with_nul();
-
-        let code = unsafe { peak_lib()?.CAN_InitializeFD(handle, timing_bytes.as_mut_ptr().cast()) };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(UsbCanSocket { handle }),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        PcanBackend.initialize_fd(handle, &build_timing_string(timing))?;
+        Ok(UsbCanSocket {
+            handle,
+            event: EventSlot::default(),
+        })
     }
 }
 
@@ -169,10 +171,8 @@ impl UsbCanSocket {
 
 impl Drop for UsbCanSocket {
     fn drop(&mut self) {
-        let Ok(peak_lib) = peak_lib() else {
-            return;
-        };
-        unsafe { peak_lib.CAN_Uninitialize(self.handle) };
+        self.event.clear();
+        let _ = PcanBackend.uninitialize(self.handle);
     }
 }
 
@@ -182,6 +182,10 @@ impl Socket for UsbCanSocket {
     fn handle(&self) -> u16 {
         self.handle
     }
+
+    fn event_slot(&self) -> &EventSlot {
+        &self.event
+    }
 }
 
 /* Channel trait implementation */
@@ -200,6 +204,14 @@ impl HasSendCan for UsbCanSocket {}
 impl HasRecvCanFd for UsbCanSocket {}
 impl HasSendCanFd for UsbCanSocket {}
 
+/* TYPED PARAMETERS */
+
+impl HasParameters for UsbCanSocket {}
+
+/* BUS STATUS */
+
+impl HasBusStatus for UsbCanSocket {}
+
 /* HARDWARE IDENTIFICATION */
 
 impl HasChannelIdentifying for UsbCanSocket {}
@@ -247,6 +259,8 @@ impl HasSetInterframeDelay for UsbCanSocket {}
 impl HasMessageFilter for UsbCanSocket {}
 impl HasSetMessageFilter for UsbCanSocket {}
 
+impl HasFilterMessages for UsbCanSocket {}
+
 impl HasReceiveStatus for UsbCanSocket {}
 impl HasSetReceiveStatus for UsbCanSocket {}
 
@@ -298,6 +312,34 @@ impl HasAnalogValue for UsbCanSocket {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bus::PciBus;
+    use crate::hw::ChannelConditionStatus;
+
+    #[test]
+    fn device_id_lookup() {
+        let info = |bus, device_id| ChannelInfo {
+            bus,
+            channel_handle: 0,
+            device_name: String::new(),
+            device_type: 0,
+            device_id,
+            controller_number: 0,
+            condition: ChannelConditionStatus::Available,
+            features: 0,
+        };
+        let channels = [
+            info(ChannelBus::Pci(PciBus::PCI1), 42),
+            info(ChannelBus::Usb(UsbBus::USB1), 7),
+            info(ChannelBus::Usb(UsbBus::USB2), 42),
+        ];
+        assert_eq!(usb_bus_with_device_id(&channels, 42).unwrap(), UsbBus::USB2);
+        assert_eq!(usb_bus_with_device_id(&channels, 7).unwrap(), UsbBus::USB1);
+        let err = usb_bus_with_device_id(&channels, 1).unwrap_err();
+        assert!(matches!(err, CanError::DeviceNotFound(1)));
+        assert_eq!(err.io_kind(), std::io::ErrorKind::NotFound);
+        assert!(!err.is_handle_lost());
+        assert_eq!(err.to_string(), "No attached channel has the device ID 1");
+    }
 
     #[test]
     fn btr0btr1_encoding() {
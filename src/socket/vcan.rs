@@ -0,0 +1,348 @@
+//! The in-process channels behind [VirtualBus](crate::bus::VirtualBus) handles.
+//!
+//! [VirtualBackend] serves the handles of these channels in-process instead of the driver. A
+//! frame written to a channel is queued at its linked peer if the peer is open, like on a bus
+//! with a single other node. The [FuzzPolicy] of a channel mutates or holds back the ISO-TP
+//! frames it writes before they are queued.
+
+use crate::bus::FuzzPolicy;
+use crate::error::CanError;
+use crate::peak_can;
+use crate::queue::TxFrame;
+use crate::socket::{Backend, BusState, CanFdFrame, CanFrame, ReceiveEvent, Timestamp};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Handle of the first virtual channel, above all handles of the driver.
+pub(crate) const FIRST_HANDLE: u16 = 0xFE01;
+pub(crate) const CHANNELS: u16 = 8;
+
+#[derive(Default)]
+struct VirtualChannel {
+    peer: Option<u16>,
+    /// `Some(fd)` while initialized.
+    open: Option<bool>,
+    /// Queued frames with the time they are received at.
+    rx: VecDeque<(TxFrame, Instant)>,
+    fuzz: Option<FuzzPolicy>,
+}
+
+struct Registry {
+    channels: Mutex<HashMap<u16, VirtualChannel>>,
+    queued: Condvar,
+    epoch: Instant,
+}
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(|| Registry {
+    channels: Mutex::new(HashMap::new()),
+    queued: Condvar::new(),
+    epoch: Instant::now(),
+});
+
+fn lock() -> MutexGuard<'static, HashMap<u16, VirtualChannel>> {
+    // a panicking test thread must not poison the channels of all other tests
+    REGISTRY.channels.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn is_virtual(handle: u16) -> bool {
+    (FIRST_HANDLE..FIRST_HANDLE + CHANNELS).contains(&handle)
+}
+
+/// Links `a` and `b`, [CanError::HwInUse] if one of them is linked already.
+pub(crate) fn link(a: u16, b: u16) -> Result<(), CanError> {
+    if a == b || !is_virtual(a) || !is_virtual(b) {
+        return Err(CanError::IllHw);
+    }
+    let mut channels = lock();
+    if channels.get(&a).is_some_and(|ch| ch.peer.is_some())
+        || channels.get(&b).is_some_and(|ch| ch.peer.is_some())
+    {
+        return Err(CanError::HwInUse);
+    }
+    channels.entry(a).or_default().peer = Some(b);
+    channels.entry(b).or_default().peer = Some(a);
+    Ok(())
+}
+
+/// Links the first two neighbouring channels which are both unlinked.
+pub(crate) fn link_free_pair() -> Result<(u16, u16), CanError> {
+    let mut channels = lock();
+    let is_free = |channels: &HashMap<u16, VirtualChannel>, handle| {
+        channels.get(&handle).is_none_or(|ch| ch.peer.is_none())
+    };
+    let a = (FIRST_HANDLE..FIRST_HANDLE + CHANNELS)
+        .step_by(2)
+        .find(|&a| is_free(&channels, a) && is_free(&channels, a + 1))
+        .ok_or(CanError::Resource)?;
+    channels.entry(a).or_default().peer = Some(a + 1);
+    channels.entry(a + 1).or_default().peer = Some(a);
+    Ok((a, a + 1))
+}
+
+/// Unlinks `handle` and its peer and clears their fuzzing policies.
+pub(crate) fn unlink(handle: u16) {
+    let mut channels = lock();
+    let peer = channels.get_mut(&handle).and_then(|ch| {
+        ch.fuzz = None;
+        ch.peer.take()
+    });
+    if let Some(ch) = peer.and_then(|peer| channels.get_mut(&peer)) {
+        ch.fuzz = None;
+        ch.peer = None;
+    }
+}
+
+pub(crate) fn set_fuzz_policy(handle: u16, policy: Option<FuzzPolicy>) {
+    lock().entry(handle).or_default().fuzz = policy;
+}
+
+/// Applies `policy` to a frame written to the bus, returns how long the frame is held back.
+fn fuzz(policy: &FuzzPolicy, frame: &mut TxFrame) -> Duration {
+    let (id, data, len) = match frame {
+        TxFrame::Can(can) => (can.frame.ID, &mut can.frame.DATA[..], &mut can.frame.LEN),
+        TxFrame::CanFd(fd) => (fd.frame.ID, &mut fd.frame.DATA[..], &mut fd.frame.DLC),
+    };
+    if *len == 0 || policy.can_id.is_some_and(|can_id| can_id != id) {
+        return Duration::ZERO;
+    }
+    match data[0] & 0xF0 {
+        0x10 => {
+            if let Some(max) = policy.truncate_first_frame {
+                // lengths up to 8 bytes are their own DLC
+                *len = (*len).min(max.min(8));
+            }
+        }
+        0x20 => data[0] = 0x20 | (data[0].wrapping_add(policy.sequence_offset) & 0x0F),
+        0x30 => return policy.flow_control_delay,
+        _ => {}
+    }
+    Duration::ZERO
+}
+
+fn open(handle: u16, fd: bool) -> Result<(), CanError> {
+    if !is_virtual(handle) {
+        return Err(CanError::IllHw);
+    }
+    let mut channels = lock();
+    let ch = channels.entry(handle).or_default();
+    if ch.open.is_some() {
+        return Err(CanError::Initialize);
+    }
+    ch.open = Some(fd);
+    ch.rx.clear();
+    Ok(())
+}
+
+fn close(handle: u16) -> Result<(), CanError> {
+    let mut channels = lock();
+    let ch = channels.entry(handle).or_default();
+    ch.open.take().ok_or(CanError::Initialize)?;
+    ch.rx.clear();
+    Ok(())
+}
+
+/// Queues `frame` at the peer of `handle`, and at `handle` itself if the frame requests an echo.
+/// CAN FD frames are lost if the peer was opened without CAN FD.
+fn write(handle: u16, mut frame: TxFrame) -> Result<(), CanError> {
+    let mut channels = lock();
+    let ch = match channels.get_mut(&handle) {
+        Some(ch) if ch.open.is_some() => ch,
+        _ => return Err(CanError::Initialize),
+    };
+    let mut due = Instant::now();
+    if let Some(policy) = &ch.fuzz {
+        due += fuzz(policy, &mut frame);
+    }
+    let peer = ch.peer;
+    let echo = match &frame {
+        TxFrame::Can(can) => can.is_echo_frame(),
+        TxFrame::CanFd(fd) => fd.is_echo_frame(),
+    };
+    if echo {
+        ch.rx.push_back((frame, due));
+        REGISTRY.queued.notify_all();
+        // the other nodes receive a plain frame
+        match &mut frame {
+            TxFrame::Can(can) => can.set_echo(false),
+            TxFrame::CanFd(fd) => fd.set_echo(false),
+        }
+    }
+    if let Some(ch) = peer.and_then(|peer| channels.get_mut(&peer)) {
+        let fd_frame = matches!(frame, TxFrame::CanFd(fd) if fd.is_fd_frame() || fd.dlc() > 8);
+        match ch.open {
+            Some(fd) if fd || !fd_frame => {
+                ch.rx.push_back((frame, due));
+                REGISTRY.queued.notify_all();
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read(handle: u16) -> Result<(TxFrame, u64), CanError> {
+    let mut channels = lock();
+    let ch = match channels.get_mut(&handle) {
+        Some(ch) if ch.open.is_some() => ch,
+        _ => return Err(CanError::Initialize),
+    };
+    match ch.rx.front() {
+        Some((_, due)) if *due <= Instant::now() => {
+            let (frame, due) = ch.rx.pop_front().expect("front frame");
+            let micros = due.duration_since(REGISTRY.epoch).as_micros() as u64;
+            Ok((frame, micros))
+        }
+        _ => Err(CanError::QrcvEmpty),
+    }
+}
+
+fn write_can(handle: u16, frame: &CanFrame) -> Result<(), CanError> {
+    write(handle, TxFrame::Can(*frame))
+}
+
+fn write_fd(handle: u16, frame: &CanFdFrame) -> Result<(), CanError> {
+    write(handle, TxFrame::CanFd(*frame))
+}
+
+fn read_can(handle: u16) -> Result<(CanFrame, Timestamp), CanError> {
+    let (frame, micros) = read(handle)?;
+    let frame = match frame {
+        TxFrame::Can(frame) => frame,
+        // only classic frames reach a classic channel
+        TxFrame::CanFd(fd) => CanFrame {
+            frame: peak_can::TPEAKMsg {
+                ID: fd.frame.ID,
+                MSGTYPE: fd.frame.MSGTYPE,
+                LEN: fd.frame.DLC,
+                DATA: fd.frame.DATA[..8].try_into().unwrap(),
+            },
+        },
+    };
+    Ok((frame, Timestamp::from_micros(micros)))
+}
+
+fn read_fd(handle: u16) -> Result<(CanFdFrame, u64), CanError> {
+    let (frame, micros) = read(handle)?;
+    let frame = match frame {
+        TxFrame::Can(can) => {
+            let mut data = [0; 64];
+            data[..8].copy_from_slice(&can.frame.DATA);
+            CanFdFrame {
+                frame: peak_can::TPEAKMsgFD {
+                    ID: can.frame.ID,
+                    MSGTYPE: can.frame.MSGTYPE,
+                    DLC: can.frame.LEN,
+                    DATA: data,
+                },
+            }
+        }
+        TxFrame::CanFd(frame) => frame,
+    };
+    Ok((frame, micros))
+}
+
+/// Waits until a frame can be read at `handle` or `timeout` elapsed.
+pub(crate) fn wait(handle: u16, timeout: Duration) -> Result<(), CanError> {
+    let deadline = Instant::now() + timeout;
+    let mut channels = lock();
+    loop {
+        let now = Instant::now();
+        let wake = match channels.get(&handle).map(|ch| ch.rx.front()) {
+            None => return Ok(()),
+            Some(Some((_, due))) if *due <= now => return Ok(()),
+            // a held back frame
+            Some(Some((_, due))) => deadline.min(*due),
+            Some(None) => deadline,
+        };
+        if now >= deadline {
+            return Ok(());
+        }
+        channels = REGISTRY
+            .queued
+            .wait_timeout(channels, wake - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
+
+/// The backend of [VirtualBus](crate::bus::VirtualBus) channels. They have no parameters, a
+/// virtual bus is always error active. [PcanBackend](crate::socket::PcanBackend) hands the
+/// virtual handles to this backend, so [CanSocket::open](crate::socket::CanSocket::open) opens
+/// them as well.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::bus::VirtualPair;
+/// # use peak_can::socket::{Baudrate, CanFrame, CanSocket, MessageType, RecvCan, SendCan};
+/// # use peak_can::socket::VirtualBackend;
+/// # use std::time::Duration;
+/// let pair = VirtualPair::new()?;
+/// let a = CanSocket::open_with_backend(pair.a(), Baudrate::Baud500K, VirtualBackend)?;
+/// let b = CanSocket::open_with_backend(pair.b(), Baudrate::Baud500K, VirtualBackend)?;
+///
+/// a.send(CanFrame::new(0x123, MessageType::Standard, &[1, 2]).unwrap())?;
+/// let (frame, _) = b.recv_timeout(Duration::from_secs(1))?;
+/// assert_eq!(frame.can_id(), 0x123);
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VirtualBackend;
+
+impl Backend for VirtualBackend {
+    fn initialize(&self, channel: u16, _btr0btr1: u16) -> Result<(), CanError> {
+        open(channel, false)
+    }
+
+    fn initialize_fd(&self, channel: u16, _bitrate: &str) -> Result<(), CanError> {
+        open(channel, true)
+    }
+
+    fn uninitialize(&self, channel: u16) -> Result<(), CanError> {
+        close(channel)
+    }
+
+    fn read(&self, channel: u16) -> Result<(CanFrame, Timestamp), CanError> {
+        read_can(channel)
+    }
+
+    fn write(&self, channel: u16, frame: &CanFrame) -> Result<(), CanError> {
+        write_can(channel, frame)
+    }
+
+    fn read_fd(&self, channel: u16) -> Result<(CanFdFrame, u64), CanError> {
+        read_fd(channel)
+    }
+
+    fn write_fd(&self, channel: u16, frame: &CanFdFrame) -> Result<(), CanError> {
+        write_fd(channel, frame)
+    }
+
+    fn get_value(&self, _channel: u16, _parameter: u8, _data: &mut [u8]) -> Result<(), CanError> {
+        Err(CanError::IllParamType)
+    }
+
+    fn set_value(&self, _channel: u16, _parameter: u8, _data: &[u8]) -> Result<(), CanError> {
+        Err(CanError::IllParamType)
+    }
+
+    fn filter_messages(
+        &self,
+        _channel: u16,
+        _from_id: u32,
+        _to_id: u32,
+        _mode: u8,
+    ) -> Result<(), CanError> {
+        Err(CanError::IllParamType)
+    }
+
+    fn bus_state(&self, _channel: u16) -> Result<BusState, CanError> {
+        Ok(BusState::ErrorActive)
+    }
+
+    fn receive_event(&self, channel: u16) -> Result<ReceiveEvent, CanError> {
+        Ok(ReceiveEvent::virtual_channel(channel))
+    }
+}
@@ -0,0 +1,197 @@
+//! Strategies for waiting on an empty receive queue.
+
+use crate::error::CanError;
+use crate::socket::RecvCan;
+
+use std::hint::spin_loop;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How polling components wait while the receive queue is empty, trading CPU load for latency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitStrategy {
+    /// Polls again immediately. Lowest latency, keeps one core busy.
+    BusySpin,
+    /// Sleeps a fixed interval between polls.
+    Sleep(Duration),
+    /// Sleeps `min` after the first empty poll and doubles the interval on every further empty
+    /// poll up to `max`. Restarts at `min` once a frame was received.
+    Backoff { min: Duration, max: Duration },
+    /// Blocks on the receive event of the socket, see [RecvCan::wait_readable]. Low latency
+    /// without keeping a core busy, implementations without an event poll every millisecond.
+    Event,
+}
+
+/// Longest single wait on the receive event, so that a shutdown is noticed without a frame.
+const EVENT_WAIT: Duration = Duration::from_millis(100);
+
+impl Default for WaitStrategy {
+    fn default() -> Self {
+        WaitStrategy::Sleep(Duration::from_millis(1))
+    }
+}
+
+/// Wait state of a single polling loop.
+#[derive(Debug)]
+pub(crate) struct Waiter {
+    strategy: WaitStrategy,
+    interval: Option<Duration>,
+}
+
+impl Waiter {
+    pub(crate) fn new(strategy: WaitStrategy) -> Self {
+        Waiter {
+            strategy,
+            interval: None,
+        }
+    }
+
+    /// Waits once after an empty poll of `socket`, never past `deadline`.
+    pub(crate) fn wait<S: RecvCan + ?Sized>(&mut self, socket: &S, deadline: Option<Instant>) {
+        let interval = match self.strategy {
+            WaitStrategy::BusySpin => {
+                spin_loop();
+                return;
+            }
+            WaitStrategy::Sleep(interval) => interval,
+            WaitStrategy::Backoff { min, max } => {
+                let interval = self.interval.map_or(min, |i| (i * 2).min(max));
+                self.interval = Some(interval);
+                interval
+            }
+            WaitStrategy::Event => {
+                let timeout = deadline.map_or(EVENT_WAIT, |deadline| {
+                    deadline
+                        .saturating_duration_since(Instant::now())
+                        .min(EVENT_WAIT)
+                });
+                if socket.wait_readable(timeout).is_err() {
+                    // the error shows up again on the next read, don't spin until then
+                    sleep(timeout.min(Duration::from_millis(1)));
+                }
+                return;
+            }
+        };
+
+        match deadline {
+            Some(deadline) => {
+                sleep(interval.min(deadline.saturating_duration_since(Instant::now())))
+            }
+            None => sleep(interval),
+        }
+    }
+
+    /// Called after a frame was received.
+    pub(crate) fn reset(&mut self) {
+        self.interval = None;
+    }
+}
+
+/// Calls `read` until it returns anything but an empty receive queue, calling `wait` with the
+/// remaining time in between. Returns [CanError::QrcvEmpty] once `timeout` elapsed.
+pub(crate) fn read_timeout<R, F, W>(
+    timeout: Duration,
+    mut read: F,
+    mut wait: W,
+) -> Result<R, CanError>
+where
+    F: FnMut() -> Result<R, CanError>,
+    W: FnMut(Duration) -> Result<(), CanError>,
+{
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        match read() {
+            Err(CanError::QrcvEmpty) => {}
+            result => return result,
+        }
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::MAX,
+        };
+        if remaining.is_zero() {
+            return Err(CanError::QrcvEmpty);
+        }
+        wait(remaining)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::VirtualPair;
+    use crate::socket::{Baudrate, CanFrame, CanSocket, MessageType, MockCanSocket, SendCan};
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let strategy = WaitStrategy::Backoff {
+            min: Duration::from_micros(1),
+            max: Duration::from_micros(3),
+        };
+        let mut waiter = Waiter::new(strategy);
+        let socket = MockCanSocket::new();
+
+        waiter.wait(&socket, None);
+        assert_eq!(waiter.interval, Some(Duration::from_micros(1)));
+        waiter.wait(&socket, None);
+        assert_eq!(waiter.interval, Some(Duration::from_micros(2)));
+        waiter.wait(&socket, None);
+        assert_eq!(waiter.interval, Some(Duration::from_micros(3)));
+
+        waiter.reset();
+        waiter.wait(&socket, None);
+        assert_eq!(waiter.interval, Some(Duration::from_micros(1)));
+    }
+
+    #[test]
+    fn event_blocks_until_a_frame_is_queued() {
+        let pair = VirtualPair::new().unwrap();
+        let a = CanSocket::open(pair.a(), Baudrate::Baud500K).unwrap();
+        let b = CanSocket::open(pair.b(), Baudrate::Baud500K).unwrap();
+        let mut waiter = Waiter::new(WaitStrategy::Event);
+
+        let start = Instant::now();
+        waiter.wait(&b, Some(start + Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(15));
+
+        a.send(CanFrame::new(0x123, MessageType::Standard, &[]).unwrap())
+            .unwrap();
+        let start = Instant::now();
+        waiter.wait(&b, None);
+        assert!(start.elapsed() < EVENT_WAIT);
+    }
+
+    #[test]
+    fn read_timeout_retries_until_deadline() {
+        let mut reads = 0;
+        let result = read_timeout(
+            Duration::from_secs(10),
+            || {
+                reads += 1;
+                if reads < 3 {
+                    Err(CanError::QrcvEmpty)
+                } else {
+                    Ok(reads)
+                }
+            },
+            |_| Ok(()),
+        );
+        assert!(matches!(result, Ok(3)));
+
+        let result: Result<(), _> = read_timeout(
+            Duration::from_millis(2),
+            || Err(CanError::QrcvEmpty),
+            |remaining| {
+                sleep(remaining);
+                Ok(())
+            },
+        );
+        assert!(matches!(result, Err(CanError::QrcvEmpty)));
+
+        let result: Result<(), _> = read_timeout(
+            Duration::from_secs(10),
+            || Err(CanError::QrcvEmpty),
+            |_| Err(CanError::Resource),
+        );
+        assert!(matches!(result, Err(CanError::Resource)));
+    }
+}
@@ -3,10 +3,8 @@
 //!
 
 use crate::channel::Channel;
-use crate::error::{CanError, CanOkError};
-use crate::peak_lib;
+use crate::error::CanError;
 use crate::peak_can;
-use std::ffi::c_void;
 
 /* Five Volts Power */
 
@@ -19,26 +17,12 @@ pub trait FiveVoltsPower {
 impl<T: HasFiveVoltsPower + Channel> FiveVoltsPower for T {
     fn five_volts(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_5VOLTS_POWER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_5VOLTS_POWER as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -51,24 +35,11 @@ pub trait SetFiveVoltsPower {
 
 impl<T: HasSetFiveVoltsPower + Channel> SetFiveVoltsPower for T {
     fn set_five_volts(&self, value: bool) -> Result<(), CanError> {
-        let mut data = match value {
+        let data = match value {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_5VOLTS_POWER as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_5VOLTS_POWER as u8, &data)
     }
 }
 
@@ -83,26 +54,12 @@ pub trait BusOffAutoreset {
 impl<T: HasBusOffAutoreset + Channel> BusOffAutoreset for T {
     fn bus_off_autoreset(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BUSOFF_AUTORESET as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_BUSOFF_AUTORESET as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -115,24 +72,11 @@ pub trait SetBusOffAutoreset {
 
 impl<T: HasSetBusOffAutoreset + Channel> SetBusOffAutoreset for T {
     fn set_bus_off_autoreset(&self, value: bool) -> Result<(), CanError> {
-        let mut data = match value {
+        let data = match value {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_BUSOFF_AUTORESET as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_BUSOFF_AUTORESET as u8, &data)
     }
 }
 
@@ -147,26 +91,12 @@ pub trait ListenOnly {
 impl<T: HasListenOnly + Channel> ListenOnly for T {
     fn listen_only(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_LISTEN_ONLY as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_LISTEN_ONLY as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -179,24 +109,11 @@ pub trait SetListenOnly {
 
 impl<T: HasSetListenOnly + Channel> SetListenOnly for T {
     fn set_listen_only(&self, value: bool) -> Result<(), CanError> {
-        let mut data = match value {
+        let data = match value {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_LISTEN_ONLY as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_LISTEN_ONLY as u8, &data)
     }
 }
 
@@ -211,26 +128,12 @@ pub trait BitrateAdapting {
 impl<T: HasBitrateAdapting + Channel> BitrateAdapting for T {
     fn bitrate_adapting(&self) -> Result<bool, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_BITRATE_ADAPTING as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => {
-                let value = u32::from_le_bytes(data);
-                if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
+        self.backend().get_value(self.channel(), peak_can::PEAK_BITRATE_ADAPTING as u8, &mut data)?;
+        let value = u32::from_le_bytes(data);
+        if value & peak_can::PEAK_PARAMETER_ON == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 }
@@ -243,24 +146,11 @@ pub trait SetBitrateAdapting {
 
 impl<T: HasSetBitrateAdapting + Channel> SetBitrateAdapting for T {
     fn set_bitrate_adapting(&self, value: bool) -> Result<(), CanError> {
-        let mut data = match value {
+        let data = match value {
             true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
             false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
         };
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_BITRATE_ADAPTING as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().set_value(self.channel(), peak_can::PEAK_BITRATE_ADAPTING as u8, &data)
     }
 }
 
@@ -275,20 +165,8 @@ pub trait InterframeDelay {
 impl<T: HasInterframeDelay + Channel> InterframeDelay for T {
     fn interframe_delay(&self) -> Result<u32, CanError> {
         let mut data = [0u8; 4];
-        let code = unsafe {
-            peak_lib()?.CAN_GetValue(
-                self.channel(),
-                peak_can::PEAK_INTERFRAME_DELAY as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(u32::from_le_bytes(data)),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        self.backend().get_value(self.channel(), peak_can::PEAK_INTERFRAME_DELAY as u8, &mut data)?;
+        Ok(u32::from_le_bytes(data))
     }
 }
 
@@ -300,20 +178,7 @@ pub trait SetInterframeDelay {
 
 impl<T: HasSetInterframeDelay + Channel> SetInterframeDelay for T {
     fn set_interframe_delay(&self, value: u32) -> Result<(), CanError> {
-        let mut data = value.to_le_bytes();
-        let code = unsafe {
-            peak_lib()?.CAN_SetValue(
-                self.channel(),
-                peak_can::PEAK_INTERFRAME_DELAY as u8,
-                data.as_mut_ptr() as *mut c_void,
-                data.len() as u32,
-            )
-        };
-
-        match CanOkError::try_from(code) {
-            Ok(CanOkError::Ok) => Ok(()),
-            Ok(CanOkError::Err(err)) => Err(err),
-            Err(_) => Err(CanError::Unknown),
-        }
+        let data = value.to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_INTERFRAME_DELAY as u8, &data)
     }
 }
@@ -0,0 +1,213 @@
+//! Frames whose payload is partly filled in at send time.
+//!
+//! A [FrameTemplate] holds a fixed frame and a list of placeholders: counters, values read from
+//! a callback such as a measurement, and checksums computed over the finished payload. It is
+//! rendered into a new frame for every transmission, e.g. by the
+//! [CyclicSender](crate::message::CyclicSender), so periodic frames carry live data without
+//! being rebuilt by the application. Bit fields use the numbering of the
+//! [payload](crate::payload) module.
+
+use crate::payload::{ByteOrder, PayloadError, extract_bits, insert_bits};
+use crate::socket::{CanFrame, Id};
+
+/// Position of a placeholder in the payload.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BitField {
+    pub start_bit: u16,
+    pub len: u8,
+    pub order: ByteOrder,
+}
+
+impl BitField {
+    pub fn new(start_bit: u16, len: u8, order: ByteOrder) -> Self {
+        BitField {
+            start_bit,
+            len,
+            order,
+        }
+    }
+
+    /// The whole byte at `offset`.
+    pub fn byte(offset: u16) -> Self {
+        BitField::new(offset * 8, 8, ByteOrder::LittleEndian)
+    }
+
+    fn mask(&self) -> u64 {
+        if self.len >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.len) - 1
+        }
+    }
+
+    fn write(&self, data: &mut [u8], value: u64) {
+        // the field was checked to fit the payload when it was added
+        let _ = insert_bits(
+            data,
+            self.start_bit,
+            self.len,
+            value & self.mask(),
+            self.order,
+        );
+    }
+}
+
+type ValueSource = Box<dyn FnMut() -> u64 + Send>;
+type ChecksumFn = Box<dyn FnMut(&[u8]) -> u64 + Send>;
+
+enum Placeholder {
+    Counter { next: u64, step: u64 },
+    Value(ValueSource),
+    Checksum(ChecksumFn),
+}
+
+/// A frame with placeholders.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::payload::ByteOrder;
+/// # use peak_can::socket::{CanFrame, MessageType};
+/// # use peak_can::template::{BitField, FrameTemplate};
+/// let frame = CanFrame::new(0x100, MessageType::Standard, &[0x11, 0, 0, 0])?;
+/// let mut template = FrameTemplate::new(frame)
+///     .with_value(BitField::new(8, 16, ByteOrder::LittleEndian), || 1234)?
+///     .with_counter(BitField::new(24, 4, ByteOrder::LittleEndian))?
+///     .with_checksum(BitField::new(28, 4, ByteOrder::LittleEndian), |data| {
+///         data.iter().map(|&b| b as u64).sum::<u64>()
+///     })?;
+///
+/// assert_eq!(template.render().data(), [0x11, 0xD2, 0x04, 0x70]);
+/// assert_eq!(template.render().data(), [0x11, 0xD2, 0x04, 0x81]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FrameTemplate {
+    frame: CanFrame,
+    placeholders: Vec<(BitField, Placeholder)>,
+}
+
+impl FrameTemplate {
+    /// A template sending `frame` where no placeholder overrides it.
+    pub fn new(frame: CanFrame) -> Self {
+        FrameTemplate {
+            frame,
+            placeholders: Vec::new(),
+        }
+    }
+
+    /// Fills `field` with a counter starting at 0 and wrapping at the field width.
+    pub fn with_counter(self, field: BitField) -> Result<Self, PayloadError> {
+        self.with_placeholder(field, Placeholder::Counter { next: 0, step: 1 })
+    }
+
+    /// Fills `field` with a counter starting at `start` and advancing by `step`, e.g. for
+    /// alive counters which skip a reserved value.
+    pub fn with_counter_from(
+        self,
+        field: BitField,
+        start: u64,
+        step: u64,
+    ) -> Result<Self, PayloadError> {
+        let next = start & field.mask();
+        self.with_placeholder(field, Placeholder::Counter { next, step })
+    }
+
+    /// Fills `field` with the value returned by `source` at render time. Bits beyond the field
+    /// width are dropped.
+    pub fn with_value<F>(self, field: BitField, source: F) -> Result<Self, PayloadError>
+    where
+        F: FnMut() -> u64 + Send + 'static,
+    {
+        self.with_placeholder(field, Placeholder::Value(Box::new(source)))
+    }
+
+    /// Fills `field` with `checksum` of the payload after the counters and values were filled
+    /// in, with the checksum field itself cleared. Checksums are computed in the order they
+    /// were added. Bits beyond the field width are dropped.
+    pub fn with_checksum<F>(self, field: BitField, checksum: F) -> Result<Self, PayloadError>
+    where
+        F: FnMut(&[u8]) -> u64 + Send + 'static,
+    {
+        self.with_placeholder(field, Placeholder::Checksum(Box::new(checksum)))
+    }
+
+    /// The frame without placeholders filled in.
+    pub fn frame(&self) -> &CanFrame {
+        &self.frame
+    }
+
+    pub fn id(&self) -> Id {
+        self.frame.id()
+    }
+
+    /// The frame with all placeholders filled in, advancing the counters.
+    pub fn render(&mut self) -> CanFrame {
+        let mut frame = self.frame;
+        let data = frame.mut_data();
+        for (field, placeholder) in &mut self.placeholders {
+            match placeholder {
+                Placeholder::Counter { next, step } => {
+                    field.write(data, *next);
+                    *next = next.wrapping_add(*step) & field.mask();
+                }
+                Placeholder::Value(source) => field.write(data, source()),
+                Placeholder::Checksum(_) => {}
+            }
+        }
+        for (field, placeholder) in &mut self.placeholders {
+            if let Placeholder::Checksum(checksum) = placeholder {
+                field.write(data, 0);
+                let value = checksum(data);
+                field.write(data, value);
+            }
+        }
+        frame
+    }
+
+    fn with_placeholder(
+        mut self,
+        field: BitField,
+        placeholder: Placeholder,
+    ) -> Result<Self, PayloadError> {
+        extract_bits(self.frame.data(), field.start_bit, field.len, field.order)
+            .ok_or(PayloadError::OutOfRange)?;
+        self.placeholders.push((field, placeholder));
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::MessageType;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn placeholders_filled_per_render() {
+        let frame = CanFrame::new(0x200, MessageType::Standard, &[0xFF, 0, 0]).unwrap();
+        let measured = Arc::new(AtomicU64::new(0x1AB));
+        let source = measured.clone();
+        let mut template = FrameTemplate::new(frame)
+            .with_counter_from(BitField::byte(2), 0xFE, 1)
+            .unwrap()
+            .with_checksum(BitField::byte(0), |data| {
+                data.iter().fold(0, |acc, &b| acc ^ b as u64)
+            })
+            .unwrap()
+            .with_value(BitField::byte(1), move || source.load(Ordering::Relaxed))
+            .unwrap();
+
+        // the value is truncated to its byte, the checksum sees the filled in bytes
+        assert_eq!(template.render().data(), [0xAB ^ 0xFE, 0xAB, 0xFE]);
+        measured.store(0x10, Ordering::Relaxed);
+        assert_eq!(template.render().data(), [0x10 ^ 0xFF, 0x10, 0xFF]);
+        assert_eq!(template.render().data(), [0x10, 0x10, 0x00]);
+        assert_eq!(template.frame().data(), [0xFF, 0, 0]);
+
+        assert!(matches!(
+            FrameTemplate::new(frame).with_counter(BitField::byte(3)),
+            Err(PayloadError::OutOfRange)
+        ));
+    }
+}
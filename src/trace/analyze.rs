@@ -0,0 +1,236 @@
+//! Offline detection of timing and content anomalies in recorded traces.
+
+use crate::trace::{TraceError, TraceRecord};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzerConfig {
+    /// Silence on the bus longer than this is reported as a [Anomaly::Gap].
+    pub max_gap: Duration,
+    /// Identical frames of the same ID within this window are reported as
+    /// [Anomaly::Duplicate].
+    pub duplicate_window: Duration,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        AnalyzerConfig {
+            max_gap: Duration::from_secs(1),
+            duplicate_window: Duration::from_micros(100),
+        }
+    }
+}
+
+/// Anomaly found in a trace. `index` is the position of the offending record, counting only
+/// records that could be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anomaly {
+    /// The timestamp is earlier than the one of the preceding record.
+    TimestampRegression {
+        index: usize,
+        previous: Duration,
+        timestamp: Duration,
+    },
+    /// No record was seen for longer than [AnalyzerConfig::max_gap].
+    Gap {
+        index: usize,
+        start: Duration,
+        duration: Duration,
+    },
+    /// The frame repeats the previous frame of the same ID and channel.
+    Duplicate { index: usize, previous_index: usize },
+    /// The frame length differs from the first frame seen with this ID and channel.
+    DlcChange {
+        index: usize,
+        can_id: u32,
+        expected: u8,
+        actual: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TraceReport {
+    pub records: usize,
+    /// Number of records that could not be read.
+    pub errors: usize,
+    pub first_timestamp: Option<Duration>,
+    pub last_timestamp: Option<Duration>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl TraceReport {
+    pub fn is_clean(&self) -> bool {
+        self.errors == 0 && self.anomalies.is_empty()
+    }
+}
+
+type FrameKey = (Option<u16>, u32, bool);
+
+struct LastFrame {
+    index: usize,
+    timestamp: Duration,
+    data: Vec<u8>,
+}
+
+/// Incremental analyzer, records are fed one by one with [TraceAnalyzer::push].
+pub struct TraceAnalyzer {
+    config: AnalyzerConfig,
+    report: TraceReport,
+    dlcs: HashMap<FrameKey, u8>,
+    last_frames: HashMap<FrameKey, LastFrame>,
+}
+
+impl TraceAnalyzer {
+    pub fn new(config: AnalyzerConfig) -> Self {
+        TraceAnalyzer {
+            config,
+            report: TraceReport::default(),
+            dlcs: HashMap::new(),
+            last_frames: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, record: &TraceRecord) {
+        let index = self.report.records;
+        self.report.records += 1;
+        let timestamp = record.timestamp;
+
+        if let Some(previous) = self.report.last_timestamp {
+            if timestamp < previous {
+                self.report.anomalies.push(Anomaly::TimestampRegression {
+                    index,
+                    previous,
+                    timestamp,
+                });
+            } else if timestamp - previous > self.config.max_gap {
+                self.report.anomalies.push(Anomaly::Gap {
+                    index,
+                    start: previous,
+                    duration: timestamp - previous,
+                });
+            }
+        }
+        self.report.first_timestamp.get_or_insert(timestamp);
+        self.report.last_timestamp = Some(timestamp);
+
+        let frame = &record.frame;
+        let key = (record.channel, frame.can_id(), frame.is_extended_frame());
+
+        let expected = *self.dlcs.entry(key).or_insert(frame.dlc());
+        if expected != frame.dlc() {
+            self.report.anomalies.push(Anomaly::DlcChange {
+                index,
+                can_id: frame.can_id(),
+                expected,
+                actual: frame.dlc(),
+            });
+        }
+
+        if let Some(last) = self.last_frames.get(&key) {
+            let elapsed = timestamp.saturating_sub(last.timestamp);
+            if elapsed <= self.config.duplicate_window && last.data == frame.data() {
+                self.report.anomalies.push(Anomaly::Duplicate {
+                    index,
+                    previous_index: last.index,
+                });
+            }
+        }
+        self.last_frames.insert(
+            key,
+            LastFrame {
+                index,
+                timestamp,
+                data: frame.data().to_vec(),
+            },
+        );
+    }
+
+    pub fn push_error(&mut self) {
+        self.report.errors += 1;
+    }
+
+    pub fn report(&self) -> &TraceReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> TraceReport {
+        self.report
+    }
+}
+
+/// Analyzes a complete trace, e.g. as returned by [open](crate::trace::open).
+pub fn analyze<I>(records: I, config: AnalyzerConfig) -> TraceReport
+where
+    I: IntoIterator<Item = Result<TraceRecord, TraceError>>,
+{
+    let mut analyzer = TraceAnalyzer::new(config);
+    for record in records {
+        match record {
+            Ok(record) => analyzer.push(&record),
+            Err(_) => analyzer.push_error(),
+        }
+    }
+    analyzer.into_report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::test_util::record;
+
+    #[test]
+    fn clean_trace() {
+        let us = Duration::from_micros;
+        let trace = vec![
+            Ok(record(us(0), Some(1), 0x100, &[1])),
+            Ok(record(us(1000), Some(1), 0x100, &[2])),
+        ];
+        let report = analyze(trace, AnalyzerConfig::default());
+
+        assert!(report.is_clean());
+        assert_eq!(report.records, 2);
+        assert_eq!(report.last_timestamp, Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn detect_anomalies() {
+        let us = Duration::from_micros;
+        let trace = vec![
+            Ok(record(us(0), Some(1), 0x100, &[1, 2])),
+            Ok(record(us(50), Some(1), 0x100, &[1, 2])),
+            Ok(record(us(40), Some(1), 0x200, &[])),
+            Ok(record(us(3_000_000), Some(1), 0x100, &[1])),
+            Err(TraceError::UnknownFormat),
+        ];
+        let report = analyze(trace, AnalyzerConfig::default());
+
+        assert_eq!(report.errors, 1);
+        assert_eq!(
+            report.anomalies,
+            vec![
+                Anomaly::Duplicate {
+                    index: 1,
+                    previous_index: 0
+                },
+                Anomaly::TimestampRegression {
+                    index: 2,
+                    previous: Duration::from_micros(50),
+                    timestamp: Duration::from_micros(40),
+                },
+                Anomaly::Gap {
+                    index: 3,
+                    start: Duration::from_micros(40),
+                    duration: Duration::from_micros(2_999_960),
+                },
+                Anomaly::DlcChange {
+                    index: 3,
+                    can_id: 0x100,
+                    expected: 2,
+                    actual: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Notes attached to frames while they pass through a processing pipeline.
+
+/// Tags and key-value pairs describing a frame, e.g. `matched rule 7` or
+/// `signal=EngineSpeed`. They are filled by the annotators of a
+/// [Dispatcher](crate::dispatch::Dispatcher) and written next to the frame by the
+/// [csv](crate::trace::csv) and [json](crate::trace::json) writers.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::trace::Annotations;
+/// let mut annotations = Annotations::new();
+/// annotations.tag("matched rule 7");
+/// annotations.set("signal", "EngineSpeed");
+/// annotations.set("signal", "VehicleSpeed");
+/// assert_eq!(annotations.get("signal"), Some("VehicleSpeed"));
+/// assert_eq!(annotations.tags().collect::<Vec<_>>(), ["matched rule 7"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotations {
+    tags: Vec<String>,
+    values: Vec<(String, String)>,
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tag, a tag added twice is kept once.
+    pub fn tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    /// Sets the value of `key`, replacing a previous value.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.values.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.values.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The tags in the order they were added.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    /// The key-value pairs in the order the keys were first set.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tags.clear();
+        self.values.clear();
+    }
+}
@@ -0,0 +1,443 @@
+//! Reader and writer for Vector ASCII logs (`.asc`) as written by CANoe and CANalyzer.
+//!
+//! Classic frames are lines like `0.001234 1  123x  Rx   d 2 DE AD`, CAN FD frames start with
+//! `CANFD` after the time. Events other than frames, e.g. error frames or statistics, are
+//! skipped when reading. Timestamps are seconds since the start of the measurement.
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType, Timestamp};
+use crate::trace::reader::{parse_hex_bytes, parse_seconds};
+use crate::trace::{TraceError, TraceFrame, TraceRecord, TraceWriter};
+
+use std::io::{BufRead, Lines, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Flags of a `CANFD` line marking an extended data length, a bit rate switch and an error
+/// state indicator frame.
+const FLAG_EDL: u32 = 0x1000;
+const FLAG_BRS: u32 = 0x2000;
+const FLAG_ESI: u32 = 0x4000;
+
+pub struct AscReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    hex: bool,
+}
+
+impl<R: BufRead> AscReader<R> {
+    pub fn new(reader: R) -> Self {
+        AscReader {
+            lines: reader.lines(),
+            line: 0,
+            hex: true,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for AscReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                ["base", base, ..] => self.hex = !base.eq_ignore_ascii_case("dec"),
+                [time, rest @ ..] => {
+                    let Some(timestamp) = parse_seconds(time) else {
+                        // header lines and block markers
+                        continue;
+                    };
+                    match parse_event(rest, self.hex) {
+                        Ok(Some((channel, frame))) => {
+                            return Some(Ok(TraceRecord {
+                                timestamp,
+                                channel: Some(channel),
+                                frame,
+                            }));
+                        }
+                        Ok(None) => continue,
+                        Err(message) => {
+                            return Some(Err(TraceError::Parse {
+                                line: self.line,
+                                message,
+                            }));
+                        }
+                    }
+                }
+                [] => continue,
+            }
+        }
+    }
+}
+
+fn parse_id(id: &str, hex: bool) -> Result<(u32, MessageType), String> {
+    let (digits, msg_type) = match id.strip_suffix(['x', 'X']) {
+        Some(digits) => (digits, MessageType::Extended),
+        None => (id, MessageType::Standard),
+    };
+    let radix = if hex { 16 } else { 10 };
+    let can_id = u32::from_str_radix(digits, radix).map_err(|_| format!("invalid CAN ID {id}"))?;
+    Ok((can_id, msg_type))
+}
+
+fn is_direction(field: &str) -> bool {
+    field == "Rx" || field == "Tx"
+}
+
+/// Parses the fields after the time, `None` for events that are not frames.
+fn parse_event(fields: &[&str], hex: bool) -> Result<Option<(u16, TraceFrame)>, String> {
+    match fields {
+        ["CANFD", channel, direction, rest @ ..] if is_direction(direction) => {
+            let Ok(channel) = channel.parse() else {
+                return Ok(None);
+            };
+            parse_fd_frame(rest, hex).map(|frame| Some((channel, frame)))
+        }
+        [channel, id, direction, kind, rest @ ..] if is_direction(direction) => {
+            let Ok(channel) = channel.parse() else {
+                return Ok(None);
+            };
+            let (can_id, msg_type) = parse_id(id, hex)?;
+            let dlc = match rest.first() {
+                Some(dlc) => {
+                    u8::from_str_radix(dlc, 16).map_err(|_| format!("invalid DLC {dlc}"))?
+                }
+                None => 0,
+            };
+            let frame = match *kind {
+                "r" => CanFrame::new_remote(can_id, msg_type, dlc),
+                "d" => {
+                    let data = rest
+                        .get(1..=dlc as usize)
+                        .and_then(|bytes| parse_hex_bytes(&bytes.concat()))
+                        .ok_or_else(|| format!("missing data of {dlc} bytes"))?;
+                    CanFrame::new(can_id, msg_type, &data)
+                }
+                _ => return Ok(None),
+            };
+            frame
+                .map(|frame| Some((channel, TraceFrame::Can(frame))))
+                .map_err(|err| err.to_string())
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_fd_frame(fields: &[&str], hex: bool) -> Result<TraceFrame, String> {
+    // <id> [<symbolic name>] <brs> <esi> <dlc> <data length> <data> ...
+    let (id, rest) = fields.split_first().ok_or("missing CAN ID")?;
+    let (can_id, msg_type) = parse_id(id, hex)?;
+    let rest = match rest.first() {
+        Some(&"0" | &"1") => rest,
+        _ => rest.get(1..).unwrap_or_default(),
+    };
+    let [brs, esi, _dlc, len, data @ ..] = rest else {
+        return Err(String::from("missing CAN FD fields"));
+    };
+    let len = len
+        .parse::<usize>()
+        .map_err(|_| format!("invalid data length {len}"))?;
+    let data = data
+        .get(..len)
+        .and_then(|bytes| parse_hex_bytes(&bytes.concat()))
+        .ok_or_else(|| format!("missing data of {len} bytes"))?;
+
+    CanFdFrame::new(can_id, msg_type, &data, true, *brs == "1")
+        .map(|frame| TraceFrame::CanFd(frame.with_esi(*esi == "1")))
+        .map_err(|err| err.to_string())
+}
+
+/// Writes records as an ASCII log with hexadecimal IDs. The time of the first record is the
+/// start of the measurement, records without a channel are written to channel 1.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::asc::AscWriter;
+/// # use std::fs::File;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut writer = AscWriter::new(File::create("capture.asc")?);
+/// for received in socket.iter().take(1000) {
+///     let (frame, timestamp) = received?;
+///     writer.write_frame(1, &frame, &timestamp)?;
+/// }
+/// writer.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// A writer dropped without [finish](AscWriter::finish) is closed as well, errors are ignored
+/// then.
+pub struct AscWriter<W: Write> {
+    // only taken by `finish`
+    writer: Option<W>,
+    started_at: SystemTime,
+    start: Option<Duration>,
+    closed: bool,
+}
+
+impl<W: Write> AscWriter<W> {
+    pub fn new(writer: W) -> Self {
+        AscWriter {
+            writer: Some(writer),
+            started_at: SystemTime::now(),
+            start: None,
+            closed: false,
+        }
+    }
+
+    /// Sets the wall clock time written into the header, the current time by default.
+    pub fn with_start_time(mut self, started_at: SystemTime) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    /// Writes a frame received on `channel` with its device timestamp.
+    pub fn write_frame(
+        &mut self,
+        channel: u16,
+        frame: &CanFrame,
+        timestamp: &Timestamp,
+    ) -> Result<(), TraceError> {
+        self.write_record(&TraceRecord {
+            timestamp: Duration::from_micros(timestamp.as_micros()),
+            channel: Some(channel),
+            frame: TraceFrame::Can(*frame),
+        })
+    }
+
+    /// Closes the trigger block and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, TraceError> {
+        self.close()?;
+        Ok(self.writer.take().expect("writer taken twice"))
+    }
+
+    fn out(&mut self) -> &mut W {
+        self.writer.as_mut().expect("writer used after finish")
+    }
+
+    fn write_header(&mut self) -> Result<(), TraceError> {
+        let date = format_date(self.started_at);
+        let out = self.out();
+        writeln!(out, "date {date}")?;
+        writeln!(out, "base hex  timestamps absolute")?;
+        writeln!(out, "no internal events logged")?;
+        writeln!(out, "Begin Triggerblock {date}")?;
+        writeln!(out, "   0.000000 Start of measurement")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceWriter for AscWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        if self.closed {
+            return Err(TraceError::Closed);
+        }
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                self.write_header()?;
+                *self.start.insert(record.timestamp)
+            }
+        };
+        let elapsed = record.timestamp.saturating_sub(start);
+        let time = format!("{}.{:06}", elapsed.as_secs(), elapsed.subsec_micros());
+        let channel = record.channel.unwrap_or(1);
+
+        let frame = &record.frame;
+        let id = if frame.is_extended_frame() {
+            format!("{:X}x", frame.can_id())
+        } else {
+            format!("{:X}", frame.can_id())
+        };
+        let data = frame
+            .data()
+            .iter()
+            .map(|b| format!(" {b:02X}"))
+            .collect::<String>();
+
+        let out = self.out();
+        match frame {
+            TraceFrame::Can(can) if can.is_remote_frame() => writeln!(
+                out,
+                "{time:>11} {channel:<2} {id:<15} Rx   r {:X}",
+                can.dlc()
+            )?,
+            TraceFrame::Can(can) => writeln!(
+                out,
+                "{time:>11} {channel:<2} {id:<15} Rx   d {:X}{data}",
+                can.dlc()
+            )?,
+            TraceFrame::CanFd(fd) => {
+                let brs = fd.is_brs() as u8;
+                let esi = fd.is_esi() as u8;
+                let flags = FLAG_EDL
+                    | if fd.is_brs() { FLAG_BRS } else { 0 }
+                    | if fd.is_esi() { FLAG_ESI } else { 0 };
+                write!(
+                    out,
+                    "{time:>11} CANFD {channel:>3} Rx {id:>10} {brs} {esi} {:x} {:>2}{data}",
+                    fd.dlc(),
+                    fd.data().len()
+                )?;
+                // message duration and length, flags, CRC and bit timings
+                writeln!(out, " 0 0 {flags:x} 0 0 0 0 0")?
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.out().flush()?;
+        Ok(())
+    }
+
+    /// Closes the trigger block, a file without records gets the header as well.
+    fn close(&mut self) -> Result<(), TraceError> {
+        if self.closed {
+            return Ok(());
+        }
+        if self.start.is_none() {
+            self.write_header()?;
+        }
+        self.closed = true;
+        writeln!(self.out(), "End TriggerBlock")?;
+        self.flush()
+    }
+}
+
+impl<W: Write> Drop for AscWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.close();
+        }
+    }
+}
+
+/// Formats a time like `Wed Jun 5 10:00:00.000 am 2024` in UTC.
+fn format_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let days = since_epoch.as_secs() / 86_400;
+    let seconds = since_epoch.as_secs() % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    let hour = seconds / 3600;
+    let (hour12, meridiem) = match hour {
+        0 => (12, "am"),
+        1..=11 => (hour, "am"),
+        12 => (12, "pm"),
+        _ => (hour - 12, "pm"),
+    };
+
+    format!(
+        "{} {} {day} {hour12:02}:{:02}:{:02}.{:03} {meridiem} {year}",
+        // 1970-01-01 was a Thursday
+        WEEKDAYS[((days + 4) % 7) as usize],
+        MONTHS[month as usize - 1],
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Year, month and day of the proleptic Gregorian calendar for days since 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const LOG: &str = "date Wed Jun 5 10:00:00.000 am 2024
+base hex  timestamps absolute
+internal events logged
+// version 13.0.0
+Begin Triggerblock Wed Jun 5 10:00:00.000 am 2024
+   0.000000 Start of measurement
+   0.001234 1  123             Rx   d 4 DE AD BE EF  Length = 0 BitCount = 0 ID = 291
+   0.002000 2  1ABCDEFx        Tx   d 0
+   0.003000 1  7DF             Rx   r 8
+   0.003500 1  ErrorFrame
+   0.004000 CANFD   1 Rx        456  Engine    1 0 9 12 00 01 02 03 04 05 06 07 08 09 0A 0B \
+0 0 3000 0 0 0 0 0
+End TriggerBlock
+";
+
+    #[test]
+    fn read_frames() {
+        let records = AscReader::new(Cursor::new(LOG))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].timestamp, Duration::from_micros(1234));
+        assert_eq!(records[0].channel, Some(1));
+        assert_eq!(records[0].frame.data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(records[1].channel, Some(2));
+        assert_eq!(records[1].frame.can_id(), 0x1AB_CDEF);
+        assert!(records[1].frame.is_extended_frame());
+        match records[2].frame {
+            TraceFrame::Can(frame) => assert!(frame.is_remote_frame() && frame.dlc() == 8),
+            _ => panic!("expected classic frame"),
+        }
+        match records[3].frame {
+            TraceFrame::CanFd(frame) => {
+                assert_eq!(frame.can_id(), 0x456);
+                assert_eq!(frame.data().len(), 12);
+                assert!(frame.is_brs());
+            }
+            _ => panic!("expected CAN FD frame"),
+        }
+
+        let dec =
+            "base dec  timestamps absolute\n   0.5 1  291  Rx   d 1 FF\n   1.0 1  ZZZ  Rx   d 0\n";
+        let records = AscReader::new(Cursor::new(dec)).collect::<Vec<_>>();
+        assert_eq!(records[0].as_ref().unwrap().frame.can_id(), 0x123);
+        assert!(matches!(records[1], Err(TraceError::Parse { line: 3, .. })));
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let records = AscReader::new(Cursor::new(LOG))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let started_at = UNIX_EPOCH + Duration::from_secs(1_717_581_600);
+        let mut writer = AscWriter::new(Vec::new()).with_start_time(started_at);
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let written = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(written.starts_with("date Wed Jun 5 10:00:00.000 am 2024\n"));
+
+        let read_back = AscReader::new(Cursor::new(written))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read_back.len(), records.len());
+        for (read, record) in read_back.iter().zip(&records) {
+            assert_eq!(read.frame, record.frame);
+            assert_eq!(read.timestamp + records[0].timestamp, record.timestamp);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Reader and writer for the log format of `candump -l` of the Linux can-utils.
+//!
+//! Each line has the form `(1436509052.249713) can0 123#DEADBEEF`. Extended identifiers are
+//! written with 8 hex digits, CAN FD frames use `##` followed by a flags nibble and remote
+//! frames are written as `123#R`.
+//!
+//! Interface names are the prefix `can` followed by the channel number, or the alias of the
+//! channel if [ChannelAliases] are set.
+//!
+//! [CanFrame] and [CanFdFrame] are displayed and parsed in the same notation, so frames can be
+//! copied between this crate and `cansend` or `candump`.
+//!
+//! ```
+//! # use peak_can::socket::{CanFdFrame, CanFrame};
+//! let frame: CanFrame = "123#DEADBEEF".parse()?;
+//! assert_eq!(frame.to_string(), "123#DEADBEEF");
+//! let fd: CanFdFrame = "12345678##1AABB".parse()?;
+//! assert!(fd.is_brs() && fd.is_extended_frame());
+//! # Ok::<(), peak_can::trace::candump::ParseFrameError>(())
+//! ```
+
+use crate::alias::ChannelAliases;
+use crate::socket::{CanFdFrame, CanFrame, MessageType};
+use crate::trace::reader::{parse_hex_bytes, parse_seconds};
+use crate::trace::{TraceError, TraceFrame, TraceRecord, TraceWriter};
+
+use core::fmt;
+use std::io::{BufRead, Lines, Write};
+use std::str::FromStr;
+
+/// Bit rate switch flag of a CAN FD frame.
+const CANFD_BRS: u8 = 0x01;
+/// Error state indicator flag of a CAN FD frame.
+const CANFD_ESI: u8 = 0x02;
+/// Error flag of the socketcan identifier.
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+
+pub struct CandumpReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    aliases: ChannelAliases,
+}
+
+impl<R: BufRead> CandumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        CandumpReader {
+            lines: reader.lines(),
+            line: 0,
+            aliases: ChannelAliases::new(),
+        }
+    }
+
+    /// Maps interface names equal to an alias to its channel.
+    pub fn with_aliases(mut self, aliases: ChannelAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for CandumpReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            return Some(
+                parse_line(line, &self.aliases).map_err(|message| TraceError::Parse {
+                    line: self.line,
+                    message,
+                }),
+            );
+        }
+    }
+}
+
+fn parse_line(line: &str, aliases: &ChannelAliases) -> Result<TraceRecord, String> {
+    let mut fields = line.split_whitespace();
+    let (Some(timestamp), Some(interface), Some(frame)) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(String::from("expected timestamp, interface and frame"));
+    };
+
+    let timestamp = timestamp
+        .strip_prefix('(')
+        .and_then(|t| t.strip_suffix(')'))
+        .and_then(parse_seconds)
+        .ok_or_else(|| format!("invalid timestamp {timestamp}"))?;
+
+    let channel = aliases.channel(interface).or_else(|| {
+        let digits = interface.len()
+            - interface
+                .trim_end_matches(|c: char| c.is_ascii_digit())
+                .len();
+        interface[interface.len() - digits..].parse::<u16>().ok()
+    });
+
+    Ok(TraceRecord {
+        timestamp,
+        channel,
+        frame: parse_frame(frame)?,
+    })
+}
+
+/// Parses a frame in the `cansend` notation, e.g. `123#DEADBEEF` or `12345678##1AABB`.
+pub fn parse_frame(s: &str) -> Result<TraceFrame, String> {
+    let (id, rest) = s
+        .split_once('#')
+        .ok_or_else(|| format!("missing '#' in frame {s}"))?;
+
+    let can_id = u32::from_str_radix(id, 16).map_err(|_| format!("invalid CAN ID {id}"))?;
+    if can_id & CAN_ERR_FLAG != 0 {
+        return Err(format!("error frame {id} not supported"));
+    }
+    let msg_type = if id.len() == 8 || can_id > 0x7FF {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    };
+
+    if let Some(fd) = rest.strip_prefix('#') {
+        let flags = fd
+            .get(..1)
+            .and_then(|f| u8::from_str_radix(f, 16).ok())
+            .ok_or_else(|| format!("missing CAN FD flags in frame {s}"))?;
+        let data = parse_hex_bytes(&fd[1..]).ok_or_else(|| format!("invalid data in frame {s}"))?;
+        CanFdFrame::new(can_id, msg_type, &data, true, flags & CANFD_BRS != 0)
+            .map(|frame| TraceFrame::CanFd(frame.with_esi(flags & CANFD_ESI != 0)))
+            .map_err(|err| err.to_string())
+    } else if let Some(dlc) = rest.strip_prefix(['R', 'r']) {
+        let dlc = match dlc {
+            "" => 0,
+            dlc => u8::from_str_radix(dlc, 16).map_err(|_| format!("invalid DLC in frame {s}"))?,
+        };
+        CanFrame::new_remote(can_id, msg_type, dlc)
+            .map(TraceFrame::Can)
+            .map_err(|err| err.to_string())
+    } else {
+        let data = parse_hex_bytes(rest).ok_or_else(|| format!("invalid data in frame {s}"))?;
+        CanFrame::new(can_id, msg_type, &data)
+            .map(TraceFrame::Can)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Writes records as `candump -l` log lines. The interface name is the prefix followed by the
+/// channel of the record, e.g. `can1`.
+pub struct CandumpWriter<W: Write> {
+    writer: W,
+    interface_prefix: String,
+    aliases: ChannelAliases,
+}
+
+impl<W: Write> CandumpWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CandumpWriter {
+            writer,
+            interface_prefix: String::from("can"),
+            aliases: ChannelAliases::new(),
+        }
+    }
+
+    pub fn with_interface_prefix(mut self, prefix: &str) -> Self {
+        self.interface_prefix = prefix.to_string();
+        self
+    }
+
+    /// Names the interfaces of channels with an alias after it.
+    pub fn with_aliases(mut self, aliases: ChannelAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TraceWriter for CandumpWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        let channel = record.channel.unwrap_or(0);
+        let interface = match self.aliases.alias(channel) {
+            Some(alias) => alias.to_string(),
+            None => format!("{}{channel}", self.interface_prefix),
+        };
+        writeln!(
+            self.writer,
+            "({}.{:06}) {interface} {}",
+            record.timestamp.as_secs(),
+            record.timestamp.subsec_micros(),
+            format_frame(&record.frame)
+        )?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Formats a frame in the `cansend` notation, the inverse of [parse_frame].
+pub fn format_frame(frame: &TraceFrame) -> String {
+    let id = if frame.is_extended_frame() {
+        format!("{:08X}", frame.can_id())
+    } else {
+        format!("{:03X}", frame.can_id())
+    };
+    let data = frame
+        .data()
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<String>();
+
+    match frame {
+        TraceFrame::Can(can) if can.is_remote_frame() => match can.dlc() {
+            0 => format!("{id}#R"),
+            dlc => format!("{id}#R{dlc:X}"),
+        },
+        TraceFrame::Can(_) => format!("{id}#{data}"),
+        TraceFrame::CanFd(fd) if !fd.is_fd_frame() => format!("{id}#{data}"),
+        TraceFrame::CanFd(fd) => {
+            let brs = if fd.is_brs() { CANFD_BRS } else { 0 };
+            let esi = if fd.is_esi() { CANFD_ESI } else { 0 };
+            let flags = brs | esi;
+            format!("{id}##{flags:X}{data}")
+        }
+    }
+}
+
+/// A frame in `cansend` notation could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseFrameError(String);
+
+impl fmt::Display for ParseFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFrameError {}
+
+impl fmt::Display for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_frame(&TraceFrame::Can(*self)))
+    }
+}
+
+impl fmt::Display for CanFdFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_frame(&TraceFrame::CanFd(*self)))
+    }
+}
+
+impl FromStr for CanFrame {
+    type Err = ParseFrameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_frame(s).map_err(ParseFrameError)? {
+            TraceFrame::Can(frame) => Ok(frame),
+            TraceFrame::CanFd(_) => Err(ParseFrameError(format!("CAN FD frame {s}"))),
+        }
+    }
+}
+
+/// Classic data frames are parsed as frames without the FD flag.
+impl FromStr for CanFdFrame {
+    type Err = ParseFrameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_frame(s).map_err(ParseFrameError)? {
+            TraceFrame::CanFd(frame) => Ok(frame),
+            TraceFrame::Can(frame) if frame.is_remote_frame() => {
+                Err(ParseFrameError(format!("remote frame {s}")))
+            }
+            TraceFrame::Can(frame) => {
+                let msg_type = if frame.is_extended_frame() {
+                    MessageType::Extended
+                } else {
+                    MessageType::Standard
+                };
+                CanFdFrame::new(frame.can_id(), msg_type, frame.data(), false, false)
+                    .map_err(|err| ParseFrameError(err.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn read_classic_and_fd_frames() {
+        let log = "(1436509052.249713) can0 123#DEADBEEF\n\
+                   \n\
+                   (1436509052.250000) vcan12 12345678#\n\
+                   (1436509052.300000) can1 456##1112233\n";
+        let records = CandumpReader::new(Cursor::new(log))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].timestamp, Duration::new(1436509052, 249_713_000));
+        assert_eq!(records[0].channel, Some(0));
+        assert_eq!(records[0].frame.can_id(), 0x123);
+        assert_eq!(records[0].frame.data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        assert_eq!(records[1].channel, Some(12));
+        assert!(records[1].frame.is_extended_frame());
+        assert!(records[1].frame.data().is_empty());
+
+        match records[2].frame {
+            TraceFrame::CanFd(frame) => {
+                assert_eq!(frame.can_id(), 0x456);
+                assert_eq!(frame.data(), &[0x11, 0x22, 0x33]);
+            }
+            _ => panic!("expected CAN FD frame"),
+        }
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let log = "(1436509052.249713) can0 123#DEADBEEF\n\
+                   (1436509052.250000) can12 12345678#\n\
+                   (1436509052.300000) can1 456##1112233\n\
+                   (1436509052.400000) can0 7DF#R\n\
+                   (1436509052.500000) can0 7DF#R8\n";
+        let mut writer = CandumpWriter::new(Vec::new());
+        for record in CandumpReader::new(Cursor::new(log)) {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), log);
+
+        let aliases = "1=powertrain".parse::<ChannelAliases>().unwrap();
+        let mut writer = CandumpWriter::new(Vec::new()).with_aliases(aliases.clone());
+        for record in CandumpReader::new(Cursor::new(log)) {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+        let written = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(written.contains(") powertrain 456##1112233\n"));
+        let channels = CandumpReader::new(Cursor::new(written))
+            .with_aliases(aliases)
+            .map(|record| record.unwrap().channel)
+            .collect::<Vec<_>>();
+        assert_eq!(channels, [Some(0), Some(12), Some(1), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn display_and_parse_frames() {
+        let remote = CanFrame::new_remote(0x1FFFF, MessageType::Extended, 2).unwrap();
+        assert_eq!(remote.to_string(), "0001FFFF#R2");
+        assert_eq!("0001FFFF#R2".parse::<CanFrame>().unwrap(), remote);
+        assert!("123##0AA".parse::<CanFrame>().is_err());
+
+        let classic: CanFdFrame = "7FF#0102".parse().unwrap();
+        assert!(!classic.is_fd_frame());
+        assert_eq!(classic.to_string(), "7FF#0102");
+        let fd = CanFdFrame::new(0x7FF, MessageType::Standard, &[0xAB; 12], true, false).unwrap();
+        assert_eq!(fd.to_string(), format!("7FF##0{}", "AB".repeat(12)));
+        assert_eq!(fd.to_string().parse::<CanFdFrame>().unwrap(), fd);
+        let esi: CanFdFrame = "7FF##3AB".parse().unwrap();
+        assert!(esi.is_esi() && esi.is_brs());
+        assert_eq!(esi.to_string(), "7FF##3AB");
+        assert!("7FF#R".parse::<CanFdFrame>().is_err());
+    }
+
+    #[test]
+    fn report_line_of_parse_error() {
+        let log = "(1.0) can0 123#00\n(2.0) can0 XYZ#00\n";
+        let records = CandumpReader::new(Cursor::new(log)).collect::<Vec<_>>();
+
+        assert!(records[0].is_ok());
+        assert!(matches!(records[1], Err(TraceError::Parse { line: 2, .. })));
+    }
+}
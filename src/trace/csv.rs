@@ -0,0 +1,371 @@
+//! Comma or semicolon separated traces.
+//!
+//! The first line must be a header naming the columns. Recognized columns (case insensitive) are
+//! `time`/`timestamp` (seconds), `channel`, `id` (hex), `extended` (`1`/`true`), `fd`, `brs` and
+//! `data` (hex bytes, optionally separated by spaces). Unknown columns are ignored.
+//!
+//! [CsvWriter] writes these columns separated by semicolons, followed by the `tags` and
+//! `annotations` of annotated records.
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType};
+use crate::trace::reader::{parse_hex_bytes, parse_seconds};
+use crate::trace::{Annotations, TraceError, TraceFrame, TraceRecord, TraceWriter};
+
+use std::io::{BufRead, Lines, Write};
+
+#[derive(Debug)]
+struct Columns {
+    delimiter: char,
+    time: usize,
+    id: usize,
+    data: usize,
+    channel: Option<usize>,
+    extended: Option<usize>,
+    fd: Option<usize>,
+    brs: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Result<Columns, String> {
+        let delimiter = if header.contains(',') { ',' } else { ';' };
+        let names = header
+            .split(delimiter)
+            .map(|n| n.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let find =
+            |candidates: &[&str]| names.iter().position(|n| candidates.contains(&n.as_str()));
+
+        Ok(Columns {
+            delimiter,
+            time: find(&["time", "timestamp"]).ok_or("missing time column")?,
+            id: find(&["id", "can_id"]).ok_or("missing id column")?,
+            data: find(&["data", "payload"]).ok_or("missing data column")?,
+            channel: find(&["channel", "bus"]),
+            extended: find(&["extended", "ide"]),
+            fd: find(&["fd"]),
+            brs: find(&["brs"]),
+        })
+    }
+
+    fn parse(&self, line: &str) -> Result<TraceRecord, String> {
+        let fields = line
+            .split(self.delimiter)
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let field = |index: usize| {
+            fields
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("missing column {}", index + 1))
+        };
+        let flag = |index: Option<usize>| match index.and_then(|i| fields.get(i)) {
+            Some(value) => matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "x"),
+            None => false,
+        };
+
+        let time = field(self.time)?;
+        let timestamp = parse_seconds(time).ok_or_else(|| format!("invalid time {time}"))?;
+
+        let channel = match self.channel.and_then(|i| fields.get(i)) {
+            Some(channel) if !channel.is_empty() => Some(
+                channel
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid channel {channel}"))?,
+            ),
+            _ => None,
+        };
+
+        let id = field(self.id)?;
+        let id_digits = id.trim_start_matches("0x").trim_start_matches("0X");
+        let can_id =
+            u32::from_str_radix(id_digits, 16).map_err(|_| format!("invalid CAN ID {id}"))?;
+        let msg_type = if flag(self.extended) || can_id > 0x7FF {
+            MessageType::Extended
+        } else {
+            MessageType::Standard
+        };
+
+        let data = field(self.data)?;
+        let data = parse_hex_bytes(data).ok_or_else(|| format!("invalid data {data}"))?;
+
+        let frame = if flag(self.fd) {
+            CanFdFrame::new(can_id, msg_type, &data, true, flag(self.brs)).map(TraceFrame::CanFd)
+        } else {
+            CanFrame::new(can_id, msg_type, &data).map(TraceFrame::Can)
+        }
+        .map_err(|err| err.to_string())?;
+
+        Ok(TraceRecord {
+            timestamp,
+            channel,
+            frame,
+        })
+    }
+}
+
+pub struct CsvReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    columns: Option<Columns>,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R) -> Self {
+        CsvReader {
+            lines: reader.lines(),
+            line: 0,
+            columns: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let result = match &self.columns {
+                Some(columns) => columns.parse(line),
+                None => match Columns::from_header(line) {
+                    Ok(columns) => {
+                        self.columns = Some(columns);
+                        continue;
+                    }
+                    Err(message) => Err(message),
+                },
+            };
+            return Some(result.map_err(|message| TraceError::Parse {
+                line: self.line,
+                message,
+            }));
+        }
+    }
+}
+
+/* WRITER */
+
+const HEADER: &str = "time;channel;id;extended;fd;brs;data;tags;annotations";
+
+/// Writes records with the timestamps as stored in them. Tags are separated by `|`, key-value
+/// pairs are written as `key=value` separated by `|`. Delimiters and line breaks within
+/// annotations are replaced by spaces.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::{CanFrame, MessageType};
+/// # use peak_can::trace::csv::CsvWriter;
+/// # use peak_can::trace::{Annotations, TraceFrame, TraceRecord};
+/// # use std::time::Duration;
+/// let record = TraceRecord {
+///     timestamp: Duration::from_millis(1500),
+///     channel: Some(1),
+///     frame: TraceFrame::Can(CanFrame::new(0x0CF00400, MessageType::Extended, &[0x10, 0x20])?),
+/// };
+/// let mut annotations = Annotations::new();
+/// annotations.tag("matched rule 7");
+/// annotations.set("signal", "EngineSpeed");
+///
+/// let mut writer = CsvWriter::new(Vec::new());
+/// writer.write_annotated(&record, &annotations)?;
+/// let csv = String::from_utf8(writer.finish()?)?;
+/// assert_eq!(
+///     csv.lines().nth(1),
+///     Some("1.500000;1;CF00400;1;0;0;10 20;matched rule 7;signal=EngineSpeed")
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct CsvWriter<W: Write> {
+    // only taken by `finish`
+    writer: Option<W>,
+    header: bool,
+    closed: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CsvWriter {
+            writer: Some(writer),
+            header: false,
+            closed: false,
+        }
+    }
+
+    /// Writes `record` followed by its annotations.
+    pub fn write_annotated(
+        &mut self,
+        record: &TraceRecord,
+        annotations: &Annotations,
+    ) -> Result<(), TraceError> {
+        if self.closed {
+            return Err(TraceError::Closed);
+        }
+        if !self.header {
+            self.header = true;
+            writeln!(self.out(), "{HEADER}")?;
+        }
+
+        let time = format!(
+            "{}.{:06}",
+            record.timestamp.as_secs(),
+            record.timestamp.subsec_micros()
+        );
+        let channel = record.channel.map(|c| c.to_string()).unwrap_or_default();
+        let frame = &record.frame;
+        let (fd, brs) = match frame {
+            TraceFrame::Can(_) => (false, false),
+            TraceFrame::CanFd(fd) => (true, fd.is_brs()),
+        };
+        let data = frame
+            .data()
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let tags = annotations.tags().map(escape).collect::<Vec<_>>().join("|");
+        let values = annotations
+            .values()
+            .map(|(key, value)| format!("{}={}", escape(key), escape(value)))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        writeln!(
+            self.out(),
+            "{time};{channel};{:X};{};{};{};{data};{tags};{values}",
+            frame.can_id(),
+            frame.is_extended_frame() as u8,
+            fd as u8,
+            brs as u8,
+        )?;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, TraceError> {
+        self.close()?;
+        Ok(self.writer.take().expect("writer taken twice"))
+    }
+
+    fn out(&mut self) -> &mut W {
+        self.writer.as_mut().expect("writer used after finish")
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace([';', '|', '\r', '\n'], " ")
+}
+
+impl<W: Write> TraceWriter for CsvWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        self.write_annotated(record, &Annotations::new())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.out().flush()?;
+        Ok(())
+    }
+
+    /// A file without records gets the header line.
+    fn close(&mut self) -> Result<(), TraceError> {
+        if self.closed {
+            return Ok(());
+        }
+        if !self.header {
+            self.header = true;
+            writeln!(self.out(), "{HEADER}")?;
+        }
+        self.closed = true;
+        self.flush()
+    }
+}
+
+impl<W: Write> Drop for CsvWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn read_with_header_mapping() {
+        let csv = "Channel;Time;ID;Extended;Data;Comment\n\
+                   1;0.5;0x123;0;DE AD;first\n\
+                   2;1.25;1ABCDE;1;;\n";
+        let records = CsvReader::new(Cursor::new(csv))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, Duration::from_millis(500));
+        assert_eq!(records[0].channel, Some(1));
+        assert_eq!(records[0].frame.can_id(), 0x123);
+        assert_eq!(records[0].frame.data(), &[0xDE, 0xAD]);
+
+        assert_eq!(records[1].channel, Some(2));
+        assert!(records[1].frame.is_extended_frame());
+        assert!(records[1].frame.data().is_empty());
+    }
+
+    #[test]
+    fn missing_column_in_header() {
+        let csv = "time,data\n0.1,00\n";
+        let mut reader = CsvReader::new(Cursor::new(csv));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(TraceError::Parse { line: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn written_records_read_back() {
+        let records = vec![
+            TraceRecord {
+                timestamp: Duration::from_micros(250),
+                channel: None,
+                frame: TraceFrame::Can(
+                    CanFrame::new(0x7E8, MessageType::Standard, &[1, 2]).unwrap(),
+                ),
+            },
+            TraceRecord {
+                timestamp: Duration::from_millis(2),
+                channel: Some(3),
+                frame: TraceFrame::CanFd(
+                    CanFdFrame::new(0x1ABCDE, MessageType::Extended, &[0xAA; 12], true, true)
+                        .unwrap(),
+                ),
+            },
+        ];
+        let mut annotations = Annotations::new();
+        annotations.tag("rule;7");
+        annotations.set("signal", "EngineSpeed");
+
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_record(&records[0]).unwrap();
+        writer.write_annotated(&records[1], &annotations).unwrap();
+        let csv = writer.finish().unwrap();
+        assert!(String::from_utf8_lossy(&csv).ends_with(";rule 7;signal=EngineSpeed\n"));
+
+        let read = CsvReader::new(Cursor::new(csv))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records);
+    }
+}
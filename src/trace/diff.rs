@@ -0,0 +1,162 @@
+//! Comparison of two recorded traces, e.g. of a golden trace with the trace of a test run.
+
+use crate::trace::{TraceFrame, TraceRecord};
+
+use std::time::Duration;
+
+/// Difference between the expected and the actual trace. `index` is the position of the
+/// record in both traces, timestamps are relative to the first record of the trace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// A frame with a different ID or ID type was recorded.
+    Id {
+        index: usize,
+        expected: TraceFrame,
+        actual: TraceFrame,
+    },
+    /// The frame has the expected ID but a different payload.
+    Payload {
+        index: usize,
+        can_id: u32,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// The frame is off by more than the tolerance from its expected time.
+    Timing {
+        index: usize,
+        can_id: u32,
+        expected: Duration,
+        actual: Duration,
+    },
+    /// The actual trace ended before this frame.
+    Missing { index: usize, expected: TraceFrame },
+    /// The actual trace continues after the end of the expected trace.
+    Extra { index: usize, actual: TraceFrame },
+}
+
+/// Compares the records of `actual` with `expected` one by one and returns all divergences.
+///
+/// Frames are compared by their ID, payload and time since the first record, which may differ
+/// by up to `tolerance`. Channels are not compared. The comparison doesn't resynchronize, after
+/// a missing or extra frame the following records are reported as diverging as well.
+pub fn diff_traces(
+    expected: &[TraceRecord],
+    actual: &[TraceRecord],
+    tolerance: Duration,
+) -> Vec<Divergence> {
+    let expected_start = expected.first().map(|r| r.timestamp).unwrap_or_default();
+    let actual_start = actual.first().map(|r| r.timestamp).unwrap_or_default();
+    let mut divergences = Vec::new();
+
+    for (index, (e, a)) in expected.iter().zip(actual).enumerate() {
+        if e.frame.can_id() != a.frame.can_id()
+            || e.frame.is_extended_frame() != a.frame.is_extended_frame()
+        {
+            divergences.push(Divergence::Id {
+                index,
+                expected: e.frame,
+                actual: a.frame,
+            });
+            continue;
+        }
+
+        if e.frame.data() != a.frame.data() {
+            divergences.push(Divergence::Payload {
+                index,
+                can_id: e.frame.can_id(),
+                expected: e.frame.data().to_vec(),
+                actual: a.frame.data().to_vec(),
+            });
+        }
+
+        let expected_time = e.timestamp.saturating_sub(expected_start);
+        let actual_time = a.timestamp.saturating_sub(actual_start);
+        if expected_time.abs_diff(actual_time) > tolerance {
+            divergences.push(Divergence::Timing {
+                index,
+                can_id: e.frame.can_id(),
+                expected: expected_time,
+                actual: actual_time,
+            });
+        }
+    }
+
+    let common = expected.len().min(actual.len());
+    divergences.extend(
+        expected[common..]
+            .iter()
+            .enumerate()
+            .map(|(i, r)| Divergence::Missing {
+                index: common + i,
+                expected: r.frame,
+            }),
+    );
+    divergences.extend(
+        actual[common..]
+            .iter()
+            .enumerate()
+            .map(|(i, r)| Divergence::Extra {
+                index: common + i,
+                actual: r.frame,
+            }),
+    );
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::test_util::record;
+
+    #[test]
+    fn report_divergences() {
+        let us = Duration::from_micros;
+        let expected = vec![
+            record(us(1_000), Some(1), 0x100, &[1]),
+            record(us(2_000), Some(1), 0x200, &[2]),
+            record(us(3_000), Some(1), 0x300, &[3]),
+            record(us(4_000), Some(1), 0x400, &[4]),
+            record(us(5_000), Some(1), 0x500, &[5]),
+        ];
+        // same trace recorded 10 s later
+        let actual = vec![
+            record(us(10_001_000), Some(1), 0x100, &[1]),
+            record(us(10_002_050), Some(1), 0x200, &[2]),
+            record(us(10_003_000), Some(1), 0x300, &[9]),
+            record(us(10_005_000), Some(1), 0x401, &[4]),
+        ];
+
+        assert!(diff_traces(&expected, &expected, Duration::ZERO).is_empty());
+        assert_eq!(
+            diff_traces(&expected, &actual, Duration::from_micros(100)),
+            vec![
+                Divergence::Payload {
+                    index: 2,
+                    can_id: 0x300,
+                    expected: vec![3],
+                    actual: vec![9],
+                },
+                Divergence::Id {
+                    index: 3,
+                    expected: expected[3].frame,
+                    actual: actual[3].frame,
+                },
+                Divergence::Missing {
+                    index: 4,
+                    expected: expected[4].frame,
+                },
+            ]
+        );
+
+        let divergences = diff_traces(&expected[..2], &actual[..2], Duration::from_micros(10));
+        assert_eq!(
+            divergences,
+            vec![Divergence::Timing {
+                index: 1,
+                can_id: 0x200,
+                expected: Duration::from_micros(1_000),
+                actual: Duration::from_micros(1_050),
+            }]
+        );
+    }
+}
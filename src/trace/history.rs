@@ -0,0 +1,251 @@
+//! An in-memory store of recent records for tools which scrub back and forth in time.
+
+use crate::filter::FilterSet;
+use crate::socket::MessageType;
+use crate::trace::{Annotations, TraceError, TraceRecord, TraceSink};
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Keeps the latest records, ordered by timestamp and indexed by ID, so that time ranges can be
+/// queried without reading the trace file again. The oldest records are dropped when the
+/// capacity or the maximum age is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::filter::{FilterSet, IdMatch};
+/// # use peak_can::socket::{CanFrame, MessageType};
+/// # use peak_can::trace::{FrameHistory, TraceFrame, TraceRecord};
+/// # use std::time::Duration;
+/// let mut history = FrameHistory::new(10_000).with_max_age(Duration::from_secs(60));
+/// for (ms, id) in [(0, 0x100), (10, 0x200), (20, 0x100), (30, 0x200)] {
+///     history.push(TraceRecord {
+///         timestamp: Duration::from_millis(ms),
+///         channel: Some(1),
+///         frame: TraceFrame::Can(CanFrame::new(id, MessageType::Standard, &[])?),
+///     });
+/// }
+///
+/// let t0 = Duration::from_millis(5);
+/// let t1 = Duration::from_millis(30);
+/// let filter = FilterSet::deny_all().allow(IdMatch::List(vec![0x100]));
+/// assert_eq!(history.frames_between(t0, t1, &filter).count(), 1);
+/// assert_eq!(history.frames_between(t0, t1, &FilterSet::allow_all()).count(), 2);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameHistory {
+    records: VecDeque<TraceRecord>,
+    // sequence number of the first record
+    first: u64,
+    // sequence numbers of the records per ID and extended flag
+    ids: HashMap<(u32, bool), VecDeque<u64>>,
+    capacity: usize,
+    max_age: Option<Duration>,
+}
+
+impl FrameHistory {
+    /// A history of at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        FrameHistory {
+            records: VecDeque::new(),
+            first: 0,
+            ids: HashMap::new(),
+            capacity,
+            max_age: None,
+        }
+    }
+
+    /// Also drops records older than `max_age` before the newest record.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Stores `record`. Records are expected in time order, an older record is sorted in at the
+    /// cost of rebuilding the ID index.
+    pub fn push(&mut self, record: TraceRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let in_order = self
+            .records
+            .back()
+            .is_none_or(|last| last.timestamp <= record.timestamp);
+        if in_order {
+            let seq = self.first + self.records.len() as u64;
+            self.ids.entry(key(&record)).or_default().push_back(seq);
+            self.records.push_back(record);
+        } else {
+            let index = self
+                .records
+                .partition_point(|r| r.timestamp <= record.timestamp);
+            self.records.insert(index, record);
+            self.reindex();
+        }
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.ids.clear();
+    }
+
+    /// Timestamps of the oldest and the newest record.
+    pub fn time_span(&self) -> Option<(Duration, Duration)> {
+        Some((
+            self.records.front()?.timestamp,
+            self.records.back()?.timestamp,
+        ))
+    }
+
+    /// The records from `t0` up to but excluding `t1` accepted by `filter`, oldest first.
+    pub fn frames_between<'a>(
+        &'a self,
+        t0: Duration,
+        t1: Duration,
+        filter: &'a FilterSet,
+    ) -> impl Iterator<Item = &'a TraceRecord> {
+        let start = self.records.partition_point(|r| r.timestamp < t0);
+        let end = self
+            .records
+            .partition_point(|r| r.timestamp < t1)
+            .max(start);
+        self.records
+            .range(start..end)
+            .filter(|record| filter.accepts(record.frame.can_id(), msg_type(record)))
+    }
+
+    /// The records of a single ID from `t0` up to but excluding `t1`, found by the index instead
+    /// of visiting every record of the range.
+    pub fn frames_of(
+        &self,
+        can_id: u32,
+        msg_type: MessageType,
+        t0: Duration,
+        t1: Duration,
+    ) -> impl Iterator<Item = &TraceRecord> {
+        let seqs = self.ids.get(&(can_id, msg_type == MessageType::Extended));
+        let seqs = seqs.map(|seqs| {
+            let start = seqs.partition_point(|&seq| self.at(seq).timestamp < t0);
+            let end = seqs
+                .partition_point(|&seq| self.at(seq).timestamp < t1)
+                .max(start);
+            seqs.range(start..end)
+        });
+        seqs.into_iter().flatten().map(|&seq| self.at(seq))
+    }
+
+    /// The last record of an ID at or before `t`, e.g. the state of a message at a cursor
+    /// position.
+    pub fn last_of(&self, can_id: u32, msg_type: MessageType, t: Duration) -> Option<&TraceRecord> {
+        let seqs = self.ids.get(&(can_id, msg_type == MessageType::Extended))?;
+        let end = seqs.partition_point(|&seq| self.at(seq).timestamp <= t);
+        Some(self.at(*seqs.get(end.checked_sub(1)?)?))
+    }
+
+    fn at(&self, seq: u64) -> &TraceRecord {
+        &self.records[(seq - self.first) as usize]
+    }
+
+    fn evict(&mut self) {
+        let newest = self.records.back().map(|r| r.timestamp);
+        while let Some(oldest) = self.records.front() {
+            let expired = self
+                .max_age
+                .zip(newest)
+                .is_some_and(|(max_age, newest)| newest.saturating_sub(oldest.timestamp) > max_age);
+            if self.records.len() <= self.capacity && !expired {
+                break;
+            }
+            let key = key(oldest);
+            if let Some(seqs) = self.ids.get_mut(&key) {
+                seqs.pop_front();
+                if seqs.is_empty() {
+                    self.ids.remove(&key);
+                }
+            }
+            self.records.pop_front();
+            self.first += 1;
+        }
+    }
+
+    fn reindex(&mut self) {
+        self.ids.clear();
+        for (index, record) in self.records.iter().enumerate() {
+            let seq = self.first + index as u64;
+            self.ids.entry(key(record)).or_default().push_back(seq);
+        }
+    }
+}
+
+fn key(record: &TraceRecord) -> (u32, bool) {
+    (record.frame.can_id(), record.frame.is_extended_frame())
+}
+
+fn msg_type(record: &TraceRecord) -> MessageType {
+    if record.frame.is_extended_frame() {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    }
+}
+
+impl TraceSink for FrameHistory {
+    fn on_frame(&mut self, record: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+        self.push(record.clone());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::test_util::record;
+
+    #[test]
+    fn eviction_and_late_records() {
+        let ms = Duration::from_millis;
+        let mut history = FrameHistory::new(4).with_max_age(ms(100));
+        for (t, id) in [(0, 1), (10, 2), (20, 1), (40, 1), (30, 2)] {
+            history.push(record(ms(t), None, id, &[]));
+        }
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.time_span(), Some((ms(10), ms(40))));
+
+        let times = |history: &FrameHistory, id| {
+            history
+                .frames_of(id, MessageType::Standard, ms(0), ms(1000))
+                .map(|r| r.timestamp.as_millis())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(&history, 1), [20, 40]);
+        assert_eq!(times(&history, 2), [10, 30]);
+        assert_eq!(
+            history.last_of(2, MessageType::Standard, ms(29)),
+            Some(&record(ms(10), None, 2, &[]))
+        );
+        assert_eq!(history.last_of(2, MessageType::Standard, ms(5)), None);
+        assert_eq!(history.last_of(2, MessageType::Extended, ms(50)), None);
+
+        history.push(record(ms(135), None, 3, &[]));
+        assert_eq!(history.time_span(), Some((ms(40), ms(135))));
+        assert_eq!(times(&history, 2), [] as [u128; 0]);
+        let all = FilterSet::allow_all();
+        assert_eq!(history.frames_between(ms(40), ms(135), &all).count(), 1);
+        assert_eq!(history.frames_between(ms(135), ms(40), &all).count(), 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! JSON Lines traces, one object per record, e.g. for log processors and data frames.
+//!
+//! Each line holds the fields `time` (seconds), `channel` (`null` if unknown), `id`, `extended`,
+//! `fd`, `brs`, `data` (hex string), `tags` (array of strings) and `annotations` (object of
+//! strings).
+
+use crate::trace::{Annotations, TraceError, TraceFrame, TraceRecord, TraceWriter};
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Writes records with the timestamps as stored in them.
+///
+/// # Examples
+///
+/// ```
+/// # use peak_can::socket::{CanFrame, MessageType};
+/// # use peak_can::trace::json::JsonWriter;
+/// # use peak_can::trace::{Annotations, TraceFrame, TraceRecord};
+/// # use std::time::Duration;
+/// let record = TraceRecord {
+///     timestamp: Duration::from_millis(1500),
+///     channel: Some(1),
+///     frame: TraceFrame::Can(CanFrame::new(0x123, MessageType::Standard, &[0xDE, 0xAD])?),
+/// };
+/// let mut annotations = Annotations::new();
+/// annotations.set("signal", "EngineSpeed");
+///
+/// let mut writer = JsonWriter::new(Vec::new());
+/// writer.write_annotated(&record, &annotations)?;
+/// assert_eq!(
+///     String::from_utf8(writer.finish()?)?,
+///     concat!(
+///         r#"{"time":1.500000,"channel":1,"id":291,"extended":false,"fd":false,"brs":false,"#,
+///         r#""data":"DEAD","tags":[],"annotations":{"signal":"EngineSpeed"}}"#,
+///         "\n"
+///     )
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct JsonWriter<W: Write> {
+    // only taken by `finish`
+    writer: Option<W>,
+    closed: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonWriter {
+            writer: Some(writer),
+            closed: false,
+        }
+    }
+
+    /// Writes `record` with its annotations.
+    pub fn write_annotated(
+        &mut self,
+        record: &TraceRecord,
+        annotations: &Annotations,
+    ) -> Result<(), TraceError> {
+        if self.closed {
+            return Err(TraceError::Closed);
+        }
+        let frame = &record.frame;
+        let (fd, brs) = match frame {
+            TraceFrame::Can(_) => (false, false),
+            TraceFrame::CanFd(fd) => (true, fd.is_brs()),
+        };
+        let channel = record
+            .channel
+            .map_or_else(|| String::from("null"), |c| c.to_string());
+        let data = frame
+            .data()
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<String>();
+        let tags = annotations.tags().map(quote).collect::<Vec<_>>().join(",");
+        let values = annotations
+            .values()
+            .map(|(key, value)| format!("{}:{}", quote(key), quote(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        writeln!(
+            self.out(),
+            r#"{{"time":{}.{:06},"channel":{channel},"id":{},"extended":{},"fd":{fd},"brs":{brs},"data":"{data}","tags":[{tags}],"annotations":{{{values}}}}}"#,
+            record.timestamp.as_secs(),
+            record.timestamp.subsec_micros(),
+            frame.can_id(),
+            frame.is_extended_frame(),
+        )?;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, TraceError> {
+        self.close()?;
+        Ok(self.writer.take().expect("writer taken twice"))
+    }
+
+    fn out(&mut self) -> &mut W {
+        self.writer.as_mut().expect("writer used after finish")
+    }
+}
+
+/// Quotes `s` as a JSON string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<W: Write> TraceWriter for JsonWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        self.write_annotated(record, &Annotations::new())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.out().flush()?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), TraceError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.flush()
+    }
+}
+
+impl<W: Write> Drop for JsonWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_annotations() {
+        assert_eq!(quote("rule \"7\"\n\\"), r#""rule \"7\"\n\\""#);
+        assert_eq!(quote("\u{1}"), r#""\u0001""#);
+    }
+}
@@ -0,0 +1,250 @@
+//! Time ordered merging of several trace files.
+//!
+//! Each source is expected to be sorted by time on its own, which is the case for all capture
+//! formats. Unsorted traces can be put in order with [sort_by_time] first.
+
+use crate::trace::{TraceError, TraceReader, TraceRecord};
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+/// Correction applied to the timestamps of a single source to bring all sources onto a common
+/// time base.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TimeOffset {
+    #[default]
+    None,
+    Add(Duration),
+    /// Subtracts the duration, saturating at zero.
+    Subtract(Duration),
+    /// Makes the first record of the source start at zero, e.g. for absolute UNIX timestamps.
+    RebaseToZero,
+}
+
+struct Source {
+    reader: TraceReader,
+    channel: Option<u16>,
+    offset: TimeOffset,
+    base: Option<Duration>,
+}
+
+impl Source {
+    fn next(&mut self) -> Option<Result<TraceRecord, TraceError>> {
+        let mut record = match self.reader.next()? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err)),
+        };
+
+        record.timestamp = match self.offset {
+            TimeOffset::None => record.timestamp,
+            TimeOffset::Add(offset) => record.timestamp + offset,
+            TimeOffset::Subtract(offset) => record.timestamp.saturating_sub(offset),
+            TimeOffset::RebaseToZero => {
+                let base = *self.base.get_or_insert(record.timestamp);
+                record.timestamp.saturating_sub(base)
+            }
+        };
+        if self.channel.is_some() {
+            record.channel = self.channel;
+        }
+        Some(Ok(record))
+    }
+}
+
+struct Pending {
+    source: usize,
+    record: TraceRecord,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // equal timestamps keep the order in which the sources were added
+        self.record
+            .timestamp
+            .cmp(&other.record.timestamp)
+            .then(self.source.cmp(&other.source))
+    }
+}
+
+/// K-way merge of trace readers into a single time ordered stream.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::trace::{self, TimeOffset, TraceMerge};
+/// let mut merge = TraceMerge::new();
+/// merge.add(trace::open("powertrain.log")?, Some(1), TimeOffset::RebaseToZero);
+/// merge.add(trace::open("body.csv")?, Some(2), TimeOffset::RebaseToZero);
+///
+/// for record in merge {
+///     let record = record?;
+///     println!("{:?} {:?} {:x}", record.timestamp, record.channel, record.frame.can_id());
+/// }
+/// # Ok::<(), peak_can::trace::TraceError>(())
+/// ```
+#[derive(Default)]
+pub struct TraceMerge {
+    sources: Vec<Source>,
+    heap: BinaryHeap<Reverse<Pending>>,
+    refill: Vec<usize>,
+}
+
+impl TraceMerge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source. If `channel` is set it replaces the channel of all records of this
+    /// source, which tags records of single bus captures with the bus they were recorded on.
+    pub fn add<I>(&mut self, reader: I, channel: Option<u16>, offset: TimeOffset)
+    where
+        I: Iterator<Item = Result<TraceRecord, TraceError>> + 'static,
+    {
+        self.refill.push(self.sources.len());
+        self.sources.push(Source {
+            reader: Box::new(reader),
+            channel,
+            offset,
+            base: None,
+        });
+    }
+}
+
+impl Iterator for TraceMerge {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(source) = self.refill.pop() {
+            match self.sources[source].next() {
+                Some(Ok(record)) => self.heap.push(Reverse(Pending { source, record })),
+                Some(Err(err)) => {
+                    // the source is asked again for its next record on the following call
+                    self.refill.push(source);
+                    return Some(Err(err));
+                }
+                None => {}
+            }
+        }
+
+        let Reverse(Pending { source, record }) = self.heap.pop()?;
+        self.refill.push(source);
+        Some(Ok(record))
+    }
+}
+
+/// Sorts records by timestamp, keeping the file order of records with equal timestamps.
+pub fn sort_by_time(records: &mut [TraceRecord]) {
+    records.sort_by_key(|record| record.timestamp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::test_util::record;
+
+    #[test]
+    fn merge_in_time_order_with_channel_tags() {
+        let ms = Duration::from_millis;
+        let mut merge = TraceMerge::new();
+        merge.add(
+            vec![
+                Ok(record(ms(1), None, 0x1, &[])),
+                Ok(record(ms(5), None, 0x2, &[])),
+                Ok(record(ms(9), None, 0x3, &[])),
+            ]
+            .into_iter(),
+            Some(1),
+            TimeOffset::None,
+        );
+        merge.add(
+            vec![
+                Ok(record(ms(2), None, 0x4, &[])),
+                Ok(record(ms(5), None, 0x5, &[])),
+            ]
+            .into_iter(),
+            Some(2),
+            TimeOffset::None,
+        );
+
+        let merged = merge
+            .map(|r| r.map(|r| (r.timestamp.as_millis(), r.channel, r.frame.can_id())))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            merged,
+            vec![
+                (1, Some(1), 0x1),
+                (2, Some(2), 0x4),
+                (5, Some(1), 0x2),
+                (5, Some(2), 0x5),
+                (9, Some(1), 0x3),
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_align_epochs() {
+        let ms = Duration::from_millis;
+        let mut merge = TraceMerge::new();
+        merge.add(
+            vec![
+                Ok(record(ms(1_000_003), None, 0x1, &[])),
+                Ok(record(ms(1_000_010), None, 0x2, &[])),
+            ]
+            .into_iter(),
+            None,
+            TimeOffset::RebaseToZero,
+        );
+        merge.add(
+            vec![Ok(record(ms(4), None, 0x3, &[]))].into_iter(),
+            None,
+            TimeOffset::Subtract(Duration::from_millis(2)),
+        );
+        merge.add(
+            vec![Ok(record(ms(0), None, 0x4, &[]))].into_iter(),
+            None,
+            TimeOffset::Add(Duration::from_millis(20)),
+        );
+
+        let merged = merge
+            .map(|r| r.map(|r| (r.timestamp.as_millis(), r.frame.can_id())))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(merged, vec![(0, 0x1), (2, 0x3), (7, 0x2), (20, 0x4)]);
+    }
+
+    #[test]
+    fn errors_are_passed_through() {
+        let ms = Duration::from_millis;
+        let mut merge = TraceMerge::new();
+        let failing = vec![
+            Ok(record(ms(1), None, 0x1, &[])),
+            Err(TraceError::Parse {
+                line: 2,
+                message: String::from("bad"),
+            }),
+            Ok(record(ms(3), None, 0x2, &[])),
+        ];
+        merge.add(failing.into_iter(), None, TimeOffset::None);
+
+        let merged = merge.collect::<Vec<_>>();
+        assert_eq!(merged.len(), 3);
+        assert!(merged[1].is_err());
+        assert_eq!(merged[2].as_ref().unwrap().frame.can_id(), 0x2);
+    }
+}
@@ -0,0 +1,158 @@
+//! Recording a channel with the driver trace and a [TraceWriter] at the same time.
+
+use crate::socket::ReceivedFrame;
+use crate::trace::{SetTraceStatus, TraceError, TraceRecord, TraceWriter};
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Mirrors received frames into a [TraceWriter] while the driver writes its own trace file.
+///
+/// The driver trace is enabled right before the start time of the mirror is taken, so both
+/// captures share the same start and can be compared frame by frame, e.g. when frames are
+/// suspected to be lost between the driver and the application.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, ReceivedFrame, RecvCan};
+/// # use peak_can::socket::usb::UsbCanSocket;
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::TraceMirror;
+/// # use peak_can::trace::candump::CandumpWriter;
+/// # use std::fs::File;
+/// let socket = UsbCanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let writer = CandumpWriter::new(File::create("capture.log")?);
+/// let mut mirror = TraceMirror::start(&socket, writer)?;
+///
+/// for _ in 0..1000 {
+///     let (frame, timestamp, host_time) = socket.recv_with_host_time()?;
+///     mirror.record(&ReceivedFrame::from_can(frame, &timestamp, host_time, Some(1), 0))?;
+/// }
+/// mirror.stop()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct TraceMirror<'a, S: SetTraceStatus, W: TraceWriter> {
+    socket: &'a S,
+    /// `None` once stopped.
+    writer: Option<W>,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+impl<'a, S: SetTraceStatus, W: TraceWriter> TraceMirror<'a, S, W> {
+    /// Enables the driver trace of `socket` and starts the mirror clock.
+    pub fn start(socket: &'a S, writer: W) -> Result<Self, TraceError> {
+        socket.set_tracing(true)?;
+        Ok(TraceMirror {
+            socket,
+            writer: Some(writer),
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        })
+    }
+
+    /// Wall clock time at which both captures were started.
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// Writes a received frame. Timestamps are absolute UNIX times derived from the common start
+    /// time and the host receive time of the frame.
+    pub fn record(&mut self, received: &ReceivedFrame) -> Result<(), TraceError> {
+        let since_epoch = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let record = TraceRecord {
+            timestamp: since_epoch
+                + received
+                    .host_timestamp
+                    .saturating_duration_since(self.started),
+            ..TraceRecord::from(received)
+        };
+        match self.writer.as_mut() {
+            Some(writer) => writer.write_record(&record),
+            None => Ok(()),
+        }
+    }
+
+    /// Disables the driver trace and flushes the writer.
+    pub fn stop(mut self) -> Result<W, TraceError> {
+        let mut writer = self.writer.take().expect("writer taken only by stop");
+        self.socket.set_tracing(false)?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// Disables the driver trace if the mirror was not stopped, e.g. when an error ended the
+/// capture early.
+impl<S: SetTraceStatus, W: TraceWriter> Drop for TraceMirror<'_, S, W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.socket.set_tracing(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CanError;
+    use crate::socket::{CanFrame, MessageType, Timestamp};
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct DriverTrace {
+        enabled: Cell<bool>,
+    }
+
+    impl SetTraceStatus for DriverTrace {
+        fn set_tracing(&self, enable: bool) -> Result<(), CanError> {
+            self.enabled.set(enable);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Records(Vec<TraceRecord>);
+
+    impl TraceWriter for Records {
+        fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+            self.0.push(record.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), TraceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn driver_trace_follows_mirror() {
+        let driver = DriverTrace::default();
+        let mut mirror = TraceMirror::start(&driver, Records::default()).unwrap();
+        assert!(driver.enabled.get());
+
+        let frame = CanFrame::new(0x10, MessageType::Standard, &[1]).unwrap();
+        let host_time = mirror.started + Duration::from_millis(5);
+        let received = ReceivedFrame::from_can(frame, &Timestamp::default(), host_time, Some(3), 0);
+        mirror.record(&received).unwrap();
+        let start = mirror.started_at().duration_since(UNIX_EPOCH).unwrap();
+
+        let records = mirror.stop().unwrap();
+        assert!(!driver.enabled.get());
+        assert_eq!(records.0[0].channel, Some(3));
+        assert_eq!(records.0[0].timestamp, start + Duration::from_millis(5));
+    }
+
+    #[test]
+    fn drop_disables_driver_trace() {
+        let driver = DriverTrace::default();
+        let mirror = TraceMirror::start(&driver, Records::default()).unwrap();
+        assert!(driver.enabled.get());
+        drop(mirror);
+        assert!(!driver.enabled.get());
+    }
+}
@@ -0,0 +1,247 @@
+//! Driver-side trace configuration and readers and writers for recorded trace files.
+//!
+//! The `Trace*` traits control the trace file written by the PCAN-Basic driver itself. Recorded
+//! traces in various formats can be read back with [open], which detects the file format, and
+//! written with the [TraceWriter] implementations of the format modules.
+
+mod analyze;
+mod annotation;
+pub mod asc;
+pub mod candump;
+pub mod csv;
+mod diff;
+mod history;
+pub mod json;
+mod merge;
+mod mirror;
+pub mod pcapng;
+mod reader;
+pub mod ring;
+mod sink;
+pub mod trc;
+mod writer;
+
+pub use analyze::{AnalyzerConfig, Anomaly, TraceAnalyzer, TraceReport, analyze};
+pub use annotation::Annotations;
+pub use diff::{Divergence, diff_traces};
+pub use history::FrameHistory;
+pub use merge::{TimeOffset, TraceMerge, sort_by_time};
+pub use mirror::TraceMirror;
+pub use reader::{TraceError, TraceFormat, TraceFrame, TraceReader, TraceRecord, open};
+pub use sink::TraceSink;
+pub use writer::{ShutdownWriter, TraceWriter};
+
+use crate::channel::Channel;
+use crate::error::CanError;
+use crate::peak_can;
+use std::path::{Path, PathBuf};
+
+/* TRACE LOCATION traits */
+
+pub(crate) trait HasTraceLocation {}
+
+pub trait TraceLocation {
+    fn trace_location(&self) -> Result<PathBuf, CanError>;
+}
+
+impl<T: HasTraceLocation + Channel> TraceLocation for T {
+    fn trace_location(&self) -> Result<PathBuf, CanError> {
+        let mut data = [0u8; peak_can::MAX_LENGTH_VERSION_STRING as usize];
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_LOCATION as u8, &mut data)?;
+        match std::str::from_utf8(&data) {
+            Ok(s) => {
+                let s = s.trim_matches(char::from(0));
+                Ok(PathBuf::from(s))
+            }
+            Err(_) => Err(CanError::Unknown),
+        }
+    }
+}
+
+pub(crate) trait HasSetTraceLocation {}
+
+pub trait SetTraceLocation {
+    fn set_trace_location<P: AsRef<Path>>(&self, path: P) -> Result<(), CanError>;
+}
+
+impl<T: HasSetTraceLocation + Channel> SetTraceLocation for T {
+    fn set_trace_location<P: AsRef<Path>>(&self, path: P) -> Result<(), CanError> {
+        let data = match path.as_ref().to_str() {
+            None => {
+                return Err(CanError::Unknown);
+            }
+            Some(s) => String::from(s),
+        };
+        self.backend().set_value(
+            self.channel(),
+            peak_can::PEAK_TRACE_LOCATION as u8,
+            data.as_bytes(),
+        )
+    }
+}
+
+pub fn set_default_trace_location<T: SetTraceLocation>(value: &T) -> Result<(), CanError> {
+    value.set_trace_location(" ")
+}
+
+/* TRACE STATUS traits */
+
+pub(crate) trait HasTraceStatus {}
+
+pub trait TraceStatus {
+    fn is_tracing(&self) -> Result<bool, CanError>;
+}
+
+impl<T: HasTraceStatus + Channel> TraceStatus for T {
+    fn is_tracing(&self) -> Result<bool, CanError> {
+        let mut data = [0u8; 4];
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_STATUS as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        if code == peak_can::PEAK_PARAMETER_ON {
+            Ok(true)
+        } else if code == peak_can::PEAK_PARAMETER_OFF {
+            Ok(false)
+        } else {
+            Err(CanError::Unknown)
+        }
+    }
+}
+
+pub(crate) trait HasSetTraceStatus {}
+
+pub trait SetTraceStatus {
+    fn set_tracing(&self, enable: bool) -> Result<(), CanError>;
+}
+
+impl<T: HasSetTraceStatus + Channel> SetTraceStatus for T {
+    fn set_tracing(&self, enable: bool) -> Result<(), CanError> {
+        let data = match enable {
+            true => peak_can::PEAK_PARAMETER_ON.to_le_bytes(),
+            false => peak_can::PEAK_PARAMETER_OFF.to_le_bytes(),
+        };
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_STATUS as u8, &data)
+    }
+}
+
+/* TRACE SIZE traits */
+
+pub(crate) trait HasTraceSize {}
+
+pub trait TraceSize {
+    fn trace_size(&self) -> Result<u8, CanError>;
+}
+
+impl<T: HasTraceSize + Channel> TraceSize for T {
+    fn trace_size(&self) -> Result<u8, CanError> {
+        let mut data = [0u8; 4];
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_SIZE as u8, &mut data)?;
+        Ok(data[0])
+    }
+}
+
+pub(crate) trait HasSetTraceSize {}
+
+pub trait SetTraceSize {
+    fn set_trace_size(&self, size_mb: u8) -> Result<(), CanError>;
+}
+
+impl<T: HasSetTraceSize + Channel> SetTraceSize for T {
+    fn set_trace_size(&self, size_mb: u8) -> Result<(), CanError> {
+        let data = [size_mb];
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_SIZE as u8, &data)
+    }
+}
+
+pub fn set_default_trace_size<T: SetTraceSize>(value: &T) -> Result<(), CanError> {
+    value.set_trace_size(0)
+}
+
+/* TRACE CONFIGURE traits */
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TraceFile {
+    Single,
+    Segmented,
+    Date,
+    Time,
+    Overwrite,
+}
+
+impl TryFrom<u32> for TraceFile {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            peak_can::TRACE_FILE_SINGLE => Ok(TraceFile::Single),
+            peak_can::TRACE_FILE_SEGMENTED => Ok(TraceFile::Segmented),
+            peak_can::TRACE_FILE_DATE => Ok(TraceFile::Date),
+            peak_can::TRACE_FILE_TIME => Ok(TraceFile::Time),
+            peak_can::TRACE_FILE_OVERWRITE => Ok(TraceFile::Overwrite),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<TraceFile> for u32 {
+    fn from(value: TraceFile) -> Self {
+        match value {
+            TraceFile::Single => peak_can::TRACE_FILE_SINGLE,
+            TraceFile::Segmented => peak_can::TRACE_FILE_SEGMENTED,
+            TraceFile::Date => peak_can::TRACE_FILE_DATE,
+            TraceFile::Time => peak_can::TRACE_FILE_TIME,
+            TraceFile::Overwrite => peak_can::TRACE_FILE_OVERWRITE,
+        }
+    }
+}
+
+pub(crate) trait HasTraceConfigure {}
+
+pub trait TraceConfigure {
+    fn trace_configuration(&self) -> Result<TraceFile, CanError>;
+}
+
+impl<T: HasTraceConfigure + Channel> TraceConfigure for T {
+    fn trace_configuration(&self) -> Result<TraceFile, CanError> {
+        let mut data = [0u8; 4];
+        self.backend().get_value(self.channel(), peak_can::PEAK_TRACE_CONFIGURE as u8, &mut data)?;
+        let code = u32::from_le_bytes(data);
+        match TraceFile::try_from(code) {
+            Ok(log_config) => Ok(log_config),
+            Err(_) => Err(CanError::Unknown),
+        }
+    }
+}
+
+pub(crate) trait HasSetTraceConfigure {}
+
+pub trait SetTraceConfigure {
+    fn configure_trace(&self, config: TraceFile) -> Result<(), CanError>;
+}
+
+impl<T: HasSetTraceConfigure + Channel> SetTraceConfigure for T {
+    fn configure_trace(&self, config: TraceFile) -> Result<(), CanError> {
+        let data = u32::from(config).to_le_bytes();
+        self.backend().set_value(self.channel(), peak_can::PEAK_TRACE_CONFIGURE as u8, &data)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::{TraceFrame, TraceRecord};
+    use crate::socket::{CanFrame, MessageType};
+    use std::time::Duration;
+
+    /// A record of a classic frame with a standard ID.
+    pub(crate) fn record(
+        timestamp: Duration,
+        channel: Option<u16>,
+        can_id: u32,
+        data: &[u8],
+    ) -> TraceRecord {
+        TraceRecord {
+            timestamp,
+            channel,
+            frame: TraceFrame::Can(CanFrame::new(can_id, MessageType::Standard, data).unwrap()),
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! Reader and writer for pcapng captures of SocketCAN interfaces, as written by Wireshark or
+//! `tcpdump -i can0`.
+//!
+//! Frames are stored in enhanced packet blocks of the `LINKTYPE_CAN_SOCKETCAN` link type: a
+//! 16 byte header with the identifier in network byte order, followed by the data. Each channel
+//! is an interface named `can` followed by the channel number, the reader takes the channel
+//! from the trailing digits of the interface name. Blocks of other types and packets of other
+//! link types are skipped.
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType};
+use crate::trace::{TraceError, TraceFrame, TraceRecord, TraceWriter};
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+const OPT_ENDOFOPT: u16 = 0;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+
+/// Size of the SocketCAN frame header before the data.
+const CAN_HEADER_SIZE: usize = 8;
+
+/// Blocks larger than this are rejected instead of allocated.
+const MAX_BLOCK_SIZE: u32 = 16 * 1024 * 1024;
+
+struct Interface {
+    link_type: u16,
+    /// Timestamp units per second.
+    resolution: u64,
+    channel: Option<u16>,
+}
+
+pub struct PcapngReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+    /// Number of blocks read, reported as the line of parse errors.
+    block: usize,
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(reader: R) -> Self {
+        PcapngReader {
+            reader,
+            big_endian: false,
+            interfaces: Vec::new(),
+            block: 0,
+        }
+    }
+
+    fn u16_at(&self, bytes: &[u8], at: usize) -> u16 {
+        let bytes = bytes[at..at + 2].try_into().unwrap();
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], at: usize) -> u32 {
+        let bytes = bytes[at..at + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    /// Reads the next block, returning its type and the bytes after the length field.
+    fn read_block(&mut self) -> Result<Option<(u32, Vec<u8>)>, TraceError> {
+        let mut header = [0u8; 8];
+        match self.reader.read_exact(&mut header[..4]) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        self.reader.read_exact(&mut header[4..])?;
+        self.block += 1;
+
+        let block_type = u32::from_le_bytes(header[..4].try_into().unwrap());
+        if block_type == SECTION_HEADER_BLOCK {
+            // each section declares its byte order
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+            self.big_endian = match u32::from_le_bytes(magic) {
+                BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => true,
+                _ => return Err(self.error("invalid byte order magic")),
+            };
+            let length = self.u32_at(&header, 4);
+            let mut body = magic.to_vec();
+            self.read_body(length, &mut body)?;
+            self.interfaces.clear();
+            return Ok(Some((block_type, body)));
+        }
+
+        let block_type = self.u32_at(&header, 0);
+        let mut body = Vec::new();
+        self.read_body(self.u32_at(&header, 4), &mut body)?;
+        Ok(Some((block_type, body)))
+    }
+
+    fn read_body(&mut self, length: u32, body: &mut Vec<u8>) -> Result<(), TraceError> {
+        if length < 12 || !length.is_multiple_of(4) || length > MAX_BLOCK_SIZE {
+            return Err(self.error("invalid block length"));
+        }
+        let start = body.len();
+        body.resize(length as usize - 8, 0);
+        self.reader.read_exact(&mut body[start..])?;
+        if self.u32_at(body, body.len() - 4) != length {
+            return Err(self.error("block lengths differ"));
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> TraceError {
+        TraceError::Parse {
+            line: self.block,
+            message: message.to_string(),
+        }
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> Result<(), TraceError> {
+        if body.len() < 12 {
+            return Err(self.error("interface description too short"));
+        }
+        let mut interface = Interface {
+            link_type: self.u16_at(body, 0),
+            resolution: 1_000_000,
+            channel: None,
+        };
+        let mut at = 8;
+        while at + 4 <= body.len() - 4 {
+            let code = self.u16_at(body, at);
+            let len = self.u16_at(body, at + 2) as usize;
+            let value = body
+                .get(at + 4..at + 4 + len)
+                .ok_or_else(|| self.error("option beyond the block"))?;
+            match code {
+                OPT_ENDOFOPT => break,
+                IF_NAME => interface.channel = channel_of(value),
+                IF_TSRESOL if len == 1 => {
+                    interface.resolution = resolution(value[0])
+                        .ok_or_else(|| self.error("unsupported timestamp resolution"))?;
+                }
+                _ => {}
+            }
+            at += 4 + len.next_multiple_of(4);
+        }
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn packet(&self, body: &[u8]) -> Result<Option<TraceRecord>, TraceError> {
+        if body.len() < 24 {
+            return Err(self.error("enhanced packet too short"));
+        }
+        let interface = self
+            .interfaces
+            .get(self.u32_at(body, 0) as usize)
+            .ok_or_else(|| self.error("packet of an undefined interface"))?;
+        if interface.link_type != LINKTYPE_CAN_SOCKETCAN {
+            return Ok(None);
+        }
+
+        let ticks = (self.u32_at(body, 4) as u64) << 32 | self.u32_at(body, 8) as u64;
+        let timestamp = Duration::new(
+            ticks / interface.resolution,
+            ((ticks % interface.resolution) as u128 * 1_000_000_000 / interface.resolution as u128)
+                as u32,
+        );
+        let captured = self.u32_at(body, 12) as usize;
+        let data = body
+            .get(20..20 + captured)
+            .ok_or_else(|| self.error("packet beyond the block"))?;
+        let frame = decode_frame(data).map_err(|message| self.error(&message))?;
+
+        Ok(Some(TraceRecord {
+            timestamp,
+            channel: interface.channel,
+            frame,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for PcapngReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (block_type, body) = match self.read_block() {
+                Ok(Some(block)) => block,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            let result = match block_type {
+                SECTION_HEADER_BLOCK => Ok(None),
+                INTERFACE_DESCRIPTION_BLOCK => self.add_interface(&body).map(|_| None),
+                ENHANCED_PACKET_BLOCK => self.packet(&body),
+                _ => Ok(None),
+            };
+            match result {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// The channel number at the end of an interface name such as `can1` or `vcan12`.
+fn channel_of(name: &[u8]) -> Option<u16> {
+    let name = std::str::from_utf8(name).ok()?.trim_end_matches('\0');
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    name[prefix.len()..].parse().ok()
+}
+
+/// Units per second of an `if_tsresol` option, a power of 10 or, with the high bit set, of 2.
+fn resolution(tsresol: u8) -> Option<u64> {
+    let exponent = (tsresol & 0x7F) as u32;
+    if tsresol & 0x80 == 0 {
+        10u64.checked_pow(exponent)
+    } else {
+        2u64.checked_pow(exponent)
+    }
+}
+
+fn decode_frame(packet: &[u8]) -> Result<TraceFrame, String> {
+    if packet.len() < CAN_HEADER_SIZE {
+        return Err(String::from("CAN frame too short"));
+    }
+    let can_id = u32::from_be_bytes(packet[..4].try_into().unwrap());
+    if can_id & CAN_ERR_FLAG != 0 {
+        return Err(format!("error frame {can_id:08X} not supported"));
+    }
+    let msg_type = if can_id & CAN_EFF_FLAG != 0 {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    };
+    let id = can_id & CAN_EFF_MASK;
+    let len = packet[4] as usize;
+    let flags = packet[5];
+
+    if can_id & CAN_RTR_FLAG != 0 {
+        return CanFrame::new_remote(id, msg_type, len as u8)
+            .map(TraceFrame::Can)
+            .map_err(|err| err.to_string());
+    }
+    let data = packet
+        .get(CAN_HEADER_SIZE..CAN_HEADER_SIZE + len)
+        .ok_or_else(|| format!("CAN frame {id:X} shorter than its length {len}"))?;
+    if flags & CANFD_FDF != 0 || len > 8 {
+        CanFdFrame::new(id, msg_type, data, true, flags & CANFD_BRS != 0)
+            .map(|frame| TraceFrame::CanFd(frame.with_esi(flags & CANFD_ESI != 0)))
+            .map_err(|err| err.to_string())
+    } else {
+        CanFrame::new(id, msg_type, data)
+            .map(TraceFrame::Can)
+            .map_err(|err| err.to_string())
+    }
+}
+
+fn encode_frame(frame: &TraceFrame) -> Vec<u8> {
+    let mut can_id = frame.can_id();
+    if frame.is_extended_frame() {
+        can_id |= CAN_EFF_FLAG;
+    }
+    let (flags, size) = match frame {
+        TraceFrame::CanFd(fd) if fd.is_fd_frame() => {
+            let brs = if fd.is_brs() { CANFD_BRS } else { 0 };
+            let esi = if fd.is_esi() { CANFD_ESI } else { 0 };
+            (CANFD_FDF | brs | esi, 64)
+        }
+        _ => (0, 8),
+    };
+    let len = match frame {
+        TraceFrame::Can(can) if can.is_remote_frame() => {
+            can_id |= CAN_RTR_FLAG;
+            can.dlc()
+        }
+        frame => frame.data().len() as u8,
+    };
+
+    let mut packet = vec![0u8; CAN_HEADER_SIZE + size];
+    packet[..4].copy_from_slice(&can_id.to_be_bytes());
+    packet[4] = len;
+    packet[5] = flags;
+    if !matches!(frame, TraceFrame::Can(can) if can.is_remote_frame()) {
+        let data = frame.data();
+        packet[CAN_HEADER_SIZE..CAN_HEADER_SIZE + data.len()].copy_from_slice(data);
+    }
+    packet
+}
+
+/// Writes records as a pcapng capture with nanosecond timestamps. An interface is added for
+/// each channel when its first record is written, records without a channel belong to `can0`.
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    section_written: bool,
+    /// Channel of each interface, the index is the interface ID.
+    interfaces: Vec<u16>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(writer: W) -> Self {
+        PcapngWriter {
+            writer,
+            section_written: false,
+            interfaces: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let length = (body.len() + 12) as u32;
+        let mut block = Vec::with_capacity(length as usize);
+        block.extend_from_slice(&block_type.to_le_bytes());
+        block.extend_from_slice(&length.to_le_bytes());
+        block.extend_from_slice(body);
+        block.extend_from_slice(&length.to_le_bytes());
+        self.writer.write_all(&block)
+    }
+
+    fn write_section_header(&mut self) -> io::Result<()> {
+        if self.section_written {
+            return Ok(());
+        }
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // the section length is not known in advance
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        self.write_block(SECTION_HEADER_BLOCK, &body)?;
+        self.section_written = true;
+        Ok(())
+    }
+
+    fn interface(&mut self, channel: u16) -> io::Result<u32> {
+        if let Some(id) = self.interfaces.iter().position(|c| *c == channel) {
+            return Ok(id as u32);
+        }
+        let mut body = Vec::with_capacity(32);
+        body.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // no snapshot length limit
+        body.extend_from_slice(&0u32.to_le_bytes());
+        push_option(&mut body, IF_NAME, format!("can{channel}").as_bytes());
+        push_option(&mut body, IF_TSRESOL, &[9]);
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+        self.interfaces.push(channel);
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+impl<W: Write> TraceWriter for PcapngWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        self.write_section_header()?;
+        let interface = self.interface(record.channel.unwrap_or(0))?;
+
+        let packet = encode_frame(&record.frame);
+        let nanos = record.timestamp.as_nanos() as u64;
+        let mut body = Vec::with_capacity(20 + packet.len());
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((nanos >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(nanos as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        body.extend_from_slice(&packet);
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// A capture without records gets the section header.
+    fn close(&mut self) -> Result<(), TraceError> {
+        self.write_section_header()?;
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_back() {
+        let remote = CanFrame::new_remote(0x7DF, MessageType::Standard, 8).unwrap();
+        let classic = CanFrame::new(0x1234_5678, MessageType::Extended, &[1, 2, 3]).unwrap();
+        let fd = CanFdFrame::new(0x123, MessageType::Standard, &[0xAB; 12], true, true)
+            .unwrap()
+            .with_esi(true);
+        let records = [
+            (1_436_509_052_249_713_123, Some(0), TraceFrame::Can(remote)),
+            (
+                1_436_509_052_250_000_000,
+                Some(12),
+                TraceFrame::Can(classic),
+            ),
+            (1_436_509_052_300_000_000, None, TraceFrame::CanFd(fd)),
+        ]
+        .map(|(nanos, channel, frame)| TraceRecord {
+            timestamp: Duration::from_nanos(nanos),
+            channel,
+            frame,
+        });
+
+        let mut writer = PcapngWriter::new(Vec::new());
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let capture = writer.into_inner();
+        assert_eq!(capture[..4], [0x0A, 0x0D, 0x0D, 0x0A]);
+
+        let read = PcapngReader::new(Cursor::new(capture))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read[..2], records[..2]);
+        // the record without a channel was written to can0
+        assert_eq!(read[2].channel, Some(0));
+        assert_eq!(read[2].frame, records[2].frame);
+    }
+
+    #[test]
+    fn read_big_endian_microseconds() {
+        let mut capture = Vec::new();
+        let mut block = |block_type: u32, body: &[u8]| {
+            let length = (body.len() + 12) as u32;
+            capture.extend_from_slice(&block_type.to_be_bytes());
+            capture.extend_from_slice(&length.to_be_bytes());
+            capture.extend_from_slice(body);
+            capture.extend_from_slice(&length.to_be_bytes());
+        };
+        block(
+            SECTION_HEADER_BLOCK,
+            &[
+                0x1A, 0x2B, 0x3C, 0x4D, 0, 1, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+        );
+        // Ethernet interface, then the CAN interface without options
+        block(INTERFACE_DESCRIPTION_BLOCK, &[0, 1, 0, 0, 0, 0, 0, 0]);
+        block(INTERFACE_DESCRIPTION_BLOCK, &[0, 227, 0, 0, 0, 0, 0, 0]);
+        // interface 1 at 1_000_001 µs, 12 of 12 bytes captured
+        let mut packet = vec![
+            0, 0, 0, 1, 0, 0, 0, 0, 0, 0x0F, 0x42, 0x41, 0, 0, 0, 12, 0, 0, 0, 12,
+        ];
+        packet.extend_from_slice(&[0x00, 0x00, 0x01, 0x23, 2, 0, 0, 0, 0xCA, 0xFE, 0, 0]);
+        block(ENHANCED_PACKET_BLOCK, &packet);
+
+        let records = PcapngReader::new(Cursor::new(capture))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].timestamp, Duration::new(1, 1000));
+        assert_eq!(records[0].channel, None);
+        assert_eq!(records[0].frame.can_id(), 0x123);
+        assert_eq!(records[0].frame.data(), [0xCA, 0xFE]);
+    }
+}
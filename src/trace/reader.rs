@@ -0,0 +1,275 @@
+//! Format independent trace records and trace format detection.
+
+use crate::error::CanError;
+use crate::socket::AnyFrame;
+use crate::trace::asc::AscReader;
+use crate::trace::candump::CandumpReader;
+use crate::trace::csv::CsvReader;
+use crate::trace::pcapng::PcapngReader;
+use crate::trace::ring::{self, RingReader};
+use crate::trace::trc::TrcReader;
+
+use core::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Number of bytes inspected to detect the format of a trace file.
+const SNIFF_LENGTH: u64 = 4096;
+
+/// The frame of a [TraceRecord].
+pub type TraceFrame = AnyFrame;
+
+/// A single frame read from a trace file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    /// Time of the record as stored in the file. Depending on the format this is either
+    /// relative to the start of the recording or an absolute UNIX time.
+    pub timestamp: Duration,
+    /// Channel number as stored in the file, if the format records one.
+    pub channel: Option<u16>,
+    pub frame: TraceFrame,
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// Configuring the driver trace of a channel failed.
+    Can(CanError),
+    /// A line or record of the file could not be parsed.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The file format could not be detected.
+    UnknownFormat,
+    /// A record was written after the writer was closed.
+    Closed,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(err) => write!(f, "{err}"),
+            TraceError::Can(err) => write!(f, "{err}"),
+            TraceError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            TraceError::UnknownFormat => write!(f, "Unknown trace format"),
+            TraceError::Closed => write!(f, "Trace writer is closed"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(value: io::Error) -> Self {
+        TraceError::Io(value)
+    }
+}
+
+impl From<CanError> for TraceError {
+    fn from(value: CanError) -> Self {
+        TraceError::Can(value)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// PEAK-System PCAN trace.
+    Trc,
+    /// Vector ASCII log.
+    Asc,
+    /// Log of the Linux can-utils `candump -l`.
+    Candump,
+    /// Comma or semicolon separated values with a header line.
+    Csv,
+    /// PCAP next generation capture of SocketCAN interfaces.
+    Pcapng,
+    /// Memory-mapped capture ring written by [RingWriter](crate::trace::ring::RingWriter).
+    Ring,
+}
+
+impl TraceFormat {
+    /// Detects the format from the first bytes of a file, falling back to the file extension
+    /// when the content is not conclusive. Vector BLF files are not supported and not detected.
+    pub fn detect(header: &[u8], extension: Option<&str>) -> Option<TraceFormat> {
+        if header.starts_with(&[0x0A, 0x0D, 0x0D, 0x0A]) {
+            return Some(TraceFormat::Pcapng);
+        }
+        if header.starts_with(ring::MAGIC) {
+            return Some(TraceFormat::Ring);
+        }
+
+        let text = String::from_utf8_lossy(header);
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        if let Some(first) = lines.next() {
+            let lower = first.to_ascii_lowercase();
+            if first.starts_with(';') {
+                return Some(TraceFormat::Trc);
+            }
+            if lower.starts_with("date ") || lower.starts_with("base ") {
+                return Some(TraceFormat::Asc);
+            }
+            if first.starts_with('(') && first.contains(')') && first.contains('#') {
+                return Some(TraceFormat::Candump);
+            }
+            let delimited = lower.split([',', ';']).map(str::trim).collect::<Vec<_>>();
+            if delimited.len() > 2 && delimited.contains(&"id") {
+                return Some(TraceFormat::Csv);
+            }
+        }
+
+        match extension.map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("trc") => Some(TraceFormat::Trc),
+            Some("asc") => Some(TraceFormat::Asc),
+            Some("log") => Some(TraceFormat::Candump),
+            Some("csv") => Some(TraceFormat::Csv),
+            Some("pcapng") => Some(TraceFormat::Pcapng),
+            _ => None,
+        }
+    }
+}
+
+/// Boxed iterator over the records of a trace file, as returned by [open].
+pub type TraceReader = Box<dyn Iterator<Item = Result<TraceRecord, TraceError>>>;
+
+/// Opens a trace file, detecting its format from the content and the file extension.
+/// Vector BLF files, which are mostly compressed, fail with [TraceError::UnknownFormat].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::trace;
+/// for record in trace::open("capture.log")? {
+///     let record = record?;
+///     println!("{:?} {:x}", record.timestamp, record.frame.can_id());
+/// }
+/// # Ok::<(), peak_can::trace::TraceError>(())
+/// ```
+pub fn open<P: AsRef<Path>>(path: P) -> Result<TraceReader, TraceError> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+
+    let mut header = Vec::new();
+    file.by_ref().take(SNIFF_LENGTH).read_to_end(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let extension = path.extension().and_then(|e| e.to_str());
+    let format = TraceFormat::detect(&header, extension).ok_or(TraceError::UnknownFormat)?;
+
+    let reader = BufReader::new(file);
+    match format {
+        TraceFormat::Trc => Ok(Box::new(TrcReader::new(reader))),
+        TraceFormat::Asc => Ok(Box::new(AscReader::new(reader))),
+        TraceFormat::Candump => Ok(Box::new(CandumpReader::new(reader))),
+        TraceFormat::Csv => Ok(Box::new(CsvReader::new(reader))),
+        TraceFormat::Ring => Ok(Box::new(RingReader::new(reader)?)),
+        TraceFormat::Pcapng => Ok(Box::new(PcapngReader::new(reader))),
+    }
+}
+
+/// Parses a decimal number of seconds such as `1436509052.249713` without losing precision.
+pub(crate) fn parse_seconds(s: &str) -> Option<Duration> {
+    let (secs, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let secs = if secs.is_empty() {
+        0
+    } else {
+        secs.parse::<u64>().ok()?
+    };
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u32>().ok()? * 10u32.pow(9 - fraction.len() as u32)
+    };
+    Some(Duration::new(secs, nanos))
+}
+
+/// Parses hexadecimal bytes, either contiguous (`DEADBEEF`) or separated by whitespace.
+pub(crate) fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let digits = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte = pair.iter().collect::<String>();
+            u8::from_str_radix(&byte, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_binary_formats() {
+        assert_eq!(TraceFormat::detect(b"LOGG\x90\x00", Some("blf")), None);
+        assert_eq!(
+            TraceFormat::detect(&[0x0A, 0x0D, 0x0D, 0x0A, 0, 0], None),
+            Some(TraceFormat::Pcapng)
+        );
+        assert_eq!(
+            TraceFormat::detect(b"PCANRING\x01\x00", None),
+            Some(TraceFormat::Ring)
+        );
+    }
+
+    #[test]
+    fn detect_text_formats() {
+        let trc = b";$FILEVERSION=2.1\n;$STARTTIME=45000.5\n";
+        assert_eq!(TraceFormat::detect(trc, None), Some(TraceFormat::Trc));
+
+        let asc = b"date Wed Jun 5 10:00:00.000 am 2024\nbase hex  timestamps absolute\n";
+        assert_eq!(TraceFormat::detect(asc, None), Some(TraceFormat::Asc));
+
+        let candump = b"(1436509052.249713) can0 123#DEADBEEF\n";
+        assert_eq!(
+            TraceFormat::detect(candump, None),
+            Some(TraceFormat::Candump)
+        );
+
+        let csv = b"Time,Channel,ID,Data\n0.001,1,123,DE AD\n";
+        assert_eq!(TraceFormat::detect(csv, None), Some(TraceFormat::Csv));
+    }
+
+    #[test]
+    fn detect_by_extension() {
+        assert_eq!(
+            TraceFormat::detect(b"", Some("TRC")),
+            Some(TraceFormat::Trc)
+        );
+        assert_eq!(
+            TraceFormat::detect(b"", Some("log")),
+            Some(TraceFormat::Candump)
+        );
+        assert_eq!(TraceFormat::detect(b"", Some("txt")), None);
+    }
+
+    #[test]
+    fn seconds_and_hex_parsing() {
+        assert_eq!(parse_seconds("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            parse_seconds("1436509052.249713"),
+            Some(Duration::new(1436509052, 249_713_000))
+        );
+        assert_eq!(parse_seconds("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_seconds("1.x"), None);
+
+        assert_eq!(
+            parse_hex_bytes("DEADbeef"),
+            Some(vec![0xDE, 0xAD, 0xBE, 0xEF])
+        );
+        assert_eq!(parse_hex_bytes("01 02 0A"), Some(vec![1, 2, 10]));
+        assert_eq!(parse_hex_bytes(""), Some(vec![]));
+        assert_eq!(parse_hex_bytes("ABC"), None);
+    }
+}
@@ -0,0 +1,549 @@
+//! Memory-mapped capture rings for sustained high frame rates.
+//!
+//! [RingWriter] copies each record into a slot of a pre-allocated, memory-mapped file, so
+//! capturing costs no system call per frame. The file is a ring: once it is full, the oldest
+//! records are overwritten. The header holds the number of records written, which indexes the
+//! slot of the next record, and every slot holds the sequence number of its record.
+//!
+//! Rings are converted offline to a standard format with [convert], or read with [RingReader]
+//! and [open](crate::trace::open).
+//!
+//! The file starts with a header of 64 bytes, followed by slots of [RECORD_SIZE] bytes. All
+//! numbers are little endian.
+//!
+//! | Header offset | Field                          |
+//! |---------------|--------------------------------|
+//! | 0             | magic `PCANRING`               |
+//! | 8             | version (u32)                  |
+//! | 12            | slot size (u32)                |
+//! | 16            | number of slots (u64)          |
+//! | 24            | number of records written (u64) |
+//!
+//! | Slot offset | Field                                        |
+//! |-------------|----------------------------------------------|
+//! | 0           | sequence number (u64), starting at 1, 0 if empty |
+//! | 8           | timestamp in nanoseconds (u64)               |
+//! | 16          | channel (u16), `0xFFFF` if unknown           |
+//! | 18          | flags (u8): extended, FD, BRS, remote        |
+//! | 19          | data length, DLC of a remote frame (u8)      |
+//! | 20          | CAN ID (u32)                                 |
+//! | 24          | data (64 bytes)                              |
+
+use crate::socket::{CanFdFrame, CanFrame, MessageType};
+use crate::trace::{Annotations, TraceError, TraceFrame, TraceRecord, TraceSink, TraceWriter};
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+
+pub(crate) const MAGIC: &[u8; 8] = b"PCANRING";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+/// Size of a slot in bytes.
+pub const RECORD_SIZE: usize = 96;
+
+const NO_CHANNEL: u16 = 0xFFFF;
+const FLAG_EXTENDED: u8 = 0x01;
+const FLAG_FD: u8 = 0x02;
+const FLAG_BRS: u8 = 0x04;
+const FLAG_REMOTE: u8 = 0x08;
+
+/// Writes records into a memory-mapped ring file.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::dispatch::Dispatcher;
+/// # use peak_can::socket::{CanFdBitTiming, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::ring::{self, RingWriter};
+/// # use peak_can::trace::trc::TrcWriter;
+/// # use std::fs::File;
+/// let timing = CanFdBitTiming::new(10, 4, 13, 2, 5, 2, 6, 1)?;
+/// let socket = CanSocket::open_fd(UsbBus::USB1, &timing)?;
+/// let mut dispatcher = Dispatcher::new().with_channel(1);
+/// // one million records, about 96 MB
+/// dispatcher.add_sink(RingWriter::create("capture.ring", 1_000_000)?);
+/// dispatcher.step(&socket);
+///
+/// // later, offline
+/// let mut trc = TrcWriter::new(File::create("capture.trc")?);
+/// ring::convert("capture.ring", &mut trc)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct RingWriter {
+    map: map::Mapping,
+    capacity: u64,
+    written: u64,
+    closed: bool,
+    // kept open for the lifetime of the mapping
+    _file: File,
+}
+
+impl RingWriter {
+    /// Creates or truncates the file at `path` and allocates `capacity` slots.
+    pub fn create<P: AsRef<Path>>(path: P, capacity: u64) -> Result<RingWriter, TraceError> {
+        let len = usize::try_from(capacity)
+            .ok()
+            .and_then(|capacity| capacity.checked_mul(RECORD_SIZE))
+            .and_then(|slots| slots.checked_add(HEADER_SIZE))
+            .filter(|_| capacity > 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid ring capacity"))?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+        let mut map = map::Mapping::new(&file, len)?;
+
+        let header = map.as_mut_slice();
+        header[..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+        header[16..24].copy_from_slice(&capacity.to_le_bytes());
+        header[24..32].copy_from_slice(&0u64.to_le_bytes());
+
+        Ok(RingWriter {
+            map,
+            capacity,
+            written: 0,
+            closed: false,
+            _file: file,
+        })
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Number of records written, including those overwritten since.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl TraceWriter for RingWriter {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        if self.closed {
+            return Err(TraceError::Closed);
+        }
+        let slot = encode(record, self.written + 1);
+        let offset = HEADER_SIZE + (self.written % self.capacity) as usize * RECORD_SIZE;
+        let file = self.map.as_mut_slice();
+        file[offset..offset + RECORD_SIZE].copy_from_slice(&slot);
+        self.written += 1;
+        file[24..32].copy_from_slice(&self.written.to_le_bytes());
+        Ok(())
+    }
+
+    /// Writes the mapped pages back to the file.
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.map.flush()?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), TraceError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        TraceWriter::flush(self)
+    }
+}
+
+impl TraceSink for RingWriter {
+    fn on_frame(&mut self, record: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+        self.write_record(record)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        TraceWriter::flush(self)
+    }
+}
+
+impl Drop for RingWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn encode(record: &TraceRecord, sequence: u64) -> [u8; RECORD_SIZE] {
+    let mut slot = [0u8; RECORD_SIZE];
+    let frame = &record.frame;
+    let mut flags = 0;
+    if frame.is_extended_frame() {
+        flags |= FLAG_EXTENDED;
+    }
+    let len = match frame {
+        TraceFrame::Can(can) if can.is_remote_frame() => {
+            flags |= FLAG_REMOTE;
+            can.dlc()
+        }
+        TraceFrame::Can(can) => can.data().len() as u8,
+        TraceFrame::CanFd(fd) => {
+            flags |= FLAG_FD;
+            if fd.is_brs() {
+                flags |= FLAG_BRS;
+            }
+            fd.data().len() as u8
+        }
+    };
+    let nanos = u64::try_from(record.timestamp.as_nanos()).unwrap_or(u64::MAX);
+
+    slot[..8].copy_from_slice(&sequence.to_le_bytes());
+    slot[8..16].copy_from_slice(&nanos.to_le_bytes());
+    slot[16..18].copy_from_slice(&record.channel.unwrap_or(NO_CHANNEL).to_le_bytes());
+    slot[18] = flags;
+    slot[19] = len;
+    slot[20..24].copy_from_slice(&frame.can_id().to_le_bytes());
+    if flags & FLAG_REMOTE == 0 {
+        slot[24..24 + frame.data().len()].copy_from_slice(frame.data());
+    }
+    slot
+}
+
+fn decode(slot: &[u8]) -> Result<TraceRecord, String> {
+    let u64_at = |at: usize| u64::from_le_bytes(slot[at..at + 8].try_into().unwrap());
+    let channel = u16::from_le_bytes([slot[16], slot[17]]);
+    let flags = slot[18];
+    let len = slot[19] as usize;
+    let can_id = u32::from_le_bytes(slot[20..24].try_into().unwrap());
+    let msg_type = if flags & FLAG_EXTENDED != 0 {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    };
+    let data = slot
+        .get(24..24 + len)
+        .ok_or_else(|| format!("invalid data length {len}"))?;
+
+    let frame = if flags & FLAG_REMOTE != 0 {
+        CanFrame::new_remote(can_id, msg_type, len as u8).map(TraceFrame::Can)
+    } else if flags & FLAG_FD != 0 {
+        CanFdFrame::new(can_id, msg_type, data, true, flags & FLAG_BRS != 0).map(TraceFrame::CanFd)
+    } else {
+        CanFrame::new(can_id, msg_type, data).map(TraceFrame::Can)
+    }
+    .map_err(|err| err.to_string())?;
+
+    Ok(TraceRecord {
+        timestamp: Duration::from_nanos(u64_at(8)),
+        channel: (channel != NO_CHANNEL).then_some(channel),
+        frame,
+    })
+}
+
+/// Reads the records of a ring, oldest first.
+pub struct RingReader {
+    file: Vec<u8>,
+    slots: std::vec::IntoIter<usize>,
+}
+
+impl RingReader {
+    /// Reads a whole ring from `reader`.
+    pub fn new<R: Read>(mut reader: R) -> Result<RingReader, TraceError> {
+        let mut file = Vec::new();
+        reader.read_to_end(&mut file)?;
+        if file.len() < HEADER_SIZE || &file[..8] != MAGIC {
+            return Err(TraceError::UnknownFormat);
+        }
+        let header_u64 = |at: usize| u64::from_le_bytes(file[at..at + 8].try_into().unwrap());
+        let version = u32::from_le_bytes(file[8..12].try_into().unwrap());
+        let record_size = u32::from_le_bytes(file[12..16].try_into().unwrap()) as usize;
+        let capacity = header_u64(16);
+        let written = header_u64(24);
+        if version != VERSION || record_size != RECORD_SIZE {
+            return Err(TraceError::Parse {
+                line: 0,
+                message: format!("unsupported ring version {version}"),
+            });
+        }
+        let available = ((file.len() - HEADER_SIZE) / RECORD_SIZE) as u64;
+        if capacity == 0 || capacity > available {
+            return Err(TraceError::Parse {
+                line: 0,
+                message: format!("truncated ring of {capacity} slots"),
+            });
+        }
+
+        let first = written.saturating_sub(capacity);
+        let slots = (first..written)
+            .map(|index| HEADER_SIZE + (index % capacity) as usize * RECORD_SIZE)
+            .collect::<Vec<_>>();
+        Ok(RingReader {
+            file,
+            slots: slots.into_iter(),
+        })
+    }
+}
+
+impl Iterator for RingReader {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.slots.next()?;
+        let slot = &self.file[offset..offset + RECORD_SIZE];
+        Some(decode(slot).map_err(|message| TraceError::Parse {
+            line: (offset - HEADER_SIZE) / RECORD_SIZE + 1,
+            message,
+        }))
+    }
+}
+
+/// Writes the records of the ring at `path` to `writer`, oldest first, and closes it. Returns
+/// the number of records.
+pub fn convert<P: AsRef<Path>, W: TraceWriter>(path: P, writer: &mut W) -> Result<u64, TraceError> {
+    let mut count = 0;
+    for record in RingReader::new(File::open(path)?)? {
+        writer.write_record(&record?)?;
+        count += 1;
+    }
+    writer.close()?;
+    Ok(count)
+}
+
+#[cfg(unix)]
+mod map {
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    const PROT_READ: c_int = 0x1;
+    const PROT_WRITE: c_int = 0x2;
+    const MAP_SHARED: c_int = 0x1;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const MS_SYNC: c_int = 0x4;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const MS_SYNC: c_int = 0x10;
+
+    unsafe extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: isize,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int;
+    }
+
+    pub(super) struct Mapping {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    impl Mapping {
+        pub(super) fn new(file: &File, len: usize) -> io::Result<Mapping> {
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mapping {
+                ptr: ptr.cast(),
+                len,
+            })
+        }
+
+        pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+
+        pub(super) fn flush(&self) -> io::Result<()> {
+            match unsafe { msync(self.ptr.cast(), self.len, MS_SYNC) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            unsafe { munmap(self.ptr.cast(), self.len) };
+        }
+    }
+
+    // the mapping is owned exclusively, like a `Vec<u8>`
+    unsafe impl Send for Mapping {}
+}
+
+#[cfg(windows)]
+mod map {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    const PAGE_READWRITE: u32 = 0x04;
+    const FILE_MAP_WRITE: u32 = 0x02;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *const c_void,
+            protect: u32,
+            size_high: u32,
+            size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+        fn FlushViewOfFile(base: *const c_void, len: usize) -> i32;
+        fn UnmapViewOfFile(base: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub(super) struct Mapping {
+        ptr: *mut u8,
+        len: usize,
+        handle: *mut c_void,
+    }
+
+    impl Mapping {
+        pub(super) fn new(file: &File, len: usize) -> io::Result<Mapping> {
+            let size = len as u64;
+            let handle = unsafe {
+                CreateFileMappingW(
+                    file.as_raw_handle(),
+                    std::ptr::null(),
+                    PAGE_READWRITE,
+                    (size >> 32) as u32,
+                    size as u32,
+                    std::ptr::null(),
+                )
+            };
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let ptr = unsafe { MapViewOfFile(handle, FILE_MAP_WRITE, 0, 0, len) };
+            if ptr.is_null() {
+                let err = io::Error::last_os_error();
+                unsafe { CloseHandle(handle) };
+                return Err(err);
+            }
+            Ok(Mapping {
+                ptr: ptr.cast(),
+                len,
+                handle,
+            })
+        }
+
+        pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+
+        pub(super) fn flush(&self) -> io::Result<()> {
+            match unsafe { FlushViewOfFile(self.ptr as *const c_void, self.len) } {
+                0 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            unsafe {
+                UnmapViewOfFile(self.ptr as *const c_void);
+                CloseHandle(self.handle);
+            }
+        }
+    }
+
+    // the mapping is owned exclusively, like a `Vec<u8>`
+    unsafe impl Send for Mapping {}
+}
+
+#[cfg(not(any(unix, windows)))]
+mod map {
+    use std::fs::File;
+    use std::io;
+
+    pub(super) struct Mapping;
+
+    impl Mapping {
+        pub(super) fn new(_: &File, _: usize) -> io::Result<Mapping> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut []
+        }
+
+        pub(super) fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::csv::CsvWriter;
+
+    #[test]
+    fn ring_overwrites_oldest_and_converts() {
+        let path = std::env::temp_dir().join(format!("peak-can-ring-{}.ring", std::process::id()));
+        let records = (0..5u32)
+            .map(|i| TraceRecord {
+                timestamp: Duration::from_nanos(1_500 * i as u64),
+                channel: if i % 2 == 0 { Some(1) } else { None },
+                frame: match i {
+                    3 => TraceFrame::CanFd(
+                        CanFdFrame::new(
+                            0x1ABCDE,
+                            MessageType::Extended,
+                            &[i as u8; 24],
+                            true,
+                            true,
+                        )
+                        .unwrap(),
+                    ),
+                    4 => TraceFrame::Can(
+                        CanFrame::new_remote(0x7DF, MessageType::Standard, 8).unwrap(),
+                    ),
+                    _ => TraceFrame::Can(
+                        CanFrame::new(0x100 + i, MessageType::Standard, &[i as u8]).unwrap(),
+                    ),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let mut ring = RingWriter::create(&path, 3).unwrap();
+        for record in &records {
+            ring.write_record(record).unwrap();
+        }
+        assert_eq!(ring.written(), 5);
+        drop(ring);
+
+        let read = RingReader::new(File::open(&path).unwrap())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records[2..]);
+
+        let mut csv = CsvWriter::new(Vec::new());
+        assert_eq!(convert(&path, &mut csv).unwrap(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
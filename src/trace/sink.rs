@@ -0,0 +1,140 @@
+//! Destinations of the traffic seen by a [Dispatcher](crate::dispatch::Dispatcher).
+
+use crate::socket::BusState;
+use crate::trace::asc::AscWriter;
+use crate::trace::candump::CandumpWriter;
+use crate::trace::csv::CsvWriter;
+use crate::trace::json::JsonWriter;
+use crate::trace::pcapng::PcapngWriter;
+use crate::trace::trc::TrcWriter;
+use crate::trace::{Annotations, ShutdownWriter, TraceError, TraceRecord, TraceWriter};
+
+use std::io::Write;
+
+/// Receives the frames and bus state changes of a dispatcher, registered with
+/// [add_sink](crate::dispatch::Dispatcher::add_sink). The trace writers of the crate are sinks,
+/// custom sinks forward the traffic anywhere else, e.g. into a database.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::dispatch::Dispatcher;
+/// # use peak_can::socket::{Baudrate, BusState, CanSocket};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::trc::TrcWriter;
+/// # use peak_can::trace::{Annotations, TraceError, TraceRecord, TraceSink};
+/// # use std::fs::File;
+/// struct Counter(u64);
+///
+/// impl TraceSink for Counter {
+///     fn on_frame(&mut self, _: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+///         self.0 += 1;
+///         Ok(())
+///     }
+///
+///     fn on_state(&mut self, _: Option<BusState>, new: BusState) -> Result<(), TraceError> {
+///         println!("bus {new:?} after {} frames", self.0);
+///         Ok(())
+///     }
+///
+///     fn flush(&mut self) -> Result<(), TraceError> {
+///         Ok(())
+///     }
+/// }
+///
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut dispatcher = Dispatcher::new().with_channel(1);
+/// dispatcher.add_sink(TrcWriter::new(File::create("capture.trc")?));
+/// dispatcher.add_sink(Counter(0));
+/// dispatcher.run(&socket);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait TraceSink {
+    fn on_frame(
+        &mut self,
+        record: &TraceRecord,
+        annotations: &Annotations,
+    ) -> Result<(), TraceError>;
+
+    /// Called when the bus state changes, `old` is `None` before the first report.
+    fn on_state(&mut self, old: Option<BusState>, new: BusState) -> Result<(), TraceError> {
+        let _ = (old, new);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError>;
+}
+
+impl<T: TraceSink + ?Sized> TraceSink for Box<T> {
+    fn on_frame(
+        &mut self,
+        record: &TraceRecord,
+        annotations: &Annotations,
+    ) -> Result<(), TraceError> {
+        (**self).on_frame(record, annotations)
+    }
+
+    fn on_state(&mut self, old: Option<BusState>, new: BusState) -> Result<(), TraceError> {
+        (**self).on_state(old, new)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        (**self).flush()
+    }
+}
+
+/// Sinks writing the records, the formats have no place for annotations.
+macro_rules! record_sinks {
+    ($($writer:ident),*) => {
+        $(
+            impl<W: Write> TraceSink for $writer<W> {
+                fn on_frame(&mut self, record: &TraceRecord, _: &Annotations) -> Result<(), TraceError> {
+                    self.write_record(record)
+                }
+
+                fn flush(&mut self) -> Result<(), TraceError> {
+                    TraceWriter::flush(self)
+                }
+            }
+        )*
+    };
+}
+
+record_sinks!(AscWriter, TrcWriter, CandumpWriter, PcapngWriter);
+
+/// Sinks writing the annotations next to the records.
+macro_rules! annotated_sinks {
+    ($($writer:ident),*) => {
+        $(
+            impl<W: Write> TraceSink for $writer<W> {
+                fn on_frame(&mut self, record: &TraceRecord, annotations: &Annotations) -> Result<(), TraceError> {
+                    self.write_annotated(record, annotations)
+                }
+
+                fn flush(&mut self) -> Result<(), TraceError> {
+                    TraceWriter::flush(self)
+                }
+            }
+        )*
+    };
+}
+
+annotated_sinks!(CsvWriter, JsonWriter);
+
+impl<W: TraceSink> TraceSink for ShutdownWriter<W> {
+    fn on_frame(
+        &mut self,
+        record: &TraceRecord,
+        annotations: &Annotations,
+    ) -> Result<(), TraceError> {
+        self.with(|writer| writer.on_frame(record, annotations))
+    }
+
+    fn on_state(&mut self, old: Option<BusState>, new: BusState) -> Result<(), TraceError> {
+        self.with(|writer| writer.on_state(old, new))
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.with(|writer| writer.flush())
+    }
+}
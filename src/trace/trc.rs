@@ -0,0 +1,656 @@
+//! PEAK-System trace files (`.trc`) and the trace written by the PCAN-Basic driver itself.
+//!
+//! [TrcReader] reads the versions 1.x and 2.x of the format, [TrcWriter] writes version 2.1 as
+//! PCAN-View does. Timestamps are the offsets from the start of the recording, the absolute
+//! start time is available from [TrcReader::start_time]. Events other than frames, e.g. status
+//! changes or error counters, are skipped when reading.
+//!
+//! [DriverTrace] lets the driver record all traffic of a channel into a `.trc` file, which can
+//! afterwards be read with [TrcReader] or [open](crate::trace::open).
+
+use crate::error::CanError;
+use crate::socket::{CanFdFrame, CanFrame, MessageType, Timestamp};
+use crate::trace::reader::{parse_hex_bytes, parse_seconds};
+use crate::trace::{
+    SetTraceConfigure, SetTraceLocation, SetTraceSize, SetTraceStatus, TraceError, TraceFile,
+    TraceFrame, TraceRecord, TraceWriter,
+};
+
+use std::io::{BufRead, Lines, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Days between the epoch of `$STARTTIME` (1899-12-30) and the UNIX epoch.
+const OLE_UNIX_EPOCH_DAYS: f64 = 25_569.0;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Columns of version 2.0 files, which don't declare them in a `$COLUMNS` line.
+const COLUMNS_V20: &str = "N,O,T,I,d,l,D";
+/// Columns of version 2.1 files and later without a `$COLUMNS` line.
+const COLUMNS_V21: &str = "N,O,T,B,I,d,R,L,D";
+
+/* DRIVER TRACE */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverTraceConfig {
+    /// Directory of the trace file, the directory of the calling process if `None`.
+    pub location: Option<PathBuf>,
+    pub file: TraceFile,
+    /// Maximum size of a trace file in megabytes, `0` for the driver default of 10 MB.
+    pub size_mb: u8,
+}
+
+impl Default for DriverTraceConfig {
+    fn default() -> Self {
+        DriverTraceConfig {
+            location: None,
+            file: TraceFile::Single,
+            size_mb: 0,
+        }
+    }
+}
+
+/// A running trace of the driver on a channel, stopped when dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::Baudrate;
+/// # use peak_can::socket::usb::UsbCanSocket;
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::trc::{DriverTrace, DriverTraceConfig};
+/// # use peak_can::trace::TraceFile;
+/// let socket = UsbCanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let config = DriverTraceConfig {
+///     location: Some("/var/log/can".into()),
+///     file: TraceFile::Segmented,
+///     size_mb: 50,
+/// };
+/// let trace = DriverTrace::start(&socket, &config)?;
+/// // ... traffic is recorded ...
+/// trace.stop()?;
+/// # Ok::<(), peak_can::error::CanError>(())
+/// ```
+pub struct DriverTrace<'a, T: SetTraceStatus> {
+    socket: &'a T,
+    running: bool,
+}
+
+impl<'a, T> DriverTrace<'a, T>
+where
+    T: SetTraceLocation + SetTraceConfigure + SetTraceSize + SetTraceStatus,
+{
+    /// Configures the trace of the channel of `socket` and starts it.
+    pub fn start(socket: &'a T, config: &DriverTraceConfig) -> Result<Self, CanError> {
+        // the driver rejects configuration changes while tracing
+        socket.set_tracing(false)?;
+        match &config.location {
+            Some(location) => socket.set_trace_location(location)?,
+            None => crate::trace::set_default_trace_location(socket)?,
+        }
+        socket.set_trace_size(config.size_mb)?;
+        socket.configure_trace(config.file)?;
+        socket.set_tracing(true)?;
+
+        Ok(DriverTrace {
+            socket,
+            running: true,
+        })
+    }
+}
+
+impl<T: SetTraceStatus> DriverTrace<'_, T> {
+    pub fn stop(mut self) -> Result<(), CanError> {
+        self.running = false;
+        self.socket.set_tracing(false)
+    }
+}
+
+impl<T: SetTraceStatus> Drop for DriverTrace<'_, T> {
+    fn drop(&mut self) {
+        if self.running {
+            let _ = self.socket.set_tracing(false);
+        }
+    }
+}
+
+/* READER */
+
+pub struct TrcReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    version: (u8, u8),
+    columns: Option<Vec<char>>,
+    start_time: Option<SystemTime>,
+}
+
+impl<R: BufRead> TrcReader<R> {
+    pub fn new(reader: R) -> Self {
+        TrcReader {
+            lines: reader.lines(),
+            line: 0,
+            // files without a version line are the oldest format
+            version: (1, 0),
+            columns: None,
+            start_time: None,
+        }
+    }
+
+    /// Wall clock time of the start of the recording, known once the header has been read,
+    /// i.e. after the first record.
+    pub fn start_time(&self) -> Option<SystemTime> {
+        self.start_time
+    }
+
+    fn parse_header(&mut self, line: &str) {
+        let Some((key, value)) = line.trim_start_matches(';').split_once('=') else {
+            return;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "$FILEVERSION" => {
+                let (major, minor) = value.split_once('.').unwrap_or((value, "0"));
+                if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+                    self.version = (major, minor);
+                }
+            }
+            "$STARTTIME" => {
+                self.start_time = value
+                    .parse::<f64>()
+                    .ok()
+                    .map(|days| (days - OLE_UNIX_EPOCH_DAYS) * SECONDS_PER_DAY)
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .map(|secs| UNIX_EPOCH + secs);
+            }
+            "$COLUMNS" => {
+                self.columns = Some(parse_columns(value));
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_line(&self, fields: &[&str]) -> Result<Option<TraceRecord>, String> {
+        if self.version.0 >= 2 {
+            let default = if self.version.1 == 0 {
+                COLUMNS_V20
+            } else {
+                COLUMNS_V21
+            };
+            let columns = match &self.columns {
+                Some(columns) => columns.clone(),
+                None => parse_columns(default),
+            };
+            parse_v2(fields, &columns)
+        } else {
+            parse_v1(fields, self.version.1)
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TrcReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+
+            let trimmed = line.trim();
+            if trimmed.starts_with(';') {
+                self.parse_header(trimmed);
+                continue;
+            }
+            let fields = trimmed.split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+            match self.parse_line(&fields) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(message) => {
+                    return Some(Err(TraceError::Parse {
+                        line: self.line,
+                        message,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+fn parse_columns(value: &str) -> Vec<char> {
+    value
+        .split(',')
+        .filter_map(|column| column.trim().chars().next())
+        .collect()
+}
+
+/// Parses an offset in milliseconds such as `1059.900`.
+fn parse_offset(offset: &str) -> Result<Duration, String> {
+    parse_seconds(offset)
+        .map(|ms| ms / 1000)
+        .ok_or_else(|| format!("invalid time offset {offset}"))
+}
+
+/// Standard IDs are written with 4 hex digits, extended IDs with 8.
+fn parse_id(id: &str) -> Result<(u32, MessageType), String> {
+    let can_id = u32::from_str_radix(id, 16).map_err(|_| format!("invalid CAN ID {id}"))?;
+    let msg_type = if id.len() > 4 {
+        MessageType::Extended
+    } else {
+        MessageType::Standard
+    };
+    Ok((can_id, msg_type))
+}
+
+fn parse_data(fields: &[&str], len: usize) -> Result<Vec<u8>, String> {
+    fields
+        .get(..len)
+        .and_then(|bytes| parse_hex_bytes(&bytes.concat()))
+        .ok_or_else(|| format!("missing data of {len} bytes"))
+}
+
+fn fd_dlc_to_len(dlc: u8) -> usize {
+    match dlc {
+        0..=8 => dlc as usize,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
+
+/// `   1)      1059.9  Rx         0300  8  00 00 00 00 00 00 00 00`, version 1.0 lacks the
+/// type, version 1.2 adds a bus column and version 1.3 a reserved column after the ID.
+fn parse_v1(fields: &[&str], minor: u8) -> Result<Option<TraceRecord>, String> {
+    let [number, offset, rest @ ..] = fields else {
+        return Ok(None);
+    };
+    if !number.ends_with(')') {
+        return Ok(None);
+    }
+    let timestamp = parse_offset(offset)?;
+
+    let (channel, rest) = match (minor >= 2, rest) {
+        (true, [bus, rest @ ..]) => {
+            let bus = bus.parse().map_err(|_| format!("invalid bus {bus}"))?;
+            (Some(bus), rest)
+        }
+        _ => (None, rest),
+    };
+    let rest = match (minor >= 1, rest) {
+        (true, ["Rx" | "Tx", rest @ ..]) => rest,
+        // warnings, errors and other events
+        (true, _) => return Ok(None),
+        (false, rest) => rest,
+    };
+    let (id, rest) = rest.split_first().ok_or("missing CAN ID")?;
+    let (can_id, msg_type) = parse_id(id)?;
+    let rest = match (minor >= 3, rest) {
+        (true, [_reserved, rest @ ..]) => rest,
+        _ => rest,
+    };
+    let (dlc, data) = rest.split_first().ok_or("missing DLC")?;
+    let dlc = dlc
+        .parse::<u8>()
+        .map_err(|_| format!("invalid DLC {dlc}"))?;
+
+    let frame = match data.first() {
+        Some(&"RTR") => CanFrame::new_remote(can_id, msg_type, dlc),
+        _ => CanFrame::new(can_id, msg_type, &parse_data(data, dlc as usize)?),
+    }
+    .map_err(|err| err.to_string())?;
+
+    Ok(Some(TraceRecord {
+        timestamp,
+        channel,
+        frame: TraceFrame::Can(frame),
+    }))
+}
+
+/// Parses a line of a version 2.x file along the declared `columns`, the data column `D` takes
+/// all remaining fields.
+fn parse_v2(fields: &[&str], columns: &[char]) -> Result<Option<TraceRecord>, String> {
+    let column = |name: char| {
+        columns
+            .iter()
+            .position(|c| *c == name)
+            .and_then(|i| fields.get(i).copied())
+    };
+    let data = columns
+        .iter()
+        .position(|c| *c == 'D')
+        .and_then(|i| fields.get(i..))
+        .unwrap_or_default();
+
+    let offset = column('O').ok_or("missing time offset")?;
+    let timestamp = parse_offset(offset)?;
+    let channel = match column('B') {
+        Some(bus) => Some(bus.parse().map_err(|_| format!("invalid bus {bus}"))?),
+        None => None,
+    };
+    let kind = column('T').ok_or("missing message type")?;
+    if !matches!(kind, "DT" | "RR" | "FD" | "FB" | "FE" | "BI") {
+        // status, error counters, error frames and events
+        return Ok(None);
+    }
+    let id = column('I').ok_or("missing CAN ID")?;
+    let (can_id, msg_type) = parse_id(id)?;
+
+    let len = match (column('l'), column('L')) {
+        (Some(len), _) => len
+            .parse::<usize>()
+            .map_err(|_| format!("invalid data length {len}"))?,
+        (None, Some(dlc)) => {
+            let dlc = dlc
+                .parse::<u8>()
+                .map_err(|_| format!("invalid DLC {dlc}"))?;
+            match kind {
+                "DT" | "RR" => dlc as usize,
+                _ => fd_dlc_to_len(dlc),
+            }
+        }
+        (None, None) => return Err(String::from("missing data length")),
+    };
+
+    let frame = match kind {
+        "RR" => CanFrame::new_remote(can_id, msg_type, len as u8).map(TraceFrame::Can),
+        "DT" => CanFrame::new(can_id, msg_type, &parse_data(data, len)?).map(TraceFrame::Can),
+        _ => {
+            let brs = matches!(kind, "FB" | "BI");
+            CanFdFrame::new(can_id, msg_type, &parse_data(data, len)?, true, brs)
+                .map(TraceFrame::CanFd)
+        }
+    }
+    .map_err(|err| err.to_string())?;
+
+    Ok(Some(TraceRecord {
+        timestamp,
+        channel,
+        frame,
+    }))
+}
+
+/* WRITER */
+
+/// Writes records as a version 2.1 trace. The time of the first record is the start of the
+/// recording, records without a channel are written to bus 1.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::trace::trc::TrcWriter;
+/// # use std::fs::File;
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let mut writer = TrcWriter::new(File::create("capture.trc")?);
+/// for received in socket.iter().take(1000) {
+///     let (frame, timestamp) = received?;
+///     writer.write_frame(1, &frame, &timestamp)?;
+/// }
+/// writer.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// A writer dropped without [finish](TrcWriter::finish) is closed as well, errors are ignored
+/// then.
+pub struct TrcWriter<W: Write> {
+    // only taken by `finish`
+    writer: Option<W>,
+    started_at: SystemTime,
+    start: Option<Duration>,
+    count: u64,
+    closed: bool,
+}
+
+impl<W: Write> TrcWriter<W> {
+    pub fn new(writer: W) -> Self {
+        TrcWriter {
+            writer: Some(writer),
+            started_at: SystemTime::now(),
+            start: None,
+            count: 0,
+            closed: false,
+        }
+    }
+
+    /// Sets the wall clock time written into the header, the current time by default.
+    pub fn with_start_time(mut self, started_at: SystemTime) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    /// Writes a frame received on `channel` with its device timestamp.
+    pub fn write_frame(
+        &mut self,
+        channel: u16,
+        frame: &CanFrame,
+        timestamp: &Timestamp,
+    ) -> Result<(), TraceError> {
+        self.write_record(&TraceRecord {
+            timestamp: Duration::from_micros(timestamp.as_micros()),
+            channel: Some(channel),
+            frame: TraceFrame::Can(*frame),
+        })
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, TraceError> {
+        self.close()?;
+        Ok(self.writer.take().expect("writer taken twice"))
+    }
+
+    fn out(&mut self) -> &mut W {
+        self.writer.as_mut().expect("writer used after finish")
+    }
+
+    fn write_header(&mut self) -> Result<(), TraceError> {
+        let since_epoch = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let days = OLE_UNIX_EPOCH_DAYS + since_epoch.as_secs_f64() / SECONDS_PER_DAY;
+        let out = self.out();
+        writeln!(out, ";$FILEVERSION=2.1")?;
+        writeln!(out, ";$STARTTIME={days:.10}")?;
+        writeln!(out, ";$COLUMNS={COLUMNS_V21}")?;
+        writeln!(out, ";")?;
+        writeln!(
+            out,
+            ";   Message   Time    Type Bus  ID     Rx/Tx  DLC  Data Bytes (hex)"
+        )?;
+        writeln!(out, ";   Number    Offset (ms)")?;
+        writeln!(
+            out,
+            ";---+-- ------+------ +- +- --+----- +- -+ -+ -- -- -- -- -- -- -- --"
+        )?;
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceWriter for TrcWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        if self.closed {
+            return Err(TraceError::Closed);
+        }
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                self.write_header()?;
+                *self.start.insert(record.timestamp)
+            }
+        };
+        self.count += 1;
+        let elapsed = record.timestamp.saturating_sub(start);
+        let offset = format!(
+            "{}.{:03}",
+            elapsed.as_millis(),
+            elapsed.subsec_micros() % 1000
+        );
+        let bus = record.channel.unwrap_or(1);
+
+        let frame = &record.frame;
+        let id = if frame.is_extended_frame() {
+            format!("{:08X}", frame.can_id())
+        } else {
+            format!("{:04X}", frame.can_id())
+        };
+        let kind = match frame {
+            TraceFrame::Can(can) if can.is_remote_frame() => "RR",
+            TraceFrame::Can(_) => "DT",
+            TraceFrame::CanFd(fd) if fd.is_brs() => "FB",
+            TraceFrame::CanFd(_) => "FD",
+        };
+        let data = match frame {
+            TraceFrame::Can(can) if can.is_remote_frame() => String::new(),
+            _ => frame
+                .data()
+                .iter()
+                .map(|b| format!(" {b:02X}"))
+                .collect::<String>(),
+        };
+
+        let count = self.count;
+        writeln!(
+            self.out(),
+            "{count:>7} {offset:>13} {kind} {bus:<2} {id:>8} Rx - {:>2}{data}",
+            frame.dlc()
+        )?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.out().flush()?;
+        Ok(())
+    }
+
+    /// The format has no footer, a file without records gets the header.
+    fn close(&mut self) -> Result<(), TraceError> {
+        if self.closed {
+            return Ok(());
+        }
+        if self.start.is_none() {
+            self.write_header()?;
+        }
+        self.closed = true;
+        self.flush()
+    }
+}
+
+impl<W: Write> Drop for TrcWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const TRACE_V11: &str = ";$FILEVERSION=1.1
+;$STARTTIME=43474.5995896991
+;
+;   Message Number
+;   |         Time Offset (ms)
+;   |         |        Type
+;   |         |        |        ID (hex)
+;   |         |        |        |     Data Length Code
+;   |         |        |        |     |   Data Bytes (hex) ...
+;   |         |        |        |     |   |
+;---+--   ----+----  --+--  ----+---  +  -+ -- -- -- -- -- -- --
+     1)      1059.9  Rx         0300  8  00 01 02 03 04 05 06 07
+     2)      1158.3  Tx     18EFC867  2  DE AD
+     3)      1200.0  Warng  FFFFFFFF  4  00 00 00 08 BUSHEAVY
+     4)      1300.5  Rx         07DF  8  RTR
+";
+
+    const TRACE_V21: &str = ";$FILEVERSION=2.1
+;$STARTTIME=45448.4166666667
+;$COLUMNS=N,O,T,B,I,d,R,L,D
+;
+      1         0.000 DT 1      0123 Rx -  4    DE AD BE EF
+      2         1.250 ST 1          - Rx -  4    00 00 00 00
+      3         2.500 FB 2  1ABCDEF0 Tx - 10    00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F
+      4         3.000 RR 1      07DF Rx -  8
+";
+
+    #[test]
+    fn read_versions() {
+        let mut reader = TrcReader::new(Cursor::new(TRACE_V11));
+        let records = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].timestamp, Duration::from_micros(1_059_900));
+        assert_eq!(records[0].channel, None);
+        assert_eq!(records[0].frame.data(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(records[1].frame.can_id(), 0x18EF_C867);
+        assert!(records[1].frame.is_extended_frame());
+        match records[2].frame {
+            TraceFrame::Can(frame) => assert!(frame.is_remote_frame() && frame.dlc() == 8),
+            _ => panic!("expected classic frame"),
+        }
+        // 2019-01-09 14:23:24 UTC
+        let start = reader
+            .start_time()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(start.as_secs(), 1_547_043_804);
+
+        let records = TrcReader::new(Cursor::new(TRACE_V21))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].channel, Some(1));
+        assert_eq!(records[0].frame.can_id(), 0x123);
+        assert!(!records[0].frame.is_extended_frame());
+        assert_eq!(records[1].timestamp, Duration::from_micros(2500));
+        assert_eq!(records[1].channel, Some(2));
+        match records[1].frame {
+            TraceFrame::CanFd(frame) => {
+                assert_eq!(frame.data().len(), 16);
+                assert!(frame.is_brs());
+            }
+            _ => panic!("expected CAN FD frame"),
+        }
+        assert!(matches!(records[2].frame, TraceFrame::Can(frame) if frame.is_remote_frame()));
+
+        let broken = ";$FILEVERSION=2.0\n      1  0.5 DT  XYZ Rx 1  FF\n";
+        let records = TrcReader::new(Cursor::new(broken)).collect::<Vec<_>>();
+        assert!(matches!(records[0], Err(TraceError::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let records = TrcReader::new(Cursor::new(TRACE_V21))
+            .chain(TrcReader::new(Cursor::new(TRACE_V11)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let started_at = UNIX_EPOCH + Duration::from_secs(1_717_581_600);
+        let mut writer = TrcWriter::new(Vec::new()).with_start_time(started_at);
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let written = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        let mut reader = TrcReader::new(Cursor::new(written));
+        let read_back = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        let start = reader.start_time().unwrap().duration_since(started_at);
+        assert!(start.unwrap_or_else(|err| err.duration()) < Duration::from_millis(1));
+        assert_eq!(read_back.len(), records.len());
+        for (read, record) in read_back.iter().zip(&records) {
+            assert_eq!(read.frame, record.frame);
+            assert_eq!(read.timestamp, record.timestamp);
+            assert_eq!(read.channel, Some(record.channel.unwrap_or(1)));
+        }
+    }
+}
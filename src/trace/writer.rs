@@ -0,0 +1,162 @@
+//! Writing trace records in the formats the crate can read back.
+
+use crate::shutdown::{Shutdown, ShutdownStage};
+use crate::trace::{TraceError, TraceRecord};
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub trait TraceWriter {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError>;
+    fn flush(&mut self) -> Result<(), TraceError>;
+
+    /// Completes the file, e.g. writes the footer of the format, and flushes it. Records written
+    /// afterwards may fail with [TraceError::Closed], closing again has no effect.
+    fn close(&mut self) -> Result<(), TraceError> {
+        self.flush()
+    }
+}
+
+impl<W: TraceWriter + ?Sized> TraceWriter for Box<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        (**self).write_record(record)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        (**self).flush()
+    }
+
+    fn close(&mut self) -> Result<(), TraceError> {
+        (**self).close()
+    }
+}
+
+/// A [TraceWriter] closed by a [Shutdown] hook of the [FlushWriters](ShutdownStage::FlushWriters)
+/// stage, so a long capture is complete even if the recording loop never gets to close it.
+/// Records written after the shutdown fail with [TraceError::Closed].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use peak_can::socket::{Baudrate, CanSocket, RecvCan};
+/// # use peak_can::bus::UsbBus;
+/// # use peak_can::shutdown::Shutdown;
+/// # use peak_can::trace::ShutdownWriter;
+/// # use peak_can::trace::asc::AscWriter;
+/// # use std::fs::File;
+/// # use std::io::BufWriter;
+/// let shutdown = Shutdown::new();
+/// let socket = CanSocket::open(UsbBus::USB1, Baudrate::Baud500K)?;
+/// let file = BufWriter::new(File::create("capture.asc")?);
+/// let writer = ShutdownWriter::new(AscWriter::new(file), &shutdown);
+///
+/// let stop = shutdown.clone();
+/// std::thread::spawn(move || {
+///     std::io::stdin().read_line(&mut String::new()).ok();
+///     stop.trigger();
+/// });
+/// for received in socket.iter().with_shutdown(shutdown.clone()) {
+///     let (frame, timestamp) = received?;
+///     writer.with(|asc| asc.write_frame(1, &frame, &timestamp))?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ShutdownWriter<W> {
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W: TraceWriter + Send + 'static> ShutdownWriter<W> {
+    pub fn new(writer: W, shutdown: &Shutdown) -> Self {
+        let writer = Arc::new(Mutex::new(writer));
+        let hook = Arc::downgrade(&writer);
+        shutdown.on_shutdown(ShutdownStage::FlushWriters, move || {
+            if let Some(writer) = hook.upgrade() {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = writer.close();
+            }
+        });
+        ShutdownWriter { writer }
+    }
+}
+
+impl<W> ShutdownWriter<W> {
+    /// Calls `f` with the writer, e.g. for methods of a format writer.
+    pub fn with<R, F: FnOnce(&mut W) -> R>(&self, f: F) -> R {
+        f(&mut self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, W> {
+        // a writer left behind by a panic is still closed on shutdown
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: TraceWriter> TraceWriter for ShutdownWriter<W> {
+    fn write_record(&mut self, record: &TraceRecord) -> Result<(), TraceError> {
+        self.lock().write_record(record)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.lock().flush()
+    }
+
+    fn close(&mut self) -> Result<(), TraceError> {
+        self.lock().close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{CanFrame, MessageType};
+    use crate::trace::TraceFrame;
+    use crate::trace::asc::AscWriter;
+    use std::io::{self, Write};
+    use std::time::Duration;
+
+    /// A buffer staying readable while the writer owns it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn footer_on_drop_and_shutdown() {
+        let record = TraceRecord {
+            timestamp: Duration::ZERO,
+            channel: Some(1),
+            frame: TraceFrame::Can(CanFrame::new(0x10, MessageType::Standard, &[1]).unwrap()),
+        };
+
+        let dropped = Shared::default();
+        let mut writer = AscWriter::new(dropped.clone());
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        assert!(dropped.text().ends_with("End TriggerBlock\n"));
+
+        let shutdown = Shutdown::new();
+        let buffer = Shared::default();
+        let mut writer = ShutdownWriter::new(AscWriter::new(buffer.clone()), &shutdown);
+        writer.write_record(&record).unwrap();
+        shutdown.trigger();
+        assert!(matches!(
+            writer.write_record(&record),
+            Err(TraceError::Closed)
+        ));
+        drop(writer);
+        assert_eq!(buffer.text().matches("End TriggerBlock").count(), 1);
+    }
+}
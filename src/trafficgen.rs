@@ -0,0 +1,435 @@
+//! Configurable traffic generation for load testing.
+//!
+//! A [TrafficGenerator] produces frames from an [IdPattern] and a [PayloadPattern] and paces
+//! them so that the generated traffic occupies a target percentage of the bus bandwidth,
+//! optionally following a [RampProfile].
+
+use crate::error::CanError;
+use crate::socket::{Baudrate, CanFrame, EXTENDED_MASK, MessageType, STANDARD_MASK, SendCan};
+
+use std::ops::RangeInclusive;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Selection of CAN IDs used by the generator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdPattern {
+    /// Always use the same ID.
+    Single(u32),
+    /// Cycle through the given IDs in order.
+    List(Vec<u32>),
+    /// Walk the range in ascending order, wrapping around at the end.
+    Sweep(RangeInclusive<u32>),
+    /// Pick a uniformly distributed ID from the range.
+    Random(RangeInclusive<u32>),
+}
+
+/// Payload content used by the generator, at most 8 bytes long.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadPattern {
+    /// Always send the same bytes.
+    Fixed(Vec<u8>),
+    /// A little endian frame counter filling `len` bytes.
+    Counter { len: usize },
+    /// A single set bit walking through `len` bytes.
+    WalkingBit { len: usize },
+    /// Random bytes with a random length between `min_len` and `max_len`.
+    Random { min_len: usize, max_len: usize },
+}
+
+/// Evolution of the target bus load over time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RampProfile {
+    /// Keep the configured load for the whole run.
+    Constant,
+    /// Increase the load linearly from `start_percent` to the configured load over `duration`.
+    Linear { start_percent: f64, duration: Duration },
+    /// Use the load of the last step whose offset has elapsed, the configured load before that.
+    Steps(Vec<(Duration, f64)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficConfig {
+    pub ids: IdPattern,
+    pub msg_type: MessageType,
+    pub payload: PayloadPattern,
+    pub baudrate: Baudrate,
+    /// Target bus load in percent (0.0 - 100.0).
+    pub load_percent: f64,
+    pub ramp: RampProfile,
+    /// Stop after this much time has elapsed.
+    pub duration: Option<Duration>,
+    /// Stop after this many frames have been sent.
+    pub max_frames: Option<u64>,
+}
+
+impl Default for TrafficConfig {
+    fn default() -> Self {
+        TrafficConfig {
+            ids: IdPattern::Single(0x100),
+            msg_type: MessageType::Standard,
+            payload: PayloadPattern::Counter { len: 8 },
+            baudrate: Baudrate::Baud500K,
+            load_percent: 30.0,
+            ramp: RampProfile::Constant,
+            duration: None,
+            max_frames: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TrafficStats {
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub bits_sent: u64,
+    /// Number of times the transmit queue was full and the frame had to be retried.
+    pub queue_full_retries: u64,
+    pub elapsed: Duration,
+}
+
+impl TrafficStats {
+    /// Achieved bus load in percent for the given baudrate.
+    pub fn load_percent(&self, baudrate: Baudrate) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.bits_sent as f64 * 100.0 / (baudrate.bits_per_second() as f64 * secs)
+    }
+}
+
+/// Number of bits a classical data frame occupies on the bus, without stuff bits.
+pub fn frame_bits(frame: &CanFrame) -> u32 {
+    let overhead = if frame.is_extended_frame() { 67 } else { 47 };
+    overhead + 8 * frame.dlc() as u32
+}
+
+pub struct TrafficGenerator {
+    config: TrafficConfig,
+    rng: XorShift64,
+    sequence: u64,
+}
+
+impl TrafficGenerator {
+    /// [CanError::IllParamVal] if the payload pattern has lengths above 8 bytes, or a
+    /// [Random](PayloadPattern::Random) `min_len` above its `max_len`.
+    pub fn new(config: TrafficConfig) -> Result<TrafficGenerator, CanError> {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        TrafficGenerator::with_seed(config, seed)
+    }
+
+    /// Creates a generator producing a reproducible sequence of frames, fails like
+    /// [new](TrafficGenerator::new).
+    pub fn with_seed(config: TrafficConfig, seed: u64) -> Result<TrafficGenerator, CanError> {
+        let max_len = match &config.payload {
+            PayloadPattern::Fixed(data) => data.len(),
+            PayloadPattern::Counter { len } | PayloadPattern::WalkingBit { len } => *len,
+            PayloadPattern::Random { min_len, max_len } if min_len > max_len => {
+                return Err(CanError::IllParamVal);
+            }
+            PayloadPattern::Random { max_len, .. } => *max_len,
+        };
+        if max_len > 8 {
+            return Err(CanError::IllParamVal);
+        }
+        Ok(TrafficGenerator {
+            config,
+            rng: XorShift64::new(seed),
+            sequence: 0,
+        })
+    }
+
+    pub fn config(&self) -> &TrafficConfig {
+        &self.config
+    }
+
+    /// Produces the next frame of the configured pattern.
+    pub fn next_frame(&mut self) -> CanFrame {
+        // IDs outside the range of the message type wrap around instead of failing the generator
+        let can_id = match self.config.msg_type {
+            MessageType::Standard => self.next_id() & STANDARD_MASK,
+            MessageType::Extended => self.next_id() & EXTENDED_MASK,
+        };
+        let data = self.next_payload();
+        self.sequence = self.sequence.wrapping_add(1);
+
+        CanFrame::new(can_id, self.config.msg_type, &data).expect("payload length was checked")
+    }
+
+    /// Target bus load in percent after `elapsed` time, taking the ramp profile into account.
+    pub fn load_at(&self, elapsed: Duration) -> f64 {
+        let target = self.config.load_percent;
+        let load = match &self.config.ramp {
+            RampProfile::Constant => target,
+            RampProfile::Linear {
+                start_percent,
+                duration,
+            } => {
+                if duration.is_zero() || elapsed >= *duration {
+                    target
+                } else {
+                    let progress = elapsed.as_secs_f64() / duration.as_secs_f64();
+                    start_percent + (target - start_percent) * progress
+                }
+            }
+            RampProfile::Steps(steps) => steps
+                .iter()
+                .filter(|(offset, _)| *offset <= elapsed)
+                .max_by_key(|(offset, _)| *offset)
+                .map(|(_, load)| *load)
+                .unwrap_or(target),
+        };
+        load.clamp(0.0, 100.0)
+    }
+
+    /// Time to wait after sending `frame` so that the load at `elapsed` is met.
+    pub fn interval(&self, frame: &CanFrame, elapsed: Duration) -> Duration {
+        let load = self.load_at(elapsed);
+        if load <= 0.0 {
+            return Duration::from_millis(1);
+        }
+        let bus_time_ns =
+            frame_bits(frame) as f64 * 1e9 / self.config.baudrate.bits_per_second() as f64;
+        Duration::from_nanos((bus_time_ns * 100.0 / load).round() as u64)
+    }
+
+    /// Time `frame` occupies the bus.
+    fn bus_time(&self, frame: &CanFrame) -> Duration {
+        let bits_per_second = self.config.baudrate.bits_per_second() as u64;
+        Duration::from_nanos(frame_bits(frame) as u64 * 1_000_000_000 / bits_per_second)
+    }
+
+    /// Sends frames on `socket` until the configured duration or frame count is reached.
+    ///
+    /// A full transmit queue is not treated as an error; the frame is retried after the time
+    /// one frame takes on the bus.
+    pub fn run<S: SendCan>(&mut self, socket: &S) -> Result<TrafficStats, CanError> {
+        let mut stats = TrafficStats::default();
+        let start = Instant::now();
+        let mut next_send = start;
+
+        loop {
+            let elapsed = start.elapsed();
+            if self.config.duration.is_some_and(|d| elapsed >= d)
+                || self.config.max_frames.is_some_and(|n| stats.frames_sent >= n)
+            {
+                break;
+            }
+
+            let now = Instant::now();
+            if next_send > now {
+                sleep(next_send - now);
+            }
+
+            let frame = self.next_frame();
+            loop {
+                match socket.send(frame) {
+                    Ok(()) => break,
+                    Err(CanError::XmtFull) | Err(CanError::QxmtFull) => {
+                        stats.queue_full_retries += 1;
+                        sleep(self.bus_time(&frame));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+
+            stats.frames_sent += 1;
+            stats.bytes_sent += frame.dlc() as u64;
+            stats.bits_sent += frame_bits(&frame) as u64;
+            next_send += self.interval(&frame, start.elapsed());
+        }
+
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+
+    fn next_id(&mut self) -> u32 {
+        match &self.config.ids {
+            IdPattern::Single(id) => *id,
+            IdPattern::List(ids) if ids.is_empty() => 0,
+            IdPattern::List(ids) => ids[(self.sequence % ids.len() as u64) as usize],
+            IdPattern::Sweep(range) => {
+                let span = (*range.end() as u64).saturating_sub(*range.start() as u64) + 1;
+                *range.start() + (self.sequence % span) as u32
+            }
+            IdPattern::Random(range) => {
+                let span = (*range.end() as u64).saturating_sub(*range.start() as u64) + 1;
+                *range.start() + (self.rng.next() % span) as u32
+            }
+        }
+    }
+
+    fn next_payload(&mut self) -> Vec<u8> {
+        match &self.config.payload {
+            PayloadPattern::Fixed(data) => data.clone(),
+            PayloadPattern::Counter { len } => self.sequence.to_le_bytes()[..*len].to_vec(),
+            PayloadPattern::WalkingBit { len } => {
+                let len = *len;
+                let mut data = vec![0u8; len];
+                if len > 0 {
+                    let bit = (self.sequence % (len as u64 * 8)) as usize;
+                    data[bit / 8] = 1 << (bit % 8);
+                }
+                data
+            }
+            PayloadPattern::Random { min_len, max_len } => {
+                let (min_len, max_len) = (*min_len, *max_len);
+                let len = min_len + (self.rng.next() % (max_len - min_len + 1) as u64) as usize;
+                let bytes = self.rng.next().to_le_bytes();
+                bytes[..len].to_vec()
+            }
+        }
+    }
+}
+
+/// Small xorshift64* generator; statistical quality is sufficient for test traffic.
+struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    fn new(seed: u64) -> XorShift64 {
+        XorShift64 {
+            state: if seed == 0 { 0x2545_F491_4F6C_DD1D } else { seed },
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_bits_standard_and_extended() {
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[0; 8]).unwrap();
+        assert_eq!(frame_bits(&frame), 111);
+
+        let frame = CanFrame::new(0x123, MessageType::Extended, &[]).unwrap();
+        assert_eq!(frame_bits(&frame), 67);
+    }
+
+    #[test]
+    fn seeded_generator_is_reproducible() {
+        let config = TrafficConfig {
+            ids: IdPattern::Random(0x100..=0x1FF),
+            payload: PayloadPattern::Random { min_len: 0, max_len: 8 },
+            ..Default::default()
+        };
+        let mut a = TrafficGenerator::with_seed(config.clone(), 42).unwrap();
+        let mut b = TrafficGenerator::with_seed(config, 42).unwrap();
+
+        for _ in 0..100 {
+            let frame = a.next_frame();
+            assert_eq!(frame, b.next_frame());
+            assert!((0x100..=0x1FF).contains(&frame.can_id()));
+        }
+    }
+
+    #[test]
+    fn sweep_and_counter_patterns() {
+        let config = TrafficConfig {
+            ids: IdPattern::Sweep(0x10..=0x12),
+            payload: PayloadPattern::Counter { len: 2 },
+            ..Default::default()
+        };
+        let mut generator = TrafficGenerator::with_seed(config, 1).unwrap();
+        let ids = (0..4).map(|_| generator.next_frame().can_id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0x10, 0x11, 0x12, 0x10]);
+        assert_eq!(generator.next_frame().data(), &[4, 0]);
+    }
+
+    #[test]
+    fn ramp_profiles() {
+        let config = TrafficConfig {
+            load_percent: 50.0,
+            ramp: RampProfile::Linear {
+                start_percent: 10.0,
+                duration: Duration::from_secs(4),
+            },
+            ..Default::default()
+        };
+        let generator = TrafficGenerator::with_seed(config, 1).unwrap();
+        assert_eq!(generator.load_at(Duration::ZERO), 10.0);
+        assert_eq!(generator.load_at(Duration::from_secs(2)), 30.0);
+        assert_eq!(generator.load_at(Duration::from_secs(10)), 50.0);
+
+        let config = TrafficConfig {
+            load_percent: 20.0,
+            ramp: RampProfile::Steps(vec![
+                (Duration::from_secs(1), 40.0),
+                (Duration::from_secs(2), 60.0),
+            ]),
+            ..Default::default()
+        };
+        let generator = TrafficGenerator::with_seed(config, 1).unwrap();
+        assert_eq!(generator.load_at(Duration::ZERO), 20.0);
+        assert_eq!(generator.load_at(Duration::from_millis(1500)), 40.0);
+        assert_eq!(generator.load_at(Duration::from_secs(3)), 60.0);
+    }
+
+    #[test]
+    fn interval_matches_target_load() {
+        let config = TrafficConfig {
+            baudrate: Baudrate::Baud500K,
+            load_percent: 50.0,
+            ..Default::default()
+        };
+        let generator = TrafficGenerator::with_seed(config, 1).unwrap();
+        let frame = CanFrame::new(0x123, MessageType::Standard, &[0; 8]).unwrap();
+        // 111 bits at 500 kbit/s take 222 us, doubled for 50% load
+        assert_eq!(generator.interval(&frame, Duration::ZERO), Duration::from_micros(444));
+    }
+
+    #[test]
+    fn payloads_longer_than_a_frame_are_rejected() {
+        for payload in [
+            PayloadPattern::Fixed(vec![0; 9]),
+            PayloadPattern::Counter { len: 9 },
+            PayloadPattern::WalkingBit { len: 64 },
+            PayloadPattern::Random { min_len: 0, max_len: 9 },
+            PayloadPattern::Random { min_len: 4, max_len: 2 },
+        ] {
+            let config = TrafficConfig {
+                payload,
+                ..Default::default()
+            };
+            assert!(matches!(
+                TrafficGenerator::with_seed(config, 1),
+                Err(CanError::IllParamVal)
+            ));
+        }
+    }
+
+    #[test]
+    fn full_transmit_queue_is_retried() {
+        let socket = crate::socket::MockCanSocket::new();
+        socket
+            .backend()
+            .fail_next_write(socket.channel(), CanError::XmtFull);
+        let config = TrafficConfig {
+            load_percent: 100.0,
+            max_frames: Some(2),
+            ..Default::default()
+        };
+        let stats = TrafficGenerator::with_seed(config, 1)
+            .unwrap()
+            .run(&socket)
+            .unwrap();
+        assert_eq!(stats.frames_sent, 2);
+        assert_eq!(stats.queue_full_retries, 1);
+        assert_eq!(socket.backend().sent(socket.channel()).len(), 2);
+    }
+}
@@ -0,0 +1,349 @@
+//! Expressions over decoded signal values, e.g. as pass/fail criteria of a test run.
+//!
+//! A [SignalWatch] keeps the latest value and rate of change of every signal it is fed with and
+//! reports a [WatchEvent] whenever a registered [Expr] starts or stops holding.
+//!
+//! # Examples
+//!
+//! ```
+//! # use peak_can::watch::{Compare, Edge, Expr, SignalWatch};
+//! # use std::time::Duration;
+//! let mut watch = SignalWatch::new();
+//! let overspeed = watch.watch("overspeed", Expr::compare("EngineSpeed", Compare::Gt, 6000.0));
+//! let surge = watch.watch("surge", Expr::rate_above("EngineSpeed", 5000.0));
+//!
+//! assert!(watch.update("EngineSpeed", 3000.0, Duration::ZERO).is_empty());
+//! let events = watch.update("EngineSpeed", 6500.0, Duration::from_millis(100));
+//! assert_eq!(events.len(), 2);
+//! assert_eq!(events[0].edge, Edge::Tripped);
+//!
+//! watch.update("EngineSpeed", 5000.0, Duration::from_secs(1));
+//! assert!(!watch.is_active(overspeed));
+//! assert_eq!(watch.trip_count(surge), 1);
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Compare {
+    fn holds(self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Compare::Lt => lhs < rhs,
+            Compare::Le => lhs <= rhs,
+            Compare::Gt => lhs > rhs,
+            Compare::Ge => lhs >= rhs,
+            Compare::Eq => lhs == rhs,
+            Compare::Ne => lhs != rhs,
+        }
+    }
+}
+
+/// A condition on signal values. An expression depending on a signal which has no value, or
+/// no rate of change, yet is unknown and does not trip.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// The physical value of `signal` compared with `value`.
+    Compare {
+        signal: String,
+        op: Compare,
+        value: f64,
+    },
+    /// The value of `signal` changed faster than `per_second` in either direction between its
+    /// last two samples.
+    RateAbove {
+        signal: String,
+        per_second: f64,
+    },
+    All(Vec<Expr>),
+    Any(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    pub fn compare(signal: &str, op: Compare, value: f64) -> Expr {
+        Expr::Compare {
+            signal: signal.to_string(),
+            op,
+            value,
+        }
+    }
+
+    pub fn rate_above(signal: &str, per_second: f64) -> Expr {
+        Expr::RateAbove {
+            signal: signal.to_string(),
+            per_second,
+        }
+    }
+
+    pub fn all(exprs: Vec<Expr>) -> Expr {
+        Expr::All(exprs)
+    }
+
+    pub fn any(exprs: Vec<Expr>) -> Expr {
+        Expr::Any(exprs)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(expr: Expr) -> Expr {
+        Expr::Not(Box::new(expr))
+    }
+
+    /// Whether the expression depends on `signal`.
+    pub fn references(&self, signal: &str) -> bool {
+        match self {
+            Expr::Compare { signal: s, .. } | Expr::RateAbove { signal: s, .. } => s == signal,
+            Expr::All(exprs) | Expr::Any(exprs) => exprs.iter().any(|e| e.references(signal)),
+            Expr::Not(expr) => expr.references(signal),
+        }
+    }
+
+    /// `None` while a referenced signal has no value or rate yet.
+    fn eval(&self, samples: &HashMap<String, Sample>) -> Option<bool> {
+        match self {
+            Expr::Compare { signal, op, value } => samples
+                .get(signal)
+                .map(|sample| op.holds(sample.value, *value)),
+            Expr::RateAbove { signal, per_second } => samples
+                .get(signal)
+                .and_then(|sample| sample.rate)
+                .map(|rate| rate.abs() > *per_second),
+            Expr::All(exprs) => {
+                let results = exprs.iter().map(|e| e.eval(samples)).collect::<Vec<_>>();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else {
+                    results.iter().all(Option::is_some).then_some(true)
+                }
+            }
+            Expr::Any(exprs) => {
+                let results = exprs.iter().map(|e| e.eval(samples)).collect::<Vec<_>>();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else {
+                    results.iter().all(Option::is_some).then_some(false)
+                }
+            }
+            Expr::Not(expr) => expr.eval(samples).map(|result| !result),
+        }
+    }
+}
+
+/// Identifies a registered watch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The expression started to hold.
+    Tripped,
+    /// The expression stopped to hold.
+    Cleared,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchEvent {
+    pub id: WatchId,
+    pub name: String,
+    pub edge: Edge,
+    /// Timestamp of the update which changed the expression.
+    pub timestamp: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    value: f64,
+    timestamp: Duration,
+    /// Change per second to the previous sample.
+    rate: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Watch {
+    id: WatchId,
+    name: String,
+    expr: Expr,
+    active: bool,
+    trips: u64,
+}
+
+/// Evaluates watch expressions on a stream of signal values.
+#[derive(Debug, Default)]
+pub struct SignalWatch {
+    watches: Vec<Watch>,
+    samples: HashMap<String, Sample>,
+    next_id: u64,
+}
+
+impl SignalWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `expr`, `name` is copied into its events.
+    pub fn watch(&mut self, name: &str, expr: Expr) -> WatchId {
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        let active = expr.eval(&self.samples) == Some(true);
+        self.watches.push(Watch {
+            id,
+            name: name.to_string(),
+            expr,
+            active,
+            trips: 0,
+        });
+        id
+    }
+
+    /// Returns `false` if no watch with `id` is registered.
+    pub fn remove(&mut self, id: WatchId) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() != len
+    }
+
+    /// Records a value of `signal` and returns the events of the watches depending on it.
+    pub fn update(&mut self, signal: &str, value: f64, timestamp: Duration) -> Vec<WatchEvent> {
+        self.update_all([(signal, value)], timestamp)
+    }
+
+    /// Records values sampled at the same time, e.g. all signals of a frame, before the
+    /// expressions are evaluated.
+    pub fn update_all<'s, I>(&mut self, values: I, timestamp: Duration) -> Vec<WatchEvent>
+    where
+        I: IntoIterator<Item = (&'s str, f64)>,
+    {
+        let mut updated = Vec::new();
+        for (signal, value) in values {
+            let sample = match self.samples.get(signal) {
+                Some(last) => {
+                    let rate = match timestamp.checked_sub(last.timestamp) {
+                        Some(dt) if !dt.is_zero() => Some((value - last.value) / dt.as_secs_f64()),
+                        _ => last.rate,
+                    };
+                    Sample {
+                        value,
+                        timestamp,
+                        rate,
+                    }
+                }
+                None => Sample {
+                    value,
+                    timestamp,
+                    rate: None,
+                },
+            };
+            self.samples.insert(signal.to_string(), sample);
+            updated.push(signal);
+        }
+
+        let mut events = Vec::new();
+        for watch in &mut self.watches {
+            if !updated.iter().any(|signal| watch.expr.references(signal)) {
+                continue;
+            }
+            let active = watch.expr.eval(&self.samples) == Some(true);
+            if active == watch.active {
+                continue;
+            }
+            watch.active = active;
+            let edge = if active {
+                watch.trips += 1;
+                Edge::Tripped
+            } else {
+                Edge::Cleared
+            };
+            events.push(WatchEvent {
+                id: watch.id,
+                name: watch.name.clone(),
+                edge,
+                timestamp,
+            });
+        }
+        events
+    }
+
+    /// Records the signals decoded from one frame.
+    #[cfg(feature = "dbc")]
+    pub fn update_signals(
+        &mut self,
+        signals: &[crate::dbc::Signal],
+        timestamp: Duration,
+    ) -> Vec<WatchEvent> {
+        self.update_all(
+            signals
+                .iter()
+                .map(|signal| (signal.name.as_str(), signal.value)),
+            timestamp,
+        )
+    }
+
+    /// Whether the expression of `id` currently holds.
+    pub fn is_active(&self, id: WatchId) -> bool {
+        self.find(id).is_some_and(|watch| watch.active)
+    }
+
+    /// How often the expression of `id` started to hold, `0` for a passed "never" criterion.
+    pub fn trip_count(&self, id: WatchId) -> u64 {
+        self.find(id).map_or(0, |watch| watch.trips)
+    }
+
+    /// The latest value of `signal`.
+    pub fn value(&self, signal: &str) -> Option<f64> {
+        self.samples.get(signal).map(|sample| sample.value)
+    }
+
+    fn find(&self, id: WatchId) -> Option<&Watch> {
+        self.watches.iter().find(|watch| watch.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_of_combined_expressions() {
+        let mut watch = SignalWatch::new();
+        let hot_and_fast = watch.watch(
+            "hot and fast",
+            Expr::all(vec![
+                Expr::compare("CoolantTemp", Compare::Ge, 110.0),
+                Expr::not(Expr::compare("VehicleSpeed", Compare::Lt, 100.0)),
+            ]),
+        );
+        let ms = Duration::from_millis;
+
+        assert!(watch.update("CoolantTemp", 115.0, ms(0)).is_empty());
+        let events = watch.update_all([("VehicleSpeed", 120.0), ("Unrelated", 1.0)], ms(10));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].edge, Edge::Tripped);
+        assert_eq!(events[0].timestamp, ms(10));
+        assert!(watch.update("VehicleSpeed", 130.0, ms(20)).is_empty());
+
+        let events = watch.update("CoolantTemp", 105.0, ms(30));
+        assert_eq!(events[0].edge, Edge::Cleared);
+        watch.update("CoolantTemp", 112.0, ms(40));
+        assert!(watch.is_active(hot_and_fast));
+        assert_eq!(watch.trip_count(hot_and_fast), 2);
+
+        // 100/s and 200/s, a second sample at the same time keeps the rate
+        let surge = watch.watch("surge", Expr::rate_above("Throttle", 150.0));
+        watch.update("Throttle", 0.0, ms(0));
+        assert!(watch.update("Throttle", 10.0, ms(100)).is_empty());
+        assert_eq!(watch.update("Throttle", 20.0, ms(150))[0].id, surge);
+        assert!(watch.update("Throttle", 20.0, ms(150)).is_empty());
+
+        assert!(watch.remove(surge));
+        assert!(!watch.remove(surge));
+    }
+}